@@ -1,9 +1,17 @@
 #!/bin/sh
 
 TOP=$(git rev-parse --show-toplevel)
+
+# attach a raw SD card image if one is given (e.g., SDCARD=fs.img make qemu)
+SD_ARGS=""
+if [ -n "$SDCARD" ]; then
+    SD_ARGS="-drive file=$SDCARD,format=raw,if=sd"
+fi
+
 $TOP/bin/qemu-system-aarch64 \
     -nographic \
     -M raspi3 \
     -serial null -serial mon:stdio \
+    $SD_ARGS \
     -kernel \
     "$@"
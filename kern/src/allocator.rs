@@ -0,0 +1,163 @@
+use core::alloc::{GlobalAlloc, Layout};
+use core::ptr;
+
+use pi::mailbox::Mailbox;
+
+use crate::mutex::Mutex;
+
+/// Smallest block handed out by the allocator. Freed blocks store the address
+/// of the next free block in place, so this must hold a `usize`.
+const MIN_BLOCK_SIZE: usize = 8;
+
+/// Number of power-of-two size classes, from `MIN_BLOCK_SIZE` up to 4GiB.
+const NUM_BINS: usize = 30;
+
+/// Thread-safe (locking) wrapper around a particular memory allocator.
+#[derive(Debug)]
+pub struct Allocator(Mutex<Option<BinAllocator>>);
+
+impl Allocator {
+    /// Returns an uninitialized `Allocator`.
+    ///
+    /// The allocator must be initialized by calling `initialize()` before the
+    /// first memory allocation. Failure to do will result in panics.
+    pub const fn uninitialized() -> Self {
+        Allocator(Mutex::new(None))
+    }
+
+    /// Initializes the memory allocator. The heap spans from the end of the
+    /// kernel binary to the end of the memory the firmware gives the ARM
+    /// cores.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the firmware doesn't report the ARM memory.
+    ///
+    /// # Safety
+    ///
+    /// The caller should assure that this method is invoked only once during
+    /// the kernel initialization, before anything is allocated.
+    pub unsafe fn initialize(&self) {
+        let (start, end) = memory_map();
+        *self.0.lock() = Some(BinAllocator::new(start, end));
+    }
+
+    /// Returns `true` if `initialize()` has been called and allocations can
+    /// be served.
+    pub fn is_initialized(&self) -> bool {
+        self.0.lock().is_some()
+    }
+
+    /// Returns the (start address, end address) of the memory managed by the
+    /// allocator, or `None` if it isn't initialized.
+    pub fn heap(&self) -> Option<(usize, usize)> {
+        self.0.lock().as_ref().map(|allocator| (allocator.start, allocator.end))
+    }
+}
+
+unsafe impl GlobalAlloc for Allocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.0
+            .lock()
+            .as_mut()
+            .expect("allocator uninitialized")
+            .alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.0
+            .lock()
+            .as_mut()
+            .expect("allocator uninitialized")
+            .dealloc(ptr, layout);
+    }
+}
+
+/// Returns the (start address, end address) of the memory available to the
+/// heap. The memory above the ARM memory belongs to the VideoCore, and its
+/// size depends on the board and on `gpu_mem` in `config.txt`.
+fn memory_map() -> (usize, usize) {
+    extern "C" {
+        static __text_end: u8;
+    }
+
+    let binary_end = unsafe { &__text_end as *const u8 as usize };
+    let memory = Mailbox::new()
+        .arm_memory()
+        .expect("firmware didn't report the ARM memory");
+    (binary_end, memory.base as usize + memory.size as usize)
+}
+
+/// Align `addr` upwards to the nearest multiple of `align`, which must be a
+/// power of two.
+fn align_up(addr: usize, align: usize) -> usize {
+    (addr + align - 1) & !(align - 1)
+}
+
+/// A simple segregated-fit allocator.
+///
+/// Every request is rounded up to a power-of-two block size. Freed blocks are
+/// pushed onto an intrusive free list for their size class and reused by the
+/// next request of the same class; when a list is empty, a fresh block is
+/// carved off the end of the heap, aligned to its own size.
+#[derive(Debug)]
+pub struct BinAllocator {
+    bins: [usize; NUM_BINS],
+    start: usize,
+    current: usize,
+    end: usize,
+}
+
+impl BinAllocator {
+    /// Creates a new allocator managing the memory in `[start, end)`.
+    fn new(start: usize, end: usize) -> BinAllocator {
+        BinAllocator {
+            bins: [0; NUM_BINS],
+            start,
+            current: start,
+            end,
+        }
+    }
+
+    /// Returns the size class index and block size for `layout`.
+    fn bin_for(layout: Layout) -> (usize, usize) {
+        let size = layout
+            .size()
+            .max(layout.align())
+            .max(MIN_BLOCK_SIZE)
+            .next_power_of_two();
+        let index = (size.trailing_zeros() - MIN_BLOCK_SIZE.trailing_zeros()) as usize;
+        (index, size)
+    }
+
+    /// Allocates a block satisfying `layout`. Returns a null pointer if the
+    /// heap is exhausted.
+    unsafe fn alloc(&mut self, layout: Layout) -> *mut u8 {
+        let (index, size) = BinAllocator::bin_for(layout);
+        if index >= NUM_BINS {
+            return ptr::null_mut();
+        }
+
+        let head = self.bins[index];
+        if head != 0 {
+            self.bins[index] = *(head as *const usize);
+            return head as *mut u8;
+        }
+
+        let start = align_up(self.current, size);
+        match start.checked_add(size) {
+            Some(end) if end <= self.end => {
+                self.current = end;
+                start as *mut u8
+            }
+            _ => ptr::null_mut(),
+        }
+    }
+
+    /// Returns the block at `ptr`, allocated with `layout`, to its free list.
+    unsafe fn dealloc(&mut self, ptr: *mut u8, layout: Layout) {
+        let (index, _) = BinAllocator::bin_for(layout);
+        *(ptr as *mut usize) = self.bins[index];
+        self.bins[index] = ptr as usize;
+    }
+}
@@ -1,18 +1,48 @@
+use alloc::boxed::Box;
 use core::fmt;
-use pi::uart::MiniUart;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use pi::interrupt::Interrupt;
+use pi::uart::{IrqHandle, MiniUart};
 use shim::io;
 
 use crate::mutex::Mutex;
+use crate::ring::RingBuffer;
+use crate::traps;
+use crate::IRQ;
+
+/// The size of the receive and transmit buffers filled and drained by the
+/// mini UART's interrupt handler.
+const BUFFER_SIZE: usize = 256;
+
+/// Bytes received by the interrupt handler, waiting to be read.
+static RX: RingBuffer<BUFFER_SIZE> = RingBuffer::new();
+
+/// Bytes written to the console, waiting for the interrupt handler to send
+/// them.
+static TX: RingBuffer<BUFFER_SIZE> = RingBuffer::new();
+
+/// The number of times received bytes were lost because `RX` or the UART's
+/// receive FIFO was full. Only the interrupt handler changes it, with a plain
+/// load and store: read-modify-write atomics never complete while the MMU
+/// is off.
+static OVERRUNS: AtomicUsize = AtomicUsize::new(0);
 
 /// A global singleton allowing read/write access to the console.
+///
+/// The console polls the mini UART until `initialize()` enables its
+/// interrupts. From then on, bytes go through ring buffers serviced by the
+/// interrupt handler, and reads sleep until a byte arrives. While IRQs are
+/// masked, such as in exception handlers and after a panic, the console
+/// falls back to polling.
 pub struct Console {
     inner: Option<MiniUart>,
+    irq: Option<IrqHandle>,
 }
 
 impl Console {
     /// Creates a new instance of `Console`.
     const fn new() -> Console {
-        Console { inner: None }
+        Console { inner: None, irq: None }
     }
 
     /// Initializes the console if it's not already initialized.
@@ -30,50 +60,165 @@ impl Console {
         self.inner.as_mut().unwrap()
     }
 
+    /// Returns `true` if the console uses the interrupt-driven buffers
+    /// rather than polling the UART.
+    fn is_buffered(&self) -> bool {
+        self.irq.is_some() && !traps::irqs_masked()
+    }
+
+    /// Returns `true` if a byte can be read without blocking.
+    pub fn has_byte(&mut self) -> bool {
+        !RX.is_empty() || (!self.is_buffered() && self.inner().has_byte())
+    }
+
     /// Reads a byte from the UART device, blocking until a byte is available.
     pub fn read_byte(&mut self) -> u8 {
-        self.inner().read_byte()
+        if self.is_buffered() {
+            return traps::wait_for(|| RX.pop());
+        }
+
+        match RX.pop() {
+            Some(byte) => byte,
+            None => self.inner().read_byte(),
+        }
     }
 
     /// Writes the byte `byte` to the UART device.
     pub fn write_byte(&mut self, byte: u8) {
+        if self.is_buffered() {
+            traps::wait_for(|| TX.push(byte).ok());
+            if let Some(irq) = self.irq.as_mut() {
+                irq.set_tx_interrupt(true);
+            }
+            return;
+        }
+
+        // Send the bytes still buffered first, keeping the output in order.
+        while let Some(buffered) = TX.pop() {
+            self.inner().write_byte(buffered);
+        }
         self.inner().write_byte(byte)
     }
 }
 
 impl io::Read for Console {
+    /// Blocks until a byte is available, then reads the bytes available
+    /// without waiting for more.
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.inner().read(buf)
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        buf[0] = self.read_byte();
+        let mut read = 1;
+        while read < buf.len() && self.has_byte() {
+            buf[read] = self.read_byte();
+            read += 1;
+        }
+        Ok(read)
     }
 }
 
 impl io::Write for Console {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.inner().write(buf)
+        for &byte in buf {
+            self.write_byte(byte);
+        }
+        Ok(buf.len())
     }
 
+    /// Blocks until every byte written has been sent.
     fn flush(&mut self) -> io::Result<()> {
+        if self.is_buffered() {
+            traps::wait_for(|| if TX.is_empty() { Some(()) } else { None });
+        }
         self.inner().flush()
     }
 }
 
-impl fmt::Write for Console {
-    fn write_str(&mut self, s: &str) -> fmt::Result {
-        self.inner().write_str(s)
+/// Global `Console` singleton.
+pub static CONSOLE: Mutex<Console> = Mutex::new(Console::new());
+
+/// Switches the console to interrupt-driven I/O: registers the mini UART's
+/// IRQ handler and enables its receive interrupt.
+pub fn initialize() {
+    let mut console = CONSOLE.lock();
+    let mut handle = console.inner().irq_handle();
+    IRQ.register(Interrupt::Aux, Box::new(move |_| service(&mut handle)));
+
+    let mut irq = console.inner().irq_handle();
+    irq.set_rx_interrupt(true);
+    console.irq = Some(irq);
+    traps::enable_interrupt(Interrupt::Aux);
+}
+
+/// Returns the number of times received bytes were lost so far because they
+/// weren't read quickly enough.
+pub fn overruns() -> usize {
+    OVERRUNS.load(Ordering::Relaxed)
+}
+
+/// The mini UART's IRQ handler: moves received bytes into `RX` and buffered
+/// bytes from `TX` into the UART, and stops the transmit interrupt once `TX`
+/// is empty.
+fn service(uart: &mut IrqHandle) {
+    if !uart.is_pending() {
+        return;
+    }
+
+    let mut overruns = 0;
+    while let Some(byte) = uart.try_read_byte() {
+        if RX.push(byte).is_err() {
+            overruns += 1;
+        }
+    }
+    if uart.take_overrun() {
+        overruns += 1;
+    }
+    OVERRUNS.store(OVERRUNS.load(Ordering::Relaxed) + overruns, Ordering::Relaxed);
+
+    while let Some(byte) = TX.peek() {
+        if !uart.try_write_byte(byte) {
+            break;
+        }
+        TX.pop();
+    }
+    if TX.is_empty() {
+        uart.set_tx_interrupt(false);
     }
 }
 
-/// Global `Console` singleton.
-pub static CONSOLE: Mutex<Console> = Mutex::new(Console::new());
+/// A handle to the global console implementing `io::Read` and `io::Write`,
+/// for code generic over streams. The console is locked for each call.
+/// Bytes pass through untouched; `tty::TTY` translates line endings.
+pub struct ConsoleIo;
+
+impl io::Read for ConsoleIo {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        io::Read::read(&mut *CONSOLE.lock(), buf)
+    }
+}
+
+impl io::Write for ConsoleIo {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        io::Write::write(&mut *CONSOLE.lock(), buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::Write::flush(&mut *CONSOLE.lock())
+    }
+}
 
-/// Internal function called by the `kprint[ln]!` macros.
+/// Internal function called by the `kprint[ln]!` macros. The output goes
+/// through `tty::TTY`, which translates line endings like for any other
+/// writer.
 #[doc(hidden)]
 pub fn _print(args: fmt::Arguments) {
     #[cfg(not(test))]
     {
-        use core::fmt::Write;
-        let mut console = CONSOLE.lock();
-        console.write_fmt(args).unwrap();
+        use shim::io::Write;
+        crate::tty::TTY.lock().write_fmt(args).unwrap();
+        crate::fbcon::mirror(args);
     }
 
     #[cfg(test)]
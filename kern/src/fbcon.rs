@@ -0,0 +1,325 @@
+//! A text console drawn on the HDMI framebuffer.
+//!
+//! The console understands the control characters and the subset of ANSI
+//! escape sequences a serial terminal is commonly sent: colors and bold
+//! (`ESC [ ... m`), cursor movement (`ESC [ A..D` and `ESC [ row;col H`),
+//! and clearing the screen (`ESC [ J`) or the line (`ESC [ K`). Once
+//! initialized, it mirrors the output of `kprint!`.
+
+mod font;
+
+#[cfg(test)]
+mod tests;
+
+use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use pi::framebuffer::{self, Color, Framebuffer};
+
+use crate::mutex::Mutex;
+
+use self::font::GLYPH_SIZE;
+
+/// The 16 ANSI colors: the 8 normal colors followed by their bright
+/// variants.
+const PALETTE: [Color; 16] = [
+    Color::rgb(0, 0, 0),
+    Color::rgb(170, 0, 0),
+    Color::rgb(0, 170, 0),
+    Color::rgb(170, 85, 0),
+    Color::rgb(0, 0, 170),
+    Color::rgb(170, 0, 170),
+    Color::rgb(0, 170, 170),
+    Color::rgb(170, 170, 170),
+    Color::rgb(85, 85, 85),
+    Color::rgb(255, 85, 85),
+    Color::rgb(85, 255, 85),
+    Color::rgb(255, 255, 85),
+    Color::rgb(85, 85, 255),
+    Color::rgb(255, 85, 255),
+    Color::rgb(85, 255, 255),
+    Color::rgb(255, 255, 255),
+];
+
+/// The palette indices of the default foreground and background colors.
+const DEFAULT_FG: usize = 7;
+const DEFAULT_BG: usize = 0;
+
+/// Maximum number of numeric parameters of an escape sequence. Further ones
+/// are ignored.
+const MAX_PARAMS: usize = 8;
+
+const ESC: u8 = 0x1B;
+
+/// Where the console is in an escape sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Normal,
+    /// After `ESC`.
+    Escape,
+    /// After `ESC [`, reading parameters.
+    Csi,
+}
+
+/// A text console of 8x8 character cells on a framebuffer. Text wraps at the
+/// right edge and scrolls up at the bottom.
+pub struct TextConsole {
+    fb: Framebuffer,
+    cols: u32,
+    rows: u32,
+    col: u32,
+    row: u32,
+    fg: usize,
+    bg: usize,
+    bold: bool,
+    state: State,
+    params: [u32; MAX_PARAMS],
+    param_count: usize,
+}
+
+impl TextConsole {
+    /// Returns a console covering `fb`, after clearing it, or `None` if `fb`
+    /// can't hold a single character cell.
+    pub fn new(mut fb: Framebuffer) -> Option<TextConsole> {
+        let (cols, rows) = (fb.width() / GLYPH_SIZE, fb.height() / GLYPH_SIZE);
+        if cols == 0 || rows == 0 {
+            return None;
+        }
+
+        fb.clear(PALETTE[DEFAULT_BG]);
+        Some(TextConsole {
+            cols,
+            rows,
+            fb,
+            col: 0,
+            row: 0,
+            fg: DEFAULT_FG,
+            bg: DEFAULT_BG,
+            bold: false,
+            state: State::Normal,
+            params: [0; MAX_PARAMS],
+            param_count: 0,
+        })
+    }
+
+    /// Returns the number of columns and rows of character cells.
+    pub fn size(&self) -> (u32, u32) {
+        (self.cols, self.rows)
+    }
+
+    /// Returns the column and row of the cursor.
+    pub fn cursor(&self) -> (u32, u32) {
+        (self.col, self.row)
+    }
+
+    fn fg_color(&self) -> Color {
+        match self.fg {
+            fg if self.bold && fg < 8 => PALETTE[fg + 8],
+            fg => PALETTE[fg],
+        }
+    }
+
+    /// Draws `byte` at the cursor and advances it, wrapping to the next line
+    /// at the right edge.
+    fn put(&mut self, byte: u8) {
+        if self.col >= self.cols {
+            self.newline();
+        }
+
+        let (fg, bg) = (self.fg_color(), PALETTE[self.bg]);
+        let mut pixels = [bg; (GLYPH_SIZE * GLYPH_SIZE) as usize];
+        for (y, &bits) in font::glyph(byte).iter().enumerate() {
+            for x in 0..GLYPH_SIZE as usize {
+                if bits & (1 << x) != 0 {
+                    pixels[y * GLYPH_SIZE as usize + x] = fg;
+                }
+            }
+        }
+
+        let (x, y) = (self.col * GLYPH_SIZE, self.row * GLYPH_SIZE);
+        self.fb.blit(x, y, GLYPH_SIZE, GLYPH_SIZE, &pixels);
+        self.col += 1;
+    }
+
+    /// Moves the cursor to the start of the next line, scrolling if it is
+    /// on the last one.
+    fn newline(&mut self) {
+        self.col = 0;
+        if self.row + 1 < self.rows {
+            self.row += 1;
+            return;
+        }
+
+        let (width, height) = (self.fb.width(), (self.rows - 1) * GLYPH_SIZE);
+        self.fb.copy_rect(0, GLYPH_SIZE, width, height, 0, 0);
+        self.fb.fill_rect(0, height, width, GLYPH_SIZE, PALETTE[self.bg]);
+    }
+
+    /// Clears the cells from column `from` to `to`, exclusive, of `row`.
+    fn clear_cells(&mut self, row: u32, from: u32, to: u32) {
+        let (x, width) = (from * GLYPH_SIZE, to.saturating_sub(from) * GLYPH_SIZE);
+        self.fb.fill_rect(x, row * GLYPH_SIZE, width, GLYPH_SIZE, PALETTE[self.bg]);
+    }
+
+    /// Returns the `index`th parameter of the escape sequence, or `default`
+    /// if it is missing or 0.
+    fn param(&self, index: usize, default: u32) -> u32 {
+        match self.params[..self.param_count].get(index) {
+            Some(&value) if value != 0 => value,
+            _ => default,
+        }
+    }
+
+    /// Runs the escape sequence ending in `command`.
+    fn execute(&mut self, command: u8) {
+        let n = self.param(0, 1);
+        match command {
+            b'A' => self.row = self.row.saturating_sub(n),
+            b'B' => self.row = core::cmp::min(self.row + n, self.rows - 1),
+            b'C' => self.col = core::cmp::min(self.col + n, self.cols - 1),
+            b'D' => self.col = self.col.saturating_sub(n),
+            b'H' | b'f' => {
+                self.row = core::cmp::min(n, self.rows) - 1;
+                self.col = core::cmp::min(self.param(1, 1), self.cols) - 1;
+            }
+            b'J' => {
+                // 0: from the cursor to the end of the screen, 2: everything.
+                let first_row = match self.param(0, 0) {
+                    2 => 0,
+                    _ => {
+                        self.clear_cells(self.row, self.col, self.cols);
+                        self.row + 1
+                    }
+                };
+                for row in first_row..self.rows {
+                    self.clear_cells(row, 0, self.cols);
+                }
+            }
+            b'K' => self.clear_cells(self.row, self.col, self.cols),
+            b'm' => self.set_graphics(),
+            _ => {}
+        }
+    }
+
+    /// Applies the parameters of a "select graphic rendition" sequence.
+    fn set_graphics(&mut self) {
+        if self.param_count == 0 {
+            self.params[0] = 0;
+            self.param_count = 1;
+        }
+
+        for i in 0..self.param_count {
+            match self.params[i] as usize {
+                0 => {
+                    self.fg = DEFAULT_FG;
+                    self.bg = DEFAULT_BG;
+                    self.bold = false;
+                }
+                1 => self.bold = true,
+                22 => self.bold = false,
+                code @ 30..=37 => self.fg = code - 30,
+                39 => self.fg = DEFAULT_FG,
+                code @ 40..=47 => self.bg = code - 40,
+                49 => self.bg = DEFAULT_BG,
+                code @ 90..=97 => self.fg = code - 90 + 8,
+                code @ 100..=107 => self.bg = code - 100 + 8,
+                _ => {}
+            }
+        }
+    }
+
+    /// Writes `byte`, interpreting control characters and escape sequences.
+    pub fn write_byte(&mut self, byte: u8) {
+        match (self.state, byte) {
+            (State::Normal, ESC) => self.state = State::Escape,
+            (State::Normal, b'\n') => self.newline(),
+            (State::Normal, b'\r') => self.col = 0,
+            (State::Normal, 0x08) => self.col = self.col.saturating_sub(1),
+            // Tab stops are every 8 columns, and at the right edge.
+            (State::Normal, b'\t') => self.col = core::cmp::min((self.col / 8 + 1) * 8, self.cols - 1),
+            (State::Normal, 0x07) => {}
+            (State::Normal, byte) => self.put(byte),
+            (State::Escape, b'[') => {
+                self.state = State::Csi;
+                self.params = [0; MAX_PARAMS];
+                self.param_count = 0;
+            }
+            (State::Escape, _) => self.state = State::Normal,
+            (State::Csi, digit @ b'0'..=b'9') => {
+                if self.param_count == 0 {
+                    self.param_count = 1;
+                }
+                if let Some(param) = self.params.get_mut(self.param_count - 1) {
+                    *param = param.saturating_mul(10).saturating_add((digit - b'0') as u32);
+                }
+            }
+            (State::Csi, b';') => {
+                // An empty first parameter is still a parameter.
+                self.param_count = core::cmp::min(core::cmp::max(self.param_count, 1) + 1, MAX_PARAMS + 1);
+            }
+            (State::Csi, command @ 0x40..=0x7E) => {
+                self.param_count = core::cmp::min(self.param_count, MAX_PARAMS);
+                self.state = State::Normal;
+                self.execute(command);
+            }
+            (State::Csi, _) => {}
+        }
+    }
+}
+
+impl fmt::Write for TextConsole {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for &byte in s.as_bytes() {
+            self.write_byte(byte);
+        }
+        Ok(())
+    }
+}
+
+/// The console on the framebuffer, once initialized.
+pub static FBCON: Mutex<Option<TextConsole>> = Mutex::new(None);
+
+/// Whether `kprint!` output is written to `FBCON`.
+static MIRROR: AtomicBool = AtomicBool::new(false);
+
+/// Errors starting the framebuffer console.
+#[derive(Debug)]
+pub enum Error {
+    /// No framebuffer could be allocated.
+    Framebuffer(framebuffer::Error),
+    /// The framebuffer is smaller than a character cell.
+    TooSmall,
+}
+
+impl From<framebuffer::Error> for Error {
+    fn from(error: framebuffer::Error) -> Error {
+        Error::Framebuffer(error)
+    }
+}
+
+/// Allocates a `width` by `height` framebuffer, starts a text console on it
+/// and mirrors the output of `kprint!` to it.
+pub fn initialize(width: u32, height: u32) -> Result<(), Error> {
+    let fb = Framebuffer::new(width, height)?;
+    *FBCON.lock() = Some(TextConsole::new(fb).ok_or(Error::TooSmall)?);
+    MIRROR.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Enables or disables mirroring the output of `kprint!` to the
+/// framebuffer console.
+pub fn set_mirroring(enabled: bool) {
+    MIRROR.store(enabled, Ordering::Relaxed);
+}
+
+/// Writes `args` to the framebuffer console if mirroring is enabled. Called
+/// by `kprint!`.
+pub fn mirror(args: fmt::Arguments) {
+    if !MIRROR.load(Ordering::Relaxed) {
+        return;
+    }
+
+    if let Some(console) = FBCON.lock().as_mut() {
+        let _ = fmt::Write::write_fmt(console, args);
+    }
+}
@@ -0,0 +1,120 @@
+use core::fmt::Write;
+
+use pi::framebuffer::{Color, Framebuffer, PixelOrder};
+
+use super::font::{self, GLYPH_SIZE};
+use super::{TextConsole, DEFAULT_BG, DEFAULT_FG, PALETTE};
+
+/// Returns a console of `cols` by `rows` cells over `pixels`, which must
+/// stay alive as long as the console.
+fn console(pixels: &mut Vec<u32>, cols: u32, rows: u32) -> TextConsole {
+    let (width, height) = (cols * GLYPH_SIZE, rows * GLYPH_SIZE);
+    pixels.resize((width * height) as usize, 0xDEAD);
+    let fb = unsafe { Framebuffer::from_raw_parts(pixels.as_mut_ptr(), width, height, width, PixelOrder::Bgr) };
+    TextConsole::new(fb).expect("console too small")
+}
+
+/// Returns `true` if the cell at `col`, `row` shows `byte` in `fg` on `bg`.
+fn shows(console: &TextConsole, col: u32, row: u32, byte: u8, fg: Color, bg: Color) -> bool {
+    font::glyph(byte).iter().enumerate().all(|(y, &bits)| {
+        (0..GLYPH_SIZE).all(|x| {
+            let expected = if bits & (1 << x) != 0 { fg } else { bg };
+            console.fb.pixel(col * GLYPH_SIZE + x, row * GLYPH_SIZE + y as u32) == Some(expected)
+        })
+    })
+}
+
+/// Returns `true` if the cells of `row` show `text` in the default colors.
+fn shows_line(console: &TextConsole, row: u32, text: &str) -> bool {
+    let (fg, bg) = (PALETTE[DEFAULT_FG], PALETTE[DEFAULT_BG]);
+    let (cols, _) = console.size();
+    let padded = text.bytes().chain(std::iter::repeat(b' '));
+    (0..cols).zip(padded).all(|(col, byte)| shows(console, col, row, byte, fg, bg))
+}
+
+#[test]
+fn draws_text() {
+    let mut pixels = Vec::new();
+    let mut console = console(&mut pixels, 4, 3);
+    assert_eq!(console.size(), (4, 3));
+    assert!(shows_line(&console, 0, ""));
+
+    write!(console, "ab\ncd\re").unwrap();
+    assert!(shows_line(&console, 0, "ab"));
+    assert!(shows_line(&console, 1, "ed"));
+    assert_eq!(console.cursor(), (1, 1));
+}
+
+#[test]
+fn wraps_and_scrolls() {
+    let mut pixels = Vec::new();
+    let mut console = console(&mut pixels, 4, 2);
+
+    write!(console, "abcdefgh").unwrap();
+    assert!(shows_line(&console, 0, "abcd"));
+    assert!(shows_line(&console, 1, "efgh"));
+
+    write!(console, "ij\n\tk").unwrap();
+    assert!(shows_line(&console, 0, "ij"));
+    assert!(shows_line(&console, 1, "   k"));
+}
+
+#[test]
+fn sets_colors() {
+    let mut pixels = Vec::new();
+    let mut console = console(&mut pixels, 8, 1);
+
+    write!(console, "\x1b[31ma\x1b[1;44mb\x1b[0mc\x1b[92;101md\x1b[39;49me").unwrap();
+    assert!(shows(&console, 0, 0, b'a', PALETTE[1], PALETTE[DEFAULT_BG]));
+    assert!(shows(&console, 1, 0, b'b', PALETTE[9], PALETTE[4]));
+    assert!(shows(&console, 2, 0, b'c', PALETTE[DEFAULT_FG], PALETTE[DEFAULT_BG]));
+    assert!(shows(&console, 3, 0, b'd', PALETTE[10], PALETTE[9]));
+    assert!(shows(&console, 4, 0, b'e', PALETTE[DEFAULT_FG], PALETTE[DEFAULT_BG]));
+}
+
+#[test]
+fn moves_cursor_and_clears() {
+    let mut pixels = Vec::new();
+    let mut console = console(&mut pixels, 4, 3);
+
+    write!(console, "abcd\nefgh\nijkl").unwrap();
+    write!(console, "\x1b[2;3H").unwrap();
+    assert_eq!(console.cursor(), (2, 1));
+    write!(console, "\x1b[K").unwrap();
+    assert!(shows_line(&console, 1, "ef"));
+
+    write!(console, "\x1b[A\x1b[2DX\x1b[B\x1b[C\x1b[H").unwrap();
+    assert!(shows_line(&console, 0, "Xbcd"));
+    assert_eq!(console.cursor(), (0, 0));
+
+    write!(console, "\x1b[2;1H\x1b[J").unwrap();
+    assert!(shows_line(&console, 0, "Xbcd"));
+    assert!(shows_line(&console, 1, ""));
+    assert!(shows_line(&console, 2, ""));
+
+    write!(console, "\x1b[2J").unwrap();
+    assert!(shows_line(&console, 0, ""));
+}
+
+#[test]
+fn draws_unknown_bytes_as_question_marks() {
+    let mut pixels = Vec::new();
+    let mut console = console(&mut pixels, 2, 1);
+    console.write_byte(0xFF);
+    assert!(shows_line(&console, 0, "?"));
+}
+
+#[test]
+fn rejects_framebuffers_smaller_than_a_cell() {
+    let mut pixels = vec![0xDEAD; (GLYPH_SIZE * GLYPH_SIZE) as usize];
+    for &(width, height) in [(GLYPH_SIZE - 1, GLYPH_SIZE), (GLYPH_SIZE, GLYPH_SIZE - 1), (0, 0)].iter() {
+        let fb = unsafe { Framebuffer::from_raw_parts(pixels.as_mut_ptr(), width, height, width, PixelOrder::Bgr) };
+        assert!(TextConsole::new(fb).is_none());
+    }
+    assert!(pixels.iter().all(|&pixel| pixel == 0xDEAD));
+
+    let mut console = console(&mut pixels, 1, 1);
+    console.write_str("\x1b[5B\x1b[5C\tab\n").unwrap();
+    assert_eq!(console.size(), (1, 1));
+    assert_eq!(console.cursor(), (0, 0));
+}
@@ -0,0 +1,7 @@
+pub mod emmc;
+pub mod sd;
+pub mod vfat;
+
+pub use self::emmc::EmmcDevice;
+pub use self::sd::Sd;
+pub use self::vfat::{PiVFatHandle, VFatFileSystem};
@@ -0,0 +1,53 @@
+use fat32::traits::BlockDevice;
+use pi::emmc::{Emmc, BLOCK_SIZE};
+use shim::io;
+
+/// An SD card driven by the native `pi::emmc` driver.
+///
+/// This is an alternative to `Sd`: both drive the same host controller, so at
+/// most one of the two may be in use. The kernel falls back to `Sd` when this
+/// driver fails to initialize.
+pub struct EmmcDevice(Emmc);
+
+impl EmmcDevice {
+    /// Initializes the EMMC controller and the inserted SD card.
+    ///
+    /// # Errors
+    ///
+    /// Returns the `pi::emmc::Error` raised during initialization converted
+    /// into an `io::Error`.
+    pub fn new() -> io::Result<EmmcDevice> {
+        Ok(EmmcDevice(Emmc::new()?))
+    }
+}
+
+impl BlockDevice for EmmcDevice {
+    fn sector_size(&self) -> u64 {
+        BLOCK_SIZE as u64
+    }
+
+    fn read_sector(&mut self, n: u64, buf: &mut [u8]) -> io::Result<usize> {
+        if n > u32::max_value() as u64 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "sector number too large"));
+        }
+
+        if buf.len() >= BLOCK_SIZE {
+            return Ok(self.0.read_blocks(n as u32, &mut buf[..BLOCK_SIZE])?);
+        }
+
+        let mut sector = [0u8; BLOCK_SIZE];
+        self.0.read_blocks(n as u32, &mut sector)?;
+        buf.copy_from_slice(&sector[..buf.len()]);
+        Ok(buf.len())
+    }
+
+    fn write_sector(&mut self, n: u64, buf: &[u8]) -> io::Result<usize> {
+        if n > u32::max_value() as u64 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "sector number too large"));
+        } else if buf.len() < BLOCK_SIZE {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "buffer too small"));
+        }
+
+        Ok(self.0.write_blocks(n as u32, &buf[..BLOCK_SIZE])?)
+    }
+}
@@ -0,0 +1,114 @@
+use core::time::Duration;
+
+use fat32::traits::BlockDevice;
+use pi::timer::spin_sleep;
+use shim::io;
+
+extern "C" {
+    /// A global representing the last SD controller error that occured.
+    static sd_err: i64;
+
+    /// Initializes the SD card controller.
+    ///
+    /// Returns 0 if initialization is successful. If initialization fails,
+    /// returns -1 if a timeout occured, or -2 if an error sending commands to
+    /// the SD controller occured.
+    fn sd_init() -> i32;
+
+    /// Reads sector `n` (512 bytes) from the SD card and writes it to `buffer`.
+    /// It is undefined behavior if `buffer` does not point to at least 512
+    /// bytes of memory. Also, the caller of this function should make sure that
+    /// `buffer` is at least 4-byte aligned.
+    ///
+    /// On success, returns the number of bytes read: a positive number.
+    ///
+    /// On error, returns 0. The true error code is stored in the `sd_err`
+    /// global. `sd_err` will be set to -1 if a timeout occured or -2 if an
+    /// error sending commands to the SD controller occured. Other error codes
+    /// are also possible but defined only as being less than zero.
+    fn sd_readsector(n: i32, buffer: *mut u8) -> i32;
+}
+
+/// Busy-waits for `us` microseconds. Called by `libsd` while it waits on the
+/// SD host controller.
+#[no_mangle]
+pub extern "C" fn wait_micros(us: u32) {
+    spin_sleep(Duration::from_micros(us as u64));
+}
+
+/// The size of a sector as read by `sd_readsector`.
+const SECTOR_SIZE: usize = 512;
+
+/// A sector buffer satisfying `sd_readsector`'s alignment requirement.
+#[repr(C, align(4))]
+struct SectorBuf([u8; SECTOR_SIZE]);
+
+/// Maps a `libsd` error code to an `io::Error`.
+fn sd_error(code: i64) -> io::Error {
+    match code {
+        -1 => io::Error::new(io::ErrorKind::TimedOut, "SD controller timed out"),
+        -2 => io::Error::new(io::ErrorKind::BrokenPipe, "error sending command to SD controller"),
+        _ => io::Error::new(io::ErrorKind::Other, "unknown SD controller error"),
+    }
+}
+
+/// A handle to an SD card controller.
+#[derive(Debug)]
+pub struct Sd;
+
+impl Sd {
+    /// Initializes the SD card controller and returns a handle to it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `TimedOut` if the controller timed out and
+    /// `BrokenPipe` if sending a command to it failed.
+    ///
+    /// # Safety
+    ///
+    /// The caller should assure that this method is invoked only once during
+    /// the kernel initialization, and that no other code drives the SD host
+    /// controller.
+    pub unsafe fn new() -> Result<Sd, io::Error> {
+        match sd_init() {
+            0 => Ok(Sd),
+            code => Err(sd_error(code as i64)),
+        }
+    }
+}
+
+impl BlockDevice for Sd {
+    /// Reads sector `n` from the SD card into `buf`. On success, the number of
+    /// bytes read is returned.
+    ///
+    /// # Errors
+    ///
+    /// An I/O error of kind `InvalidInput` is returned if `n` is greater than
+    /// 2^31 - 1 (the maximum value for an `i32`).
+    ///
+    /// An error of kind `TimedOut` is returned if a timeout occurs while
+    /// reading from the SD card, and one of kind `BrokenPipe` if sending a
+    /// command to the SD controller fails.
+    ///
+    /// An error of kind `Other` is returned for all other errors.
+    fn read_sector(&mut self, n: u64, buf: &mut [u8]) -> io::Result<usize> {
+        if n > i32::max_value() as u64 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "sector number too large"));
+        }
+
+        let mut sector = SectorBuf([0u8; SECTOR_SIZE]);
+        let read = unsafe { sd_readsector(n as i32, sector.0.as_mut_ptr()) };
+        if read <= 0 {
+            return Err(sd_error(unsafe { sd_err }));
+        }
+
+        let len = core::cmp::min(buf.len(), read as usize);
+        buf[..len].copy_from_slice(&sector.0[..len]);
+        Ok(len)
+    }
+
+    /// `libsd` has no write support; always returns an error.
+    fn write_sector(&mut self, _n: u64, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(io::ErrorKind::PermissionDenied, "SD card is read-only"))
+    }
+}
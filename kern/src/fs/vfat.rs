@@ -0,0 +1,187 @@
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use core::fmt;
+
+use fat32::traits::BlockDevice;
+use fat32::vfat::{self, VFat, VFatHandle};
+use shim::io;
+
+use crate::mutex::Mutex;
+use crate::vfs::{self, Timestamp};
+
+/// A handle to a FAT32 filesystem shared through the kernel's spin lock.
+#[derive(Clone)]
+pub struct PiVFatHandle(Arc<Mutex<VFat<Self>>>);
+
+impl fmt::Debug for PiVFatHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PiVFatHandle")
+    }
+}
+
+impl VFatHandle for PiVFatHandle {
+    fn new(val: VFat<PiVFatHandle>) -> Self {
+        PiVFatHandle(Arc::new(Mutex::new(val)))
+    }
+
+    fn lock<R>(&self, f: impl FnOnce(&mut VFat<PiVFatHandle>) -> R) -> R {
+        f(&mut self.0.lock())
+    }
+}
+
+/// A FAT32 filesystem mountable in the VFS.
+pub struct VFatFileSystem(PiVFatHandle);
+
+impl VFatFileSystem {
+    /// Mounts the FAT32 filesystem in the first FAT32 partition of `device`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error from reading the device, or an error of kind
+    /// `InvalidData` if it holds no valid FAT32 partition.
+    pub fn new<T: BlockDevice + 'static>(device: T) -> io::Result<VFatFileSystem> {
+        match VFat::<PiVFatHandle>::from(device) {
+            Ok(handle) => Ok(VFatFileSystem(handle)),
+            Err(vfat::Error::Io(e)) => Err(e),
+            Err(_) => Err(io::Error::new(io::ErrorKind::InvalidData, "no FAT32 filesystem found")),
+        }
+    }
+
+    /// Returns the handle to the underlying filesystem.
+    pub fn handle(&self) -> &PiVFatHandle {
+        &self.0
+    }
+}
+
+impl vfs::FileSystem for VFatFileSystem {
+    fn kind(&self) -> &str {
+        "vfat"
+    }
+
+    fn root(&self) -> io::Result<Box<dyn vfs::Dir>> {
+        Ok(Box::new(self.0.root()))
+    }
+
+    fn sync(&self) -> io::Result<()> {
+        self.0.lock(|vfat| vfat.sync())
+    }
+}
+
+fn timestamp(ts: vfat::Timestamp) -> Timestamp {
+    Timestamp {
+        year: ts.year() as u16,
+        month: ts.month(),
+        day: ts.day(),
+        hour: ts.hour(),
+        minute: ts.minute(),
+        second: ts.second(),
+    }
+}
+
+impl vfs::Metadata for vfat::Metadata {
+    fn read_only(&self) -> bool {
+        vfat::Metadata::read_only(self)
+    }
+
+    fn hidden(&self) -> bool {
+        vfat::Metadata::hidden(self)
+    }
+
+    fn created(&self) -> Option<Timestamp> {
+        Some(timestamp(self.created))
+    }
+
+    fn accessed(&self) -> Option<Timestamp> {
+        Some(timestamp(self.accessed))
+    }
+
+    fn modified(&self) -> Option<Timestamp> {
+        Some(timestamp(self.modified))
+    }
+}
+
+impl vfs::File for vfat::File<PiVFatHandle> {
+    fn name(&self) -> &str {
+        vfat::File::name(self)
+    }
+
+    fn metadata(&self) -> &dyn vfs::Metadata {
+        vfat::File::metadata(self)
+    }
+
+    fn size(&self) -> u64 {
+        vfat::File::size(self)
+    }
+
+    fn sync(&mut self) -> io::Result<()> {
+        io::Write::flush(self)
+    }
+
+    fn set_len(&mut self, size: u64) -> io::Result<()> {
+        vfat::File::set_len(self, size)
+    }
+}
+
+impl vfs::Dir for vfat::Dir<PiVFatHandle> {
+    fn name(&self) -> &str {
+        vfat::Dir::name(self)
+    }
+
+    fn metadata(&self) -> &dyn vfs::Metadata {
+        vfat::Dir::metadata(self)
+    }
+
+    fn entries(&self) -> io::Result<Box<dyn Iterator<Item = Box<dyn vfs::Entry>>>> {
+        let entries = vfat::Dir::entries(self)?;
+        Ok(Box::new(entries.map(|entry| Box::new(entry) as Box<dyn vfs::Entry>)))
+    }
+
+    fn find(&self, name: &str) -> io::Result<Box<dyn vfs::Entry>> {
+        Ok(Box::new(vfat::Dir::find(self, name)?))
+    }
+
+    fn create_file(&self, name: &str) -> io::Result<Box<dyn vfs::File>> {
+        Ok(Box::new(vfat::Dir::create_file(self, name)?))
+    }
+
+    fn create_dir(&self, name: &str) -> io::Result<Box<dyn vfs::Dir>> {
+        Ok(Box::new(vfat::Dir::create_dir(self, name)?))
+    }
+
+    fn remove(&self, name: &str) -> io::Result<()> {
+        match vfat::Dir::find(self, name)? {
+            vfat::Entry::File(_) => self.remove_file(name),
+            vfat::Entry::Dir(_) => self.remove_dir(name),
+        }
+    }
+
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        vfat::Dir::rename(self, from, to)
+    }
+}
+
+impl vfs::Entry for vfat::Entry<PiVFatHandle> {
+    fn name(&self) -> &str {
+        vfat::Entry::name(self)
+    }
+
+    fn metadata(&self) -> &dyn vfs::Metadata {
+        vfat::Entry::metadata(self)
+    }
+
+    fn size(&self) -> u64 {
+        self.as_file().map_or(0, |file| file.size())
+    }
+
+    fn is_file(&self) -> bool {
+        vfat::Entry::is_file(self)
+    }
+
+    fn into_file(self: Box<Self>) -> Option<Box<dyn vfs::File>> {
+        vfat::Entry::into_file(*self).map(|file| Box::new(file) as Box<dyn vfs::File>)
+    }
+
+    fn into_dir(self: Box<Self>) -> Option<Box<dyn vfs::Dir>> {
+        vfat::Entry::into_dir(*self).map(|dir| Box::new(dir) as Box<dyn vfs::Dir>)
+    }
+}
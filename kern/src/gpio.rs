@@ -0,0 +1,84 @@
+//! Per-pin callbacks for GPIO events.
+//!
+//! Pins detect events once configured with `Gpio<Input>::enable_event`.
+//! Every detected event raises one of the GPIO interrupts, whose IRQ handler
+//! runs the callback registered for the pin and clears its event status.
+
+#[cfg(test)]
+mod tests;
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use pi::gpio;
+use pi::interrupt::Interrupt;
+
+use crate::mutex::Mutex;
+use crate::traps;
+use crate::{GPIO_EVENTS, IRQ};
+
+/// A function run in the IRQ handler when its pin detects an event. It is
+/// passed the pin number.
+pub type GpioHandler = Box<dyn FnMut(u8) + Send>;
+
+/// The GPIO interrupts. Each covers a different bank of pins.
+const INTERRUPTS: [Interrupt; 3] = [Interrupt::Gpio0, Interrupt::Gpio1, Interrupt::Gpio2];
+
+/// The callbacks registered for GPIO pins.
+pub struct GpioEvents(Mutex<Vec<(u8, GpioHandler)>>);
+
+impl GpioEvents {
+    /// Returns a table without callbacks.
+    pub const fn new() -> GpioEvents {
+        GpioEvents(Mutex::new(Vec::new()))
+    }
+
+    /// Registers `handler` to run when `pin` detects an event, replacing the
+    /// callback registered before, if any. IRQs are masked meanwhile, since
+    /// `Mutex` doesn't keep the IRQ handler out.
+    pub fn register(&self, pin: u8, handler: GpioHandler) {
+        traps::without_irqs(|| {
+            let mut handlers = self.0.lock();
+            match handlers.iter_mut().find(|(p, _)| *p == pin) {
+                Some(entry) => entry.1 = handler,
+                None => handlers.push((pin, handler)),
+            }
+        })
+    }
+
+    /// Removes the callback of `pin`. Returns `false` if it had none.
+    pub fn unregister(&self, pin: u8) -> bool {
+        traps::without_irqs(|| {
+            let mut handlers = self.0.lock();
+            let len = handlers.len();
+            handlers.retain(|(p, _)| *p != pin);
+            handlers.len() != len
+        })
+    }
+
+    /// Runs the callbacks of the pins whose bits are set in `pending`.
+    fn run(&self, pending: u64) {
+        for (pin, handler) in self.0.lock().iter_mut() {
+            if pending & (1 << *pin) != 0 {
+                handler(*pin);
+            }
+        }
+    }
+
+    /// Runs the callbacks of the pins with pending events, then clears all
+    /// pending events, including those of pins without a callback.
+    fn dispatch(&self) {
+        let pending = gpio::pending_events();
+        self.run(pending);
+        gpio::clear_events(pending);
+    }
+}
+
+/// Registers the IRQ handler for GPIO events and enables the GPIO
+/// interrupts.
+pub fn initialize() {
+    for &int in INTERRUPTS.iter() {
+        IRQ.register(int, Box::new(|_| GPIO_EVENTS.dispatch()));
+        traps::enable_interrupt(int);
+    }
+}
@@ -0,0 +1,49 @@
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use super::GpioEvents;
+
+/// Registers a callback for `pin` on `events` counting its calls in
+/// `count`.
+fn count_events(events: &GpioEvents, pin: u8, count: &Arc<AtomicUsize>) {
+    let count = count.clone();
+    events.register(
+        pin,
+        Box::new(move |p| {
+            assert_eq!(p, pin);
+            count.fetch_add(1, Ordering::Relaxed);
+        }),
+    );
+}
+
+#[test]
+fn runs_callbacks_of_pending_pins() {
+    let events = GpioEvents::new();
+    let (a, b) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+    count_events(&events, 5, &a);
+    count_events(&events, 40, &b);
+
+    events.run(1 << 5);
+    events.run(1 << 5 | 1 << 40 | 1 << 7);
+    events.run(0);
+    assert_eq!(a.load(Ordering::Relaxed), 2);
+    assert_eq!(b.load(Ordering::Relaxed), 1);
+}
+
+#[test]
+fn register_replaces_and_unregister_removes() {
+    let events = GpioEvents::new();
+    let (old, new) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+    count_events(&events, 3, &old);
+    count_events(&events, 3, &new);
+
+    events.run(1 << 3);
+    assert_eq!(old.load(Ordering::Relaxed), 0);
+    assert_eq!(new.load(Ordering::Relaxed), 1);
+
+    assert!(events.unregister(3));
+    assert!(!events.unregister(3));
+    events.run(1 << 3);
+    assert_eq!(new.load(Ordering::Relaxed), 1);
+}
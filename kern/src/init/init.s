@@ -75,12 +75,15 @@ switch_to_el1:
     msr     SCTLR_EL1, x2
 
     // set up exception handlers
-    // FIXME: load `_vectors` addr into appropriate register (guide: 10.4)
+    ldr     x2, =_vectors
+    msr     VBAR_EL1, x2
 
     // change execution level to EL1 (ref: C5.2.19)
     mov     x2, #0x3c5
     msr     SPSR_EL2, x2
-    // FIXME: Return to EL1 at `set_stack`.
+    adr     x2, set_stack
+    msr     ELR_EL2, x2
+    eret
 
 set_stack:
     // set the current stack pointer
@@ -103,24 +106,132 @@ go_kmain:
     bl      kinit
     b       halt
 
+// Saves the registers the vector hasn't saved yet, along with the return
+// address and state, in a `TrapFrame` on the stack, and calls
+// `handle_exception(info, esr, tf)`. On entry, x0 holds the `Info` and lr
+// the address to return to in the vector. The SIMD/FP registers are saved
+// too: the kernel uses them, in `memcpy` for instance, and so do handlers.
 context_save:
-    // FIXME: Save the remaining context to the stack.
-
+    sub     sp, sp, #784
+    stp     x1, x2, [sp, #16]
+    stp     x3, x4, [sp, #32]
+    stp     x5, x6, [sp, #48]
+    stp     x7, x8, [sp, #64]
+    stp     x9, x10, [sp, #80]
+    stp     x11, x12, [sp, #96]
+    stp     x13, x14, [sp, #112]
+    stp     x15, x16, [sp, #128]
+    stp     x17, x18, [sp, #144]
+    stp     x19, x20, [sp, #160]
+    stp     x21, x22, [sp, #176]
+    stp     x23, x24, [sp, #192]
+    stp     x25, x26, [sp, #208]
+    stp     x27, x28, [sp, #224]
+    stp     x29, lr, [sp, #240]
+    mrs     x1, ELR_EL1
+    mrs     x2, SPSR_EL1
+    stp     x1, x2, [sp]
+    mrs     x1, FPCR
+    mrs     x2, FPSR
+    stp     x1, x2, [sp, #256]
+    stp     q0, q1, [sp, #272]
+    stp     q2, q3, [sp, #304]
+    stp     q4, q5, [sp, #336]
+    stp     q6, q7, [sp, #368]
+    stp     q8, q9, [sp, #400]
+    stp     q10, q11, [sp, #432]
+    stp     q12, q13, [sp, #464]
+    stp     q14, q15, [sp, #496]
+    stp     q16, q17, [sp, #528]
+    stp     q18, q19, [sp, #560]
+    stp     q20, q21, [sp, #592]
+    stp     q22, q23, [sp, #624]
+    stp     q24, q25, [sp, #656]
+    stp     q26, q27, [sp, #688]
+    stp     q28, q29, [sp, #720]
+    stp     q30, q31, [sp, #752]
+
+    mrs     x1, ESR_EL1
+    mov     x2, sp
+    bl      handle_exception
+
+// Restores the registers saved by `context_save` and returns to the vector.
 .global context_restore
 context_restore:
-    // FIXME: Restore the context from the stack.
-
+    ldp     q0, q1, [sp, #272]
+    ldp     q2, q3, [sp, #304]
+    ldp     q4, q5, [sp, #336]
+    ldp     q6, q7, [sp, #368]
+    ldp     q8, q9, [sp, #400]
+    ldp     q10, q11, [sp, #432]
+    ldp     q12, q13, [sp, #464]
+    ldp     q14, q15, [sp, #496]
+    ldp     q16, q17, [sp, #528]
+    ldp     q18, q19, [sp, #560]
+    ldp     q20, q21, [sp, #592]
+    ldp     q22, q23, [sp, #624]
+    ldp     q24, q25, [sp, #656]
+    ldp     q26, q27, [sp, #688]
+    ldp     q28, q29, [sp, #720]
+    ldp     q30, q31, [sp, #752]
+    ldp     x1, x2, [sp, #256]
+    msr     FPCR, x1
+    msr     FPSR, x2
+    ldp     x1, x2, [sp]
+    msr     ELR_EL1, x1
+    msr     SPSR_EL1, x2
+    ldp     x1, x2, [sp, #16]
+    ldp     x3, x4, [sp, #32]
+    ldp     x5, x6, [sp, #48]
+    ldp     x7, x8, [sp, #64]
+    ldp     x9, x10, [sp, #80]
+    ldp     x11, x12, [sp, #96]
+    ldp     x13, x14, [sp, #112]
+    ldp     x15, x16, [sp, #128]
+    ldp     x17, x18, [sp, #144]
+    ldp     x19, x20, [sp, #160]
+    ldp     x21, x22, [sp, #176]
+    ldp     x23, x24, [sp, #192]
+    ldp     x25, x26, [sp, #208]
+    ldp     x27, x28, [sp, #224]
+    ldp     x29, lr, [sp, #240]
+    add     sp, sp, #784
     ret
 
-// #define HANDLER(source, kind) \
-//     .align 7; \
-//     stp     lr, x0, [SP, #-16]!; \
-//     mov     x0, ##source; \
-//     movk    x0, ##kind, LSL #16; \
-//     bl      context_save; \
-//     ldp     lr, x0, [SP], #16; \
-//     eret
+// An exception vector entry: calls `context_save` with the `Info` for
+// `source` and `kind` in x0, then returns from the exception.
+.macro HANDLER source, kind
+    .align 7
+    stp     lr, x0, [SP, #-16]!
+    mov     x0, #\source
+    movk    x0, #\kind, LSL #16
+    bl      context_save
+    ldp     lr, x0, [SP], #16
+    eret
+.endm
 
 .align 11
 _vectors:
-    // FIXME: Setup the 16 exception vectors.
+    // current EL with SP_EL0
+    HANDLER 0, 0
+    HANDLER 0, 1
+    HANDLER 0, 2
+    HANDLER 0, 3
+
+    // current EL with SP_ELx
+    HANDLER 1, 0
+    HANDLER 1, 1
+    HANDLER 1, 2
+    HANDLER 1, 3
+
+    // lower EL using AArch64
+    HANDLER 2, 0
+    HANDLER 2, 1
+    HANDLER 2, 2
+    HANDLER 2, 3
+
+    // lower EL using AArch32
+    HANDLER 3, 0
+    HANDLER 3, 1
+    HANDLER 3, 2
+    HANDLER 3, 3
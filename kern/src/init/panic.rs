@@ -1,6 +1,13 @@
 use core::panic::PanicInfo;
 
+use crate::console::kprintln;
+use crate::traps;
+
 #[panic_handler]
-fn panic(_info: &PanicInfo) -> ! {
+fn panic(info: &PanicInfo) -> ! {
+    // With IRQs masked, the console polls the UART instead of relying on
+    // the interrupt handler.
+    traps::disable_irqs();
+    kprintln!("kernel panic: {}", info);
     loop {}
 }
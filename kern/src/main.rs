@@ -8,21 +8,82 @@
 #![cfg_attr(not(test), no_main)]
 #![feature(negative_impls)]
 
+extern crate alloc;
+
 #[cfg(not(test))]
 mod init;
 
+pub mod allocator;
 pub mod console;
+pub mod fbcon;
+pub mod fs;
+pub mod gpio;
 pub mod mutex;
+pub mod random;
+pub mod ring;
 pub mod shell;
+pub mod traps;
+pub mod tty;
+pub mod vfs;
+pub mod watchdog;
 
+use allocator::Allocator;
 use console::{Console, CONSOLE, kprintln};
 use pi::uart::MiniUart;
 use core::fmt::Write;
+use fs::{EmmcDevice, Sd, VFatFileSystem};
+use gpio::GpioEvents;
+use shim::path::Path;
+use traps::Irq;
+use vfs::Vfs;
+
+#[cfg_attr(not(test), global_allocator)]
+pub static ALLOCATOR: Allocator = Allocator::uninitialized();
+
+pub static VFS: Vfs = Vfs::new();
+
+pub static IRQ: Irq = Irq::new();
+
+pub static GPIO_EVENTS: GpioEvents = GpioEvents::new();
+
+/// Mounts the FAT32 filesystem on the SD card as the root directory, read
+/// through the native EMMC driver or, if that can't bring up the card,
+/// through `libsd`. The kernel keeps running without files if both fail.
+fn mount_root() {
+    let fs = match EmmcDevice::new() {
+        Ok(device) => VFatFileSystem::new(device),
+        Err(e) => {
+            kprintln!("native SD driver failed ({:?}), trying libsd", e);
+            // This runs once, and the failed `EmmcDevice` no longer drives
+            // the controller.
+            unsafe { Sd::new() }.and_then(VFatFileSystem::new)
+        }
+    };
+    match fs.and_then(|fs| VFS.lock().mount(Path::new("/"), alloc::boxed::Box::new(fs))) {
+        Ok(()) => {}
+        Err(e) => kprintln!("failed to mount the SD card: {:?}", e),
+    }
+}
 
 // FIXME: You need to add dependencies here to
 // test your drivers (Phase 2). Add them as needed.
 
 fn kmain() -> ! {
+    unsafe {
+        ALLOCATOR.initialize();
+    }
+
+    if let Err(e) = fbcon::initialize(640, 480) {
+        kprintln!("no framebuffer console: {:?}", e);
+    }
+
+    mount_root();
+
+    console::initialize();
+    gpio::initialize();
+    watchdog::initialize();
+    traps::enable_irqs();
+
     // FIXME: Start the shell.
     shell::shell("> ")
 }
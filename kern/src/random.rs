@@ -0,0 +1,181 @@
+//! The kernel's cryptographically secure random number generator.
+//!
+//! Random bytes come from ChaCha20 run as a "fast key erasure" generator:
+//! each block of key stream replaces the key with its first half and hands
+//! out the second, so bytes already returned can't be recovered from the
+//! generator's state. The key is seeded from the hardware RNG and from timer
+//! jitter on first use, and reseeded from the hardware RNG every
+//! `RESEED_INTERVAL` bytes.
+
+mod chacha;
+mod commands;
+
+#[cfg(test)]
+mod tests;
+
+use crate::mutex::Mutex;
+
+use self::chacha::BLOCK_SIZE;
+
+pub use self::commands::COMMANDS;
+
+/// Size of the generator's key in bytes.
+pub const SEED_SIZE: usize = 32;
+
+/// Number of bytes handed out before the generator is reseeded.
+const RESEED_INTERVAL: usize = 1 << 20;
+
+/// The nonces of the blocks producing output and mixing in entropy, so the
+/// two never share key stream.
+const OUTPUT_NONCE: [u32; 3] = [0, 0, 0];
+const MIX_NONCE: [u32; 3] = [1, 0, 0];
+
+/// A ChaCha20 random number generator.
+pub struct Csprng {
+    key: [u32; 8],
+    /// Key stream not handed out yet, at the end of `buffer`.
+    buffer: [u8; BLOCK_SIZE - SEED_SIZE],
+    available: usize,
+    /// Bytes handed out since the last reseed.
+    output: usize,
+}
+
+impl Csprng {
+    /// Returns a generator seeded with `seed`.
+    pub fn new(seed: &[u8; SEED_SIZE]) -> Csprng {
+        let mut rng = Csprng { key: [0; 8], buffer: [0; BLOCK_SIZE - SEED_SIZE], available: 0, output: 0 };
+        rng.add_entropy(seed);
+        rng
+    }
+
+    /// Replaces the key with the first half of `block`, returning the rest.
+    fn rekey(&mut self, block: [u8; BLOCK_SIZE]) -> [u8; BLOCK_SIZE - SEED_SIZE] {
+        for (word, bytes) in self.key.iter_mut().zip(block.chunks_exact(4)) {
+            *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+
+        let mut rest = [0; BLOCK_SIZE - SEED_SIZE];
+        rest.copy_from_slice(&block[SEED_SIZE..]);
+        rest
+    }
+
+    /// Mixes `entropy` into the key. Output that was buffered is discarded,
+    /// so everything returned afterwards depends on `entropy`.
+    pub fn add_entropy(&mut self, entropy: &[u8]) {
+        for chunk in entropy.chunks(SEED_SIZE) {
+            for (i, &byte) in chunk.iter().enumerate() {
+                self.key[i / 4] ^= (byte as u32) << (8 * (i % 4));
+            }
+            let block = chacha::block(&self.key, 0, &MIX_NONCE);
+            self.rekey(block);
+        }
+
+        self.buffer = [0; BLOCK_SIZE - SEED_SIZE];
+        self.available = 0;
+        self.output = 0;
+    }
+
+    /// Returns `true` once the generator has handed out `RESEED_INTERVAL`
+    /// bytes since it was last seeded.
+    pub fn needs_reseed(&self) -> bool {
+        self.output >= RESEED_INTERVAL
+    }
+
+    /// Fills `buf` with random bytes.
+    pub fn fill_bytes(&mut self, buf: &mut [u8]) {
+        for byte in buf.iter_mut() {
+            if self.available == 0 {
+                let block = chacha::block(&self.key, 0, &OUTPUT_NONCE);
+                self.buffer = self.rekey(block);
+                self.available = self.buffer.len();
+            }
+
+            // Erase each byte as it is handed out.
+            let index = self.buffer.len() - self.available;
+            *byte = core::mem::replace(&mut self.buffer[index], 0);
+            self.available -= 1;
+        }
+        self.output = self.output.saturating_add(buf.len());
+    }
+
+    /// Returns 32 random bits.
+    pub fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0; 4];
+        self.fill_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    /// Returns 64 random bits.
+    pub fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+}
+
+/// The kernel's generator, seeded on first use.
+static RNG: Mutex<Option<Csprng>> = Mutex::new(None);
+
+/// Returns the value of the ARM generic timer's counter, which ticks much
+/// faster than the system timer.
+fn counter() -> u64 {
+    #[cfg(not(test))]
+    unsafe {
+        let count: u64;
+        core::arch::asm!("isb; mrs {}, CNTPCT_EL0", out(reg) count);
+        count
+    }
+
+    #[cfg(test)]
+    0
+}
+
+/// Returns bytes gathered from timer jitter: the number of ticks a short
+/// busy loop takes varies with cache, bus and DRAM refresh timing. Each byte
+/// folds the low bits of several measurements, since a single one holds
+/// little entropy.
+fn timer_jitter() -> [u8; SEED_SIZE] {
+    let mut jitter = [0u8; SEED_SIZE];
+    for byte in jitter.iter_mut() {
+        for _ in 0..8 {
+            let start = counter();
+            for _ in 0..(start & 0x3F) {
+                core::hint::spin_loop();
+            }
+            *byte = byte.rotate_left(3) ^ counter().wrapping_sub(start) as u8;
+        }
+    }
+    jitter
+}
+
+/// Returns a seed read from the hardware RNG.
+fn hardware_seed() -> [u8; SEED_SIZE] {
+    let mut seed = [0; SEED_SIZE];
+    pi::rng::Rng::new().fill_bytes(&mut seed);
+    seed
+}
+
+/// Runs `f` on the kernel's generator, seeding or reseeding it first if
+/// needed.
+fn with_rng<T>(f: impl FnOnce(&mut Csprng) -> T) -> T {
+    let mut guard = RNG.lock();
+    let rng = guard.get_or_insert_with(|| {
+        let mut rng = Csprng::new(&hardware_seed());
+        rng.add_entropy(&timer_jitter());
+        rng
+    });
+    if rng.needs_reseed() {
+        rng.add_entropy(&hardware_seed());
+    }
+    f(rng)
+}
+
+/// Fills `buf` with random bytes from the kernel's generator.
+pub fn fill_bytes(buf: &mut [u8]) {
+    with_rng(|rng| rng.fill_bytes(buf))
+}
+
+/// Returns 64 random bits from the kernel's generator.
+pub fn next_u64() -> u64 {
+    with_rng(|rng| rng.next_u64())
+}
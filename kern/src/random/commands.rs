@@ -0,0 +1,33 @@
+use core::cmp;
+
+use shim::io;
+
+use crate::shell::{parse_number, write_hex_line, Builtin, Context, Error, ShellCommand, HEXDUMP_WIDTH};
+
+/// The largest number of bytes `rand` prints, keeping a mistyped length
+/// from flooding the console.
+const MAX_LEN: u64 = 4096;
+
+/// The shell commands using the kernel's random number generator.
+pub static COMMANDS: &[&dyn ShellCommand] =
+    &[&Builtin { name: "rand", help: "print random bytes in hex", usage: "rand [len]", run: rand }];
+
+/// Prints `args[0]` random bytes, 16 without an argument.
+fn rand(_: &mut Context, args: &[&str], io: &mut dyn io::Write) -> Result<(), Error> {
+    let len = match args {
+        [] => HEXDUMP_WIDTH as u64,
+        [len] => parse_number(len)?,
+        _ => return Err(Error::Usage),
+    };
+    if len > MAX_LEN {
+        return Err(Error::Usage);
+    }
+
+    let mut bytes = [0; HEXDUMP_WIDTH];
+    for offset in (0..len).step_by(HEXDUMP_WIDTH) {
+        let line = &mut bytes[..cmp::min(len - offset, HEXDUMP_WIDTH as u64) as usize];
+        super::fill_bytes(line);
+        write_hex_line(io, offset, line)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use super::chacha;
+use super::{Csprng, SEED_SIZE};
+
+#[test]
+fn chacha_block_matches_rfc_8439() {
+    // Section 2.3.2 of RFC 8439.
+    let mut key = [0; 8];
+    for (i, word) in key.iter_mut().enumerate() {
+        let byte = 4 * i as u32;
+        *word = u32::from_le_bytes([byte as u8, byte as u8 + 1, byte as u8 + 2, byte as u8 + 3]);
+    }
+    let nonce = [0x0900_0000, 0x4a00_0000, 0];
+
+    let expected: [u8; 64] = [
+        0x10, 0xf1, 0xe7, 0xe4, 0xd1, 0x3b, 0x59, 0x15, 0x50, 0x0f, 0xdd, 0x1f, 0xa3, 0x20, 0x71, 0xc4, //
+        0xc7, 0xd1, 0xf4, 0xc7, 0x33, 0xc0, 0x68, 0x03, 0x04, 0x22, 0xaa, 0x9a, 0xc3, 0xd4, 0x6c, 0x4e, //
+        0xd2, 0x82, 0x64, 0x46, 0x07, 0x9f, 0xaa, 0x09, 0x14, 0xc2, 0xd7, 0x05, 0xd9, 0x8b, 0x02, 0xa2, //
+        0xb5, 0x12, 0x9c, 0xd1, 0xde, 0x16, 0x4e, 0xb9, 0xcb, 0xd0, 0x83, 0xe8, 0xa2, 0x50, 0x3c, 0x4e, //
+    ];
+    assert_eq!(&chacha::block(&key, 1, &nonce)[..], &expected[..]);
+}
+
+#[test]
+fn output_depends_only_on_seed() {
+    let mut whole = [0; 100];
+    Csprng::new(&[7; SEED_SIZE]).fill_bytes(&mut whole);
+
+    let mut pieces = [0; 100];
+    let mut rng = Csprng::new(&[7; SEED_SIZE]);
+    for chunk in pieces.chunks_mut(13) {
+        rng.fill_bytes(chunk);
+    }
+    assert_eq!(&whole[..], &pieces[..]);
+
+    let mut other = [0; 100];
+    Csprng::new(&[8; SEED_SIZE]).fill_bytes(&mut other);
+    assert_ne!(&whole[..], &other[..]);
+}
+
+#[test]
+fn key_stream_never_repeats() {
+    let mut rng = Csprng::new(&[0; SEED_SIZE]);
+    let mut blocks = [[0; 32]; 4];
+    for block in blocks.iter_mut() {
+        rng.fill_bytes(block);
+    }
+
+    for (i, a) in blocks.iter().enumerate() {
+        assert!(blocks[i + 1..].iter().all(|b| a != b));
+    }
+}
+
+#[test]
+fn entropy_changes_output() {
+    let mut a = Csprng::new(&[1; SEED_SIZE]);
+    let mut b = Csprng::new(&[1; SEED_SIZE]);
+    assert_eq!(a.next_u64(), b.next_u64());
+
+    b.add_entropy(b"jitter");
+    assert_ne!(a.next_u64(), b.next_u64());
+    assert!(!a.needs_reseed());
+}
@@ -0,0 +1,80 @@
+//! A lock-free byte queue between an interrupt handler and the rest of the
+//! kernel.
+
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(test)]
+mod tests;
+
+/// A fixed-size FIFO of bytes for one producer and one consumer, holding up
+/// to `N - 1` bytes.
+///
+/// Neither side ever waits for the other, so the producer or the consumer
+/// may be an interrupt handler preempting the other side. There must be at
+/// most one of each at a time: `push` may only be called by the producer
+/// and `pop` and `peek` only by the consumer.
+pub struct RingBuffer<const N: usize> {
+    buf: UnsafeCell<[u8; N]>,
+    /// The index the next byte is pushed at. Only the producer writes it.
+    head: AtomicUsize,
+    /// The index the next byte is popped from. Only the consumer writes it.
+    tail: AtomicUsize,
+}
+
+unsafe impl<const N: usize> Sync for RingBuffer<N> {}
+
+impl<const N: usize> RingBuffer<N> {
+    /// Returns an empty buffer.
+    pub const fn new() -> RingBuffer<N> {
+        RingBuffer {
+            buf: UnsafeCell::new([0; N]),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    /// Appends `byte`. Returns it back if the buffer is full.
+    pub fn push(&self, byte: u8) -> Result<(), u8> {
+        let head = self.head.load(Ordering::Relaxed);
+        let next = (head + 1) % N;
+        if next == self.tail.load(Ordering::Acquire) {
+            return Err(byte);
+        }
+
+        unsafe { (*self.buf.get())[head] = byte };
+        self.head.store(next, Ordering::Release);
+        Ok(())
+    }
+
+    /// Returns the oldest byte without removing it, or `None` if the buffer
+    /// is empty.
+    pub fn peek(&self) -> Option<u8> {
+        let tail = self.tail.load(Ordering::Relaxed);
+        if tail == self.head.load(Ordering::Acquire) {
+            return None;
+        }
+
+        Some(unsafe { (*self.buf.get())[tail] })
+    }
+
+    /// Removes and returns the oldest byte, or `None` if the buffer is empty.
+    pub fn pop(&self) -> Option<u8> {
+        let byte = self.peek()?;
+        let tail = self.tail.load(Ordering::Relaxed);
+        self.tail.store((tail + 1) % N, Ordering::Release);
+        Some(byte)
+    }
+
+    /// Returns the number of bytes in the buffer.
+    pub fn len(&self) -> usize {
+        let head = self.head.load(Ordering::Acquire);
+        let tail = self.tail.load(Ordering::Acquire);
+        (head + N - tail) % N
+    }
+
+    /// Returns `true` if the buffer holds no bytes.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
@@ -0,0 +1,44 @@
+use super::RingBuffer;
+
+#[test]
+fn pushes_and_pops_in_order() {
+    let ring = RingBuffer::<4>::new();
+    assert!(ring.is_empty());
+    assert_eq!(ring.pop(), None);
+
+    assert_eq!(ring.push(1), Ok(()));
+    assert_eq!(ring.push(2), Ok(()));
+    assert_eq!(ring.len(), 2);
+    assert_eq!(ring.peek(), Some(1));
+    assert_eq!(ring.pop(), Some(1));
+    assert_eq!(ring.pop(), Some(2));
+    assert_eq!(ring.pop(), None);
+    assert!(ring.is_empty());
+}
+
+#[test]
+fn rejects_bytes_when_full() {
+    let ring = RingBuffer::<4>::new();
+    for byte in 0..3 {
+        assert_eq!(ring.push(byte), Ok(()));
+    }
+    assert_eq!(ring.len(), 3);
+    assert_eq!(ring.push(3), Err(3));
+
+    assert_eq!(ring.pop(), Some(0));
+    assert_eq!(ring.push(3), Ok(()));
+    assert_eq!(ring.push(4), Err(4));
+}
+
+#[test]
+fn wraps_around() {
+    let ring = RingBuffer::<4>::new();
+    for byte in 0..20 {
+        assert_eq!(ring.push(byte), Ok(()));
+        assert_eq!(ring.push(byte + 100), Ok(()));
+        assert_eq!(ring.len(), 2);
+        assert_eq!(ring.pop(), Some(byte));
+        assert_eq!(ring.pop(), Some(byte + 100));
+    }
+    assert!(ring.is_empty());
+}
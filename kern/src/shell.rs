@@ -1,99 +1,310 @@
-use core::str;
-use stack_vec::StackVec;
+mod builtins;
+mod complete;
+mod editor;
+mod env;
+mod gpio;
+mod mem;
+mod parse;
+mod power;
 
-use crate::console::{kprint, kprintln, CONSOLE};
+#[cfg(test)]
+mod tests;
 
-// Accept commands at most 512 bytes in length 
+use shim::io;
+use shim::path::PathBuf;
+
+use crate::console::kprintln;
+use crate::tty::{Termios, TtyIo, TTY};
+use crate::watchdog;
+
+use self::complete::ShellCompleter;
+use self::editor::LineEditor;
+use self::parse::Command;
+
+pub use self::env::Env;
+
+// Accept commands at most 512 bytes in length
 const MAX_BYTES_PER_COMMAND: usize = 512;
 
 // Accept at most 64 arguments per command
 const MAX_ARGS_PER_COMMAND: usize = 64;
 
-/// Error type for `Command` parse failures.
+// Accept at most 1024 bytes of arguments after variable expansion
+const MAX_EXPANDED_BYTES: usize = 1024;
+
+/// Number of bytes shown on each line of a hex dump.
+pub const HEXDUMP_WIDTH: usize = 16;
+
+/// The commands of the kernel shell. Each subsystem contributes a slice of
+/// its own commands.
+pub static REGISTRY: Registry = Registry::new(&[
+    builtins::COMMANDS,
+    mem::COMMANDS,
+    gpio::COMMANDS,
+    power::COMMANDS,
+    crate::vfs::COMMANDS,
+    crate::random::COMMANDS,
+]);
+
+/// Error type for failed shell commands.
 #[derive(Debug)]
-enum Error {
-    Empty,
-    TooManyArgs,
+pub enum Error {
+    /// The arguments don't match the command's usage. The shell prints the
+    /// usage string.
+    Usage,
+    /// The command failed with an I/O error.
+    Io(io::Error),
 }
 
-/// A structure representing a single shell command.
-struct Command<'a> {
-    args: StackVec<'a, &'a str>,
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Error {
+        Error::Io(error)
+    }
 }
 
-impl<'a> Command<'a> {
-    /// Parse a command from a string `s` using `buf` as storage for the
-    /// arguments.
+/// A command that can be run from the shell.
+pub trait ShellCommand: Sync {
+    /// Returns the name the command is run by.
+    fn name(&self) -> &'static str;
+
+    /// Returns a one-line description of the command.
+    fn help(&self) -> &'static str;
+
+    /// Returns the synopsis of the command's arguments, starting with its
+    /// name, like `ls [-a] [dir]`.
+    fn usage(&self) -> &'static str;
+
+    /// Runs the command with the arguments `args`, not including the name,
+    /// writing its output to `io`.
     ///
     /// # Errors
     ///
-    /// If `s` contains no arguments, returns `Error::Empty`. If there are more
-    /// arguments than `buf` can hold, returns `Error::TooManyArgs`.
-    fn parse(s: &'a str, buf: &'a mut [&'a str]) -> Result<Command<'a>, Error> {
-        let mut args = StackVec::new(buf);
-        for arg in s.split(' ').filter(|a| !a.is_empty()) {
-            args.push(arg).map_err(|_| Error::TooManyArgs)?;
-        }
+    /// Returns `Error::Usage` if `args` don't match the usage, or the I/O
+    /// error that made the command fail.
+    fn run(&self, ctx: &mut Context, args: &[&str], io: &mut dyn io::Write) -> Result<(), Error>;
+}
 
-        if args.is_empty() {
-            return Err(Error::Empty);
-        }
+/// A `ShellCommand` implemented by a function.
+pub struct Builtin {
+    pub name: &'static str,
+    pub help: &'static str,
+    pub usage: &'static str,
+    pub run: fn(&mut Context, &[&str], &mut dyn io::Write) -> Result<(), Error>,
+}
 
-        Ok(Command { args })
+impl ShellCommand for Builtin {
+    fn name(&self) -> &'static str {
+        self.name
     }
 
-    /// Returns this command's path. This is equivalent to the first argument.
-    fn path(&self) -> &str {
-        self.args[0]
+    fn help(&self) -> &'static str {
+        self.help
+    }
+
+    fn usage(&self) -> &'static str {
+        self.usage
+    }
+
+    fn run(&self, ctx: &mut Context, args: &[&str], io: &mut dyn io::Write) -> Result<(), Error> {
+        (self.run)(ctx, args, io)
     }
 }
 
-/// Starts a shell using `prefix` as the prefix for each line. This function
-/// returns if the `exit` command is called.
-pub fn shell(prefix: &str) -> ! {
-    loop {
-        kprint!("{}", prefix);
-        let mut buf = [0u8; MAX_BYTES_PER_COMMAND];
-        let cmd_str = get_cmd_str(&mut buf);
-        let str_buf: &mut [&str] = &mut [""; MAX_ARGS_PER_COMMAND];
-        kprintln!("");
-        match Command::parse(cmd_str, str_buf) {
-            Ok(cmd) if cmd.args[0] == "echo" => echo(&cmd.args[1..]).expect("echo error"),
-            Ok(cmd) => kprintln!("unknown command: {}", cmd.args[0]),
-            Err(_) => kprintln!("error"),
+/// A set of shell commands, made of one slice of commands per subsystem.
+pub struct Registry(&'static [&'static [&'static dyn ShellCommand]]);
+
+impl Registry {
+    /// Returns a registry of the commands in `groups`.
+    pub const fn new(groups: &'static [&'static [&'static dyn ShellCommand]]) -> Registry {
+        Registry(groups)
+    }
+
+    /// Returns an iterator over all commands.
+    pub fn iter(&self) -> impl Iterator<Item = &'static dyn ShellCommand> {
+        self.0.iter().flat_map(|group| group.iter()).copied()
+    }
+
+    /// Returns the command named `name`, if any.
+    pub fn find(&self, name: &str) -> Option<&'static dyn ShellCommand> {
+        self.iter().find(|command| command.name() == name)
+    }
+}
+
+/// The state of a shell that commands may use and change.
+pub struct Context {
+    /// The working directory, against which relative paths are resolved.
+    pub cwd: PathBuf,
+    /// The variables expanded in commands.
+    pub env: Env,
+    /// The commands the shell runs.
+    pub registry: &'static Registry,
+}
+
+/// Parses a decimal or `0x`-prefixed hexadecimal number.
+///
+/// # Errors
+///
+/// Returns `Error::Usage` if `s` is not a number.
+pub fn parse_number(s: &str) -> Result<u64, Error> {
+    let result = if s.starts_with("0x") || s.starts_with("0X") {
+        u64::from_str_radix(&s[2..], 16)
+    } else {
+        s.parse()
+    };
+
+    result.map_err(|_| Error::Usage)
+}
+
+/// Writes one line of a canonical hex dump: the offset `offset`, up to
+/// `HEXDUMP_WIDTH` bytes from `bytes` in hex, and the same bytes as ASCII.
+pub fn write_hex_line(io: &mut dyn io::Write, offset: u64, bytes: &[u8]) -> io::Result<()> {
+    write!(io, "{:08x} ", offset)?;
+    for i in 0..HEXDUMP_WIDTH {
+        if i % 8 == 0 {
+            write!(io, " ")?;
         }
+        match bytes.get(i) {
+            Some(byte) => write!(io, "{:02x} ", byte)?,
+            None => write!(io, "   ")?,
+        }
+    }
+
+    write!(io, " |")?;
+    for &byte in bytes.iter().take(HEXDUMP_WIDTH) {
+        let c = if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' };
+        write!(io, "{}", c)?;
     }
+    writeln!(io, "|")
 }
 
-fn get_cmd_str<'a> (buf: &'a mut [u8; MAX_BYTES]) -> &'a str {
-    let mut i = 0;
-    loop {
-        match CONSOLE.lock().read_byte() {
-            b'\r' | b'\n' => break,
-            8 | 127 => {
-                // backspace
-                if i <= 0 {
+/// An interactive shell running the commands of a registry.
+pub struct Shell {
+    editor: LineEditor,
+    context: Context,
+}
+
+impl Shell {
+    /// Returns a shell running the commands in `registry`, with `/` as its
+    /// working directory.
+    pub fn new(registry: &'static Registry) -> Shell {
+        Shell {
+            editor: LineEditor::new(),
+            context: Context { cwd: PathBuf::from("/"), env: Env::new(), registry },
+        }
+    }
+
+    /// Returns the state commands run with.
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+
+    /// Reads lines from `io`, showing `prompt` before each, and runs them
+    /// until the input ends. A read interrupted by a signal, such as Ctrl-C
+    /// typed into a `Tty`, cancels the line being edited.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of `io` other than reaching the end of the input.
+    pub fn run<T: io::Read + io::Write>(&mut self, io: &mut T, prompt: &str) -> io::Result<()> {
+        loop {
+            let mut buf = [0u8; MAX_BYTES_PER_COMMAND];
+            let mut completer = ShellCompleter { cwd: &self.context.cwd, registry: self.context.registry };
+            let line = match self.editor.read_line(&mut Idle(&mut *io), prompt, &mut completer, &mut buf) {
+                Ok(line) => line,
+                Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+                // A signal from the terminal cancels the line.
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {
+                    io.write_all(b"\n")?;
                     continue;
                 }
-                kprint!("{} {}", 8 as char, 8 as char);
-                i -= 1;
-            },
-            _ if i >= MAX_BYTES_PER_COMMAND => continue,
-            byte => {
-                buf[i] = byte;
-                kprint!("{}", byte as char);
-                i += 1;
-            },
+                Err(e) => return Err(e),
+            };
+
+            io.write_all(b"\n")?;
+            self.execute(line, io)?;
+        }
+    }
+
+    /// Runs the commands in `line`, writing their output to `io`. A line
+    /// with a syntax error is reported and not run at all.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error only if writing to `io` fails.
+    pub fn execute(&mut self, line: &str, io: &mut dyn io::Write) -> io::Result<()> {
+        if let Err(e) = parse::check(line, &self.context.env) {
+            return writeln!(io, "error: {}", e);
+        }
+
+        // Commands separated by `;` are parsed one at a time, so that each
+        // sees the variables set by the ones before it.
+        let mut rest = Some(line);
+        while let Some(s) = rest {
+            let mut storage = [0u8; MAX_EXPANDED_BYTES];
+            let str_buf: &mut [&str] = &mut [""; MAX_ARGS_PER_COMMAND];
+            match Command::parse(s, &self.context.env, &mut storage, str_buf) {
+                Ok((cmd, next)) => {
+                    self.dispatch(&cmd, io)?;
+                    rest = next;
+                }
+                Err(parse::Error::Empty) => break,
+                Err(e) => return writeln!(io, "error: {}", e),
+            }
         }
+
+        Ok(())
+    }
+
+    /// Runs the command `cmd` and reports its errors.
+    fn dispatch(&mut self, cmd: &Command, io: &mut dyn io::Write) -> io::Result<()> {
+        let command = match self.context.registry.find(cmd.path()) {
+            Some(command) => command,
+            None => return writeln!(io, "unknown command: {}", cmd.path()),
+        };
+
+        let result = command.run(&mut self.context, &cmd.args[1..], io);
+        // Finishing a command shows the kernel isn't stuck.
+        watchdog::feed();
+        match result {
+            Ok(()) => Ok(()),
+            Err(Error::Usage) => writeln!(io, "usage: {}", command.usage()),
+            Err(Error::Io(e)) => writeln!(io, "{}: {}", command.name(), e),
+        }
+    }
+}
+
+/// A stream whose reads, waiting for the user to type, don't count as a
+/// hang for the watchdog. Only the wait is exempt: work done between reads,
+/// like listing a directory for tab completion, still is watched.
+struct Idle<'a, T>(&'a mut T);
+
+impl<T: io::Read> io::Read for Idle<'_, T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        watchdog::idle(|| self.0.read(buf))
     }
-    let str = str::from_utf8(&buf[..i]).unwrap();
-    str.clone()
 }
 
-fn echo(args: &[&str]) -> Result<(), Error> {
-    for arg in args {
-        kprint!("{} ", arg);
+impl<T: io::Write> io::Write for Idle<'_, T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
     }
-    kprintln!("");
-    Ok(())
-}
\ No newline at end of file
+}
+
+/// Starts a shell using `prefix` as the prefix for each line. This function
+/// never returns: the shell is restarted if its console fails.
+pub fn shell(prefix: &str) -> ! {
+    // The line editor reads key presses as they're typed and echoes them
+    // itself.
+    TTY.lock().set_termios(Termios { canonical: false, echo: false, ..Termios::COOKED });
+
+    let mut shell = Shell::new(&REGISTRY);
+    loop {
+        if let Err(e) = shell.run(&mut TtyIo, prefix) {
+            kprintln!("shell: {}", e);
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use shim::io;
+
+use super::{Builtin, Context, Error, ShellCommand};
+
+/// The commands built into the shell itself.
+pub static COMMANDS: &[&dyn ShellCommand] = &[
+    &Builtin { name: "echo", help: "print the arguments", usage: "echo [arg]...", run: echo },
+    &Builtin { name: "help", help: "list commands or describe one", usage: "help [command]", run: help },
+    &Builtin { name: "set", help: "set or list shell variables", usage: "set [NAME=value]...", run: set },
+    &Builtin { name: "unset", help: "remove shell variables", usage: "unset NAME...", run: unset },
+];
+
+fn echo(_: &mut Context, args: &[&str], io: &mut dyn io::Write) -> Result<(), Error> {
+    for (i, arg) in args.iter().enumerate() {
+        let separator = if i == 0 { "" } else { " " };
+        write!(io, "{}{}", separator, arg)?;
+    }
+    writeln!(io)?;
+    Ok(())
+}
+
+/// Lists every command with its description, or shows the usage of one.
+fn help(ctx: &mut Context, args: &[&str], io: &mut dyn io::Write) -> Result<(), Error> {
+    match args {
+        [] => {
+            for command in ctx.registry.iter() {
+                writeln!(io, "{:<10} {}", command.name(), command.help())?;
+            }
+        }
+        [name] => match ctx.registry.find(name) {
+            Some(command) => writeln!(io, "usage: {}\n{}", command.usage(), command.help())?,
+            None => return Err(io::Error::new(io::ErrorKind::NotFound, "no such command").into()),
+        },
+        _ => return Err(Error::Usage),
+    }
+
+    Ok(())
+}
+
+/// Sets the variables given as `NAME=value`, or lists all variables.
+fn set(ctx: &mut Context, args: &[&str], io: &mut dyn io::Write) -> Result<(), Error> {
+    if args.is_empty() {
+        for (name, value) in ctx.env.iter() {
+            writeln!(io, "{}={}", name, value)?;
+        }
+        return Ok(());
+    }
+
+    for arg in args {
+        let i = arg.find('=').ok_or(Error::Usage)?;
+        ctx.env.set(&arg[..i], &arg[i + 1..])?;
+    }
+
+    Ok(())
+}
+
+/// Removes the variables named in `args`.
+fn unset(ctx: &mut Context, args: &[&str], _: &mut dyn io::Write) -> Result<(), Error> {
+    if args.is_empty() {
+        return Err(Error::Usage);
+    }
+
+    for name in args {
+        ctx.env.unset(name);
+    }
+
+    Ok(())
+}
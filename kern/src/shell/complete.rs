@@ -0,0 +1,51 @@
+use shim::path::Path;
+
+use crate::vfs;
+use crate::VFS;
+
+use super::editor::Completer;
+use super::Registry;
+
+/// Completes command names in the first word of a line and paths in the
+/// others, relative to the working directory.
+pub struct ShellCompleter<'a> {
+    pub cwd: &'a Path,
+    pub registry: &'a Registry,
+}
+
+impl<'a> Completer for ShellCompleter<'a> {
+    fn complete(&mut self, line: &str, candidate: &mut dyn FnMut(&str, bool)) -> usize {
+        let start = line.rfind(' ').map_or(0, |i| i + 1);
+        let word = &line[start..];
+        if line[..start].trim().is_empty() {
+            for command in self.registry.iter().filter(|command| command.name().starts_with(word)) {
+                candidate(command.name(), false);
+            }
+            return start;
+        }
+
+        // Only the name after the last `/` is completed.
+        let (dir, prefix, start) = match word.rfind('/') {
+            Some(i) => (&word[..=i], &word[i + 1..], start + i + 1),
+            None => ("", word, start),
+        };
+
+        let dir = VFS.lock().open_dir(&vfs::normalize(self.cwd, Path::new(dir)));
+        let entries = match dir.and_then(|dir| dir.entries()) {
+            Ok(entries) => entries,
+            Err(_) => return start,
+        };
+
+        for entry in entries {
+            let name = entry.name();
+            let matches = name.len() >= prefix.len()
+                && name.is_char_boundary(prefix.len())
+                && name[..prefix.len()].eq_ignore_ascii_case(prefix);
+            if matches && name != "." && name != ".." {
+                candidate(name, entry.is_dir());
+            }
+        }
+
+        start
+    }
+}
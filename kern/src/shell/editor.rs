@@ -0,0 +1,446 @@
+use core::str;
+
+use shim::io;
+
+use super::MAX_BYTES_PER_COMMAND;
+
+/// Number of previous commands remembered by the history.
+pub const HISTORY_SIZE: usize = 16;
+
+const BELL: u8 = 0x07;
+const BACKSPACE: u8 = 0x08;
+const ESC: u8 = 0x1B;
+const DEL: u8 = 0x7F;
+
+/// Returns the byte sent by the terminal for Ctrl and `key`.
+const fn ctrl(key: u8) -> u8 {
+    key & 0x1F
+}
+
+/// A key press decoded from the bytes sent by the terminal.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Key {
+    Char(u8),
+    Enter,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Home,
+    End,
+    Up,
+    Down,
+    KillToEnd,
+    KillToStart,
+    KillWord,
+    Tab,
+    Ignored,
+}
+
+/// Supplies completions for the word before the cursor.
+pub trait Completer {
+    /// Calls `candidate` with each completion of the last word of `line`,
+    /// which holds the text before the cursor, and whether it names a
+    /// directory. Returns the index in `line` where the text the candidates
+    /// replace starts.
+    fn complete(&mut self, line: &str, candidate: &mut dyn FnMut(&str, bool)) -> usize;
+}
+
+/// The outcome of completing a word.
+enum Completion {
+    /// The word was extended.
+    Done,
+    /// Several candidates share no more than the word.
+    Ambiguous,
+    /// There are no candidates.
+    None,
+}
+
+/// Returns `true` if `name` could have been typed into the line.
+fn is_typeable(name: &str) -> bool {
+    name.bytes().all(|b| (b' '..=b'~').contains(&b))
+}
+
+/// A ring of the most recent command lines.
+pub struct History {
+    lines: [[u8; MAX_BYTES_PER_COMMAND]; HISTORY_SIZE],
+    lens: [usize; HISTORY_SIZE],
+    /// Index of the slot the next line is stored in.
+    next: usize,
+    len: usize,
+}
+
+impl History {
+    /// Returns an empty history.
+    pub const fn new() -> History {
+        History {
+            lines: [[0; MAX_BYTES_PER_COMMAND]; HISTORY_SIZE],
+            lens: [0; HISTORY_SIZE],
+            next: 0,
+            len: 0,
+        }
+    }
+
+    /// Adds `line` as the most recent line, replacing the oldest one if the
+    /// history is full. Blank lines and repeats of the most recent line are
+    /// not added.
+    pub fn push(&mut self, line: &[u8]) {
+        if line.iter().all(|&b| b == b' ') || self.get(0) == Some(line) {
+            return;
+        }
+
+        let len = core::cmp::min(line.len(), MAX_BYTES_PER_COMMAND);
+        self.lines[self.next][..len].copy_from_slice(&line[..len]);
+        self.lens[self.next] = len;
+        self.next = (self.next + 1) % HISTORY_SIZE;
+        self.len = core::cmp::min(self.len + 1, HISTORY_SIZE);
+    }
+
+    /// Returns the line entered `age` lines ago; 0 is the most recent one.
+    pub fn get(&self, age: usize) -> Option<&[u8]> {
+        if age >= self.len {
+            return None;
+        }
+
+        let index = (self.next + HISTORY_SIZE - 1 - age) % HISTORY_SIZE;
+        Some(&self.lines[index][..self.lens[index]])
+    }
+}
+
+/// An editable line being read from the terminal.
+struct Line<'a, T: io::Read + io::Write> {
+    io: &'a mut T,
+    prompt: &'a str,
+    buf: &'a mut [u8; MAX_BYTES_PER_COMMAND],
+    len: usize,
+    cursor: usize,
+}
+
+impl<'a, T: io::Read + io::Write> Line<'a, T> {
+    fn read_byte(&mut self) -> io::Result<u8> {
+        let mut byte = [0u8];
+        match self.io.read(&mut byte)? {
+            0 => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "end of input")),
+            _ => Ok(byte[0]),
+        }
+    }
+
+    /// Reads the bytes of one key press and decodes them. Escape sequences
+    /// are decoded in both their CSI (`ESC [`) and SS3 (`ESC O`) forms.
+    fn read_key(&mut self) -> io::Result<Key> {
+        let key = match self.read_byte()? {
+            b'\r' | b'\n' => Key::Enter,
+            b'\t' => Key::Tab,
+            BACKSPACE | DEL => Key::Backspace,
+            b if b == ctrl(b'A') => Key::Home,
+            b if b == ctrl(b'E') => Key::End,
+            b if b == ctrl(b'K') => Key::KillToEnd,
+            b if b == ctrl(b'U') => Key::KillToStart,
+            b if b == ctrl(b'W') => Key::KillWord,
+            ESC => return self.read_escape(),
+            b @ b' '..=b'~' => Key::Char(b),
+            _ => Key::Ignored,
+        };
+
+        Ok(key)
+    }
+
+    /// Decodes the rest of an escape sequence after the `ESC` byte.
+    fn read_escape(&mut self) -> io::Result<Key> {
+        match self.read_byte()? {
+            b'[' | b'O' => {}
+            _ => return Ok(Key::Ignored),
+        }
+
+        // Numeric parameters end at the sequence's final byte.
+        let mut param = 0u32;
+        loop {
+            let key = match self.read_byte()? {
+                b @ b'0'..=b'9' => {
+                    param = param.saturating_mul(10).saturating_add((b - b'0') as u32);
+                    continue;
+                }
+                b';' => continue,
+                b'A' => Key::Up,
+                b'B' => Key::Down,
+                b'C' => Key::Right,
+                b'D' => Key::Left,
+                b'H' => Key::Home,
+                b'F' => Key::End,
+                b'~' => match param {
+                    1 | 7 => Key::Home,
+                    3 => Key::Delete,
+                    4 | 8 => Key::End,
+                    _ => Key::Ignored,
+                },
+                _ => Key::Ignored,
+            };
+
+            return Ok(key);
+        }
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.io.write_all(bytes)
+    }
+
+    /// Moves the terminal cursor `n` columns to the left.
+    fn back(&mut self, n: usize) -> io::Result<()> {
+        for _ in 0..n {
+            self.write(&[BACKSPACE])?;
+        }
+        Ok(())
+    }
+
+    /// Moves the cursor to `position` in the line.
+    fn move_to(&mut self, position: usize) -> io::Result<()> {
+        if position < self.cursor {
+            self.back(self.cursor - position)?;
+        } else {
+            let (from, to) = (self.cursor, position);
+            self.io.write_all(&self.buf[from..to])?;
+        }
+
+        self.cursor = position;
+        Ok(())
+    }
+
+    /// Redraws the line from the cursor onwards after the part of the line
+    /// after the cursor changed from `old_len` to the current length.
+    fn redraw_tail(&mut self, old_len: usize) -> io::Result<()> {
+        let (cursor, len) = (self.cursor, self.len);
+        self.io.write_all(&self.buf[cursor..len])?;
+        for _ in len..old_len {
+            self.write(b" ")?;
+        }
+        self.back(core::cmp::max(len, old_len) - cursor)
+    }
+
+    /// Removes the bytes in `start..end` and redraws the line, leaving the
+    /// cursor at `start`.
+    fn remove(&mut self, start: usize, end: usize) -> io::Result<()> {
+        if start == end {
+            return Ok(());
+        }
+
+        self.move_to(start)?;
+        let old_len = self.len;
+        self.buf.copy_within(end..old_len, start);
+        self.len -= end - start;
+        self.redraw_tail(old_len)
+    }
+
+    fn insert(&mut self, byte: u8) -> io::Result<()> {
+        if self.len >= MAX_BYTES_PER_COMMAND {
+            return Ok(());
+        }
+
+        let (cursor, len) = (self.cursor, self.len);
+        self.buf.copy_within(cursor..len, cursor + 1);
+        self.buf[cursor] = byte;
+        self.len += 1;
+        self.redraw_tail(len)?;
+        self.move_to(cursor + 1)
+    }
+
+    /// Replaces the whole line with `line`, leaving the cursor at its end.
+    fn replace(&mut self, line: &[u8]) -> io::Result<()> {
+        self.move_to(0)?;
+        let old_len = self.len;
+        let len = core::cmp::min(line.len(), MAX_BYTES_PER_COMMAND);
+        self.buf[..len].copy_from_slice(&line[..len]);
+        self.len = len;
+        self.redraw_tail(old_len)?;
+        self.move_to(len)
+    }
+
+    /// Replaces the bytes in `start..cursor` with `text`, leaving the cursor
+    /// after it. Nothing changes if the line would become too long.
+    fn replace_before_cursor(&mut self, start: usize, text: &[u8]) -> io::Result<()> {
+        let (cursor, old_len) = (self.cursor, self.len);
+        let len = old_len - (cursor - start) + text.len();
+        if len > MAX_BYTES_PER_COMMAND {
+            return Ok(());
+        }
+
+        self.move_to(start)?;
+        self.buf.copy_within(cursor..old_len, start + text.len());
+        self.buf[start..start + text.len()].copy_from_slice(text);
+        self.len = len;
+        self.redraw_tail(old_len)?;
+        self.move_to(start + text.len())
+    }
+
+    /// Writes the prompt and the line, leaving the cursor where it was.
+    fn redraw_line(&mut self) -> io::Result<()> {
+        let (prompt, len, cursor) = (self.prompt, self.len, self.cursor);
+        self.write(prompt.as_bytes())?;
+        self.io.write_all(&self.buf[..len])?;
+        self.back(len - cursor)
+    }
+
+    /// Extends the word before the cursor to the longest prefix shared by
+    /// the candidates of `completer`. A single candidate is completed with
+    /// a trailing space, or with `/` if it is a directory.
+    fn complete(&mut self, completer: &mut dyn Completer) -> io::Result<Completion> {
+        let mut common = [0u8; MAX_BYTES_PER_COMMAND];
+        let mut common_len = 0;
+        let mut count = 0;
+
+        let text = str::from_utf8(&self.buf[..self.cursor]).unwrap();
+        let start = completer.complete(text, &mut |name, is_dir| {
+            if !is_typeable(name) {
+                return;
+            }
+
+            let suffix: &[u8] = if is_dir { b"/" } else { b"" };
+            let candidate = name.bytes().chain(suffix.iter().copied());
+            if count == 0 {
+                for (slot, b) in common.iter_mut().zip(candidate) {
+                    *slot = b;
+                    common_len += 1;
+                }
+            } else {
+                common_len = candidate.zip(common[..common_len].iter()).take_while(|(a, b)| a == *b).count();
+            }
+            count += 1;
+        });
+
+        if count == 0 {
+            return Ok(Completion::None);
+        } else if count == 1 && common_len > 0 && common[common_len - 1] != b'/' && common_len < common.len() {
+            common[common_len] = b' ';
+            common_len += 1;
+        }
+
+        if common_len <= self.cursor - start {
+            return Ok(Completion::Ambiguous);
+        }
+
+        self.replace_before_cursor(start, &common[..common_len])?;
+        Ok(Completion::Done)
+    }
+
+    /// Lists the candidates of `completer` below the line, then redraws it.
+    fn list_completions(&mut self, completer: &mut dyn Completer) -> io::Result<()> {
+        let text = str::from_utf8(&self.buf[..self.cursor]).unwrap();
+        let io = &mut *self.io;
+        let mut result = io.write_all(b"\r\n");
+        completer.complete(text, &mut |name, is_dir| {
+            if result.is_ok() && is_typeable(name) {
+                let separator: &[u8] = if is_dir { b"/  " } else { b"  " };
+                result = io.write_all(name.as_bytes()).and_then(|_| io.write_all(separator));
+            }
+        });
+
+        result?;
+        self.write(b"\r\n")?;
+        self.redraw_line()
+    }
+
+    /// Returns the start of the word before the cursor, skipping the spaces
+    /// directly before it.
+    fn word_start(&self) -> usize {
+        let line = &self.buf[..self.cursor];
+        let end = line.iter().rposition(|&b| b != b' ').map_or(0, |i| i + 1);
+        line[..end].iter().rposition(|&b| b == b' ').map_or(0, |i| i + 1)
+    }
+}
+
+/// An editor for command lines, with cursor movement and history.
+///
+/// Supported keys are the arrow keys, Home, End and Delete, and Ctrl-A (start
+/// of line), Ctrl-E (end of line), Ctrl-K (delete to end of line), Ctrl-U
+/// (delete to start of line) and Ctrl-W (delete previous word). Only
+/// printable ASCII characters are accepted into the line.
+///
+/// Tab completes the word before the cursor using a `Completer`; pressing it
+/// again when the word is ambiguous lists the candidates.
+///
+/// The editor doesn't allocate, so it works before the heap is initialized.
+pub struct LineEditor {
+    history: History,
+}
+
+impl LineEditor {
+    /// Returns an editor with an empty history.
+    pub const fn new() -> LineEditor {
+        LineEditor { history: History::new() }
+    }
+
+    /// Writes `prompt` to `io`, then reads and echoes a line from `io` into
+    /// `buf` until Enter is pressed. The terminating newline is neither stored
+    /// nor echoed. Non-blank lines are added to the history.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of `io`, or an error of kind `UnexpectedEof` if
+    /// `io` reaches the end of its input.
+    pub fn read_line<'b, T: io::Read + io::Write>(
+        &mut self,
+        io: &mut T,
+        prompt: &str,
+        completer: &mut dyn Completer,
+        buf: &'b mut [u8; MAX_BYTES_PER_COMMAND],
+    ) -> io::Result<&'b str> {
+        // The line being edited is kept in `saved` while browsing history.
+        let mut saved = [0u8; MAX_BYTES_PER_COMMAND];
+        let mut saved_len = 0;
+        let mut age: Option<usize> = None;
+
+        let mut tabbed = false;
+
+        let mut line = Line { io, prompt, buf: &mut *buf, len: 0, cursor: 0 };
+        line.write(prompt.as_bytes())?;
+        loop {
+            let key = line.read_key()?;
+            match key {
+                Key::Enter => break,
+                Key::Char(byte) => line.insert(byte)?,
+                Key::Backspace if line.cursor > 0 => line.remove(line.cursor - 1, line.cursor)?,
+                Key::Delete if line.cursor < line.len => line.remove(line.cursor, line.cursor + 1)?,
+                Key::Left if line.cursor > 0 => line.move_to(line.cursor - 1)?,
+                Key::Right if line.cursor < line.len => line.move_to(line.cursor + 1)?,
+                Key::Home => line.move_to(0)?,
+                Key::End => line.move_to(line.len)?,
+                Key::KillToEnd => line.remove(line.cursor, line.len)?,
+                Key::KillToStart => line.remove(0, line.cursor)?,
+                Key::KillWord => line.remove(line.word_start(), line.cursor)?,
+                Key::Up => {
+                    let next = age.map_or(0, |age| age + 1);
+                    if let Some(entry) = self.history.get(next) {
+                        if age.is_none() {
+                            saved[..line.len].copy_from_slice(&line.buf[..line.len]);
+                            saved_len = line.len;
+                        }
+                        age = Some(next);
+                        line.replace(entry)?;
+                    }
+                }
+                Key::Down => match age {
+                    Some(0) => {
+                        age = None;
+                        line.replace(&saved[..saved_len])?;
+                    }
+                    Some(n) => {
+                        age = Some(n - 1);
+                        line.replace(self.history.get(n - 1).unwrap())?;
+                    }
+                    None => {}
+                },
+                Key::Tab => match line.complete(completer)? {
+                    Completion::Done => {}
+                    Completion::Ambiguous if tabbed => line.list_completions(completer)?,
+                    Completion::Ambiguous | Completion::None => line.write(&[BELL])?,
+                },
+                _ => {}
+            }
+
+            tabbed = key == Key::Tab;
+        }
+
+        let len = line.len;
+        self.history.push(&buf[..len]);
+        Ok(str::from_utf8(&buf[..len]).expect("line editor accepted non-ASCII byte"))
+    }
+}
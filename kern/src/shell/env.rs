@@ -0,0 +1,118 @@
+use core::str;
+
+use shim::io;
+
+/// Maximum number of variables in an environment.
+const MAX_VARS: usize = 16;
+
+/// Maximum length of a variable name in bytes.
+const MAX_NAME_LEN: usize = 32;
+
+/// Maximum length of a variable value in bytes.
+const MAX_VALUE_LEN: usize = 128;
+
+#[derive(Copy, Clone)]
+struct Var {
+    name: [u8; MAX_NAME_LEN],
+    name_len: usize,
+    value: [u8; MAX_VALUE_LEN],
+    value_len: usize,
+}
+
+impl Var {
+    const EMPTY: Var = Var { name: [0; MAX_NAME_LEN], name_len: 0, value: [0; MAX_VALUE_LEN], value_len: 0 };
+
+    fn name(&self) -> &str {
+        str::from_utf8(&self.name[..self.name_len]).unwrap()
+    }
+
+    fn value(&self) -> &str {
+        str::from_utf8(&self.value[..self.value_len]).unwrap()
+    }
+}
+
+/// Returns `true` if `name` is a valid variable name: a letter or `_`
+/// followed by letters, digits and `_`.
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// The shell's variables, expanded in commands by `$NAME` and `${NAME}`.
+///
+/// Like the line editor, the table has a fixed size and doesn't allocate.
+pub struct Env {
+    vars: [Var; MAX_VARS],
+    len: usize,
+}
+
+impl Env {
+    /// Returns an environment without variables.
+    pub const fn new() -> Env {
+        Env { vars: [Var::EMPTY; MAX_VARS], len: 0 }
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.vars[..self.len].iter().position(|var| var.name() == name)
+    }
+
+    /// Returns the value of the variable `name`, if it is set.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.position(name).map(|i| self.vars[i].value())
+    }
+
+    /// Sets the variable `name` to `value`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidInput` if `name` is not a valid name
+    /// or either is too long, and of kind `Other` if the table is full.
+    pub fn set(&mut self, name: &str, value: &str) -> io::Result<()> {
+        if !is_valid_name(name) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid variable name"));
+        } else if name.len() > MAX_NAME_LEN || value.len() > MAX_VALUE_LEN {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "variable too long"));
+        }
+
+        let i = match self.position(name) {
+            Some(i) => i,
+            None if self.len == MAX_VARS => {
+                return Err(io::Error::new(io::ErrorKind::Other, "too many variables"));
+            }
+            None => {
+                self.len += 1;
+                self.len - 1
+            }
+        };
+
+        let var = &mut self.vars[i];
+        var.name[..name.len()].copy_from_slice(name.as_bytes());
+        var.name_len = name.len();
+        var.value[..value.len()].copy_from_slice(value.as_bytes());
+        var.value_len = value.len();
+        Ok(())
+    }
+
+    /// Removes the variable `name`. Returns `false` if it wasn't set.
+    pub fn unset(&mut self, name: &str) -> bool {
+        match self.position(name) {
+            Some(i) => {
+                self.vars.copy_within(i + 1..self.len, i);
+                self.len -= 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns an iterator over the names and values of the variables, in
+    /// the order they were first set.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.vars[..self.len].iter().map(|var| (var.name(), var.value()))
+    }
+}
@@ -0,0 +1,184 @@
+use alloc::boxed::Box;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use pi::gpio::{self as gpio, DynPin, Event, Function, GpioController, Pull, PIN_COUNT};
+use shim::io;
+
+use super::{parse_number, Builtin, Context, Error, ShellCommand};
+use crate::GPIO_EVENTS;
+
+/// Commands driving the GPIO pins directly. Pins claimed by a driver can be
+/// read but not changed.
+pub static COMMANDS: &[&dyn ShellCommand] = &[&Builtin {
+    name: "gpio",
+    help: "configure, drive and read GPIO pins",
+    usage: "gpio mode <pin> in|out|alt0..5 | set|clear|toggle|read <pin> | pull <pin> up|down|off | \
+            watch <pin> rising|falling|both | unwatch <pin> | events | status",
+    run: gpio,
+}];
+
+/// The names of the pin functions, as used by `gpio mode` and `gpio status`.
+const FUNCTIONS: [(&str, Function); 8] = [
+    ("in", Function::Input),
+    ("out", Function::Output),
+    ("alt0", Function::Alt0),
+    ("alt1", Function::Alt1),
+    ("alt2", Function::Alt2),
+    ("alt3", Function::Alt3),
+    ("alt4", Function::Alt4),
+    ("alt5", Function::Alt5),
+];
+
+/// The pins watched with `gpio watch`.
+static WATCHED: AtomicU64 = AtomicU64::new(0);
+
+/// The number of events detected on each watched pin, counted by its
+/// callback. Like `WATCHED`, it is updated with a plain load and store, as
+/// read-modify-write atomics never complete while the MMU is off; only the
+/// callback increments it.
+static EVENT_COUNTS: [AtomicUsize; PIN_COUNT as usize] = [const { AtomicUsize::new(0) }; PIN_COUNT as usize];
+
+/// The name the shell claims pins with while changing them.
+const OWNER: &str = "shell";
+
+/// Number of rows of pins shown by `gpio status`, making three columns.
+const STATUS_ROWS: u8 = 18;
+
+fn function_name(function: Function) -> &'static str {
+    FUNCTIONS.iter().find(|&&(_, f)| f == function).map_or("?", |&(name, _)| name)
+}
+
+fn level_name(level: bool) -> &'static str {
+    if level {
+        "high"
+    } else {
+        "low"
+    }
+}
+
+/// Parses a pin number.
+fn pin(arg: &str) -> Result<u8, Error> {
+    match parse_number(arg)? {
+        pin if pin < PIN_COUNT as u64 => Ok(pin as u8),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidInput, "no such pin (0-53)").into()),
+    }
+}
+
+/// Parses the number of a pin to change and runs `f` on it while the shell
+/// holds a claim on it, so that no driver may have claimed it. The claim is
+/// released when the pin is dropped on return.
+fn with_free_pin<T>(arg: &str, f: impl FnOnce(&mut DynPin) -> T) -> Result<T, Error> {
+    let mut pin = GpioController::new().take_dyn(pin(arg)?, OWNER).map_err(io::Error::from)?;
+    Ok(f(&mut pin))
+}
+
+fn gpio(_: &mut Context, args: &[&str], io: &mut dyn io::Write) -> Result<(), Error> {
+    match args {
+        ["mode", p, name] => {
+            let function = FUNCTIONS.iter().find(|&&(n, _)| n == *name).ok_or(Error::Usage)?.1;
+            with_free_pin(p, |pin| pin.set_function(function))?;
+        }
+        ["set", p] => with_free_pin(p, |pin| pin.set())?.map_err(io::Error::from)?,
+        ["clear", p] => with_free_pin(p, |pin| pin.clear())?.map_err(io::Error::from)?,
+        ["toggle", p] => with_free_pin(p, |pin| pin.toggle())?.map_err(io::Error::from)?,
+        ["read", p] => writeln!(io, "{}", level_name(gpio::levels() & (1 << pin(p)?) != 0))?,
+        ["pull", p, pull] => {
+            let pull = match *pull {
+                "up" => Pull::Up,
+                "down" => Pull::Down,
+                "off" => Pull::Off,
+                _ => return Err(Error::Usage),
+            };
+            with_free_pin(p, |pin| pin.set_pull(pull))?;
+        }
+        ["watch", p, edge] => watch(p, edge)?,
+        ["unwatch", p] => unwatch(p)?,
+        ["events"] => events(io)?,
+        ["status"] => status(io)?,
+        _ => return Err(Error::Usage),
+    }
+
+    Ok(())
+}
+
+/// Starts counting the `edge` events of a pin, from zero.
+fn watch(arg: &str, edge: &str) -> Result<(), Error> {
+    let events: &[Event] = match edge {
+        "rising" => &[Event::RisingEdge],
+        "falling" => &[Event::FallingEdge],
+        "both" => &[Event::RisingEdge, Event::FallingEdge],
+        _ => return Err(Error::Usage),
+    };
+
+    with_free_pin(arg, |pin| {
+        let number = pin.pin();
+        EVENT_COUNTS[number as usize].store(0, Ordering::Relaxed);
+        GPIO_EVENTS.register(
+            number,
+            Box::new(|pin| {
+                let count = &EVENT_COUNTS[pin as usize];
+                count.store(count.load(Ordering::Relaxed) + 1, Ordering::Relaxed);
+            }),
+        );
+        WATCHED.store(WATCHED.load(Ordering::Relaxed) | 1 << number, Ordering::Relaxed);
+
+        pin.disable_event(Event::RisingEdge);
+        pin.disable_event(Event::FallingEdge);
+        for &event in events {
+            pin.enable_event(event);
+        }
+    })
+}
+
+/// Stops counting the events of a pin watched with `gpio watch`.
+fn unwatch(arg: &str) -> Result<(), Error> {
+    let watched = with_free_pin(arg, |pin| {
+        pin.disable_event(Event::RisingEdge);
+        pin.disable_event(Event::FallingEdge);
+        WATCHED.store(WATCHED.load(Ordering::Relaxed) & !(1 << pin.pin()), Ordering::Relaxed);
+        GPIO_EVENTS.unregister(pin.pin())
+    })?;
+    if !watched {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "pin isn't watched").into());
+    }
+    Ok(())
+}
+
+/// Prints the number of events counted for each watched pin.
+fn events(io: &mut dyn io::Write) -> io::Result<()> {
+    let watched = WATCHED.load(Ordering::Relaxed);
+    for pin in (0..PIN_COUNT).filter(|&pin| watched & (1 << pin) != 0) {
+        writeln!(io, "{:>2} {}", pin, EVENT_COUNTS[pin as usize].load(Ordering::Relaxed))?;
+    }
+    Ok(())
+}
+
+/// Prints the function and level of every pin, in columns, followed by the
+/// ranges of pins claimed by drivers.
+fn status(io: &mut dyn io::Write) -> io::Result<()> {
+    let levels = gpio::levels();
+    for row in 0..STATUS_ROWS {
+        for number in (row..PIN_COUNT).step_by(STATUS_ROWS as usize) {
+            let function = gpio::function(number).map_or("?", function_name);
+            let level = levels & (1 << number) != 0;
+            if number != row {
+                write!(io, "    ")?;
+            }
+            write!(io, "{:>2} {:<4} {:<4}", number, function, level_name(level))?;
+        }
+        writeln!(io)?;
+    }
+
+    let controller = GpioController::new();
+    let mut start = 0;
+    while start < PIN_COUNT {
+        let owner = controller.owner(start);
+        let end = (start..PIN_COUNT).find(|&pin| controller.owner(pin) != owner).unwrap_or(PIN_COUNT);
+        if let Some(owner) = owner {
+            writeln!(io, "pins {}-{} claimed by {}", start, end - 1, owner)?;
+        }
+        start = end;
+    }
+
+    Ok(())
+}
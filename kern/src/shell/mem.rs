@@ -0,0 +1,188 @@
+use core::cmp;
+
+use pi::common::IO_BASE;
+use shim::io;
+use volatile::prelude::*;
+use volatile::Volatile;
+
+use super::{parse_number, write_hex_line, Builtin, Context, Error, ShellCommand, HEXDUMP_WIDTH};
+
+/// Size of the peripheral window starting at `IO_BASE`. Everything below
+/// `IO_BASE` is RAM.
+const IO_SIZE: usize = 0x0100_0000;
+
+/// Commands reading and writing arbitrary physical memory, for poking at
+/// peripheral registers. Nothing stops them from corrupting the kernel.
+pub static COMMANDS: &[&dyn ShellCommand] = &[
+    &Builtin { name: "peek", help: "read a value from memory", usage: "peek <addr> [8|16|32|64]", run: peek },
+    &Builtin { name: "poke", help: "write a value to memory", usage: "poke <addr> <val> [8|16|32|64]", run: poke },
+    &Builtin { name: "md", help: "dump memory in hex", usage: "md <addr> <len>", run: md },
+    &Builtin { name: "mf", help: "fill memory with a byte", usage: "mf <addr> <len> <byte>", run: mf },
+];
+
+/// The size of a single memory access.
+#[derive(Debug, Clone, Copy)]
+enum Width {
+    U8,
+    U16,
+    U32,
+    U64,
+}
+
+impl Width {
+    /// Parses a width in bits. Without an argument, the width is 32 bits,
+    /// the size of most peripheral registers.
+    fn parse(arg: Option<&&str>) -> Result<Width, Error> {
+        match arg.map(|s| parse_number(s)).transpose()? {
+            Some(8) => Ok(Width::U8),
+            Some(16) => Ok(Width::U16),
+            None | Some(32) => Ok(Width::U32),
+            Some(64) => Ok(Width::U64),
+            Some(_) => Err(Error::Usage),
+        }
+    }
+
+    fn bytes(self) -> usize {
+        match self {
+            Width::U8 => 1,
+            Width::U16 => 2,
+            Width::U32 => 4,
+            Width::U64 => 8,
+        }
+    }
+
+    fn max(self) -> u64 {
+        !0 >> (64 - 8 * self.bytes())
+    }
+}
+
+/// Reads `width` bytes at `addr` with a single volatile access.
+///
+/// # Safety
+///
+/// `addr` must be mapped and aligned to `width`.
+unsafe fn read(addr: usize, width: Width) -> u64 {
+    match width {
+        Width::U8 => (*(addr as *const Volatile<u8>)).read() as u64,
+        Width::U16 => (*(addr as *const Volatile<u16>)).read() as u64,
+        Width::U32 => (*(addr as *const Volatile<u32>)).read() as u64,
+        Width::U64 => (*(addr as *const Volatile<u64>)).read(),
+    }
+}
+
+/// Writes the low `width` bytes of `value` to `addr` with a single volatile
+/// access.
+///
+/// # Safety
+///
+/// `addr` must be mapped and aligned to `width`, and writing to it must not
+/// break anything the kernel relies on.
+unsafe fn write(addr: usize, value: u64, width: Width) {
+    match width {
+        Width::U8 => (*(addr as *mut Volatile<u8>)).write(value as u8),
+        Width::U16 => (*(addr as *mut Volatile<u16>)).write(value as u16),
+        Width::U32 => (*(addr as *mut Volatile<u32>)).write(value as u32),
+        Width::U64 => (*(addr as *mut Volatile<u64>)).write(value),
+    }
+}
+
+/// Returns the address range of `len` bytes at `addr`, writing a warning to
+/// `io` if it isn't entirely in RAM or in the peripheral window.
+fn range(addr: u64, len: u64, io: &mut dyn io::Write) -> Result<(usize, usize), Error> {
+    let end = addr
+        .checked_add(len)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "range overflows the address space"))?;
+    let (start, end) = (addr as usize, end as usize);
+
+    let in_ram = end <= IO_BASE;
+    let in_io = start >= IO_BASE && end <= IO_BASE + IO_SIZE;
+    if !in_ram && !in_io {
+        writeln!(io, "warning: {:#x}..{:#x} is outside RAM and the peripheral window", start, end)?;
+    }
+
+    Ok((start, end))
+}
+
+/// Returns the address `addr` after checking that it is aligned to `width`
+/// and warning if it is outside known memory.
+fn address(addr: &str, width: Width, io: &mut dyn io::Write) -> Result<usize, Error> {
+    let (addr, _) = range(parse_number(addr)?, width.bytes() as u64, io)?;
+    if addr % width.bytes() != 0 {
+        let message = "address is not aligned to the access width";
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, message).into());
+    }
+
+    Ok(addr)
+}
+
+/// Prints the value of `width` bits at an address.
+fn peek(_: &mut Context, args: &[&str], io: &mut dyn io::Write) -> Result<(), Error> {
+    let (addr, width) = match args {
+        [addr] | [addr, _] => (addr, Width::parse(args.get(1))?),
+        _ => return Err(Error::Usage),
+    };
+
+    let addr = address(addr, width, io)?;
+    let value = unsafe { read(addr, width) };
+    writeln!(io, "{:#010x}: {:#0digits$x}", addr, value, digits = 2 + 2 * width.bytes())?;
+    Ok(())
+}
+
+/// Writes a value of `width` bits to an address.
+fn poke(_: &mut Context, args: &[&str], io: &mut dyn io::Write) -> Result<(), Error> {
+    let (addr, value, width) = match args {
+        [addr, value] | [addr, value, _] => (addr, parse_number(value)?, Width::parse(args.get(2))?),
+        _ => return Err(Error::Usage),
+    };
+
+    if value > width.max() {
+        let message = "value doesn't fit in the access width";
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, message).into());
+    }
+
+    let addr = address(addr, width, io)?;
+    unsafe { write(addr, value, width) };
+    Ok(())
+}
+
+/// Dumps `len` bytes of memory in the canonical hex + ASCII format, reading
+/// one byte at a time.
+fn md(_: &mut Context, args: &[&str], io: &mut dyn io::Write) -> Result<(), Error> {
+    let (start, end) = match args {
+        [addr, len] => range(parse_number(addr)?, parse_number(len)?, io)?,
+        _ => return Err(Error::Usage),
+    };
+
+    let mut line = [0u8; HEXDUMP_WIDTH];
+    let mut addr = start;
+    while addr < end {
+        let count = cmp::min(HEXDUMP_WIDTH, end - addr);
+        for (i, byte) in line[..count].iter_mut().enumerate() {
+            *byte = unsafe { read(addr + i, Width::U8) } as u8;
+        }
+
+        write_hex_line(io, addr as u64, &line[..count])?;
+        addr += count;
+    }
+
+    Ok(())
+}
+
+/// Sets `len` bytes of memory to a value, writing one byte at a time.
+fn mf(_: &mut Context, args: &[&str], io: &mut dyn io::Write) -> Result<(), Error> {
+    let (addr, len, byte) = match args {
+        [addr, len, byte] => (parse_number(addr)?, parse_number(len)?, parse_number(byte)?),
+        _ => return Err(Error::Usage),
+    };
+
+    if byte > Width::U8.max() {
+        return Err(Error::Usage);
+    }
+
+    let (start, end) = range(addr, len, io)?;
+    for addr in start..end {
+        unsafe { write(addr, byte, Width::U8) };
+    }
+
+    Ok(())
+}
@@ -0,0 +1,305 @@
+use core::fmt;
+use core::str;
+
+use stack_vec::StackVec;
+
+use super::env::{is_valid_name, Env};
+use super::{MAX_ARGS_PER_COMMAND, MAX_EXPANDED_BYTES};
+
+/// Error type for `Command` parse failures. Positions are byte offsets in
+/// the parsed line.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The command has no arguments.
+    Empty,
+    /// The command has more arguments than the argument buffer can hold.
+    TooManyArgs,
+    /// The expanded arguments don't fit in the storage buffer.
+    TooLong,
+    /// The quote at `position` is never closed.
+    UnterminatedQuote { quote: char, position: usize },
+    /// The line ends with an unescaped backslash.
+    TrailingBackslash,
+    /// The `${` at `position` has no closing `}`.
+    UnterminatedBrace { position: usize },
+    /// The `${...}` at `position` doesn't hold a valid variable name.
+    BadSubstitution { position: usize },
+    /// The `;` at `position` doesn't follow a command.
+    UnexpectedSeparator { position: usize },
+}
+
+impl Error {
+    /// Returns the error with its position moved `offset` bytes later, for
+    /// errors in a command that starts `offset` bytes into a line.
+    fn shifted(self, offset: usize) -> Error {
+        match self {
+            Error::UnterminatedQuote { quote, position } => {
+                Error::UnterminatedQuote { quote, position: position + offset }
+            }
+            Error::UnterminatedBrace { position } => Error::UnterminatedBrace { position: position + offset },
+            Error::BadSubstitution { position } => Error::BadSubstitution { position: position + offset },
+            Error::UnexpectedSeparator { position } => Error::UnexpectedSeparator { position: position + offset },
+            error => error,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Empty => write!(f, "empty command"),
+            Error::TooManyArgs => write!(f, "too many arguments (at most {})", MAX_ARGS_PER_COMMAND),
+            Error::TooLong => write!(f, "command too long after expansion"),
+            Error::UnterminatedQuote { quote, position } => {
+                let kind = if quote == '"' { "double" } else { "single" };
+                write!(f, "unterminated {} quote at column {}", kind, position + 1)
+            }
+            Error::TrailingBackslash => write!(f, "backslash at end of line"),
+            Error::UnterminatedBrace { position } => write!(f, "missing '}}' for '${{' at column {}", position + 1),
+            Error::BadSubstitution { position } => write!(f, "bad substitution at column {}", position + 1),
+            Error::UnexpectedSeparator { position } => write!(f, "unexpected ';' at column {}", position + 1),
+        }
+    }
+}
+
+/// Storage for the bytes of the arguments being parsed.
+struct Output<'b> {
+    buf: &'b mut [u8],
+    len: usize,
+}
+
+impl<'b> Output<'b> {
+    fn push(&mut self, s: &str) -> Result<(), Error> {
+        let end = self.len + s.len();
+        if end > self.buf.len() {
+            return Err(Error::TooLong);
+        }
+
+        self.buf[self.len..end].copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+
+    fn push_char(&mut self, c: char) -> Result<(), Error> {
+        self.push(c.encode_utf8(&mut [0; 4]))
+    }
+}
+
+/// What `Lexer::next_word` found.
+enum Token {
+    Word,
+    Separator,
+    End,
+}
+
+/// Splits a line into words, removing quotes and escapes and expanding
+/// variables.
+struct Lexer<'s, 'e> {
+    input: &'s str,
+    pos: usize,
+    env: &'e Env,
+}
+
+impl<'s, 'e> Lexer<'s, 'e> {
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    /// Writes the next word to `out`. Words are separated by unquoted spaces
+    /// and tabs. A word that is empty after expansion is skipped unless it
+    /// contains quotes.
+    fn next_word(&mut self, out: &mut Output) -> Result<Token, Error> {
+        loop {
+            while let Some(' ') | Some('\t') = self.peek() {
+                self.bump();
+            }
+
+            match self.peek() {
+                None => return Ok(Token::End),
+                Some(';') => {
+                    self.bump();
+                    return Ok(Token::Separator);
+                }
+                Some(_) => {}
+            }
+
+            let (start, mut quoted) = (out.len, false);
+            while let Some(c) = self.peek() {
+                if c == ' ' || c == '\t' || c == ';' {
+                    break;
+                }
+
+                let position = self.pos;
+                self.bump();
+                match c {
+                    '\\' => match self.bump() {
+                        Some(c) => out.push_char(c)?,
+                        None => return Err(Error::TrailingBackslash),
+                    },
+                    '\'' => {
+                        quoted = true;
+                        loop {
+                            match self.bump() {
+                                Some('\'') => break,
+                                Some(c) => out.push_char(c)?,
+                                None => return Err(Error::UnterminatedQuote { quote: '\'', position }),
+                            }
+                        }
+                    }
+                    '"' => {
+                        quoted = true;
+                        self.double_quoted(position, out)?;
+                    }
+                    '$' => self.expand(position, out)?,
+                    c => out.push_char(c)?,
+                }
+            }
+
+            if quoted || out.len > start {
+                return Ok(Token::Word);
+            }
+        }
+    }
+
+    /// Reads the rest of a double-quoted string opened at `position`. Inside,
+    /// variables are expanded and a backslash only escapes `$`, `"` and `\`.
+    fn double_quoted(&mut self, position: usize, out: &mut Output) -> Result<(), Error> {
+        loop {
+            let at = self.pos;
+            match self.bump() {
+                Some('"') => return Ok(()),
+                Some('\\') => match self.peek() {
+                    Some(c) if c == '$' || c == '"' || c == '\\' => {
+                        self.bump();
+                        out.push_char(c)?;
+                    }
+                    _ => out.push("\\")?,
+                },
+                Some('$') => self.expand(at, out)?,
+                Some(c) => out.push_char(c)?,
+                None => return Err(Error::UnterminatedQuote { quote: '"', position }),
+            }
+        }
+    }
+
+    /// Expands the variable after the `$` at `position`. Unset variables
+    /// expand to nothing; a `$` not followed by a name is kept as is.
+    fn expand(&mut self, position: usize, out: &mut Output) -> Result<(), Error> {
+        let rest = &self.input[self.pos..];
+        let name = if rest.starts_with('{') {
+            let end = rest.find('}').ok_or(Error::UnterminatedBrace { position })?;
+            let name = &rest[1..end];
+            if !is_valid_name(name) {
+                return Err(Error::BadSubstitution { position });
+            }
+            self.pos += end + 1;
+            name
+        } else {
+            let len = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(rest.len());
+            let name = &rest[..len];
+            if !is_valid_name(name) {
+                return out.push("$");
+            }
+            self.pos += len;
+            name
+        };
+
+        out.push(self.env.get(name).unwrap_or(""))
+    }
+}
+
+/// A structure representing a single shell command.
+pub struct Command<'a> {
+    pub args: StackVec<'a, &'a str>,
+}
+
+impl<'a> Command<'a> {
+    /// Parses the first command in `s`, which ends at an unquoted `;` or at
+    /// the end of `s`, expanding variables from `env`. The arguments are
+    /// stored in `storage`, and `buf` holds the list of arguments. Returns
+    /// the command and, if it ended at a `;`, the rest of `s`.
+    ///
+    /// Single quotes keep everything up to the next single quote as is.
+    /// Double quotes allow `$` expansions and the escapes `\$`, `\"` and
+    /// `\\`. Outside quotes, a backslash escapes any character. Expansions
+    /// are not split into several arguments.
+    ///
+    /// # Errors
+    ///
+    /// If the command contains no arguments, returns `Error::Empty`, or
+    /// `Error::UnexpectedSeparator` if it ends at a `;`. If there are more
+    /// arguments than `buf` can hold, returns `Error::TooManyArgs`, and if
+    /// they don't fit in `storage`, `Error::TooLong`. Syntax errors are
+    /// reported with their position in `s`.
+    pub fn parse<'s>(
+        s: &'s str,
+        env: &Env,
+        storage: &'a mut [u8],
+        buf: &'a mut [&'a str],
+    ) -> Result<(Command<'a>, Option<&'s str>), Error> {
+        let mut lexer = Lexer { input: s, pos: 0, env };
+        let mut out = Output { buf: storage, len: 0 };
+        let mut ends = [0usize; MAX_ARGS_PER_COMMAND];
+        let mut count = 0;
+
+        let rest = loop {
+            match lexer.next_word(&mut out)? {
+                Token::Word if count == ends.len() || count == buf.len() => return Err(Error::TooManyArgs),
+                Token::Word => {
+                    ends[count] = out.len;
+                    count += 1;
+                }
+                Token::Separator if count == 0 => {
+                    return Err(Error::UnexpectedSeparator { position: lexer.pos - 1 });
+                }
+                Token::Separator => break Some(&s[lexer.pos..]),
+                Token::End if count == 0 => return Err(Error::Empty),
+                Token::End => break None,
+            }
+        };
+
+        let storage: &'a [u8] = out.buf;
+        let mut args = StackVec::new(buf);
+        let mut start = 0;
+        for &end in &ends[..count] {
+            let arg = str::from_utf8(&storage[start..end]).expect("arguments split a character");
+            args.push(arg).map_err(|_| Error::TooManyArgs)?;
+            start = end;
+        }
+
+        Ok((Command { args }, rest))
+    }
+
+    /// Returns this command's path. This is equivalent to the first argument.
+    pub fn path(&self) -> &str {
+        self.args[0]
+    }
+}
+
+/// Checks that every command in `line` parses, so that a syntax error
+/// anywhere in a line keeps all of it from running.
+///
+/// # Errors
+///
+/// Returns the first error other than `Error::Empty` that `Command::parse`
+/// reports, with its position in `line`.
+pub fn check(line: &str, env: &Env) -> Result<(), Error> {
+    let mut rest = Some(line);
+    while let Some(s) = rest {
+        let mut storage = [0u8; MAX_EXPANDED_BYTES];
+        let mut buf = [""; MAX_ARGS_PER_COMMAND];
+        match Command::parse(s, env, &mut storage, &mut buf) {
+            Ok((_, next)) => rest = next,
+            Err(Error::Empty) => break,
+            Err(e) => return Err(e.shifted(line.len() - s.len())),
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,69 @@
+use core::time::Duration;
+
+use pi::pm::{self, Pm};
+use shim::io;
+
+use super::{parse_number, Builtin, Context, Error, ShellCommand};
+use crate::watchdog;
+
+/// Commands resetting the board and controlling the watchdog that resets it
+/// when the kernel hangs.
+pub static COMMANDS: &[&dyn ShellCommand] = &[
+    &Builtin {
+        name: "reboot",
+        help: "reset the board, or halt it",
+        usage: "reboot [halt | <partition>]",
+        run: reboot,
+    },
+    &Builtin {
+        name: "watchdog",
+        help: "reset the board if the kernel hangs",
+        usage: "watchdog [status | start <ms> | stop]",
+        run: watchdog,
+    },
+];
+
+/// Resets the board into the default partition, which runs the bootloader
+/// again, into the partition `args[0]`, or halts it.
+fn reboot(_: &mut Context, args: &[&str], io: &mut dyn io::Write) -> Result<(), Error> {
+    let partition = match args {
+        [] => 0,
+        ["halt"] => pm::HALT_PARTITION,
+        [partition] => match parse_number(partition)? {
+            partition if partition < pm::HALT_PARTITION as u64 => partition as u8,
+            _ => return Err(Error::Usage),
+        },
+        _ => return Err(Error::Usage),
+    };
+
+    let halt = partition == pm::HALT_PARTITION;
+    writeln!(io, "{}", if halt { "halting" } else { "rebooting" })?;
+    io.flush()?;
+
+    let mut pm = Pm::new();
+    if halt {
+        pm.halt()
+    } else {
+        pm.reset(partition)
+    }
+}
+
+fn watchdog(_: &mut Context, args: &[&str], io: &mut dyn io::Write) -> Result<(), Error> {
+    match args {
+        [] | ["status"] => match watchdog::timeout() {
+            Some(timeout) => {
+                let remaining = Pm::new().watchdog_remaining();
+                writeln!(io, "enabled, timeout {} ms, {} ms left", timeout.as_millis(), remaining.as_millis())?
+            }
+            None => writeln!(io, "disabled")?,
+        },
+        ["start", ms] => {
+            let timeout = Duration::from_millis(parse_number(ms)?);
+            watchdog::enable(timeout).map_err(io::Error::from)?
+        }
+        ["stop"] => watchdog::disable(),
+        _ => return Err(Error::Usage),
+    }
+
+    Ok(())
+}
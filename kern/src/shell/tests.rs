@@ -0,0 +1,259 @@
+use shim::io;
+
+use super::{builtins, mem, Builtin, Context, Error, Registry, Shell, ShellCommand};
+use crate::tty::{Termios, Tty};
+
+/// Prints each argument on its own line, in brackets.
+fn args(_: &mut Context, args: &[&str], io: &mut dyn io::Write) -> Result<(), Error> {
+    for arg in args {
+        writeln!(io, "[{}]", arg)?;
+    }
+    Ok(())
+}
+
+static TEST_COMMANDS: &[&dyn ShellCommand] = &[
+    &Builtin { name: "args", help: "print arguments", usage: "args [arg]...", run: args },
+    &Builtin { name: "arguments", help: "print arguments", usage: "arguments [arg]...", run: args },
+];
+
+static TEST_REGISTRY: Registry = Registry::new(&[builtins::COMMANDS, mem::COMMANDS, TEST_COMMANDS]);
+
+/// A stream that reads scripted input and records everything written.
+struct Script {
+    input: Vec<u8>,
+    pos: usize,
+    output: Vec<u8>,
+}
+
+impl Script {
+    fn new(input: &str) -> Script {
+        Script { input: input.as_bytes().to_vec(), pos: 0, output: Vec::new() }
+    }
+}
+
+impl io::Read for Script {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = std::cmp::min(buf.len(), self.input.len() - self.pos);
+        buf[..n].copy_from_slice(&self.input[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+impl io::Write for Script {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.output.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Returns the lines a terminal shows after receiving `output`, with
+/// trailing spaces removed.
+fn render(output: &[u8]) -> Vec<String> {
+    let mut lines = vec![Vec::new()];
+    let mut column = 0;
+    for &byte in output {
+        let line = lines.last_mut().unwrap();
+        match byte {
+            b'\n' => {
+                lines.push(Vec::new());
+                column = 0;
+            }
+            b'\r' => column = 0,
+            0x08 => column -= 1,
+            0x07 => {}
+            byte if column < line.len() => {
+                line[column] = byte;
+                column += 1;
+            }
+            byte => {
+                line.push(byte);
+                column += 1;
+            }
+        }
+    }
+
+    lines.into_iter().map(|line| String::from_utf8(line).unwrap().trim_end().to_string()).collect()
+}
+
+/// Runs a shell on `input` and returns the lines of the terminal.
+fn run(input: &str) -> Vec<String> {
+    let mut shell = Shell::new(&TEST_REGISTRY);
+    let mut script = Script::new(input);
+    shell.run(&mut script, "> ").expect("shell failed");
+    render(&script.output)
+}
+
+/// Runs each line of `input` with `Shell::execute` and returns the output.
+fn execute(lines: &[&str]) -> String {
+    let mut shell = Shell::new(&TEST_REGISTRY);
+    let mut output = Vec::new();
+    for line in lines {
+        shell.execute(line, &mut output).expect("write failed");
+    }
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn runs_commands() {
+    assert_eq!(execute(&["echo hello   world"]), "hello world\n");
+    assert_eq!(execute(&["args a 'b c' \"d\"\\ e"]), "[a]\n[b c]\n[d e]\n");
+    assert_eq!(execute(&["", "   "]), "");
+}
+
+#[test]
+fn reports_errors() {
+    assert_eq!(execute(&["nope 1 2"]), "unknown command: nope\n");
+    assert_eq!(execute(&["unset"]), "usage: unset NAME...\n");
+    assert_eq!(execute(&["set 1=x"]), "set: invalid variable name\n");
+    assert_eq!(execute(&["echo 'abc"]), "error: unterminated single quote at column 6\n");
+    assert_eq!(execute(&["echo a; echo \"b"]), "error: unterminated double quote at column 14\n");
+    assert_eq!(execute(&["; echo a"]), "error: unexpected ';' at column 1\n");
+}
+
+#[test]
+fn help() {
+    let output = execute(&["help"]);
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines.len(), 10);
+    assert_eq!(lines[0], "echo       print the arguments");
+    assert_eq!(lines[4], "peek       read a value from memory");
+    assert_eq!(lines[9], "arguments  print arguments");
+
+    assert_eq!(execute(&["help echo"]), "usage: echo [arg]...\nprint the arguments\n");
+    assert_eq!(execute(&["help nope"]), "help: no such command\n");
+    assert_eq!(execute(&["help a b"]), "usage: help [command]\n");
+}
+
+#[test]
+fn variables() {
+    assert_eq!(execute(&["set X=1 Y=two; echo $X ${Y}s \"$X\" '$X'"]), "1 twos 1 $X\n");
+    assert_eq!(execute(&["set A=1", "set B=2", "set", "unset A", "set"]), "A=1\nB=2\nB=2\n");
+    assert_eq!(execute(&["set X=1", "unset X; args $X"]), "");
+
+    let mut shell = Shell::new(&TEST_REGISTRY);
+    shell.execute("set X=y", &mut Vec::new()).unwrap();
+    assert_eq!(shell.context().env.get("X"), Some("y"));
+}
+
+#[test]
+fn runs_until_end_of_input() {
+    assert_eq!(run(""), [">"]);
+    assert_eq!(run("echo hi\r"), ["> echo hi", "hi", ">"]);
+    assert_eq!(run("echo a\recho b\n"), ["> echo a", "a", "> echo b", "b", ">"]);
+}
+
+#[test]
+fn edits_lines() {
+    let output = |input: &str| run(input)[1].clone();
+
+    // Left arrow, then insert.
+    assert_eq!(output("echo ac\x1b[Db\r"), "abc");
+    // Home and End, via escape sequences and Ctrl-A / Ctrl-E.
+    assert_eq!(output("cho x\x1b[He\x1b[Fy\r"), "xy");
+    assert_eq!(output("cho x\x01e\x05y\r"), "xy");
+    // Backspace and Delete.
+    assert_eq!(output("echo abd\x7f\x08c\r"), "ac");
+    assert_eq!(output("echo abc\x1b[D\x1b[D\x1b[3~\r"), "ac");
+    // Ctrl-K, Ctrl-U and Ctrl-W.
+    assert_eq!(output("echo abc\x1b[D\x1b[D\x0b\r"), "a");
+    assert_eq!(output("junk\x15echo a\r"), "a");
+    assert_eq!(output("echo a bcd\x17x\r"), "a x");
+
+    let lines = run("echo abc\x1b[D\x1b[D\x0b\r");
+    assert_eq!(lines[0], "> echo a");
+}
+
+#[test]
+fn history() {
+    let lines = run("echo a\recho b\r\x1b[A\x1b[A\r\x1b[A\x1b[A\x1b[A\x1b[B\r");
+    assert_eq!(lines, ["> echo a", "a", "> echo b", "b", "> echo a", "a", "> echo b", "b", ">"]);
+
+    // Going down past the newest entry restores the line being edited.
+    let lines = run("echo a\recho c\x1b[A\x1b[B\r");
+    assert_eq!(lines[2..4], ["> echo c", "c"]);
+}
+
+#[test]
+fn signals_cancel_lines() {
+    let mut shell = Shell::new(&TEST_REGISTRY);
+    let mut script = Script::new("echo a\x03echo b\r\x1aecho c\r");
+    let mut tty = Tty::new(&mut script);
+    tty.set_termios(Termios { canonical: false, echo: false, ..Termios::COOKED });
+    shell.run(&mut tty, "> ").expect("shell failed");
+    assert_eq!(render(&script.output), ["> echo a", "> echo b", "b", ">", "> echo c", "c", ">"]);
+}
+
+#[test]
+fn completes_commands() {
+    assert_eq!(run("ec\tx\r"), ["> echo x", "x", ">"]);
+
+    // `arg` is the common prefix of two commands; a second Tab lists them.
+    assert_eq!(run("ar\t\t\r"), ["> arg", "args  arguments", "> arg", "unknown command: arg", ">"]);
+
+    // Nothing matches.
+    assert_eq!(run("zz\t\r")[0], "> zz");
+}
+
+/// Runs `lines` and returns the output without the warnings about
+/// addresses outside RAM, which every host address triggers.
+fn execute_quiet(lines: &[String]) -> String {
+    let lines: Vec<&str> = lines.iter().map(|line| line.as_str()).collect();
+    execute(&lines).lines().filter(|line| !line.starts_with("warning:")).map(|line| format!("{}\n", line)).collect()
+}
+
+#[test]
+fn memory() {
+    let mut buf = [0u64; 4];
+    let addr = buf.as_mut_ptr() as usize;
+
+    let output = execute_quiet(&[
+        format!("poke {:#x} 0x11223344", addr),
+        format!("poke {} 0xab 8", addr + 4),
+        format!("poke {:#x} 0xffffffffffffffff 64", addr + 8),
+        format!("peek {:#x}", addr),
+        format!("peek {:#x} 16", addr + 2),
+        format!("mf {:#x} 3 0x41", addr + 16),
+    ]);
+    assert_eq!(output, format!("{:#010x}: 0x11223344\n{:#010x}: 0x1122\n", addr, addr + 2));
+    let bytes: Vec<u8> = buf.iter().flat_map(|word| word.to_le_bytes().to_vec()).collect();
+    assert_eq!(&bytes[..20], b"\x44\x33\x22\x11\xab\0\0\0\xff\xff\xff\xff\xff\xff\xff\xffAAA\0");
+
+    let output = execute_quiet(&[format!("md {:#x} 20", addr + 8)]);
+    let expected = format!(
+        "{:08x}  ff ff ff ff ff ff ff ff  41 41 41 00 00 00 00 00  |........AAA.....|\n\
+         {:08x}  00 00 00 00                                       |....|\n",
+        addr + 8,
+        addr + 24
+    );
+    assert_eq!(output, expected);
+
+    let output = execute_quiet(&[
+        format!("peek {:#x} 32", addr + 2),
+        format!("peek {:#x} 12", addr),
+        format!("poke {:#x} 0x100 8", addr),
+        format!("mf {:#x} 1 256", addr),
+        "md 0xffffffffffffffff 2".to_string(),
+    ]);
+    assert_eq!(
+        output,
+        "peek: address is not aligned to the access width\n\
+         usage: peek <addr> [8|16|32|64]\n\
+         poke: value doesn't fit in the access width\n\
+         usage: mf <addr> <len> <byte>\n\
+         md: range overflows the address space\n"
+    );
+}
+
+#[test]
+fn warns_outside_known_memory() {
+    let mut word = 0u32;
+    let addr = &mut word as *mut u32 as usize;
+    let output = execute(&[&format!("peek {:#x}", addr)]);
+    let warning = format!("warning: {:#x}..{:#x} is outside RAM and the peripheral window\n", addr, addr + 4);
+    assert!(output.starts_with(&warning), "{:?}", output);
+}
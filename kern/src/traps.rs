@@ -0,0 +1,167 @@
+//! Exception handling.
+//!
+//! Every exception vector in `init.s` saves the interrupted code's registers
+//! in a `TrapFrame` and calls `handle_exception`. IRQs are dispatched to the
+//! handlers registered in `IRQ` for each pending interrupt, and `svc`
+//! instructions are system calls run by `syscall::handle`. Any other
+//! exception is a kernel bug.
+
+mod irq;
+pub mod syscall;
+
+use pi::interrupt::{Controller, Interrupt};
+
+use crate::console::kprintln;
+use crate::IRQ;
+
+pub use self::irq::{Irq, IrqHandler};
+
+/// The kind of an exception, from its vector.
+#[repr(u16)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Synchronous = 0,
+    Irq = 1,
+    Fiq = 2,
+    SError = 3,
+}
+
+/// Where an exception was taken from, from its vector.
+#[repr(u16)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    CurrentSpEl0 = 0,
+    CurrentSpElx = 1,
+    LowerAArch64 = 2,
+    LowerAArch32 = 3,
+}
+
+/// Information about an exception, as passed by the vector.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Info {
+    pub source: Source,
+    pub kind: Kind,
+}
+
+/// The state of the interrupted code saved by `context_save`, followed by
+/// the `lr` and `x0` the vector saves before calling it. Changes to the
+/// registers are restored when the exception returns.
+#[repr(C)]
+#[derive(Debug)]
+pub struct TrapFrame {
+    /// The address execution resumes at (`ELR_EL1`).
+    pub elr: u64,
+    /// The saved processor state (`SPSR_EL1`).
+    pub spsr: u64,
+    /// Registers `x1` to `x29`.
+    pub x: [u64; 29],
+    /// The return address of `context_save` in the vector.
+    vector_lr: u64,
+    /// The floating-point control and status registers.
+    pub fpcr: u64,
+    pub fpsr: u64,
+    /// SIMD/FP registers `q0` to `q31`.
+    pub q: [u128; 32],
+    /// Register `x30`.
+    pub lr: u64,
+    pub x0: u64,
+}
+
+shim::const_assert_size!(TrapFrame, 800);
+
+/// Unmasks IRQs on the current core.
+pub fn enable_irqs() {
+    #[cfg(not(test))]
+    unsafe {
+        core::arch::asm!("msr DAIFClr, #0b0010")
+    }
+}
+
+/// Masks IRQs on the current core.
+pub fn disable_irqs() {
+    #[cfg(not(test))]
+    unsafe {
+        core::arch::asm!("msr DAIFSet, #0b0010")
+    }
+}
+
+/// Returns `true` if IRQs are masked on the current core, as they are in
+/// exception handlers.
+pub fn irqs_masked() -> bool {
+    #[cfg(not(test))]
+    unsafe {
+        let daif: u64;
+        core::arch::asm!("mrs {}, DAIF", out(reg) daif);
+        daif & (1 << 7) != 0
+    }
+
+    #[cfg(test)]
+    false
+}
+
+/// Runs `f` with IRQs masked on the current core, then restores the mask
+/// it had before. Tables that IRQ handlers walk must only change in `f`, so
+/// a handler never sees them half-changed.
+pub fn without_irqs<T>(f: impl FnOnce() -> T) -> T {
+    let masked = irqs_masked();
+    disable_irqs();
+    let result = f();
+    if !masked {
+        enable_irqs();
+    }
+    result
+}
+
+/// Sleeps until `ready` returns a value, checking it after every IRQ.
+///
+/// `ready` runs with IRQs masked, so an IRQ can't slip in between a failed
+/// check and going to sleep. IRQs must not be masked when calling this, or
+/// it never returns.
+pub fn wait_for<T>(mut ready: impl FnMut() -> Option<T>) -> T {
+    loop {
+        disable_irqs();
+        if let Some(value) = ready() {
+            enable_irqs();
+            return value;
+        }
+
+        // A pending IRQ wakes the core even while masked, and is taken as
+        // soon as IRQs are unmasked.
+        #[cfg(not(test))]
+        unsafe {
+            core::arch::asm!("wfi")
+        }
+        enable_irqs();
+    }
+}
+
+/// Enables the interrupt `int` in the interrupt controller. Its handler
+/// should be registered in `IRQ` first.
+pub fn enable_interrupt(int: Interrupt) {
+    Controller::new().enable(int);
+}
+
+/// This function is called when an exception occurs. The `info` parameter
+/// specifies the source and kind of exception that has occurred. The `esr`
+/// is the value of the exception syndrome register. Finally, `tf` is a
+/// pointer to the trap frame for the exception.
+#[no_mangle]
+pub extern "C" fn handle_exception(info: Info, esr: u32, tf: &mut TrapFrame) {
+    if info.kind == Kind::Irq {
+        let controller = Controller::new();
+        for int in Interrupt::iter().filter(|&int| controller.is_pending(int)) {
+            IRQ.invoke(int, tf);
+        }
+        return;
+    }
+
+    if info.kind == Kind::Synchronous && esr >> 26 == syscall::EC_SVC {
+        // The immediate of the `svc` is the low 16 bits of the syndrome.
+        syscall::handle(esr as u16, tf);
+        return;
+    }
+
+    kprintln!("unhandled exception {:?} (ESR {:#010x}) at {:#x}", info, esr, tf.elr);
+    panic!("unhandled exception");
+}
@@ -0,0 +1,41 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use pi::interrupt::Interrupt;
+
+use crate::mutex::Mutex;
+use crate::traps::{self, TrapFrame};
+
+/// A function run in the IRQ handler while its interrupt is pending. It must
+/// clear the interrupt's cause in the peripheral.
+pub type IrqHandler = Box<dyn FnMut(&mut TrapFrame) + Send>;
+
+/// The IRQ handlers registered for each interrupt.
+pub struct Irq(Mutex<Vec<(Interrupt, IrqHandler)>>);
+
+impl Irq {
+    /// Returns a table without handlers.
+    pub const fn new() -> Irq {
+        Irq(Mutex::new(Vec::new()))
+    }
+
+    /// Registers `handler` for the interrupt `int`, replacing the handler
+    /// registered before, if any. IRQs are masked meanwhile, since `Mutex`
+    /// doesn't keep the IRQ handler out.
+    pub fn register(&self, int: Interrupt, handler: IrqHandler) {
+        traps::without_irqs(|| {
+            let mut handlers = self.0.lock();
+            match handlers.iter_mut().find(|(i, _)| *i == int) {
+                Some(entry) => entry.1 = handler,
+                None => handlers.push((int, handler)),
+            }
+        })
+    }
+
+    /// Runs the handler registered for `int`, if any.
+    pub fn invoke(&self, int: Interrupt, tf: &mut TrapFrame) {
+        if let Some((_, handler)) = self.0.lock().iter_mut().find(|(i, _)| *i == int) {
+            handler(tf);
+        }
+    }
+}
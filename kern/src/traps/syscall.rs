@@ -0,0 +1,80 @@
+//! System calls.
+//!
+//! A system call is made with `svc #n`, where `n` is its number. Arguments
+//! are passed in `x0` to `x6`. The result is returned in `x0` and the
+//! `Status` of the call in `x7`.
+
+use crate::random;
+use crate::traps::TrapFrame;
+use crate::ALLOCATOR;
+
+#[cfg(test)]
+mod tests;
+
+/// The exception class in `ESR_EL1` of an `svc` instruction.
+pub const EC_SVC: u32 = 0b010101;
+
+/// The address the kernel binary is loaded at, from `layout.ld`. The stack
+/// grows down from there.
+const KERNEL_START: usize = 0x80000;
+
+/// The lowest address of the stack. The page below holds the firmware's
+/// spin tables for the other cores.
+const STACK_BOTTOM: usize = 0x1000;
+
+/// `getrandom(buf: *mut u8, len: usize) -> usize`: fills `len` bytes at `buf`
+/// with random bytes from the kernel's generator and returns `len`.
+pub const NR_GETRANDOM: u16 = 1;
+
+/// The status of a system call, returned in `x7`.
+#[repr(u64)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok = 0,
+    /// There is no system call with this number.
+    NoSuchSyscall = 1,
+    /// A buffer argument doesn't lie in memory the caller may write: the
+    /// stack or the heap.
+    BadAddress = 2,
+}
+
+/// Runs the system call `num` with the arguments in `tf`, storing its result
+/// in `tf`.
+pub fn handle(num: u16, tf: &mut TrapFrame) {
+    let result = match num {
+        NR_GETRANDOM => sys_getrandom(tf.x0 as usize, tf.x[0] as usize),
+        _ => Err(Status::NoSuchSyscall),
+    };
+
+    let status = match result {
+        Ok(value) => {
+            tf.x0 = value;
+            Status::Ok
+        }
+        Err(status) => status,
+    };
+    // `x` starts at `x1`.
+    tf.x[6] = status as u64;
+}
+
+/// Returns `true` if `[buf, buf + len)` lies within the stack or within the
+/// heap `heap`. The kernel binary, the memory past the heap and the
+/// peripherals are never writable, and neither is the null pointer.
+fn is_writable(buf: usize, len: usize, heap: Option<(usize, usize)>) -> bool {
+    let end = match buf.checked_add(len) {
+        Some(end) => end,
+        None => return false,
+    };
+
+    let within = |(start, stop): (usize, usize)| start <= buf && end <= stop;
+    within((STACK_BOTTOM, KERNEL_START)) || heap.is_some_and(within)
+}
+
+fn sys_getrandom(buf: usize, len: usize) -> Result<u64, Status> {
+    if !is_writable(buf, len, ALLOCATOR.heap()) {
+        return Err(Status::BadAddress);
+    }
+
+    random::fill_bytes(unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, len) });
+    Ok(len as u64)
+}
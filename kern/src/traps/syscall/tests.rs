@@ -0,0 +1,29 @@
+use super::{is_writable, KERNEL_START, STACK_BOTTOM};
+
+const HEAP: Option<(usize, usize)> = Some((0x10_0000, 0x3B40_0000));
+
+#[test]
+fn accepts_stack_and_heap_buffers() {
+    assert!(is_writable(KERNEL_START - 64, 64, HEAP));
+    assert!(is_writable(STACK_BOTTOM, KERNEL_START - STACK_BOTTOM, None));
+    assert!(is_writable(0x10_0000, 4096, HEAP));
+    assert!(is_writable(0x3B40_0000 - 1, 1, HEAP));
+    assert!(is_writable(0x20_0000, 0, HEAP));
+}
+
+#[test]
+fn rejects_other_memory() {
+    // Null, the firmware's page and the kernel binary.
+    assert!(!is_writable(0, 16, HEAP));
+    assert!(!is_writable(STACK_BOTTOM - 1, 16, HEAP));
+    assert!(!is_writable(KERNEL_START, 16, HEAP));
+    assert!(!is_writable(KERNEL_START - 8, 16, HEAP));
+
+    // Past the heap, the peripherals, and wrapping around.
+    assert!(!is_writable(0x3B40_0000 - 1, 2, HEAP));
+    assert!(!is_writable(0x3F20_0000, 4, HEAP));
+    assert!(!is_writable(usize::MAX - 3, 8, HEAP));
+
+    // The heap is only writable once the allocator is initialized.
+    assert!(!is_writable(0x10_0000, 4096, None));
+}
@@ -0,0 +1,324 @@
+//! The terminal line discipline.
+//!
+//! A `Tty` sits between a raw byte stream, such as the console, and its
+//! readers and writers. Like a POSIX terminal configured with termios, it
+//! either hands out input as it arrives (raw mode) or collects and edits
+//! whole lines before handing them out (canonical mode), and it optionally
+//! echoes input, translates line endings and turns Ctrl-C and Ctrl-Z into
+//! signals.
+
+use shim::io;
+
+use crate::console::ConsoleIo;
+use crate::mutex::Mutex;
+
+#[cfg(test)]
+mod tests;
+
+/// Maximum length of a line in canonical mode, including its newline.
+pub const LINE_SIZE: usize = 256;
+
+const BACKSPACE: u8 = 0x08;
+const DEL: u8 = 0x7F;
+
+/// Returns the byte sent by the terminal for Ctrl and `key`.
+const fn ctrl(key: u8) -> u8 {
+    key & 0x1F
+}
+
+/// The settings of a `Tty`, named after their termios flags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Termios {
+    /// Reads return one line at a time, once it is terminated by a newline
+    /// or Ctrl-D. Until then, Backspace erases the last character and Ctrl-U
+    /// the whole line. Ctrl-D on an empty line ends the input. (`ICANON`)
+    pub canonical: bool,
+    /// Input is written back to the terminal. (`ECHO`)
+    pub echo: bool,
+    /// Ctrl-C and Ctrl-Z raise `Signal::Interrupt` and `Signal::Suspend`
+    /// instead of being read. (`ISIG`)
+    pub signals: bool,
+    /// Carriage returns are read as newlines. (`ICRNL`)
+    pub cr_to_nl: bool,
+    /// Newlines are written as carriage return and newline. (`ONLCR`)
+    pub nl_to_crnl: bool,
+}
+
+impl Termios {
+    /// The settings of an interactive terminal: every feature is enabled.
+    pub const COOKED: Termios = Termios {
+        canonical: true,
+        echo: true,
+        signals: true,
+        cr_to_nl: true,
+        nl_to_crnl: true,
+    };
+
+    /// Settings passing bytes through untouched in both directions.
+    pub const RAW: Termios = Termios {
+        canonical: false,
+        echo: false,
+        signals: false,
+        cr_to_nl: false,
+        nl_to_crnl: false,
+    };
+}
+
+impl Default for Termios {
+    fn default() -> Termios {
+        Termios::COOKED
+    }
+}
+
+/// A signal raised by a control character typed into a `Tty`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    /// Ctrl-C.
+    Interrupt,
+    /// Ctrl-Z.
+    Suspend,
+}
+
+impl Signal {
+    /// Returns the signal raised by `byte`, if any.
+    fn from_byte(byte: u8) -> Option<Signal> {
+        match byte {
+            b if b == ctrl(b'C') => Some(Signal::Interrupt),
+            b if b == ctrl(b'Z') => Some(Signal::Suspend),
+            _ => None,
+        }
+    }
+
+    /// Returns the error returned by the read interrupted by the signal.
+    fn error(self) -> io::Error {
+        match self {
+            Signal::Interrupt => io::Error::new(io::ErrorKind::Interrupted, "interrupted"),
+            Signal::Suspend => io::Error::new(io::ErrorKind::Interrupted, "suspended"),
+        }
+    }
+}
+
+/// A line discipline over the stream `T`.
+///
+/// A signal discards the input not read yet and makes the pending read
+/// return an error of kind `Interrupted`. `take_signal()` tells which signal
+/// it was.
+pub struct Tty<T> {
+    io: T,
+    termios: Termios,
+    /// The line being edited or read in canonical mode.
+    line: [u8; LINE_SIZE],
+    len: usize,
+    /// The number of bytes of a complete line read so far.
+    pos: usize,
+    /// Whether the line was terminated and can be read.
+    complete: bool,
+    signal: Option<Signal>,
+}
+
+impl<T> Tty<T> {
+    /// Returns a line discipline over `io` with the `Termios::COOKED`
+    /// settings.
+    pub const fn new(io: T) -> Tty<T> {
+        Tty {
+            io,
+            termios: Termios::COOKED,
+            line: [0; LINE_SIZE],
+            len: 0,
+            pos: 0,
+            complete: false,
+            signal: None,
+        }
+    }
+
+    /// Returns the current settings.
+    pub fn termios(&self) -> Termios {
+        self.termios
+    }
+
+    /// Changes the settings. A line being edited in canonical mode is kept
+    /// for the next canonical read.
+    pub fn set_termios(&mut self, termios: Termios) {
+        self.termios = termios;
+    }
+
+    /// Returns the last signal raised, if it wasn't taken already.
+    pub fn take_signal(&mut self) -> Option<Signal> {
+        self.signal.take()
+    }
+}
+
+impl<T: io::Read + io::Write> Tty<T> {
+    /// Writes `bytes` to the terminal, translating newlines if enabled.
+    fn output(&mut self, bytes: &[u8]) -> io::Result<()> {
+        if !self.termios.nl_to_crnl {
+            return self.io.write_all(bytes);
+        }
+
+        for (i, part) in bytes.split(|&b| b == b'\n').enumerate() {
+            if i > 0 {
+                self.io.write_all(b"\r\n")?;
+            }
+            self.io.write_all(part)?;
+        }
+        Ok(())
+    }
+
+    /// Echoes the input byte `byte` if enabled. Control characters other
+    /// than line endings and tabs are shown as `^X`.
+    fn echo(&mut self, byte: u8) -> io::Result<()> {
+        match byte {
+            _ if !self.termios.echo => Ok(()),
+            b'\n' | b'\r' | b'\t' | b' '..=b'~' => self.output(&[byte]),
+            _ => self.output(&[b'^', byte ^ 0x40]),
+        }
+    }
+
+    /// Erases the echo of the last byte of the line, if any, and removes it.
+    fn erase(&mut self) -> io::Result<()> {
+        if self.len == 0 {
+            return Ok(());
+        }
+
+        self.len -= 1;
+        let width = match self.line[self.len] {
+            b'\r' | b'\t' | b' '..=b'~' => 1,
+            _ => 2,
+        };
+        if self.termios.echo {
+            for _ in 0..width {
+                self.output(b"\x08 \x08")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies the input translation to `byte`. Returns an error, after
+    /// discarding the input, if it raises a signal.
+    fn translate(&mut self, byte: u8) -> io::Result<u8> {
+        if self.termios.signals {
+            if let Some(signal) = Signal::from_byte(byte) {
+                self.len = 0;
+                self.pos = 0;
+                self.complete = false;
+                self.signal = Some(signal);
+                self.echo(byte)?;
+                return Err(signal.error());
+            }
+        }
+
+        match byte {
+            b'\r' if self.termios.cr_to_nl => Ok(b'\n'),
+            byte => Ok(byte),
+        }
+    }
+
+    /// Reads and edits input until the line is complete. Returns `false` if
+    /// the input ended before any byte of the line.
+    fn edit_line(&mut self) -> io::Result<bool> {
+        loop {
+            let mut byte = [0u8];
+            if self.io.read(&mut byte)? == 0 {
+                self.complete = self.len > 0;
+                return Ok(self.complete);
+            }
+
+            match self.translate(byte[0])? {
+                BACKSPACE | DEL => self.erase()?,
+                b if b == ctrl(b'U') => {
+                    while self.len > 0 {
+                        self.erase()?;
+                    }
+                }
+                b if b == ctrl(b'D') => {
+                    self.complete = self.len > 0;
+                    return Ok(self.complete);
+                }
+                b'\n' => {
+                    self.line[self.len] = b'\n';
+                    self.len += 1;
+                    self.complete = true;
+                    self.echo(b'\n')?;
+                    return Ok(true);
+                }
+                // Keep room for the newline.
+                _ if self.len >= LINE_SIZE - 1 => {}
+                byte => {
+                    self.line[self.len] = byte;
+                    self.len += 1;
+                    self.echo(byte)?;
+                }
+            }
+        }
+    }
+}
+
+impl<T: io::Read + io::Write> io::Read for Tty<T> {
+    /// In canonical mode, reads from the current line, waiting for it to be
+    /// complete first. Returns 0 at the end of the input.
+    ///
+    /// In raw mode, reads the input received so far, waiting for at least
+    /// one byte.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        if !self.termios.canonical {
+            let read = self.io.read(buf)?;
+            for byte in buf[..read].iter_mut() {
+                *byte = self.translate(*byte)?;
+                self.echo(*byte)?;
+            }
+            return Ok(read);
+        }
+
+        if !self.complete && !self.edit_line()? {
+            return Ok(0);
+        }
+
+        let read = core::cmp::min(buf.len(), self.len - self.pos);
+        buf[..read].copy_from_slice(&self.line[self.pos..self.pos + read]);
+        self.pos += read;
+        if self.pos == self.len {
+            self.len = 0;
+            self.pos = 0;
+            self.complete = false;
+        }
+        Ok(read)
+    }
+}
+
+impl<T: io::Read + io::Write> io::Write for Tty<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.output(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.io.flush()
+    }
+}
+
+/// The terminal on the console.
+pub static TTY: Mutex<Tty<ConsoleIo>> = Mutex::new(Tty::new(ConsoleIo));
+
+/// A handle to `TTY` implementing `io::Read` and `io::Write`, locking it for
+/// each call.
+pub struct TtyIo;
+
+impl io::Read for TtyIo {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        io::Read::read(&mut *TTY.lock(), buf)
+    }
+}
+
+impl io::Write for TtyIo {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        io::Write::write(&mut *TTY.lock(), buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::Write::flush(&mut *TTY.lock())
+    }
+}
@@ -0,0 +1,149 @@
+use shim::io::{self, Read, Write};
+
+use super::{Signal, Termios, Tty};
+
+/// A stream that reads scripted input and records everything written.
+struct Script {
+    input: Vec<u8>,
+    pos: usize,
+    output: Vec<u8>,
+}
+
+impl Script {
+    fn new(input: &[u8]) -> Script {
+        Script { input: input.to_vec(), pos: 0, output: Vec::new() }
+    }
+}
+
+impl io::Read for Script {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = std::cmp::min(buf.len(), self.input.len() - self.pos);
+        buf[..n].copy_from_slice(&self.input[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+impl io::Write for Script {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.output.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Reads from `tty` until the end of its input, returning the bytes read
+/// by each call.
+fn reads<T: io::Read + io::Write>(tty: &mut Tty<T>) -> Vec<Vec<u8>> {
+    let mut reads = Vec::new();
+    let mut buf = [0u8; 64];
+    loop {
+        match tty.read(&mut buf) {
+            Ok(0) => return reads,
+            Ok(n) => reads.push(buf[..n].to_vec()),
+            Err(e) => panic!("read failed: {}", e),
+        }
+    }
+}
+
+#[test]
+fn canonical_reads_lines() {
+    let mut script = Script::new(b"one\rtwo\n");
+    let mut tty = Tty::new(&mut script);
+    assert_eq!(reads(&mut tty), vec![b"one\n".to_vec(), b"two\n".to_vec()]);
+    assert_eq!(script.output, b"one\r\ntwo\r\n");
+}
+
+#[test]
+fn canonical_reads_partial_lines() {
+    let mut script = Script::new(b"hello\n");
+    let mut tty = Tty::new(&mut script);
+    let mut buf = [0u8; 4];
+    assert_eq!(tty.read(&mut buf).unwrap(), 4);
+    assert_eq!(&buf, b"hell");
+    assert_eq!(tty.read(&mut buf).unwrap(), 2);
+    assert_eq!(&buf[..2], b"o\n");
+    assert_eq!(tty.read(&mut buf).unwrap(), 0);
+}
+
+#[test]
+fn canonical_edits_lines() {
+    let mut script = Script::new(b"ab\x7fc\x15xy\x08z\n");
+    let mut tty = Tty::new(&mut script);
+    assert_eq!(reads(&mut tty), vec![b"xz\n".to_vec()]);
+    assert_eq!(script.output, b"ab\x08 \x08c\x08 \x08\x08 \x08xy\x08 \x08z\r\n");
+}
+
+#[test]
+fn canonical_end_of_input() {
+    let mut script = Script::new(b"abc\x04\x04more");
+    let mut tty = Tty::new(&mut script);
+    let mut buf = [0u8; 16];
+    assert_eq!(tty.read(&mut buf).unwrap(), 3);
+    assert_eq!(&buf[..3], b"abc");
+    assert_eq!(tty.read(&mut buf).unwrap(), 0);
+}
+
+#[test]
+fn control_characters_echo_as_carets() {
+    let mut script = Script::new(b"a\x01\x7f\n");
+    let mut tty = Tty::new(&mut script);
+    assert_eq!(reads(&mut tty), vec![b"a\n".to_vec()]);
+    assert_eq!(script.output, b"a^A\x08 \x08\x08 \x08\r\n");
+}
+
+#[test]
+fn signals_interrupt_reads() {
+    let mut script = Script::new(b"ab\x03cd\n\x1a");
+    let mut tty = Tty::new(&mut script);
+    let mut buf = [0u8; 16];
+
+    let error = tty.read(&mut buf).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::Interrupted);
+    assert_eq!(tty.take_signal(), Some(Signal::Interrupt));
+    assert_eq!(tty.take_signal(), None);
+
+    // The input typed before the signal is discarded.
+    assert_eq!(tty.read(&mut buf).unwrap(), 3);
+    assert_eq!(&buf[..3], b"cd\n");
+
+    assert_eq!(tty.read(&mut buf).unwrap_err().kind(), io::ErrorKind::Interrupted);
+    assert_eq!(tty.take_signal(), Some(Signal::Suspend));
+    assert_eq!(script.output, b"ab^Ccd\r\n^Z");
+}
+
+#[test]
+fn raw_mode_passes_bytes_through() {
+    let mut script = Script::new(b"a\r\x03\x7f");
+    let mut tty = Tty::new(&mut script);
+    tty.set_termios(Termios::RAW);
+    assert_eq!(reads(&mut tty), vec![b"a\r\x03\x7f".to_vec()]);
+
+    tty.write_all(b"x\ny").unwrap();
+    assert_eq!(script.output, b"x\ny");
+}
+
+#[test]
+fn raw_mode_with_echo_and_signals() {
+    let mut script = Script::new(b"ab\rc\x03");
+    let mut tty = Tty::new(&mut script);
+    tty.set_termios(Termios { canonical: false, ..Termios::COOKED });
+
+    let mut buf = [0u8; 3];
+    assert_eq!(tty.read(&mut buf).unwrap(), 3);
+    assert_eq!(&buf, b"ab\n");
+    assert_eq!(tty.read(&mut buf).unwrap_err().kind(), io::ErrorKind::Interrupted);
+    assert_eq!(tty.take_signal(), Some(Signal::Interrupt));
+    assert_eq!(script.output, b"ab\r\nc^C");
+}
+
+#[test]
+fn translates_newlines_on_output() {
+    let mut script = Script::new(b"");
+    let mut tty = Tty::new(&mut script);
+    tty.write_all(b"one\ntwo\n\n").unwrap();
+    assert_eq!(script.output, b"one\r\ntwo\r\n\r\n");
+}
@@ -0,0 +1,247 @@
+//! The virtual filesystem layer.
+//!
+//! Every filesystem the kernel can access implements the traits in this
+//! module and is used through trait objects, so the shell and system calls
+//! don't need to know which backend holds a file. Filesystems are attached
+//! to the directory tree in a `MountTable`.
+
+mod commands;
+mod mount;
+
+#[cfg(test)]
+mod tests;
+
+use alloc::boxed::Box;
+use core::fmt;
+
+use shim::io;
+use shim::path::{Component, Path, PathBuf};
+
+use crate::mutex::{Mutex, MutexGuard};
+
+pub use self::commands::COMMANDS;
+pub use self::mount::{Mount, MountTable};
+
+/// A date and time as reported by a filesystem.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Timestamp {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+/// Metadata of a file or directory.
+///
+/// Backends that don't keep timestamps return `None` from the timestamp
+/// methods.
+pub trait Metadata {
+    /// Returns `true` if the entry may not be written to.
+    fn read_only(&self) -> bool;
+
+    /// Returns `true` if the entry should not be shown by default.
+    fn hidden(&self) -> bool;
+
+    /// Returns the time the entry was created.
+    fn created(&self) -> Option<Timestamp>;
+
+    /// Returns the time the entry was last accessed.
+    fn accessed(&self) -> Option<Timestamp>;
+
+    /// Returns the time the entry was last modified.
+    fn modified(&self) -> Option<Timestamp>;
+}
+
+/// An open file.
+pub trait File: io::Read + io::Write + io::Seek {
+    /// Returns the name of the file.
+    fn name(&self) -> &str;
+
+    /// Returns the metadata of the file.
+    fn metadata(&self) -> &dyn Metadata;
+
+    /// Returns the size of the file in bytes.
+    fn size(&self) -> u64;
+
+    /// Writes any data buffered for the file to its backing store.
+    fn sync(&mut self) -> io::Result<()>;
+
+    /// Truncates or zero-extends the file to `size` bytes.
+    ///
+    /// # Errors
+    ///
+    /// The default implementation always fails with `PermissionDenied`.
+    fn set_len(&mut self, _size: u64) -> io::Result<()> {
+        Err(read_only())
+    }
+}
+
+/// An open directory.
+///
+/// The modifying methods have default implementations that fail with
+/// `PermissionDenied`, for backends that are read-only.
+pub trait Dir {
+    /// Returns the name of the directory.
+    fn name(&self) -> &str;
+
+    /// Returns the metadata of the directory.
+    fn metadata(&self) -> &dyn Metadata;
+
+    /// Returns an iterator over the entries in the directory.
+    fn entries(&self) -> io::Result<Box<dyn Iterator<Item = Box<dyn Entry>>>>;
+
+    /// Returns the entry named `name`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `NotFound` if there is no such entry. The
+    /// default implementation compares names exactly; backends with other
+    /// rules override it.
+    fn find(&self, name: &str) -> io::Result<Box<dyn Entry>> {
+        self.entries()?
+            .find(|entry| entry.name() == name)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no such file or directory"))
+    }
+
+    /// Creates an empty file named `name` in the directory.
+    fn create_file(&self, _name: &str) -> io::Result<Box<dyn File>> {
+        Err(read_only())
+    }
+
+    /// Creates an empty directory named `name` in the directory.
+    fn create_dir(&self, _name: &str) -> io::Result<Box<dyn Dir>> {
+        Err(read_only())
+    }
+
+    /// Removes the file or empty directory named `name`.
+    fn remove(&self, _name: &str) -> io::Result<()> {
+        Err(read_only())
+    }
+
+    /// Renames the entry named `from` to `to` within the directory.
+    fn rename(&self, _from: &str, _to: &str) -> io::Result<()> {
+        Err(read_only())
+    }
+}
+
+/// An entry in a directory: either a file or a directory.
+pub trait Entry {
+    /// Returns the name of the entry.
+    fn name(&self) -> &str;
+
+    /// Returns the metadata of the entry.
+    fn metadata(&self) -> &dyn Metadata;
+
+    /// Returns the size of the entry in bytes; zero for directories.
+    fn size(&self) -> u64;
+
+    /// Returns `true` if the entry is a file.
+    fn is_file(&self) -> bool;
+
+    /// Returns `true` if the entry is a directory.
+    fn is_dir(&self) -> bool {
+        !self.is_file()
+    }
+
+    /// Returns the file if the entry is a file, `None` otherwise.
+    fn into_file(self: Box<Self>) -> Option<Box<dyn File>>;
+
+    /// Returns the directory if the entry is a directory, `None` otherwise.
+    fn into_dir(self: Box<Self>) -> Option<Box<dyn Dir>>;
+}
+
+/// A mountable filesystem.
+pub trait FileSystem: Send + Sync {
+    /// Returns a short name for the type of the filesystem, like `vfat`.
+    fn kind(&self) -> &str;
+
+    /// Opens the root directory of the filesystem.
+    fn root(&self) -> io::Result<Box<dyn Dir>>;
+
+    /// Writes all buffered data to the backing store.
+    fn sync(&self) -> io::Result<()>;
+}
+
+/// Adapts a directory to the `Entry` trait, so that the root directory of a
+/// filesystem can be returned as an entry.
+pub struct DirEntry(pub Box<dyn Dir>);
+
+impl Entry for DirEntry {
+    fn name(&self) -> &str {
+        self.0.name()
+    }
+
+    fn metadata(&self) -> &dyn Metadata {
+        self.0.metadata()
+    }
+
+    fn size(&self) -> u64 {
+        0
+    }
+
+    fn is_file(&self) -> bool {
+        false
+    }
+
+    fn into_file(self: Box<Self>) -> Option<Box<dyn File>> {
+        None
+    }
+
+    fn into_dir(self: Box<Self>) -> Option<Box<dyn Dir>> {
+        Some(self.0)
+    }
+}
+
+/// Returns the error reported when modifying a read-only backend.
+fn read_only() -> io::Error {
+    io::Error::new(io::ErrorKind::PermissionDenied, "read-only filesystem")
+}
+
+/// Resolves `path` against the directory `cwd` and returns the resulting
+/// absolute path with all `.` and `..` components removed. `..` at the root
+/// stays at the root. A relative `cwd` is taken relative to the root.
+///
+/// Resolution is purely lexical: there are no symbolic links, so `a/..` is
+/// always the directory containing `a`, even when `a` is a mount point.
+pub fn normalize(cwd: &Path, path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::from("/");
+    for component in cwd.join(path).components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => normalized = PathBuf::from("/"),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::Normal(name) => normalized.push(name),
+        }
+    }
+
+    normalized
+}
+
+/// The kernel's mount table, shared by everyone accessing files.
+pub struct Vfs(Mutex<MountTable>);
+
+impl Vfs {
+    /// Returns a `Vfs` with nothing mounted.
+    pub const fn new() -> Vfs {
+        Vfs(Mutex::new(MountTable::new()))
+    }
+
+    /// Locks the mount table.
+    pub fn lock(&self) -> MutexGuard<'_, MountTable> {
+        self.0.lock()
+    }
+}
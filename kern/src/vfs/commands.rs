@@ -0,0 +1,158 @@
+use alloc::vec::Vec;
+use core::cmp;
+use core::str;
+
+use shim::io;
+use shim::path::{Path, PathBuf};
+
+use crate::shell::{parse_number, write_hex_line, Builtin, Context, Error, ShellCommand, HEXDUMP_WIDTH};
+use crate::vfs::{self, Entry};
+use crate::VFS;
+
+/// The shell commands for browsing mounted filesystems.
+pub static COMMANDS: &[&dyn ShellCommand] = &[
+    &Builtin { name: "pwd", help: "print the working directory", usage: "pwd", run: pwd },
+    &Builtin { name: "cd", help: "change the working directory", usage: "cd [dir]", run: cd },
+    &Builtin { name: "ls", help: "list a directory", usage: "ls [-a] [dir]", run: ls },
+    &Builtin { name: "cat", help: "print files", usage: "cat <file>...", run: cat },
+    &Builtin {
+        name: "hexdump",
+        help: "print part of a file in hex",
+        usage: "hexdump <file> [offset [len]]",
+        run: hexdump,
+    },
+];
+
+fn pwd(ctx: &mut Context, args: &[&str], io: &mut dyn io::Write) -> Result<(), Error> {
+    if !args.is_empty() {
+        return Err(Error::Usage);
+    }
+
+    writeln!(io, "{}", ctx.cwd.display())?;
+    Ok(())
+}
+
+/// Changes the working directory to `args[0]`, or to the root without
+/// arguments.
+fn cd(ctx: &mut Context, args: &[&str], _: &mut dyn io::Write) -> Result<(), Error> {
+    let dir = match args {
+        [] => PathBuf::from("/"),
+        [dir] => vfs::normalize(&ctx.cwd, Path::new(dir)),
+        _ => return Err(Error::Usage),
+    };
+
+    VFS.lock().open_dir(&dir)?;
+    ctx.cwd = dir;
+    Ok(())
+}
+
+/// Writes one line of `ls` output for `entry`.
+fn write_entry(entry: &dyn Entry, io: &mut dyn io::Write) -> io::Result<()> {
+    let metadata = entry.metadata();
+    let flag = |set, c| if set { c } else { '-' };
+    write!(
+        io,
+        "{}{}{} ",
+        flag(entry.is_dir(), 'd'),
+        flag(metadata.read_only(), 'r'),
+        flag(metadata.hidden(), 'h')
+    )?;
+
+    match metadata.modified() {
+        Some(modified) => write!(io, "{} ", modified)?,
+        None => write!(io, "{:19} ", "-")?,
+    }
+    writeln!(io, "{:>10} {}", entry.size(), entry.name())
+}
+
+/// Lists the directory `args[0]`, or the working directory. Hidden entries
+/// and names starting with `.` are only shown with `-a`.
+fn ls(ctx: &mut Context, args: &[&str], io: &mut dyn io::Write) -> Result<(), Error> {
+    let (all, args) = match args {
+        ["-a", rest @ ..] => (true, rest),
+        _ => (false, args),
+    };
+    let path = match args {
+        [] => ctx.cwd.clone(),
+        [path] => vfs::normalize(&ctx.cwd, Path::new(path)),
+        _ => return Err(Error::Usage),
+    };
+
+    let entry = VFS.lock().open(&path)?;
+    if entry.is_file() {
+        write_entry(&*entry, io)?;
+        return Ok(());
+    }
+
+    let dir = entry.into_dir().unwrap();
+    for entry in dir.entries()? {
+        if all || !(entry.metadata().hidden() || entry.name().starts_with('.')) {
+            write_entry(&*entry, io)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints the contents of the files in `args`. Files that aren't valid UTF-8
+/// are printed with non-printable bytes replaced by `.`.
+fn cat(ctx: &mut Context, args: &[&str], io: &mut dyn io::Write) -> Result<(), Error> {
+    if args.is_empty() {
+        return Err(Error::Usage);
+    }
+
+    for path in args {
+        let mut file = VFS.lock().open_file(&vfs::normalize(&ctx.cwd, Path::new(path)))?;
+        let mut data = Vec::new();
+        io::Read::read_to_end(&mut *file, &mut data)?;
+
+        if str::from_utf8(&data).is_err() {
+            for byte in data.iter_mut() {
+                if !(byte.is_ascii_graphic() || byte.is_ascii_whitespace()) {
+                    *byte = b'.';
+                }
+            }
+        }
+
+        io.write_all(&data)?;
+        if !data.is_empty() && !data.ends_with(b"\n") {
+            writeln!(io)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints `len` bytes of the file `args[0]` starting at `offset` in the
+/// canonical hex + ASCII format. By default, the whole file is shown.
+fn hexdump(ctx: &mut Context, args: &[&str], io: &mut dyn io::Write) -> Result<(), Error> {
+    let (path, offset, len) = match args {
+        [path] => (path, 0, None),
+        [path, offset] => (path, parse_number(offset)?, None),
+        [path, offset, len] => (path, parse_number(offset)?, Some(parse_number(len)?)),
+        _ => return Err(Error::Usage),
+    };
+
+    let mut file = VFS.lock().open_file(&vfs::normalize(&ctx.cwd, Path::new(path)))?;
+    let end = match len {
+        Some(len) => cmp::min(file.size(), offset.saturating_add(len)),
+        None => file.size(),
+    };
+    if offset >= end {
+        return Ok(());
+    }
+    io::Seek::seek(&mut *file, io::SeekFrom::Start(offset))?;
+
+    let mut line = [0u8; HEXDUMP_WIDTH];
+    let mut position = offset;
+    while position < end {
+        let count = cmp::min(HEXDUMP_WIDTH as u64, end - position) as usize;
+        io::Read::read_exact(&mut *file, &mut line[..count])?;
+
+        write_hex_line(io, position, &line[..count])?;
+        position += count as u64;
+    }
+
+    writeln!(io, "{:08x}", end)?;
+    Ok(())
+}
@@ -0,0 +1,260 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use shim::io;
+use shim::path::{Path, PathBuf};
+
+use crate::vfs::{normalize, Dir, DirEntry, Entry, File, FileSystem};
+
+/// A filesystem attached to the directory tree.
+pub struct Mount {
+    path: PathBuf,
+    fs: Box<dyn FileSystem>,
+}
+
+impl Mount {
+    /// Returns the directory the filesystem is mounted on.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the mounted filesystem.
+    pub fn fs(&self) -> &dyn FileSystem {
+        &*self.fs
+    }
+}
+
+/// The set of mounted filesystems.
+///
+/// A path belongs to the filesystem whose mount point is its longest prefix.
+/// Every method taking a path resolves it relative to the root with
+/// `vfs::normalize`; callers with a working directory resolve against it
+/// first.
+pub struct MountTable {
+    mounts: Vec<Mount>,
+}
+
+fn not_found() -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, "no such file or directory")
+}
+
+fn busy() -> io::Error {
+    io::Error::new(io::ErrorKind::Other, "mount point busy")
+}
+
+fn root() -> &'static Path {
+    Path::new("/")
+}
+
+/// Splits the normalized `path` into its parent directory and last name.
+fn split(path: &Path) -> io::Result<(&Path, &str)> {
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => {
+            let name = name
+                .to_str()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path is not valid UTF-8"))?;
+            Ok((parent, name))
+        }
+        _ => Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid path")),
+    }
+}
+
+impl MountTable {
+    /// Returns an empty mount table.
+    pub const fn new() -> MountTable {
+        MountTable { mounts: Vec::new() }
+    }
+
+    /// Returns the mounted filesystems in the order they were mounted.
+    pub fn mounts(&self) -> &[Mount] {
+        &self.mounts
+    }
+
+    /// Mounts `fs` on `path`. Anything the directory contained is hidden
+    /// until the filesystem is unmounted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `AlreadyExists` if a filesystem is already
+    /// mounted on `path`. Unless `path` is the root, it must be an existing
+    /// directory; otherwise the error from opening it is returned.
+    pub fn mount(&mut self, path: &Path, fs: Box<dyn FileSystem>) -> io::Result<()> {
+        let path = normalize(root(), path);
+        if self.mounts.iter().any(|mount| mount.path == path) {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, "already mounted"));
+        } else if path != root() {
+            self.open_dir(&path)?;
+        }
+
+        self.mounts.push(Mount { path, fs });
+        Ok(())
+    }
+
+    /// Syncs and unmounts the filesystem mounted on `path` and returns it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `NotFound` if nothing is mounted on `path`
+    /// and of kind `Other` if another filesystem is mounted below it. If
+    /// syncing fails, the filesystem stays mounted and the error is returned.
+    pub fn unmount(&mut self, path: &Path) -> io::Result<Box<dyn FileSystem>> {
+        let path = normalize(root(), path);
+        let index = self
+            .mounts
+            .iter()
+            .position(|mount| mount.path == path)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "not mounted"))?;
+        if self.mounts.iter().any(|mount| mount.path != path && mount.path.starts_with(&path)) {
+            return Err(busy());
+        }
+
+        self.mounts[index].fs.sync()?;
+        Ok(self.mounts.remove(index).fs)
+    }
+
+    /// Returns the mount holding the normalized `path` and the rest of the
+    /// path relative to the root of the mounted filesystem.
+    fn resolve<'a, 'p>(&'a self, path: &'p Path) -> io::Result<(&'a Mount, &'p Path)> {
+        let mount = self
+            .mounts
+            .iter()
+            .filter(|mount| path.starts_with(&mount.path))
+            .max_by_key(|mount| mount.path.components().count())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no filesystem mounted"))?;
+
+        Ok((mount, path.strip_prefix(&mount.path).map_err(|_| not_found())?))
+    }
+
+    /// Returns `true` if a filesystem is mounted on the normalized `path`.
+    fn is_mount_point(&self, path: &Path) -> bool {
+        self.mounts.iter().any(|mount| mount.path == path)
+    }
+
+    /// Opens the file or directory at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `NotFound` if the entry doesn't exist, if a
+    /// component other than the last one is not a directory or if no
+    /// filesystem holds `path`. Errors from the backend are passed on.
+    pub fn open(&self, path: &Path) -> io::Result<Box<dyn Entry>> {
+        let path = normalize(root(), path);
+        let (mount, rest) = self.resolve(&path)?;
+
+        let mut entry: Box<dyn Entry> = Box::new(DirEntry(mount.fs.root()?));
+        for component in rest.components() {
+            let name = component
+                .as_os_str()
+                .to_str()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path is not valid UTF-8"))?;
+            entry = entry.into_dir().ok_or_else(not_found)?.find(name)?;
+        }
+
+        Ok(entry)
+    }
+
+    /// Opens the file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Fails like `open`, and with an error of kind `InvalidInput` if the
+    /// entry is a directory.
+    pub fn open_file(&self, path: &Path) -> io::Result<Box<dyn File>> {
+        self.open(path)?
+            .into_file()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "is a directory"))
+    }
+
+    /// Opens the directory at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Fails like `open`, and with an error of kind `InvalidInput` if the
+    /// entry is a file.
+    pub fn open_dir(&self, path: &Path) -> io::Result<Box<dyn Dir>> {
+        self.open(path)?
+            .into_dir()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a directory"))
+    }
+
+    /// Creates an empty file at `path`, whose parent directory must exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidInput` for the root, and otherwise
+    /// the errors of opening the parent directory or creating the file.
+    pub fn create_file(&self, path: &Path) -> io::Result<Box<dyn File>> {
+        let path = normalize(root(), path);
+        let (parent, name) = split(&path)?;
+        self.open_dir(parent)?.create_file(name)
+    }
+
+    /// Creates an empty directory at `path`, whose parent must exist.
+    ///
+    /// # Errors
+    ///
+    /// Fails like `create_file`.
+    pub fn create_dir(&self, path: &Path) -> io::Result<Box<dyn Dir>> {
+        let path = normalize(root(), path);
+        let (parent, name) = split(&path)?;
+        self.open_dir(parent)?.create_dir(name)
+    }
+
+    /// Removes the file or empty directory at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `Other` if `path` is a mount point, and
+    /// otherwise fails like `create_file` or with the backend's error.
+    pub fn remove(&self, path: &Path) -> io::Result<()> {
+        let path = normalize(root(), path);
+        if self.is_mount_point(&path) {
+            return Err(busy());
+        }
+
+        let (parent, name) = split(&path)?;
+        self.open_dir(parent)?.remove(name)
+    }
+
+    /// Renames the entry at `from` to `to`. Both must be in the same
+    /// directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidInput` if the entries are in
+    /// different directories and of kind `Other` if either is a mount point.
+    /// Otherwise fails like `create_file` or with the backend's error.
+    pub fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let (from, to) = (normalize(root(), from), normalize(root(), to));
+        if self.is_mount_point(&from) || self.is_mount_point(&to) {
+            return Err(busy());
+        }
+
+        let (parent, from_name) = split(&from)?;
+        let (to_parent, to_name) = split(&to)?;
+        if parent != to_parent {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "cannot rename across directories"));
+        }
+
+        self.open_dir(parent)?.rename(from_name, to_name)
+    }
+
+    /// Syncs every mounted filesystem.
+    ///
+    /// # Errors
+    ///
+    /// Every filesystem is synced even if one fails; the first error is
+    /// returned.
+    pub fn sync(&self) -> io::Result<()> {
+        let mut result = Ok(());
+        for mount in &self.mounts {
+            if let Err(e) = mount.fs.sync() {
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+
+        result
+    }
+}
@@ -0,0 +1,221 @@
+use std::sync::{Arc, Mutex};
+
+use shim::io;
+use shim::path::Path;
+
+use super::{normalize, Dir, Entry, File, FileSystem, Metadata, MountTable, Timestamp};
+
+struct NoMetadata;
+
+impl Metadata for NoMetadata {
+    fn read_only(&self) -> bool {
+        false
+    }
+
+    fn hidden(&self) -> bool {
+        false
+    }
+
+    fn created(&self) -> Option<Timestamp> {
+        None
+    }
+
+    fn accessed(&self) -> Option<Timestamp> {
+        None
+    }
+
+    fn modified(&self) -> Option<Timestamp> {
+        None
+    }
+}
+
+/// A directory of an in-memory filesystem. Clones share their children, so
+/// changes made through one handle are seen through the others.
+#[derive(Clone)]
+struct Node {
+    name: String,
+    children: Arc<Mutex<Vec<Node>>>,
+}
+
+impl Node {
+    /// Returns a directory named `name` holding `children`.
+    fn new(name: &str, children: Vec<Node>) -> Node {
+        Node { name: name.to_string(), children: Arc::new(Mutex::new(children)) }
+    }
+}
+
+impl Dir for Node {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn metadata(&self) -> &dyn Metadata {
+        &NoMetadata
+    }
+
+    fn entries(&self) -> io::Result<Box<dyn Iterator<Item = Box<dyn Entry>>>> {
+        let children = self.children.lock().unwrap().clone();
+        Ok(Box::new(children.into_iter().map(|child| Box::new(child) as Box<dyn Entry>)))
+    }
+
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        let mut children = self.children.lock().unwrap();
+        match children.iter_mut().find(|child| child.name == from) {
+            Some(child) => {
+                child.name = to.to_string();
+                Ok(())
+            }
+            None => Err(io::Error::new(io::ErrorKind::NotFound, "no such directory")),
+        }
+    }
+}
+
+impl Entry for Node {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn metadata(&self) -> &dyn Metadata {
+        &NoMetadata
+    }
+
+    fn size(&self) -> u64 {
+        0
+    }
+
+    fn is_file(&self) -> bool {
+        false
+    }
+
+    fn into_file(self: Box<Self>) -> Option<Box<dyn File>> {
+        None
+    }
+
+    fn into_dir(self: Box<Self>) -> Option<Box<dyn Dir>> {
+        Some(self)
+    }
+}
+
+/// An in-memory filesystem of directories whose root is named after the
+/// filesystem, so tests can tell which one a path resolved to.
+struct MemFs {
+    root: Node,
+    sync_fails: bool,
+}
+
+impl MemFs {
+    fn new(name: &str, children: Vec<Node>) -> Box<MemFs> {
+        Box::new(MemFs { root: Node::new(name, children), sync_fails: false })
+    }
+}
+
+impl FileSystem for MemFs {
+    fn kind(&self) -> &str {
+        "memfs"
+    }
+
+    fn root(&self) -> io::Result<Box<dyn Dir>> {
+        Ok(Box::new(self.root.clone()))
+    }
+
+    fn sync(&self) -> io::Result<()> {
+        if self.sync_fails {
+            Err(io::Error::new(io::ErrorKind::Other, "sync failed"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+fn dir(name: &str) -> Node {
+    Node::new(name, Vec::new())
+}
+
+/// Returns a table with a filesystem on `/` holding `/mnt/usb`, `/mnt/usbx`
+/// and `/mnt/a`, and a filesystem named `usb` holding `/photos` and `/inner`
+/// mounted on `/mnt/usb`.
+fn table() -> MountTable {
+    let mnt = Node::new("mnt", vec![dir("usb"), dir("usbx"), dir("a")]);
+    let mut table = MountTable::new();
+    table.mount(Path::new("/"), MemFs::new("rootfs", vec![mnt])).unwrap();
+    table.mount(Path::new("/mnt/usb"), MemFs::new("usb", vec![dir("photos"), dir("inner")])).unwrap();
+    table
+}
+
+/// Returns the name of the directory `path` opens in `table`.
+fn open_name(table: &MountTable, path: &str) -> String {
+    table.open_dir(Path::new(path)).expect(path).name().to_string()
+}
+
+fn assert_kind<T>(result: io::Result<T>, kind: io::ErrorKind) {
+    match result {
+        Ok(_) => panic!("expected an error of kind {:?}", kind),
+        Err(e) => assert_eq!(e.kind(), kind),
+    }
+}
+
+#[test]
+fn normalizes_dots_above_the_root() {
+    let cases = [
+        ("/", "..", "/"),
+        ("/", "../../a/./b", "/a/b"),
+        ("/a/b", "../../../c/./d", "/c/d"),
+        ("/a", "./b/..", "/a"),
+        ("/a", "b/../../..", "/"),
+        ("a", "b", "/a/b"),
+        ("/x", "/y/../z", "/z"),
+    ];
+    for &(cwd, path, expected) in cases.iter() {
+        assert_eq!(normalize(Path::new(cwd), Path::new(path)), Path::new(expected), "{} + {}", cwd, path);
+    }
+}
+
+#[test]
+fn resolves_the_longest_mount_prefix() {
+    let table = table();
+    assert_eq!(open_name(&table, "/"), "rootfs");
+    assert_eq!(open_name(&table, "/mnt"), "mnt");
+    assert_eq!(open_name(&table, "/mnt/usb"), "usb");
+    assert_eq!(open_name(&table, "/mnt/usb/photos"), "photos");
+    assert_eq!(open_name(&table, "/mnt/usb/../usb/./inner"), "inner");
+
+    // Mount points match whole components only.
+    assert_eq!(open_name(&table, "/mnt/usbx"), "usbx");
+    assert_kind(table.open(Path::new("/mnt/a/photos")), io::ErrorKind::NotFound);
+}
+
+#[test]
+fn refuses_to_unmount_busy_mounts() {
+    let mut table = table();
+    table.mount(Path::new("/mnt/usb/inner"), MemFs::new("inner", Vec::new())).unwrap();
+    assert_kind(table.mount(Path::new("/mnt/usb/"), MemFs::new("again", Vec::new())), io::ErrorKind::AlreadyExists);
+
+    // A mount point with a filesystem mounted below it.
+    assert_kind(table.unmount(Path::new("/mnt/usb")), io::ErrorKind::Other);
+    assert_kind(table.remove(Path::new("/mnt/usb/inner")), io::ErrorKind::Other);
+    assert_eq!(table.mounts().len(), 3);
+
+    table.unmount(Path::new("/mnt/usb/inner")).unwrap();
+    assert_kind(table.unmount(Path::new("/mnt/usb/inner")), io::ErrorKind::NotFound);
+    table.unmount(Path::new("/mnt/usb")).unwrap();
+    assert_eq!(open_name(&table, "/mnt/usb"), "usb");
+
+    // A filesystem that can't be synced stays mounted.
+    let failing = Box::new(MemFs { root: dir("failing"), sync_fails: true });
+    table.mount(Path::new("/mnt/a"), failing).unwrap();
+    assert_kind(table.unmount(Path::new("/mnt/a")), io::ErrorKind::Other);
+    assert_eq!(open_name(&table, "/mnt/a"), "failing");
+}
+
+#[test]
+fn refuses_to_rename_across_mounts() {
+    let table = table();
+    assert_kind(table.rename(Path::new("/mnt/a"), Path::new("/mnt/usb/a")), io::ErrorKind::InvalidInput);
+    assert_kind(table.rename(Path::new("/mnt/usb/photos"), Path::new("/mnt/photos")), io::ErrorKind::InvalidInput);
+    assert_kind(table.rename(Path::new("/mnt/usb"), Path::new("/mnt/usb2")), io::ErrorKind::Other);
+    assert_kind(table.rename(Path::new("/mnt/a"), Path::new("/mnt/usb")), io::ErrorKind::Other);
+
+    table.rename(Path::new("/mnt/usb/photos"), Path::new("/mnt/usb/pictures")).unwrap();
+    assert_eq!(open_name(&table, "/mnt/usb/pictures"), "pictures");
+    assert_kind(table.open(Path::new("/mnt/usb/photos")), io::ErrorKind::NotFound);
+}
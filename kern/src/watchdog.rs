@@ -0,0 +1,106 @@
+//! Resetting the board when the kernel hangs.
+//!
+//! While enabled, the PM watchdog is restarted by the `Timer1` interrupt,
+//! which fires twice per timeout, but only while the main flow shows it is
+//! alive: it called `feed` since the previous tick, or it is idle in `idle`.
+//! The watchdog resets the board when neither happens for a whole timeout.
+//!
+//! The feeding points, and the hangs they leave detected, are:
+//!
+//!   * The shell calls `feed` after each command and reads its input in
+//!     `idle`. A command stuck in a loop, spinning on a lock or blocked in a
+//!     wait resets the board, as do a panic or a hang with IRQs masked,
+//!     which stop the ticks. Commands expected to run longer than half a
+//!     timeout must call `feed` themselves.
+//!   * `idle` covers everything its closure does. It must only wrap waits
+//!     that may legitimately last forever, like waiting for a key press.
+
+use alloc::boxed::Box;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use core::time::Duration;
+
+use pi::interrupt::Interrupt;
+use pi::pm::{self, Pm};
+use pi::timer::{self, Timer};
+
+use crate::traps;
+use crate::IRQ;
+
+/// The watchdog timeout in microseconds, or 0 while disabled.
+static TIMEOUT: AtomicU64 = AtomicU64::new(0);
+
+/// Set by `feed` and cleared by each tick. Like `IDLE`, it is only loaded and
+/// stored: read-modify-write atomics never complete while the MMU is off.
+static HEARTBEAT: AtomicBool = AtomicBool::new(false);
+
+/// Whether the main flow is in `idle`.
+static IDLE: AtomicBool = AtomicBool::new(false);
+
+/// Registers the `Timer1` IRQ handler feeding the watchdog.
+pub fn initialize() {
+    IRQ.register(Interrupt::Timer1, Box::new(|_| tick()));
+    traps::enable_interrupt(Interrupt::Timer1);
+}
+
+/// Returns the watchdog timeout, or `None` while it is disabled.
+pub fn timeout() -> Option<Duration> {
+    match TIMEOUT.load(Ordering::Relaxed) {
+        0 => None,
+        micros => Some(Duration::from_micros(micros)),
+    }
+}
+
+/// Starts resetting the board if the kernel hangs for `timeout`.
+pub fn enable(timeout: Duration) -> Result<(), pm::Error> {
+    Pm::new().start_watchdog(timeout)?;
+    HEARTBEAT.store(true, Ordering::Relaxed);
+    TIMEOUT.store(timeout.as_micros() as u64, Ordering::Relaxed);
+    timer::tick_in(timeout / 2);
+    Ok(())
+}
+
+/// Stops the watchdog.
+pub fn disable() {
+    TIMEOUT.store(0, Ordering::Relaxed);
+    Pm::new().stop_watchdog();
+}
+
+/// Shows that the main flow is making progress, letting the next tick
+/// restart the watchdog.
+pub fn feed() {
+    HEARTBEAT.store(true, Ordering::Relaxed);
+}
+
+/// Runs `f`, a wait that may last forever, keeping the watchdog fed
+/// meanwhile.
+pub fn idle<T>(f: impl FnOnce() -> T) -> T {
+    let was_idle = IDLE.load(Ordering::Relaxed);
+    IDLE.store(true, Ordering::Relaxed);
+    let result = f();
+    IDLE.store(was_idle, Ordering::Relaxed);
+    feed();
+    result
+}
+
+/// The `Timer1` IRQ handler: restarts the watchdog if the main flow is
+/// alive, and sets up the next tick while the watchdog is enabled.
+fn tick() {
+    let mut timer = Timer::new();
+    let timeout = match timeout() {
+        Some(timeout) => timeout,
+        None => {
+            timer.clear_tick();
+            return;
+        }
+    };
+
+    // The main flow can't run while this handler does, so the heartbeat
+    // can't be lost between the load and the store.
+    let alive = HEARTBEAT.load(Ordering::Relaxed) || IDLE.load(Ordering::Relaxed);
+    HEARTBEAT.store(false, Ordering::Relaxed);
+    if alive {
+        // The timeout was accepted by `enable`.
+        let _ = Pm::new().start_watchdog(timeout);
+    }
+    timer.tick_in(timeout / 2);
+}
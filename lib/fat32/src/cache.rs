@@ -0,0 +1,276 @@
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::fmt;
+
+use shim::io;
+
+use crate::traits::BlockDevice;
+
+/// Counters describing how well a `CachedDevice` is doing.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Accesses served from the cache.
+    pub hits: u64,
+    /// Accesses that had to load the sector into the cache.
+    pub misses: u64,
+    /// Sectors dropped from the cache to make room for others.
+    pub evictions: u64,
+    /// Dirty sectors written back to the device.
+    pub writebacks: u64,
+}
+
+impl fmt::Display for CacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} hits, {} misses, {} evictions, {} writebacks",
+            self.hits, self.misses, self.evictions, self.writebacks
+        )
+    }
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    data: Vec<u8>,
+    dirty: bool,
+    /// Value of the access counter when the entry was last used.
+    last_used: u64,
+}
+
+/// A write-back cache of the sectors of a block device.
+///
+/// The cache works in logical sectors, which may be larger than the sectors
+/// of the underlying device: logical sector `n` covers the `factor` device
+/// sectors starting at `n * factor`. At most `capacity` logical sectors are
+/// kept; the least recently used one is evicted, and written back if dirty,
+/// to make room for another.
+///
+/// Writes only reach the device when a dirty sector is evicted or `sync` is
+/// called. Dropping a `CachedDevice` without calling `sync` loses them.
+///
+/// Neither keeps the order in which sectors were written: `sync` writes them
+/// in sector order and eviction in least recently used order. Changes that
+/// must reach the device before others, so that an interrupted operation
+/// leaves the volume consistent, must be synced before the others are made.
+#[derive(Debug)]
+pub struct CachedDevice<T: BlockDevice> {
+    device: T,
+    sector_size: u64,
+    capacity: usize,
+    cache: BTreeMap<u64, CacheEntry>,
+    clock: u64,
+    stats: CacheStats,
+}
+
+impl<T: BlockDevice> CachedDevice<T> {
+    /// Creates a cache of up to `capacity` logical sectors of `sector_size`
+    /// bytes in front of `device`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero or `sector_size` is not a non-zero
+    /// multiple of the sector size of `device`.
+    pub fn new(device: T, sector_size: u64, capacity: usize) -> CachedDevice<T> {
+        let physical = device.sector_size();
+        assert!(capacity > 0, "cache capacity must be non-zero");
+        assert!(
+            sector_size >= physical && sector_size % physical == 0,
+            "logical sector size must be a multiple of the device sector size"
+        );
+
+        CachedDevice {
+            device,
+            sector_size,
+            capacity,
+            cache: BTreeMap::new(),
+            clock: 0,
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// Returns the counters accumulated since the cache was created or the
+    /// counters were last reset.
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Resets all counters to zero.
+    pub fn reset_stats(&mut self) {
+        self.stats = CacheStats::default();
+    }
+
+    /// Returns the maximum number of logical sectors kept in the cache.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of logical sectors currently cached.
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// Returns `true` if no sectors are cached.
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    /// Returns the number of cached sectors that haven't been written back.
+    pub fn dirty(&self) -> usize {
+        self.cache.values().filter(|entry| entry.dirty).count()
+    }
+
+    /// Returns the number of device sectors in a logical sector.
+    fn factor(&self) -> u64 {
+        self.sector_size / self.device.sector_size()
+    }
+
+    /// Reads logical sector `n` from the device into `buf`.
+    fn read_physical(device: &mut T, factor: u64, n: u64, buf: &mut [u8]) -> io::Result<()> {
+        let physical = device.sector_size() as usize;
+        for (i, chunk) in buf.chunks_mut(physical).enumerate() {
+            device.read_sector(n * factor + i as u64, chunk)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes `buf` to logical sector `n` of the device.
+    fn write_physical(device: &mut T, factor: u64, n: u64, buf: &[u8]) -> io::Result<()> {
+        let physical = device.sector_size() as usize;
+        for (i, chunk) in buf.chunks(physical).enumerate() {
+            device.write_sector(n * factor + i as u64, chunk)?;
+        }
+
+        Ok(())
+    }
+
+    /// Drops the least recently used sector, writing it back if it is dirty.
+    fn evict(&mut self) -> io::Result<()> {
+        let victim = self
+            .cache
+            .iter()
+            .min_by_key(|(_, entry)| entry.last_used)
+            .map(|(&n, _)| n);
+        let n = match victim {
+            Some(n) => n,
+            None => return Ok(()),
+        };
+
+        let factor = self.factor();
+        let entry = &self.cache[&n];
+        if entry.dirty {
+            CachedDevice::write_physical(&mut self.device, factor, n, &entry.data)?;
+            self.stats.writebacks += 1;
+        }
+
+        self.cache.remove(&n);
+        self.stats.evictions += 1;
+        Ok(())
+    }
+
+    /// Returns the cache entry for sector `n`, loading it first if it isn't
+    /// cached. If `load` is `false`, a missing sector is not read from the
+    /// device because the caller will overwrite all of it.
+    fn entry(&mut self, n: u64, load: bool) -> io::Result<&mut CacheEntry> {
+        self.clock += 1;
+        if self.cache.contains_key(&n) {
+            self.stats.hits += 1;
+        } else {
+            self.stats.misses += 1;
+            if self.cache.len() >= self.capacity {
+                self.evict()?;
+            }
+
+            let mut data = vec![0u8; self.sector_size as usize];
+            if load {
+                let factor = self.factor();
+                CachedDevice::read_physical(&mut self.device, factor, n, &mut data)?;
+            }
+            self.cache.insert(n, CacheEntry { data, dirty: false, last_used: 0 });
+        }
+
+        let entry = self.cache.get_mut(&n).unwrap();
+        entry.last_used = self.clock;
+        Ok(entry)
+    }
+
+    /// Returns a reference to the contents of logical sector `n`, reading it
+    /// from the device if it isn't cached.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the sector has to be read and reading fails, or
+    /// if a dirty sector has to be evicted and writing it back fails.
+    pub fn get(&mut self, n: u64) -> io::Result<&[u8]> {
+        Ok(&self.entry(n, true)?.data)
+    }
+
+    /// Returns a mutable reference to the contents of logical sector `n`,
+    /// reading it from the device if it isn't cached. The sector is marked
+    /// dirty.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the sector has to be read and reading fails, or
+    /// if a dirty sector has to be evicted and writing it back fails.
+    pub fn get_mut(&mut self, n: u64) -> io::Result<&mut [u8]> {
+        let entry = self.entry(n, true)?;
+        entry.dirty = true;
+        Ok(&mut entry.data)
+    }
+
+    /// Writes every dirty sector back to the device, in sector order rather
+    /// than in the order they were written.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error encountered. Sectors written before it are no
+    /// longer dirty.
+    pub fn sync(&mut self) -> io::Result<()> {
+        let factor = self.factor();
+        for (&n, entry) in self.cache.iter_mut() {
+            if entry.dirty {
+                CachedDevice::write_physical(&mut self.device, factor, n, &entry.data)?;
+                entry.dirty = false;
+                self.stats.writebacks += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes back dirty sectors and returns the underlying device.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error, and drops the cache, if syncing fails.
+    pub fn into_inner(mut self) -> io::Result<T> {
+        self.sync()?;
+        Ok(self.device)
+    }
+}
+
+impl<T: BlockDevice> BlockDevice for CachedDevice<T> {
+    fn sector_size(&self) -> u64 {
+        self.sector_size
+    }
+
+    fn read_sector(&mut self, n: u64, buf: &mut [u8]) -> io::Result<usize> {
+        let data = self.get(n)?;
+        let len = core::cmp::min(buf.len(), data.len());
+        buf[..len].copy_from_slice(&data[..len]);
+        Ok(len)
+    }
+
+    fn write_sector(&mut self, n: u64, buf: &[u8]) -> io::Result<usize> {
+        let sector_size = self.sector_size as usize;
+        if buf.len() < sector_size {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "buffer shorter than sector"));
+        }
+
+        let entry = self.entry(n, false)?;
+        entry.data.copy_from_slice(&buf[..sector_size]);
+        entry.dirty = true;
+        Ok(sector_size)
+    }
+}
@@ -0,0 +1,20 @@
+#![cfg_attr(feature = "no_std", no_std)]
+#![feature(decl_macro)]
+
+#[macro_use]
+extern crate alloc;
+
+#[cfg(test)]
+mod tests;
+
+pub mod cache;
+pub mod mbr;
+pub mod partition;
+pub mod traits;
+pub mod vfat;
+
+pub use cache::{CacheStats, CachedDevice};
+pub use mbr::{MasterBootRecord, PartitionEntry};
+pub use partition::Partition;
+pub use traits::BlockDevice;
+pub use vfat::{VFat, VFatHandle};
@@ -0,0 +1,218 @@
+use core::fmt;
+use core::mem;
+
+use shim::const_assert_size;
+use shim::io;
+
+use crate::traits::BlockDevice;
+
+/// Partition type identifiers for FAT32 partitions (CHS and LBA addressed).
+pub const PARTITION_TYPE_FAT32: [u8; 2] = [0x0B, 0x0C];
+
+/// Cylinder-head-sector address of a partition boundary.
+#[repr(C, packed)]
+#[derive(Copy, Clone)]
+pub struct CHS {
+    head: u8,
+    sector_cylinder: [u8; 2],
+}
+
+impl CHS {
+    /// Returns the head number.
+    pub fn head(&self) -> u8 {
+        self.head
+    }
+
+    /// Returns the sector number (bits 0-5 of the second byte).
+    pub fn sector(&self) -> u8 {
+        self.sector_cylinder[0] & 0b0011_1111
+    }
+
+    /// Returns the 10-bit cylinder number.
+    pub fn cylinder(&self) -> u16 {
+        (((self.sector_cylinder[0] & 0b1100_0000) as u16) << 2) | self.sector_cylinder[1] as u16
+    }
+}
+
+impl fmt::Debug for CHS {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CHS")
+            .field("head", &self.head())
+            .field("sector", &self.sector())
+            .field("cylinder", &self.cylinder())
+            .finish()
+    }
+}
+
+const_assert_size!(CHS, 3);
+
+/// A single entry of the MBR partition table.
+#[repr(C, packed)]
+#[derive(Copy, Clone)]
+pub struct PartitionEntry {
+    boot_indicator: u8,
+    starting_chs: CHS,
+    partition_type: u8,
+    ending_chs: CHS,
+    relative_sector: u32,
+    total_sectors: u32,
+}
+
+impl PartitionEntry {
+    /// Returns `true` if the partition is marked as active (bootable).
+    pub fn is_bootable(&self) -> bool {
+        self.boot_indicator == 0x80
+    }
+
+    /// Returns `true` if the entry describes a partition. Unused entries have
+    /// a partition type of `0`.
+    pub fn is_used(&self) -> bool {
+        self.partition_type != 0
+    }
+
+    /// Returns `true` if the partition type is one of the FAT32 types.
+    pub fn is_fat32(&self) -> bool {
+        PARTITION_TYPE_FAT32.contains(&self.partition_type)
+    }
+
+    /// Returns the partition type identifier.
+    pub fn partition_type(&self) -> u8 {
+        self.partition_type
+    }
+
+    /// Returns the CHS address of the first sector of the partition.
+    pub fn starting_chs(&self) -> CHS {
+        self.starting_chs
+    }
+
+    /// Returns the CHS address of the last sector of the partition.
+    pub fn ending_chs(&self) -> CHS {
+        self.ending_chs
+    }
+
+    /// Returns the logical block address of the first sector of the partition.
+    pub fn start_lba(&self) -> u64 {
+        u32::from_le(self.relative_sector) as u64
+    }
+
+    /// Returns the number of sectors in the partition.
+    pub fn num_sectors(&self) -> u64 {
+        u32::from_le(self.total_sectors) as u64
+    }
+}
+
+impl fmt::Debug for PartitionEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PartitionEntry")
+            .field("bootable", &self.is_bootable())
+            .field("starting_chs", &self.starting_chs())
+            .field("partition_type", &self.partition_type())
+            .field("ending_chs", &self.ending_chs())
+            .field("start_lba", &self.start_lba())
+            .field("num_sectors", &self.num_sectors())
+            .finish()
+    }
+}
+
+const_assert_size!(PartitionEntry, 16);
+
+/// The master boot record (MBR).
+#[repr(C, packed)]
+pub struct MasterBootRecord {
+    bootstrap: [u8; 436],
+    disk_id: [u8; 10],
+    partition_table: [PartitionEntry; 4],
+    signature: [u8; 2],
+}
+
+impl fmt::Debug for MasterBootRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MasterBootRecord")
+            .field("disk_id", &{ self.disk_id })
+            .field("partition_table", &{ self.partition_table })
+            .field("signature", &{ self.signature })
+            .finish()
+    }
+}
+
+const_assert_size!(MasterBootRecord, 512);
+
+#[derive(Debug)]
+pub enum Error {
+    /// There was an I/O error while reading the MBR.
+    Io(io::Error),
+    /// Partition `.0` (0-indexed) contains an invalid or unknown boot
+    /// indicator.
+    UnknownBootIndicator(u8),
+    /// The MBR magic signature was invalid.
+    BadSignature,
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Error {
+        Error::Io(error)
+    }
+}
+
+impl MasterBootRecord {
+    /// Reads and returns the master boot record (MBR) from `device`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `BadSignature` if the MBR contains an invalid magic signature.
+    /// Returns `UnknownBootIndicator(n)` if partition `n` contains an invalid
+    /// boot indicator. Returns `Io(err)` if the I/O error `err` occured while
+    /// reading the MBR.
+    pub fn from<T: BlockDevice>(mut device: T) -> Result<MasterBootRecord, Error> {
+        let mut buf = [0u8; 512];
+        if device.read_sector(0, &mut buf)? != buf.len() {
+            return Err(Error::Io(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "short MBR read",
+            )));
+        }
+
+        let mbr: MasterBootRecord = unsafe { mem::transmute(buf) };
+        if mbr.signature != [0x55, 0xAA] {
+            return Err(Error::BadSignature);
+        }
+
+        for (i, entry) in mbr.partition_table.iter().enumerate() {
+            match entry.boot_indicator {
+                0x00 | 0x80 => {}
+                _ => return Err(Error::UnknownBootIndicator(i as u8)),
+            }
+        }
+
+        Ok(mbr)
+    }
+
+    /// Returns the optional 32-bit disk signature, stored at offset 440 of
+    /// the MBR.
+    pub fn disk_signature(&self) -> u32 {
+        let id = self.disk_id;
+        u32::from_le_bytes([id[4], id[5], id[6], id[7]])
+    }
+
+    /// Returns all four entries of the partition table, including unused
+    /// ones.
+    pub fn partition_table(&self) -> &[PartitionEntry; 4] {
+        &self.partition_table
+    }
+
+    /// Returns an iterator over the used entries of the partition table
+    /// together with their index in the table.
+    pub fn partitions(&self) -> impl Iterator<Item = (usize, &PartitionEntry)> {
+        self.partition_table
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.is_used())
+    }
+
+    /// Returns the first FAT32 partition, if any.
+    pub fn first_fat32(&self) -> Option<&PartitionEntry> {
+        self.partitions()
+            .map(|(_, entry)| entry)
+            .find(|entry| entry.is_fat32())
+    }
+}
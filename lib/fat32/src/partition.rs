@@ -0,0 +1,78 @@
+use shim::io;
+
+use crate::mbr::PartitionEntry;
+use crate::traits::BlockDevice;
+
+/// A contiguous range of sectors on a parent block device.
+///
+/// Sector `0` of a `Partition` is sector `start` of the parent device. Reads
+/// and writes past the end of the partition fail instead of spilling over into
+/// the sectors that follow it on the parent device.
+#[derive(Debug)]
+pub struct Partition<T: BlockDevice> {
+    device: T,
+    start: u64,
+    num_sectors: u64,
+}
+
+impl<T: BlockDevice> Partition<T> {
+    /// Creates a new partition of `num_sectors` sectors beginning at sector
+    /// `start` of `device`.
+    pub fn new(device: T, start: u64, num_sectors: u64) -> Partition<T> {
+        Partition {
+            device,
+            start,
+            num_sectors,
+        }
+    }
+
+    /// Creates a new partition spanning the sectors described by the MBR
+    /// partition table entry `entry`.
+    pub fn from_entry(device: T, entry: &PartitionEntry) -> Partition<T> {
+        Partition::new(device, entry.start_lba(), entry.num_sectors())
+    }
+
+    /// Returns the sector of the parent device where this partition begins.
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    /// Returns the number of sectors in this partition.
+    pub fn num_sectors(&self) -> u64 {
+        self.num_sectors
+    }
+
+    /// Consumes `self`, returning the parent device.
+    pub fn into_inner(self) -> T {
+        self.device
+    }
+
+    /// Maps the partition-relative sector `n` to a sector of the parent
+    /// device.
+    fn physical_sector(&self, n: u64) -> io::Result<u64> {
+        if n >= self.num_sectors {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "sector out of partition bounds",
+            ));
+        }
+
+        Ok(self.start + n)
+    }
+}
+
+impl<T: BlockDevice> BlockDevice for Partition<T> {
+    fn sector_size(&self) -> u64 {
+        self.device.sector_size()
+    }
+
+    fn read_sector(&mut self, n: u64, buf: &mut [u8]) -> io::Result<usize> {
+        let sector = self.physical_sector(n)?;
+        self.device.read_sector(sector, buf)
+    }
+
+    fn write_sector(&mut self, n: u64, buf: &[u8]) -> io::Result<usize> {
+        let sector = self.physical_sector(n)?;
+        self.device.write_sector(sector, buf)
+    }
+}
@@ -0,0 +1,6 @@
+mod cache;
+mod fsck;
+mod mbr;
+mod mkfs;
+mod vfat;
+mod vfat_write;
@@ -0,0 +1,192 @@
+use std::io::Cursor;
+use std::sync::{Arc, Mutex};
+
+use shim::io;
+
+use super::mkfs::Image;
+use super::vfat::StdVFatHandle;
+use crate::cache::{CacheStats, CachedDevice};
+use crate::traits::BlockDevice;
+use crate::vfat::{VFat, VFatHandle};
+
+/// A 512-byte-sector device that logs the sectors it reads and writes.
+#[derive(Clone, Default)]
+struct LoggingDevice {
+    data: Arc<Mutex<Vec<u8>>>,
+    log: Arc<Mutex<Vec<(char, u64)>>>,
+}
+
+impl LoggingDevice {
+    fn new(sectors: usize) -> LoggingDevice {
+        let data = (0..sectors * 512).map(|i| (i / 512) as u8).collect();
+        LoggingDevice { data: Arc::new(Mutex::new(data)), log: Default::default() }
+    }
+
+    fn take_log(&self) -> Vec<(char, u64)> {
+        std::mem::take(&mut *self.log.lock().unwrap())
+    }
+
+    fn sector(&self, n: usize) -> Vec<u8> {
+        self.data.lock().unwrap()[n * 512..(n + 1) * 512].to_vec()
+    }
+}
+
+impl BlockDevice for LoggingDevice {
+    fn read_sector(&mut self, n: u64, buf: &mut [u8]) -> io::Result<usize> {
+        self.log.lock().unwrap().push(('r', n));
+        let data = self.data.lock().unwrap();
+        let n = n as usize;
+        let len = std::cmp::min(buf.len(), 512);
+        buf[..len].copy_from_slice(&data[n * 512..n * 512 + len]);
+        Ok(len)
+    }
+
+    fn write_sector(&mut self, n: u64, buf: &[u8]) -> io::Result<usize> {
+        self.log.lock().unwrap().push(('w', n));
+        let mut data = self.data.lock().unwrap();
+        let n = n as usize;
+        data[n * 512..(n + 1) * 512].copy_from_slice(&buf[..512]);
+        Ok(512)
+    }
+}
+
+fn stats(hits: u64, misses: u64, evictions: u64, writebacks: u64) -> CacheStats {
+    CacheStats { hits, misses, evictions, writebacks }
+}
+
+#[test]
+fn check_cache_hits_and_misses() {
+    let device = LoggingDevice::new(16);
+    let mut cache = CachedDevice::new(device.clone(), 512, 4);
+
+    assert_eq!(cache.get(3).unwrap()[0], 3);
+    assert_eq!(cache.get(3).unwrap()[511], 3);
+    let mut buf = [0u8; 512];
+    cache.read_sector(5, &mut buf).unwrap();
+    cache.read_sector(3, &mut buf).unwrap();
+    assert_eq!(buf[0], 3);
+
+    assert_eq!(device.take_log(), [('r', 3), ('r', 5)]);
+    assert_eq!(cache.stats(), stats(2, 2, 0, 0));
+    assert_eq!(cache.len(), 2);
+
+    cache.reset_stats();
+    assert_eq!(cache.stats(), CacheStats::default());
+}
+
+#[test]
+fn check_cache_lru_eviction() {
+    let device = LoggingDevice::new(16);
+    let mut cache = CachedDevice::new(device.clone(), 512, 3);
+
+    for &n in &[0, 1, 2, 0, 3] {
+        cache.get(n).unwrap();
+    }
+    // Sector 1 was the least recently used one when 3 was loaded.
+    assert_eq!(cache.len(), 3);
+    assert_eq!(cache.stats(), stats(1, 4, 1, 0));
+
+    device.take_log();
+    cache.get(0).unwrap();
+    cache.get(2).unwrap();
+    cache.get(1).unwrap();
+    assert_eq!(device.take_log(), [('r', 1)]);
+    assert_eq!(cache.stats(), stats(3, 5, 2, 0));
+}
+
+#[test]
+fn check_cache_write_back() {
+    let device = LoggingDevice::new(16);
+    let mut cache = CachedDevice::new(device.clone(), 512, 2);
+
+    cache.get_mut(4).unwrap()[0] = 0xAA;
+    cache.write_sector(7, &[0xBB; 512]).unwrap();
+    assert_eq!(cache.dirty(), 2);
+    // Whole-sector writes don't read the sector first.
+    assert_eq!(device.take_log(), [('r', 4)]);
+    assert_eq!(device.sector(4)[0], 4);
+
+    // Evicting sector 4 writes it back.
+    cache.get(9).unwrap();
+    assert_eq!(device.take_log(), [('w', 4), ('r', 9)]);
+    assert_eq!(device.sector(4)[0], 0xAA);
+    assert_eq!(device.sector(4)[1], 4);
+
+    cache.sync().unwrap();
+    assert_eq!(device.take_log(), [('w', 7)]);
+    assert_eq!(device.sector(7), vec![0xBB; 512]);
+    assert_eq!(cache.dirty(), 0);
+    assert_eq!(cache.stats().writebacks, 2);
+
+    // Nothing left to write.
+    cache.sync().unwrap();
+    assert!(device.take_log().is_empty());
+}
+
+#[test]
+fn check_cache_sync_in_sector_order() {
+    let device = LoggingDevice::new(16);
+    let mut cache = CachedDevice::new(device.clone(), 512, 8);
+    for &n in &[6, 2, 9, 4] {
+        cache.write_sector(n, &[n as u8; 512]).unwrap();
+    }
+
+    let device = cache.into_inner().unwrap();
+    assert_eq!(device.take_log(), [('w', 2), ('w', 4), ('w', 6), ('w', 9)]);
+}
+
+#[test]
+fn check_cache_logical_sectors() {
+    let device = LoggingDevice::new(16);
+    let mut cache = CachedDevice::new(device.clone(), 2048, 2);
+    assert_eq!(cache.sector_size(), 2048);
+
+    let sector = cache.get(1).unwrap();
+    assert_eq!(sector.len(), 2048);
+    assert_eq!((sector[0], sector[512], sector[1024], sector[2047]), (4, 5, 6, 7));
+    assert_eq!(device.take_log(), [('r', 4), ('r', 5), ('r', 6), ('r', 7)]);
+
+    cache.get_mut(2).unwrap()[1024] = 0xCC;
+    cache.sync().unwrap();
+    let log = device.take_log();
+    assert_eq!(&log[4..], [('w', 8), ('w', 9), ('w', 10), ('w', 11)]);
+    assert_eq!(device.sector(10)[0], 0xCC);
+
+    let mut short = [0u8; 100];
+    assert_eq!(cache.read_sector(1, &mut short).unwrap(), 100);
+    let err = cache.write_sector(1, &[0; 1024]).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn check_cache_read_errors() {
+    let mut cache = CachedDevice::new(Cursor::new(vec![0u8; 512 * 2]), 512, 2);
+    assert!(cache.get(5).is_err());
+    assert!(cache.is_empty());
+    assert!(cache.get(1).is_ok());
+}
+
+#[test]
+#[should_panic]
+fn check_cache_bad_sector_size() {
+    CachedDevice::new(LoggingDevice::new(4), 768, 4);
+}
+
+#[test]
+fn check_vfat_uses_cache() {
+    let mut image = Image::new(64, 1);
+    for i in 0..20 {
+        image.add_file(2, &format!("FILE{}", i), b"");
+    }
+    let vfat = VFat::<StdVFatHandle>::from(image.device()).unwrap();
+
+    let root = vfat.root();
+    root.entries().unwrap().count();
+    let first = vfat.lock(|vfat| vfat.cache_stats());
+    root.entries().unwrap().count();
+    let second = vfat.lock(|vfat| vfat.cache_stats());
+
+    // The second listing reads the same FAT and directory sectors again.
+    assert_eq!(second.misses, first.misses);
+    assert!(second.hits > first.hits);
+}
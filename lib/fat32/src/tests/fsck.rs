@@ -0,0 +1,239 @@
+//! A consistency checker for FAT32 images, standing in for `fsck.vfat` in
+//! tests.
+
+use std::collections::HashSet;
+
+use super::mkfs::{PARTITION_START, SECTOR_SIZE};
+
+struct Volume<'a> {
+    data: &'a [u8],
+    sectors_per_cluster: usize,
+    reserved_sectors: usize,
+    num_fats: usize,
+    sectors_per_fat: usize,
+    num_clusters: u32,
+    root: u32,
+    fsinfo_sector: usize,
+}
+
+fn u16_at(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+impl<'a> Volume<'a> {
+    fn new(disk: &'a [u8]) -> Volume<'a> {
+        let data = &disk[PARTITION_START * SECTOR_SIZE..];
+        let sectors_per_cluster = data[13] as usize;
+        let reserved_sectors = u16_at(data, 14) as usize;
+        let num_fats = data[16] as usize;
+        let total_sectors = u32_at(data, 32) as usize;
+        let sectors_per_fat = u32_at(data, 36) as usize;
+        let data_start = reserved_sectors + num_fats * sectors_per_fat;
+        Volume {
+            data,
+            sectors_per_cluster,
+            reserved_sectors,
+            num_fats,
+            sectors_per_fat,
+            num_clusters: ((total_sectors - data_start) / sectors_per_cluster) as u32,
+            root: u32_at(data, 44),
+            fsinfo_sector: u16_at(data, 48) as usize,
+        }
+    }
+
+    fn cluster_size(&self) -> usize {
+        self.sectors_per_cluster * SECTOR_SIZE
+    }
+
+    fn fat(&self, copy: usize, cluster: u32) -> u32 {
+        let start = (self.reserved_sectors + copy * self.sectors_per_fat) * SECTOR_SIZE;
+        u32_at(self.data, start + cluster as usize * 4) & 0x0FFF_FFFF
+    }
+
+    fn cluster(&self, cluster: u32) -> &'a [u8] {
+        let data_start = (self.reserved_sectors + self.num_fats * self.sectors_per_fat) * SECTOR_SIZE;
+        let start = data_start + (cluster as usize - 2) * self.cluster_size();
+        &self.data[start..start + self.cluster_size()]
+    }
+}
+
+struct Checker<'a> {
+    volume: Volume<'a>,
+    used: HashSet<u32>,
+    problems: Vec<String>,
+}
+
+impl<'a> Checker<'a> {
+    /// Returns the chain starting at `start`, marking its clusters used.
+    fn claim_chain(&mut self, start: u32, what: &str) -> Vec<u32> {
+        let mut chain = vec![];
+        let mut cluster = start;
+        loop {
+            if cluster < 2 || cluster >= self.volume.num_clusters + 2 {
+                self.problems.push(format!("{}: invalid cluster {}", what, cluster));
+                return chain;
+            } else if !self.used.insert(cluster) {
+                self.problems.push(format!("{}: cluster {} is cross-linked", what, cluster));
+                return chain;
+            }
+
+            chain.push(cluster);
+            match self.volume.fat(0, cluster) {
+                next if next >= 0x0FFF_FFF8 => return chain,
+                0 => {
+                    self.problems.push(format!("{}: chain runs into free cluster {}", what, cluster));
+                    return chain;
+                }
+                next => cluster = next,
+            }
+        }
+    }
+
+    fn check_dir(&mut self, start: u32, parent: u32, path: &str) {
+        let chain = self.claim_chain(start, path);
+        let data: Vec<u8> = chain.iter().flat_map(|&c| self.volume.cluster(c).to_vec()).collect();
+        let is_root = start == self.volume.root;
+
+        let mut short_names = HashSet::new();
+        let mut lfn: Option<(u8, u8)> = None;
+        for (i, slot) in data.chunks(32).enumerate() {
+            match slot[0] {
+                0x00 => break,
+                0xE5 => {
+                    if lfn.take().is_some() {
+                        self.problems.push(format!("{}: LFN followed by deleted entry", path));
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+
+            if slot[11] & 0x3F == 0x0F {
+                let seq = slot[0] & 0x1F;
+                let checksum = slot[13];
+                lfn = match lfn {
+                    _ if slot[0] & 0x40 != 0 => Some((seq, checksum)),
+                    Some((prev, sum)) if prev == seq + 1 && sum == checksum => Some((seq, checksum)),
+                    _ => {
+                        self.problems.push(format!("{}: broken LFN sequence at slot {}", path, i));
+                        None
+                    }
+                };
+                continue;
+            }
+
+            let mut short = [0u8; 11];
+            short.copy_from_slice(&slot[..11]);
+            let name = String::from_utf8_lossy(&short).to_string();
+            if let Some((seq, checksum)) = lfn.take() {
+                let expected = short.iter().fold(0u8, |sum, &b| sum.rotate_right(1).wrapping_add(b));
+                if seq != 1 || checksum != expected {
+                    self.problems.push(format!("{}: orphan LFN before {:?}", path, name));
+                }
+            }
+
+            let attributes = slot[11];
+            if attributes & 0x08 != 0 {
+                continue;
+            }
+
+            let cluster = (u16_at(slot, 20) as u32) << 16 | u16_at(slot, 26) as u32;
+            let size = u32_at(slot, 28);
+            let entry_path = format!("{}/{}", path, name.trim_end());
+
+            let dot = &short == b".          ";
+            if dot || &short == b"..         " {
+                let target = if dot { start } else { parent };
+                let expected = if target == self.volume.root { 0 } else { target };
+                if is_root || i != if dot { 0 } else { 1 } || cluster != expected {
+                    self.problems.push(format!("{}: bad {:?} entry", path, name.trim_end()));
+                }
+                continue;
+            } else if !is_root && i < 2 {
+                self.problems.push(format!("{}: missing . and .. entries", path));
+            }
+
+            let valid = short.iter().all(|&b| {
+                b == b' ' || b.is_ascii_uppercase() || b.is_ascii_digit() || b"!#$%&'()-@^_`{}~".contains(&b)
+            });
+            if !valid || short[0] == b' ' {
+                self.problems.push(format!("{}: invalid short name", entry_path));
+            }
+            if !short_names.insert(short) {
+                self.problems.push(format!("{}: duplicate short name", entry_path));
+            }
+
+            if attributes & 0x10 != 0 {
+                if size != 0 {
+                    self.problems.push(format!("{}: directory with non-zero size", entry_path));
+                }
+                self.check_dir(cluster, start, &entry_path);
+            } else if size == 0 {
+                if cluster != 0 {
+                    self.problems.push(format!("{}: empty file with clusters", entry_path));
+                }
+            } else {
+                let chain = self.claim_chain(cluster, &entry_path);
+                let expected = (size as usize + self.volume.cluster_size() - 1) / self.volume.cluster_size();
+                if chain.len() != expected {
+                    self.problems.push(format!(
+                        "{}: size {} needs {} clusters, chain has {}",
+                        entry_path,
+                        size,
+                        expected,
+                        chain.len()
+                    ));
+                }
+            }
+        }
+
+        if lfn.is_some() {
+            self.problems.push(format!("{}: trailing LFN entries", path));
+        }
+    }
+}
+
+/// Checks the FAT32 volume on `disk` and returns the problems found.
+pub fn fsck(disk: &[u8]) -> Vec<String> {
+    let volume = Volume::new(disk);
+    let root = volume.root;
+    let mut checker = Checker { volume, used: HashSet::new(), problems: vec![] };
+    checker.check_dir(root, 0, "");
+
+    let volume = &checker.volume;
+    let mut free = 0;
+    for cluster in 2..volume.num_clusters + 2 {
+        for copy in 1..volume.num_fats {
+            if volume.fat(copy, cluster) != volume.fat(0, cluster) {
+                checker.problems.push(format!("FAT copy {} differs at cluster {}", copy, cluster));
+            }
+        }
+
+        let used = checker.used.contains(&cluster);
+        match volume.fat(0, cluster) {
+            0 if used => checker.problems.push(format!("cluster {} used but free", cluster)),
+            0 => free += 1,
+            0x0FFF_FFF7 => {}
+            _ if !used => checker.problems.push(format!("cluster {} lost", cluster)),
+            _ => {}
+        }
+    }
+
+    let fsinfo = &volume.data[volume.fsinfo_sector * SECTOR_SIZE..];
+    let count = u32_at(fsinfo, 488);
+    if count != 0xFFFF_FFFF && count != free {
+        checker.problems.push(format!("FSInfo free count {} but {} clusters free", count, free));
+    }
+
+    checker.problems
+}
+
+/// Panics with the problems found if the volume on `disk` isn't consistent.
+pub fn assert_clean(disk: &[u8]) {
+    let problems = fsck(disk);
+    assert!(problems.is_empty(), "fsck found problems: {:#?}", problems);
+}
@@ -0,0 +1,168 @@
+use std::io::Cursor;
+
+use crate::mbr::{self, MasterBootRecord};
+use crate::partition::Partition;
+use crate::traits::BlockDevice;
+
+/// Returns a zeroed fake disk of `sectors` 512-byte sectors.
+fn disk(sectors: usize) -> Cursor<Vec<u8>> {
+    Cursor::new(vec![0u8; sectors * 512])
+}
+
+/// Writes partition table entry `index` and a valid signature to sector 0 of
+/// `disk`.
+fn set_partition(disk: &mut Cursor<Vec<u8>>, index: usize, boot: u8, kind: u8, start: u32, len: u32) {
+    let buf = disk.get_mut();
+    let entry = &mut buf[446 + index * 16..446 + (index + 1) * 16];
+    entry[0] = boot;
+    entry[4] = kind;
+    entry[8..12].copy_from_slice(&start.to_le_bytes());
+    entry[12..16].copy_from_slice(&len.to_le_bytes());
+    buf[510] = 0x55;
+    buf[511] = 0xAA;
+}
+
+macro_rules! assert_mbr_err {
+    ($mbr:expr, $pat:pat) => {
+        match $mbr {
+            Err($pat) => {}
+            Err(e) => panic!("unexpected error: {:?}", e),
+            Ok(mbr) => panic!("unexpected MBR: {:?}", mbr),
+        }
+    };
+}
+
+#[test]
+fn check_mbr_size() {
+    assert_eq!(std::mem::size_of::<MasterBootRecord>(), 512);
+    assert_eq!(std::mem::size_of::<mbr::PartitionEntry>(), 16);
+}
+
+#[test]
+fn check_mbr_signature() {
+    let mut data = disk(1);
+    assert_mbr_err!(MasterBootRecord::from(&mut data), mbr::Error::BadSignature);
+
+    data.get_mut()[510] = 0x55;
+    data.get_mut()[511] = 0xAA;
+    MasterBootRecord::from(&mut data).expect("valid MBR");
+
+    data.get_mut()[511] = 0xBB;
+    assert_mbr_err!(MasterBootRecord::from(&mut data), mbr::Error::BadSignature);
+}
+
+#[test]
+fn check_mbr_boot_indicator() {
+    let mut data = disk(1);
+    set_partition(&mut data, 0, 0x80, 0x0C, 8, 16);
+    set_partition(&mut data, 1, 0x00, 0x83, 24, 16);
+    MasterBootRecord::from(&mut data).expect("valid MBR");
+
+    set_partition(&mut data, 2, 0x01, 0x83, 40, 16);
+    assert_mbr_err!(MasterBootRecord::from(&mut data), mbr::Error::UnknownBootIndicator(2));
+}
+
+#[test]
+fn check_mbr_disk_signature() {
+    let mut data = disk(1);
+    set_partition(&mut data, 0, 0x80, 0x0C, 8, 16);
+    data.get_mut()[436..440].copy_from_slice(&[0xFF; 4]);
+    data.get_mut()[440..444].copy_from_slice(&0x1234_5678u32.to_le_bytes());
+    data.get_mut()[444..446].copy_from_slice(&[0xFF; 2]);
+
+    let mbr = MasterBootRecord::from(&mut data).expect("valid MBR");
+    assert_eq!(mbr.disk_signature(), 0x1234_5678);
+}
+
+#[test]
+fn check_mbr_short_device() {
+    let mut data = Cursor::new(vec![0u8; 100]);
+    assert_mbr_err!(MasterBootRecord::from(&mut data), mbr::Error::Io(_));
+}
+
+#[test]
+fn check_mbr_partitions() {
+    let mut data = disk(1);
+    set_partition(&mut data, 0, 0x80, 0x0B, 63, 2048);
+    set_partition(&mut data, 2, 0x00, 0x83, 4096, 1 << 20);
+
+    let mbr = MasterBootRecord::from(&mut data).expect("valid MBR");
+    let partitions: Vec<_> = mbr.partitions().collect();
+    assert_eq!(partitions.len(), 2);
+
+    let (index, first) = partitions[0];
+    assert_eq!(index, 0);
+    assert!(first.is_bootable());
+    assert!(first.is_fat32());
+    assert_eq!(first.partition_type(), 0x0B);
+    assert_eq!(first.start_lba(), 63);
+    assert_eq!(first.num_sectors(), 2048);
+
+    let (index, second) = partitions[1];
+    assert_eq!(index, 2);
+    assert!(!second.is_bootable());
+    assert!(!second.is_fat32());
+    assert_eq!(second.start_lba(), 4096);
+    assert_eq!(second.num_sectors(), 1 << 20);
+
+    assert_eq!(mbr.first_fat32().map(|e| e.start_lba()), Some(63));
+}
+
+#[test]
+fn check_partition_translation() {
+    let mut data = disk(16);
+    for (i, sector) in data.get_mut().chunks_mut(512).enumerate() {
+        sector.iter_mut().for_each(|b| *b = i as u8);
+    }
+
+    let mut partition = Partition::new(&mut data, 4, 8);
+    assert_eq!(partition.sector_size(), 512);
+
+    let mut buf = [0u8; 512];
+    for n in 0..8 {
+        assert_eq!(partition.read_sector(n, &mut buf).expect("in bounds"), 512);
+        assert!(buf.iter().all(|&b| b == (n + 4) as u8));
+    }
+
+    let mut vec = vec![];
+    partition.read_all_sector(0, &mut vec).expect("in bounds");
+    partition.read_all_sector(7, &mut vec).expect("in bounds");
+    assert_eq!(vec.len(), 1024);
+    assert!(vec[..512].iter().all(|&b| b == 4));
+    assert!(vec[512..].iter().all(|&b| b == 11));
+
+    let err = partition.read_sector(8, &mut buf).expect_err("out of bounds");
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn check_partition_write() {
+    let mut data = disk(16);
+    let mut partition = Partition::new(&mut data, 2, 4);
+
+    assert_eq!(partition.write_sector(1, &[0xAB; 512]).expect("in bounds"), 512);
+    partition.write_sector(4, &[0xCD; 512]).expect_err("out of bounds");
+    partition.write_sector(0, &[0xCD; 100]).expect_err("short buffer");
+
+    let buf = data.into_inner();
+    assert!(buf[..3 * 512].iter().all(|&b| b == 0));
+    assert!(buf[3 * 512..4 * 512].iter().all(|&b| b == 0xAB));
+    assert!(buf[4 * 512..].iter().all(|&b| b == 0));
+}
+
+#[test]
+fn check_partition_from_mbr() {
+    let mut data = disk(32);
+    set_partition(&mut data, 1, 0x00, 0x0C, 16, 8);
+    data.get_mut()[16 * 512] = 0x42;
+
+    let mbr = MasterBootRecord::from(&mut data).expect("valid MBR");
+    let entry = *mbr.first_fat32().expect("FAT32 partition");
+    let mut partition = Partition::from_entry(data, &entry);
+    assert_eq!(partition.start(), 16);
+    assert_eq!(partition.num_sectors(), 8);
+
+    let mut buf = [0u8; 512];
+    partition.read_sector(0, &mut buf).expect("in bounds");
+    assert_eq!(buf[0], 0x42);
+}
@@ -0,0 +1,308 @@
+//! Builds small FAT32 disk images in memory for tests.
+
+use std::io::Cursor;
+
+pub const SECTOR_SIZE: usize = 512;
+
+/// First sector of the FAT32 partition on the fake disk.
+pub const PARTITION_START: usize = 8;
+
+const RESERVED_SECTORS: usize = 32;
+const NUM_FATS: usize = 2;
+
+pub const EOC: u32 = 0x0FFF_FFFF;
+
+/// Date and time stamped on every entry: 2019-03-14 12:34:56.
+pub const DATE: u16 = ((2019 - 1980) << 9) | (3 << 5) | 14;
+pub const TIME: u16 = (12 << 11) | (34 << 5) | (56 / 2);
+
+/// An in-memory disk holding an MBR and a single FAT32 partition.
+pub struct Image {
+    pub data: Vec<u8>,
+    sectors_per_cluster: usize,
+    sectors_per_fat: usize,
+    num_clusters: u32,
+    next_free: u32,
+    short_names: usize,
+}
+
+impl Image {
+    /// Formats a disk with a FAT32 partition of `num_clusters` data clusters
+    /// of `sectors_per_cluster` sectors each. The root directory occupies
+    /// cluster 2.
+    pub fn new(num_clusters: u32, sectors_per_cluster: u8) -> Image {
+        let sectors_per_cluster = sectors_per_cluster as usize;
+        let fat_bytes = (num_clusters as usize + 2) * 4;
+        let sectors_per_fat = (fat_bytes + SECTOR_SIZE - 1) / SECTOR_SIZE;
+        let total_sectors = RESERVED_SECTORS
+            + NUM_FATS * sectors_per_fat
+            + num_clusters as usize * sectors_per_cluster;
+
+        let mut image = Image {
+            data: vec![0; (PARTITION_START + total_sectors) * SECTOR_SIZE],
+            sectors_per_cluster,
+            sectors_per_fat,
+            num_clusters,
+            next_free: 3,
+            short_names: 0,
+        };
+
+        // MBR with one FAT32 (LBA) partition.
+        let mbr = &mut image.data[..SECTOR_SIZE];
+        mbr[446] = 0x80;
+        mbr[446 + 4] = 0x0C;
+        mbr[446 + 8..446 + 12].copy_from_slice(&(PARTITION_START as u32).to_le_bytes());
+        mbr[446 + 12..446 + 16].copy_from_slice(&(total_sectors as u32).to_le_bytes());
+        mbr[510] = 0x55;
+        mbr[511] = 0xAA;
+
+        // Boot sector.
+        let bs = &mut image.data[PARTITION_START * SECTOR_SIZE..][..SECTOR_SIZE];
+        bs[..3].copy_from_slice(&[0xEB, 0x58, 0x90]);
+        bs[3..11].copy_from_slice(b"MKFSTEST");
+        bs[11..13].copy_from_slice(&(SECTOR_SIZE as u16).to_le_bytes());
+        bs[13] = sectors_per_cluster as u8;
+        bs[14..16].copy_from_slice(&(RESERVED_SECTORS as u16).to_le_bytes());
+        bs[16] = NUM_FATS as u8;
+        bs[21] = 0xF8;
+        bs[32..36].copy_from_slice(&(total_sectors as u32).to_le_bytes());
+        bs[36..40].copy_from_slice(&(sectors_per_fat as u32).to_le_bytes());
+        bs[44..48].copy_from_slice(&2u32.to_le_bytes());
+        bs[48..50].copy_from_slice(&1u16.to_le_bytes());
+        bs[50..52].copy_from_slice(&6u16.to_le_bytes());
+        bs[64] = 0x80;
+        bs[66] = 0x29;
+        bs[67..71].copy_from_slice(&0x1234_5678u32.to_le_bytes());
+        bs[71..82].copy_from_slice(b"TESTVOLUME ");
+        bs[82..90].copy_from_slice(b"FAT32   ");
+        bs[510] = 0x55;
+        bs[511] = 0xAA;
+
+        // FSInfo.
+        let fsinfo = &mut image.data[(PARTITION_START + 1) * SECTOR_SIZE..][..SECTOR_SIZE];
+        fsinfo[..4].copy_from_slice(&0x4161_5252u32.to_le_bytes());
+        fsinfo[484..488].copy_from_slice(&0x6141_7272u32.to_le_bytes());
+        fsinfo[488..492].copy_from_slice(&(num_clusters - 1).to_le_bytes());
+        fsinfo[492..496].copy_from_slice(&3u32.to_le_bytes());
+        fsinfo[508..512].copy_from_slice(&0xAA55_0000u32.to_le_bytes());
+
+        image.set_fat(0, 0x0FFF_FFF8);
+        image.set_fat(1, EOC);
+        image.set_fat(2, EOC);
+        image
+    }
+
+    /// Returns the raw disk with the FSInfo free cluster count updated.
+    pub fn finish(mut self) -> Vec<u8> {
+        let free = (2..self.num_clusters + 2).filter(|&c| self.fat(c) == 0).count() as u32;
+        let offset = (PARTITION_START + 1) * SECTOR_SIZE + 488;
+        self.data[offset..offset + 4].copy_from_slice(&free.to_le_bytes());
+        self.data
+    }
+
+    /// Returns the image as a block device.
+    pub fn device(self) -> Cursor<Vec<u8>> {
+        Cursor::new(self.finish())
+    }
+
+    /// Returns the partition (without the MBR and the sectors before it).
+    pub fn partition_only(self) -> Cursor<Vec<u8>> {
+        Cursor::new(self.finish()[PARTITION_START * SECTOR_SIZE..].to_vec())
+    }
+
+    pub fn cluster_size(&self) -> usize {
+        self.sectors_per_cluster * SECTOR_SIZE
+    }
+
+    fn fat_offset(&self, fat: usize, cluster: u32) -> usize {
+        (PARTITION_START + RESERVED_SECTORS + fat * self.sectors_per_fat) * SECTOR_SIZE
+            + cluster as usize * 4
+    }
+
+    pub fn cluster_offset(&self, cluster: u32) -> usize {
+        assert!(cluster >= 2 && cluster < self.num_clusters + 2);
+        (PARTITION_START + RESERVED_SECTORS + NUM_FATS * self.sectors_per_fat) * SECTOR_SIZE
+            + (cluster as usize - 2) * self.cluster_size()
+    }
+
+    pub fn set_fat(&mut self, cluster: u32, value: u32) {
+        for fat in 0..NUM_FATS {
+            let offset = self.fat_offset(fat, cluster);
+            self.data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    pub fn fat(&self, cluster: u32) -> u32 {
+        let offset = self.fat_offset(0, cluster);
+        let mut raw = [0u8; 4];
+        raw.copy_from_slice(&self.data[offset..offset + 4]);
+        u32::from_le_bytes(raw)
+    }
+
+    /// Links `clusters` into a chain terminated with an end-of-chain marker.
+    pub fn link(&mut self, clusters: &[u32]) {
+        for pair in clusters.windows(2) {
+            self.set_fat(pair[0], pair[1]);
+        }
+        if let Some(&last) = clusters.last() {
+            self.set_fat(last, EOC);
+        }
+    }
+
+    /// Allocates and links a chain of `n` consecutive free clusters.
+    pub fn alloc(&mut self, n: usize) -> Vec<u32> {
+        let chain: Vec<u32> = (self.next_free..self.next_free + n as u32).collect();
+        self.next_free += n as u32;
+        self.link(&chain);
+        chain
+    }
+
+    /// Returns the chain starting at `start`.
+    pub fn chain(&self, start: u32) -> Vec<u32> {
+        let mut chain = vec![start];
+        loop {
+            let next = self.fat(*chain.last().unwrap()) & 0x0FFF_FFFF;
+            if next >= 0x0FFF_FFF8 {
+                return chain;
+            }
+            chain.push(next);
+        }
+    }
+
+    /// Writes `contents` to the clusters of `chain`.
+    pub fn write_chain(&mut self, chain: &[u32], contents: &[u8]) {
+        let cluster_size = self.cluster_size();
+        for (&cluster, chunk) in chain.iter().zip(contents.chunks(cluster_size)) {
+            let offset = self.cluster_offset(cluster);
+            self.data[offset..offset + chunk.len()].copy_from_slice(chunk);
+        }
+    }
+
+    /// Appends the raw 32-byte `entry` to the directory starting at `dir`,
+    /// growing the directory by a cluster when it is full.
+    pub fn push_entry(&mut self, dir: u32, entry: &[u8; 32]) {
+        let cluster_size = self.cluster_size();
+        for cluster in self.chain(dir) {
+            let offset = self.cluster_offset(cluster);
+            for slot in (offset..offset + cluster_size).step_by(32) {
+                if self.data[slot] == 0x00 {
+                    self.data[slot..slot + 32].copy_from_slice(entry);
+                    return;
+                }
+            }
+        }
+
+        let last = *self.chain(dir).last().unwrap();
+        let new = self.alloc(1)[0];
+        self.set_fat(last, new);
+        let offset = self.cluster_offset(new);
+        self.data[offset..offset + 32].copy_from_slice(entry);
+    }
+
+    /// Builds a regular directory entry.
+    pub fn regular_entry(short: &[u8; 11], attributes: u8, cluster: u32, size: u32) -> [u8; 32] {
+        let mut entry = [0u8; 32];
+        entry[..11].copy_from_slice(short);
+        entry[11] = attributes;
+        entry[13] = 100;
+        entry[14..16].copy_from_slice(&TIME.to_le_bytes());
+        entry[16..18].copy_from_slice(&DATE.to_le_bytes());
+        entry[18..20].copy_from_slice(&DATE.to_le_bytes());
+        entry[20..22].copy_from_slice(&((cluster >> 16) as u16).to_le_bytes());
+        entry[22..24].copy_from_slice(&TIME.to_le_bytes());
+        entry[24..26].copy_from_slice(&DATE.to_le_bytes());
+        entry[26..28].copy_from_slice(&(cluster as u16).to_le_bytes());
+        entry[28..32].copy_from_slice(&size.to_le_bytes());
+        entry
+    }
+
+    /// Builds the LFN entries for `name`, in on-disk order.
+    pub fn lfn_entries(name: &str, short: &[u8; 11]) -> Vec<[u8; 32]> {
+        let checksum = short.iter().fold(0u8, |sum, &b| sum.rotate_right(1).wrapping_add(b));
+        let mut chars: Vec<u16> = name.encode_utf16().collect();
+        if chars.len() % 13 != 0 {
+            chars.push(0);
+        }
+        while chars.len() % 13 != 0 {
+            chars.push(0xFFFF);
+        }
+
+        let count = chars.len() / 13;
+        let mut entries = vec![];
+        for seq in (1..=count).rev() {
+            let part = &chars[(seq - 1) * 13..seq * 13];
+            let mut entry = [0u8; 32];
+            entry[0] = seq as u8 | if seq == count { 0x40 } else { 0 };
+            entry[11] = 0x0F;
+            entry[13] = checksum;
+            let offsets = (0..5).map(|i| 1 + 2 * i)
+                .chain((0..6).map(|i| 14 + 2 * i))
+                .chain((0..2).map(|i| 28 + 2 * i));
+            for (offset, c) in offsets.zip(part) {
+                entry[offset..offset + 2].copy_from_slice(&c.to_le_bytes());
+            }
+            entries.push(entry);
+        }
+        entries
+    }
+
+    /// Returns the 8.3 form of `name` if it is a valid uppercase 8.3 name.
+    fn short_name(name: &str) -> Option<[u8; 11]> {
+        let (base, ext) = match name.rfind('.') {
+            Some(i) => (&name[..i], &name[i + 1..]),
+            None => (name, ""),
+        };
+        let valid = |s: &str, max: usize| {
+            s.len() <= max && s.bytes().all(|b| b.is_ascii_uppercase() || b.is_ascii_digit() || b == b'_')
+        };
+        if base.is_empty() || !valid(base, 8) || !valid(ext, 3) {
+            return None;
+        }
+
+        let mut short = [b' '; 11];
+        short[..base.len()].copy_from_slice(base.as_bytes());
+        short[8..8 + ext.len()].copy_from_slice(ext.as_bytes());
+        Some(short)
+    }
+
+    /// Adds an entry named `name` to `dir`, preceded by LFN entries unless
+    /// `name` is a valid 8.3 name.
+    pub fn add_entry(&mut self, dir: u32, name: &str, attributes: u8, cluster: u32, size: u32) {
+        let short = match Image::short_name(name) {
+            Some(short) => short,
+            None => {
+                self.short_names += 1;
+                let mut short = [b' '; 11];
+                let basis = format!("LFN~{}", self.short_names);
+                short[..basis.len()].copy_from_slice(basis.as_bytes());
+                for entry in Image::lfn_entries(name, &short) {
+                    self.push_entry(dir, &entry);
+                }
+                short
+            }
+        };
+
+        self.push_entry(dir, &Image::regular_entry(&short, attributes, cluster, size));
+    }
+
+    /// Adds a file named `name` holding `contents` to `dir`. Returns its
+    /// chain.
+    pub fn add_file(&mut self, dir: u32, name: &str, contents: &[u8]) -> Vec<u32> {
+        let clusters = (contents.len() + self.cluster_size() - 1) / self.cluster_size();
+        let chain = self.alloc(clusters);
+        self.write_chain(&chain, contents);
+        let start = chain.first().cloned().unwrap_or(0);
+        self.add_entry(dir, name, 0x20, start, contents.len() as u32);
+        chain
+    }
+
+    /// Adds a subdirectory named `name` to `dir`. Returns its first cluster.
+    pub fn add_dir(&mut self, dir: u32, name: &str) -> u32 {
+        let cluster = self.alloc(1)[0];
+        self.add_entry(dir, name, 0x10, cluster, 0);
+        self.push_entry(cluster, &Image::regular_entry(b".          ", 0x10, cluster, 0));
+        let parent = if dir == 2 { 0 } else { dir };
+        self.push_entry(cluster, &Image::regular_entry(b"..         ", 0x10, parent, 0));
+        cluster
+    }
+}
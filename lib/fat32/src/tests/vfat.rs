@@ -0,0 +1,411 @@
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::sync::{Arc, Mutex};
+
+use shim::io;
+
+use super::mkfs::{Image, DATE, PARTITION_START, SECTOR_SIZE, TIME};
+use crate::vfat::{Attributes, Dir, Entry, Error, VFat, VFatHandle};
+
+#[derive(Clone, Debug)]
+pub struct StdVFatHandle(Arc<Mutex<VFat<StdVFatHandle>>>);
+
+impl VFatHandle for StdVFatHandle {
+    fn new(val: VFat<StdVFatHandle>) -> Self {
+        StdVFatHandle(Arc::new(Mutex::new(val)))
+    }
+
+    fn lock<R>(&self, f: impl FnOnce(&mut VFat<StdVFatHandle>) -> R) -> R {
+        f(&mut self.0.lock().expect("poisoned"))
+    }
+}
+
+fn mount(image: Image) -> StdVFatHandle {
+    VFat::<StdVFatHandle>::from(image.device()).expect("failed to mount image")
+}
+
+pub fn names(dir: &Dir<StdVFatHandle>) -> Vec<String> {
+    dir.entries().expect("entries").map(|e| e.name().to_string()).collect()
+}
+
+pub fn read_file(vfat: &StdVFatHandle, path: &str) -> Vec<u8> {
+    let mut file = vfat.open(path).expect("open").into_file().expect("not a file");
+    let mut data = vec![];
+    file.read_to_end(&mut data).expect("read");
+    data
+}
+
+pub fn pattern(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 7 + i / 251) as u8).collect()
+}
+
+macro_rules! assert_vfat_err {
+    ($vfat:expr, $pat:pat) => {
+        match $vfat {
+            Err($pat) => {}
+            Err(e) => panic!("unexpected error: {:?}", e),
+            Ok(vfat) => panic!("unexpected filesystem: {:?}", vfat),
+        }
+    };
+}
+
+#[test]
+fn check_mount() {
+    let vfat = mount(Image::new(64, 1));
+    vfat.lock(|vfat| {
+        assert_eq!(vfat.cluster_size(), 512);
+        assert_eq!(vfat.num_clusters(), 64);
+        assert_eq!(vfat.root_dir_cluster().number(), 2);
+    });
+
+    assert!(names(&vfat.root()).is_empty());
+    assert!(vfat.open("/").unwrap().is_dir());
+    assert!(vfat.open("").unwrap().is_dir());
+}
+
+#[test]
+fn check_mount_partition_only() {
+    let image = Image::new(64, 2);
+    let sectors = (image.data.len() / SECTOR_SIZE - PARTITION_START) as u64;
+    let vfat = VFat::<StdVFatHandle>::from_partition(image.partition_only(), 0, sectors)
+        .expect("failed to mount partition");
+    vfat.lock(|vfat| assert_eq!(vfat.cluster_size(), 1024));
+}
+
+#[test]
+fn check_mount_errors() {
+    let mut image = Image::new(64, 1);
+    image.data[446 + 4] = 0x83;
+    assert_vfat_err!(VFat::<StdVFatHandle>::from(image.device()), Error::NoFat32Partition);
+
+    let mut image = Image::new(64, 1);
+    image.data[PARTITION_START * SECTOR_SIZE + 510] = 0;
+    assert_vfat_err!(VFat::<StdVFatHandle>::from(image.device()), Error::BadSignature);
+
+    let mut image = Image::new(64, 1);
+    image.data[PARTITION_START * SECTOR_SIZE + 13] = 3;
+    assert_vfat_err!(VFat::<StdVFatHandle>::from(image.device()), Error::Unsupported(_));
+
+    let image = Image::new(64, 1);
+    let truncated = Cursor::new(image.data[..SECTOR_SIZE].to_vec());
+    assert_vfat_err!(VFat::<StdVFatHandle>::from(truncated), Error::Io(_));
+}
+
+#[test]
+fn check_short_and_long_names() {
+    let mut image = Image::new(64, 1);
+    image.add_file(2, "README.TXT", b"hi");
+    image.add_file(2, "a much longer file name.markdown", b"");
+    image.add_file(2, "ünïcødé ✓.txt", b"x");
+    image.add_dir(2, "DOCS");
+    let vfat = mount(image);
+
+    assert_eq!(
+        names(&vfat.root()),
+        ["README.TXT", "a much longer file name.markdown", "ünïcødé ✓.txt", "DOCS"]
+    );
+}
+
+#[test]
+fn check_name_exactly_one_lfn_entry() {
+    let mut image = Image::new(64, 1);
+    image.add_file(2, "thirteen char", b"");
+    image.add_file(2, "twenty-six characters long", b"");
+    let vfat = mount(image);
+
+    assert_eq!(names(&vfat.root()), ["thirteen char", "twenty-six characters long"]);
+}
+
+#[test]
+fn check_lowercase_case_flags() {
+    let mut image = Image::new(64, 1);
+    let mut entry = Image::regular_entry(b"NOTES   TXT", 0x20, 0, 0);
+    entry[12] = 0x08 | 0x10;
+    image.push_entry(2, &entry);
+    let mut entry = Image::regular_entry(b"README     ", 0x20, 0, 0);
+    entry[12] = 0x08;
+    image.push_entry(2, &entry);
+    let vfat = mount(image);
+
+    assert_eq!(names(&vfat.root()), ["notes.txt", "readme"]);
+}
+
+#[test]
+fn check_bad_lfn_falls_back_to_short_name() {
+    let mut image = Image::new(64, 1);
+    let short = *b"LONGNA~1TXT";
+    let mut lfn = Image::lfn_entries("long name.txt", &short);
+    lfn[0][13] ^= 0xFF;
+    for entry in &lfn {
+        image.push_entry(2, entry);
+    }
+    image.push_entry(2, &Image::regular_entry(&short, 0x20, 0, 0));
+
+    // A sequence missing its first (last on disk) entry is dropped as well.
+    let short = *b"OTHERN~1TXT";
+    let lfn = Image::lfn_entries("other name with more characters.txt", &short);
+    for entry in &lfn[1..] {
+        image.push_entry(2, entry);
+    }
+    image.push_entry(2, &Image::regular_entry(&short, 0x20, 0, 0));
+    let vfat = mount(image);
+
+    assert_eq!(names(&vfat.root()), ["LONGNA~1.TXT", "OTHERN~1.TXT"]);
+}
+
+#[test]
+fn check_deleted_and_volume_entries_skipped() {
+    let mut image = Image::new(64, 1);
+    image.push_entry(2, &Image::regular_entry(b"TESTVOLUME ", 0x08, 0, 0));
+    image.add_file(2, "deleted file.txt", b"gone");
+    image.add_file(2, "KEPT", b"kept");
+    let start = image.cluster_offset(2);
+    // Mark the LFN entries and the regular entry of the first file deleted.
+    for slot in 1..=3 {
+        image.data[start + slot * 32] = 0xE5;
+    }
+    let vfat = mount(image);
+
+    assert_eq!(names(&vfat.root()), ["KEPT"]);
+}
+
+#[test]
+fn check_entries_stop_at_end_marker() {
+    let mut image = Image::new(64, 1);
+    image.add_file(2, "FIRST", b"");
+    let offset = image.cluster_offset(2) + 2 * 32;
+    image.data[offset..offset + 32].copy_from_slice(&Image::regular_entry(b"HIDDEN     ", 0x20, 0, 0));
+    let vfat = mount(image);
+
+    assert_eq!(names(&vfat.root()), ["FIRST"]);
+}
+
+#[test]
+fn check_multi_cluster_directory() {
+    let mut image = Image::new(64, 1);
+    let expected: Vec<String> = (0..40).map(|i| format!("FILE{}", i)).collect();
+    for name in &expected {
+        image.add_file(2, name, b"");
+    }
+    let root_chain = image.chain(2);
+    let vfat = mount(image);
+
+    assert!(root_chain.len() > 1);
+    assert_eq!(names(&vfat.root()), expected);
+}
+
+#[test]
+fn check_find_case_insensitive() {
+    let mut image = Image::new(64, 1);
+    image.add_file(2, "Mixed Case.txt", b"contents");
+    let vfat = mount(image);
+
+    let root = vfat.root();
+    assert_eq!(root.find("mixed case.TXT").unwrap().name(), "Mixed Case.txt");
+    assert_eq!(root.find("missing").unwrap_err().kind(), io::ErrorKind::NotFound);
+}
+
+#[test]
+fn check_open_nested() {
+    let mut image = Image::new(64, 1);
+    let docs = image.add_dir(2, "docs");
+    let nested = image.add_dir(docs, "nested dir");
+    image.add_file(nested, "deep.txt", b"deep");
+    image.add_file(2, "top.txt", b"top");
+    let vfat = mount(image);
+
+    assert_eq!(read_file(&vfat, "/docs/nested dir/deep.txt"), b"deep");
+    assert_eq!(read_file(&vfat, "DOCS/NESTED DIR/DEEP.TXT"), b"deep");
+    assert_eq!(read_file(&vfat, "/docs/./nested dir/../nested dir//deep.txt"), b"deep");
+    assert_eq!(read_file(&vfat, "/docs/nested dir/../../top.txt"), b"top");
+    assert_eq!(read_file(&vfat, "/../../top.txt"), b"top");
+
+    let dir = vfat.open("/docs/nested dir").unwrap().into_dir().unwrap();
+    assert_eq!(dir.name(), "nested dir");
+    assert_eq!(names(&dir), [".", "..", "deep.txt"]);
+
+    // `..` of a first-level directory refers to the root.
+    let docs = vfat.open("/docs").unwrap().into_dir().unwrap();
+    let parent = docs.find("..").unwrap().into_dir().unwrap();
+    assert_eq!(names(&parent), ["docs", "top.txt"]);
+}
+
+#[test]
+fn check_open_errors() {
+    let mut image = Image::new(64, 1);
+    image.add_file(2, "file.txt", b"");
+    let vfat = mount(image);
+
+    assert_eq!(vfat.open("/missing").unwrap_err().kind(), io::ErrorKind::NotFound);
+    assert_eq!(vfat.open("/file.txt/x").unwrap_err().kind(), io::ErrorKind::NotFound);
+    assert_eq!(vfat.open("/missing/x").unwrap_err().kind(), io::ErrorKind::NotFound);
+}
+
+#[test]
+fn check_read_small_and_empty() {
+    let mut image = Image::new(64, 1);
+    image.add_file(2, "SMALL", b"hello, world");
+    image.add_file(2, "EMPTY", b"");
+    let vfat = mount(image);
+
+    assert_eq!(read_file(&vfat, "/SMALL"), b"hello, world");
+    assert_eq!(read_file(&vfat, "/EMPTY"), b"");
+
+    let file = vfat.open("/EMPTY").unwrap().into_file().unwrap();
+    assert_eq!(file.size(), 0);
+    assert!(!file.first_cluster().is_data());
+}
+
+#[test]
+fn check_read_multi_cluster() {
+    let contents = pattern(4 * 1024 + 123);
+    let mut image = Image::new(64, 2);
+    image.add_file(2, "big.bin", &contents);
+    let vfat = mount(image);
+
+    assert_eq!(read_file(&vfat, "/big.bin"), contents);
+
+    // Reads in odd-sized chunks cross sector and cluster boundaries.
+    let mut file = vfat.open("/big.bin").unwrap().into_file().unwrap();
+    let mut data = vec![];
+    let mut buf = [0u8; 333];
+    loop {
+        let n = file.read(&mut buf).unwrap();
+        if n == 0 {
+            break;
+        }
+        data.extend_from_slice(&buf[..n]);
+    }
+    assert_eq!(data, contents);
+}
+
+#[test]
+fn check_read_fragmented() {
+    let contents = pattern(5 * 512);
+    let mut image = Image::new(64, 1);
+    let chain = [20, 9, 33, 4, 12];
+    image.link(&chain);
+    image.write_chain(&chain, &contents);
+    image.add_entry(2, "fragmented.bin", 0x20, chain[0], contents.len() as u32);
+    let vfat = mount(image);
+
+    assert_eq!(read_file(&vfat, "/fragmented.bin"), contents);
+}
+
+#[test]
+fn check_read_broken_chain() {
+    let mut image = Image::new(64, 1);
+    let chain = image.add_file(2, "BROKEN", &pattern(3 * 512));
+    image.set_fat(chain[1], 0);
+    let dir = image.add_dir(2, "LOOP");
+    image.set_fat(dir, dir);
+    let vfat = mount(image);
+
+    let mut file = vfat.open("/BROKEN").unwrap().into_file().unwrap();
+    let mut data = vec![];
+    let err = file.read_to_end(&mut data).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+    let dir = vfat.open("/LOOP").unwrap().into_dir().unwrap();
+    let err = dir.entries().err().expect("looping chain read");
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn check_seek() {
+    let contents = pattern(3 * 512 + 10);
+    let mut image = Image::new(64, 1);
+    image.add_file(2, "SEEK", &contents);
+    let vfat = mount(image);
+
+    let mut file = vfat.open("/SEEK").unwrap().into_file().unwrap();
+    let mut buf = [0u8; 16];
+
+    assert_eq!(file.seek(SeekFrom::Start(1000)).unwrap(), 1000);
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf[..], &contents[1000..1016]);
+
+    // Seeking backwards restarts the chain walk.
+    assert_eq!(file.seek(SeekFrom::Current(-516)).unwrap(), 500);
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf[..], &contents[500..516]);
+
+    assert_eq!(file.seek(SeekFrom::End(-4)).unwrap(), contents.len() as u64 - 4);
+    assert_eq!(file.read(&mut buf).unwrap(), 4);
+    assert_eq!(&buf[..4], &contents[contents.len() - 4..]);
+    assert_eq!(file.read(&mut buf).unwrap(), 0);
+
+    assert_eq!(file.seek(SeekFrom::End(0)).unwrap(), contents.len() as u64);
+    let err = file.seek(SeekFrom::End(1)).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    let err = file.seek(SeekFrom::Current(-(contents.len() as i64) - 1)).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    assert_eq!(file.seek(SeekFrom::Start(0)).unwrap(), 0);
+}
+
+#[test]
+fn check_metadata() {
+    let mut image = Image::new(64, 1);
+    image.add_entry(2, "SECRET", 0x01 | 0x02 | 0x20, 0, 0);
+    image.add_dir(2, "DIR");
+    let vfat = mount(image);
+
+    let secret = vfat.open("/SECRET").unwrap();
+    let metadata = secret.metadata();
+    assert!(metadata.read_only());
+    assert!(metadata.hidden());
+    assert_eq!(metadata.attributes.to_string(), "-rh-a");
+    assert_eq!(metadata.modified.to_string(), "2019-03-14 12:34:56");
+    assert_eq!(metadata.created.date.raw(), DATE);
+    assert_eq!(metadata.created.time.raw(), TIME);
+    assert_eq!(metadata.accessed.to_string(), "2019-03-14 00:00:00");
+
+    let dir = vfat.open("/DIR").unwrap();
+    assert!(dir.is_dir());
+    assert!(dir.metadata().attributes.directory());
+    assert_eq!(dir.metadata().attributes.to_string(), "d----");
+    assert!(Attributes::from(0x0F).lfn());
+}
+
+#[test]
+fn check_entry_accessors() {
+    let mut image = Image::new(64, 1);
+    image.add_file(2, "FILE", b"");
+    image.add_dir(2, "DIR");
+    let vfat = mount(image);
+
+    let entries: Vec<Entry<StdVFatHandle>> = vfat.root().entries().unwrap().collect();
+    assert!(entries[0].is_file() && entries[0].as_file().is_some() && entries[0].as_dir().is_none());
+    assert!(entries[1].is_dir() && entries[1].as_dir().is_some() && entries[1].as_file().is_none());
+}
+
+/// Walks a real FAT32 image named by the `FAT32_TEST_IMG` environment
+/// variable, reading every file. Run it with `cargo test -- --ignored`.
+#[test]
+#[ignore = "needs a FAT32 image named by FAT32_TEST_IMG"]
+fn check_real_image() {
+    let path = std::env::var("FAT32_TEST_IMG").expect("FAT32_TEST_IMG names no image");
+
+    fn walk(dir: &Dir<StdVFatHandle>, depth: usize) -> usize {
+        let mut files = 0;
+        for entry in dir.entries().expect("entries") {
+            match entry {
+                Entry::File(mut file) => {
+                    let mut data = vec![];
+                    file.read_to_end(&mut data).expect("read");
+                    assert_eq!(data.len() as u64, file.size());
+                    files += 1;
+                }
+                Entry::Dir(ref sub) if sub.name() != "." && sub.name() != ".." => {
+                    assert!(depth < 32, "directory tree too deep");
+                    files += walk(sub, depth + 1);
+                }
+                Entry::Dir(_) => {}
+            }
+        }
+        files
+    }
+
+    let device = std::fs::File::open(path).expect("open image");
+    let vfat = VFat::<StdVFatHandle>::from(device).expect("mount image");
+    walk(&vfat.root(), 0);
+}
@@ -0,0 +1,711 @@
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use shim::io;
+
+use super::fsck::{assert_clean, fsck};
+use super::mkfs::{Image, PARTITION_START, SECTOR_SIZE};
+use super::vfat::{names, pattern, read_file, StdVFatHandle};
+use crate::traits::BlockDevice;
+use crate::vfat::{Date, Time, Timestamp, VFat, VFatHandle};
+
+/// A disk whose contents stay accessible after it is handed to `VFat`.
+#[derive(Clone)]
+struct SharedDisk(Arc<Mutex<Vec<u8>>>);
+
+impl SharedDisk {
+    fn contents(&self) -> Vec<u8> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+impl BlockDevice for SharedDisk {
+    fn read_sector(&mut self, n: u64, buf: &mut [u8]) -> io::Result<usize> {
+        let data = self.0.lock().unwrap();
+        let start = n as usize * SECTOR_SIZE;
+        if start + SECTOR_SIZE > data.len() {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "sector out of range"));
+        }
+        buf[..SECTOR_SIZE].copy_from_slice(&data[start..start + SECTOR_SIZE]);
+        Ok(SECTOR_SIZE)
+    }
+
+    fn write_sector(&mut self, n: u64, buf: &[u8]) -> io::Result<usize> {
+        let mut data = self.0.lock().unwrap();
+        let start = n as usize * SECTOR_SIZE;
+        if start + SECTOR_SIZE > data.len() {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "sector out of range"));
+        }
+        data[start..start + SECTOR_SIZE].copy_from_slice(&buf[..SECTOR_SIZE]);
+        Ok(SECTOR_SIZE)
+    }
+}
+
+/// A disk whose sectors from `bad` on can't be read.
+struct BadSectors {
+    disk: SharedDisk,
+    bad: u64,
+}
+
+impl BlockDevice for BadSectors {
+    fn read_sector(&mut self, n: u64, buf: &mut [u8]) -> io::Result<usize> {
+        if n >= self.bad {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "bad sector"));
+        }
+        self.disk.read_sector(n, buf)
+    }
+
+    fn write_sector(&mut self, n: u64, buf: &[u8]) -> io::Result<usize> {
+        self.disk.write_sector(n, buf)
+    }
+}
+
+/// A disk that fails every write after the first `limit`, as if the
+/// machine stopped part way through a sync. Counts the writes that made it.
+struct Interrupted {
+    disk: SharedDisk,
+    limit: usize,
+    writes: Arc<AtomicUsize>,
+}
+
+impl BlockDevice for Interrupted {
+    fn read_sector(&mut self, n: u64, buf: &mut [u8]) -> io::Result<usize> {
+        self.disk.read_sector(n, buf)
+    }
+
+    fn write_sector(&mut self, n: u64, buf: &[u8]) -> io::Result<usize> {
+        let writes = self.writes.load(Ordering::Relaxed);
+        if writes >= self.limit {
+            return Err(io::Error::new(io::ErrorKind::BrokenPipe, "interrupted"));
+        }
+        self.writes.store(writes + 1, Ordering::Relaxed);
+        self.disk.write_sector(n, buf)
+    }
+}
+
+fn mount(image: Image) -> (StdVFatHandle, SharedDisk) {
+    let disk = SharedDisk(Arc::new(Mutex::new(image.finish())));
+    let vfat = VFat::<StdVFatHandle>::from(disk.clone()).expect("failed to mount image");
+    (vfat, disk)
+}
+
+fn remount(disk: &SharedDisk) -> StdVFatHandle {
+    VFat::<StdVFatHandle>::from(disk.clone()).expect("failed to remount image")
+}
+
+fn free_clusters(vfat: &StdVFatHandle) -> u32 {
+    vfat.lock(|vfat| vfat.free_clusters()).unwrap()
+}
+
+fn march_clock() -> Timestamp {
+    Timestamp { date: Date::new(2019, 3, 14), time: Time::new(12, 34, 56) }
+}
+
+fn april_clock() -> Timestamp {
+    Timestamp { date: Date::new(2019, 4, 1), time: Time::new(8, 0, 10) }
+}
+
+/// Returns the number of used slots in the root directory.
+fn root_slots(disk: &SharedDisk) -> usize {
+    let data = disk.contents();
+    let image_root = PARTITION_START * SECTOR_SIZE + (32 + 2) * SECTOR_SIZE;
+    data[image_root..image_root + SECTOR_SIZE]
+        .chunks(32)
+        .take_while(|slot| slot[0] != 0)
+        .filter(|slot| slot[0] != 0xE5)
+        .count()
+}
+
+#[test]
+fn check_create_and_write() {
+    let (vfat, disk) = mount(Image::new(64, 1));
+    let free = free_clusters(&vfat);
+
+    let mut file = vfat.create_file("/hello.txt").unwrap();
+    assert_eq!(file.size(), 0);
+    file.write_all(b"hello, world").unwrap();
+    assert_eq!(file.size(), 12);
+    drop(file);
+
+    assert_eq!(read_file(&vfat, "/hello.txt"), b"hello, world");
+    assert_eq!(free_clusters(&vfat), free - 1);
+    assert_clean(&disk.contents());
+
+    let vfat = remount(&disk);
+    assert_eq!(names(&vfat.root()), ["hello.txt"]);
+    assert_eq!(read_file(&vfat, "/HELLO.TXT"), b"hello, world");
+    assert_eq!(free_clusters(&vfat), free - 1);
+}
+
+#[test]
+fn check_create_empty_file() {
+    let (vfat, disk) = mount(Image::new(64, 1));
+    vfat.create_file("/empty").unwrap();
+
+    let file = vfat.open("/EMPTY").unwrap().into_file().unwrap();
+    assert_eq!(file.size(), 0);
+    assert!(!file.first_cluster().is_data());
+    assert_clean(&disk.contents());
+}
+
+#[test]
+fn check_create_existing() {
+    let (vfat, _) = mount(Image::new(64, 1));
+    vfat.create_file("/Name.txt").unwrap();
+    vfat.create_dir("/dir").unwrap();
+
+    let err = vfat.create_file("/name.TXT").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+    let err = vfat.create_dir("/DIR").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+}
+
+#[test]
+fn check_invalid_names() {
+    let (vfat, _) = mount(Image::new(64, 1));
+    let root = vfat.root();
+    let long = "x".repeat(256);
+    for name in &["", ".", "..", "a:b", "what?", "star*", "tab\t", "dot.", "space ", &long] {
+        let err = root.create_file(name).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "name {:?}", name);
+    }
+
+    let longest = "x".repeat(255);
+    root.create_file(&longest).unwrap();
+    assert_eq!(names(&root), [longest]);
+}
+
+#[test]
+fn check_short_names() {
+    let (vfat, disk) = mount(Image::new(64, 1));
+    for name in &["README", "notes.txt", "MAKEFILE.IN"] {
+        vfat.create_file(name).unwrap();
+    }
+    // Exact 8.3 names, including all-lowercase ones, need no LFN entries.
+    assert_eq!(root_slots(&disk), 3);
+
+    for name in &["Mixed.txt", "long file name.text", "long file name.tex2", ".hidden", "a.b.c"] {
+        vfat.create_file(name).unwrap();
+    }
+    assert_eq!(
+        names(&vfat.root()),
+        ["README", "notes.txt", "MAKEFILE.IN", "Mixed.txt", "long file name.text", "long file name.tex2", ".hidden", "a.b.c"]
+    );
+    assert_clean(&disk.contents());
+
+    // The aliases are unique and derived from the long names.
+    let data = disk.contents();
+    let root = PARTITION_START * SECTOR_SIZE + (32 + 2) * SECTOR_SIZE;
+    let shorts: Vec<&[u8]> = data[root..root + SECTOR_SIZE]
+        .chunks(32)
+        .take_while(|slot| slot[0] != 0)
+        .filter(|slot| slot[11] != 0x0F)
+        .map(|slot| &slot[..11])
+        .collect();
+    assert_eq!(
+        shorts,
+        [
+            &b"README     "[..],
+            b"NOTES   TXT",
+            b"MAKEFILEIN ",
+            b"MIXED~1 TXT",
+            b"LONGFI~1TEX",
+            b"LONGFI~2TEX",
+            b"HIDDEN~1   ",
+            b"AB~1    C  ",
+        ]
+    );
+}
+
+#[test]
+fn check_unicode_name() {
+    let (vfat, disk) = mount(Image::new(64, 1));
+    let mut file = vfat.create_file("/ünïcødé ✓ name that spans several entries.txt").unwrap();
+    file.write_all(b"x").unwrap();
+
+    let vfat = remount(&disk);
+    assert_eq!(names(&vfat.root()), ["ünïcødé ✓ name that spans several entries.txt"]);
+    assert_eq!(read_file(&vfat, "/ünïcødé ✓ NAME THAT SPANS SEVERAL ENTRIES.TXT"), b"x");
+    assert_clean(&disk.contents());
+}
+
+#[test]
+fn check_write_multi_cluster() {
+    let contents = pattern(10 * 1024 + 17);
+    let (vfat, disk) = mount(Image::new(64, 2));
+
+    let mut file = vfat.create_file("/big.bin").unwrap();
+    // Odd-sized writes cross sector and cluster boundaries.
+    for chunk in contents.chunks(777) {
+        file.write_all(chunk).unwrap();
+    }
+
+    assert_eq!(read_file(&vfat, "/big.bin"), contents);
+    assert_clean(&disk.contents());
+}
+
+#[test]
+fn check_append_and_overwrite() {
+    let (vfat, disk) = mount(Image::new(64, 1));
+    let mut expected = pattern(700);
+    let mut file = vfat.create_file("/log.txt").unwrap();
+    file.write_all(&expected).unwrap();
+    drop(file);
+
+    // Append by seeking to the end of a reopened file.
+    let mut file = vfat.open("/log.txt").unwrap().into_file().unwrap();
+    file.seek(SeekFrom::End(0)).unwrap();
+    file.write_all(b"appended line\n").unwrap();
+    expected.extend_from_slice(b"appended line\n");
+
+    // Overwrite across the first sector boundary without growing.
+    file.seek(SeekFrom::Start(500)).unwrap();
+    file.write_all(&[0xAA; 30]).unwrap();
+    expected[500..530].copy_from_slice(&[0xAA; 30]);
+    assert_eq!(file.size(), expected.len() as u64);
+
+    // Overwrite the tail and grow past it.
+    file.seek(SeekFrom::End(-4)).unwrap();
+    file.write_all(&[0x55; 600]).unwrap();
+    expected.truncate(expected.len() - 4);
+    expected.extend_from_slice(&[0x55; 600]);
+    drop(file);
+
+    assert_eq!(read_file(&vfat, "/log.txt"), expected);
+    assert_clean(&disk.contents());
+}
+
+#[test]
+fn check_write_then_read_same_handle() {
+    let (vfat, _) = mount(Image::new(64, 1));
+    let mut file = vfat.create_file("/rw").unwrap();
+    file.write_all(&pattern(1500)).unwrap();
+
+    file.seek(SeekFrom::Start(1000)).unwrap();
+    let mut buf = [0u8; 500];
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf[..], &pattern(1500)[1000..]);
+}
+
+#[test]
+fn check_set_len() {
+    let (vfat, disk) = mount(Image::new(64, 1));
+    let free = free_clusters(&vfat);
+    let contents = pattern(5 * 512);
+    let mut file = vfat.create_file("/file").unwrap();
+    file.write_all(&contents).unwrap();
+    assert_eq!(free_clusters(&vfat), free - 5);
+
+    file.set_len(1000).unwrap();
+    assert_eq!(file.size(), 1000);
+    assert_eq!(file.seek(SeekFrom::Current(0)).unwrap(), 1000);
+    assert_eq!(free_clusters(&vfat), free - 2);
+    assert_eq!(read_file(&vfat, "/file"), &contents[..1000]);
+    assert_clean(&disk.contents());
+
+    file.set_len(1024).unwrap();
+    file.set_len(1500).unwrap();
+    let mut expected = contents[..1000].to_vec();
+    expected.resize(1500, 0);
+    assert_eq!(read_file(&vfat, "/file"), expected);
+    assert_clean(&disk.contents());
+
+    file.set_len(0).unwrap();
+    assert_eq!(free_clusters(&vfat), free);
+    assert!(!vfat.open("/file").unwrap().into_file().unwrap().first_cluster().is_data());
+    assert_clean(&disk.contents());
+
+    // The emptied file can grow again.
+    file.write_all(b"again").unwrap();
+    assert_eq!(read_file(&vfat, "/file"), b"again");
+    assert_clean(&disk.contents());
+}
+
+#[test]
+fn check_remove_file() {
+    let (vfat, disk) = mount(Image::new(64, 1));
+    let free = free_clusters(&vfat);
+    vfat.create_file("/a long name.txt").unwrap().write_all(&pattern(2000)).unwrap();
+    vfat.create_file("/KEEP").unwrap().write_all(b"keep").unwrap();
+    vfat.create_file("/empty").unwrap();
+
+    vfat.remove("/A LONG NAME.TXT").unwrap();
+    vfat.remove("/empty").unwrap();
+    assert_eq!(names(&vfat.root()), ["KEEP"]);
+    assert_eq!(free_clusters(&vfat), free - 1);
+    assert_clean(&disk.contents());
+
+    let err = vfat.remove("/missing").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    let err = vfat.root().remove_dir("KEEP").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn check_deleted_slots_reused() {
+    let (vfat, disk) = mount(Image::new(64, 1));
+    for i in 0..10 {
+        vfat.create_file(&format!("/FILE{}", i)).unwrap();
+    }
+    vfat.remove("/FILE3").unwrap();
+    vfat.remove("/FILE4").unwrap();
+
+    // Needs one LFN slot and one regular slot: fits where the files were.
+    vfat.create_file("/Mixed.txt").unwrap();
+    let mut expected: Vec<String> = (0..10).map(|i| format!("FILE{}", i)).collect();
+    expected[3] = "Mixed.txt".into();
+    expected.remove(4);
+    assert_eq!(names(&vfat.root()), expected);
+    assert_eq!(root_slots(&disk), 10);
+    assert_clean(&disk.contents());
+}
+
+#[test]
+fn check_directory_growth() {
+    let (vfat, disk) = mount(Image::new(128, 1));
+    let expected: Vec<String> = (0..50).map(|i| format!("a long file name {}", i)).collect();
+    for name in &expected {
+        vfat.create_file(name).unwrap();
+    }
+
+    assert_eq!(names(&vfat.root()), expected);
+    assert!(vfat.lock(|vfat| vfat.chain(vfat.root_dir_cluster())).unwrap().len() > 1);
+    assert_clean(&disk.contents());
+}
+
+#[test]
+fn check_create_and_remove_dir() {
+    let (vfat, disk) = mount(Image::new(64, 1));
+    let free = free_clusters(&vfat);
+
+    let docs = vfat.create_dir("/docs").unwrap();
+    assert_eq!(names(&docs), [".", ".."]);
+    let nested = vfat.create_dir("/docs/Nested Dir").unwrap();
+    nested.create_file("deep.txt").unwrap().write_all(b"deep").unwrap();
+
+    assert_eq!(read_file(&vfat, "/docs/nested dir/../nested dir/deep.txt"), b"deep");
+    assert_eq!(names(&vfat.open("/docs/Nested Dir/..").unwrap().into_dir().unwrap()), [".", "..", "Nested Dir"]);
+    assert_clean(&disk.contents());
+
+    let err = vfat.remove("/docs").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::Other);
+    let err = docs.remove_file("Nested Dir").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+    vfat.remove("/docs/nested dir/deep.txt").unwrap();
+    vfat.remove("/docs/nested dir").unwrap();
+    vfat.remove("/docs").unwrap();
+    assert!(names(&vfat.root()).is_empty());
+    assert_eq!(free_clusters(&vfat), free);
+    assert_clean(&disk.contents());
+}
+
+#[test]
+fn check_rename() {
+    let (vfat, disk) = mount(Image::new(64, 1));
+    vfat.create_file("/SHORT.TXT").unwrap().write_all(b"contents").unwrap();
+    vfat.create_dir("/dir").unwrap().create_file("inner").unwrap();
+    vfat.create_file("/other").unwrap();
+
+    vfat.rename("/short.txt", "/a much longer name.txt").unwrap();
+    assert_eq!(read_file(&vfat, "/a much longer name.txt"), b"contents");
+    vfat.rename("/a much longer name.txt", "/A Much Longer Name.TXT").unwrap();
+    vfat.rename("/dir", "/renamed dir").unwrap();
+    assert_eq!(names(&vfat.root()), ["other", "renamed dir", "A Much Longer Name.TXT"]);
+    assert_eq!(names(&vfat.open("/renamed dir").unwrap().into_dir().unwrap()), [".", "..", "inner"]);
+    assert_clean(&disk.contents());
+
+    let err = vfat.rename("/other", "/RENAMED DIR").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+    let err = vfat.rename("/missing", "/new").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    let err = vfat.rename("/other", "/renamed dir/other").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    let err = vfat.rename("/other", "/bad:name").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    assert_clean(&disk.contents());
+}
+
+#[test]
+fn check_timestamps() {
+    let (vfat, disk) = mount(Image::new(64, 1));
+    vfat.lock(|vfat| vfat.set_clock(march_clock));
+    let mut file = vfat.create_file("/stamped").unwrap();
+    assert_eq!(file.metadata().created.to_string(), "2019-03-14 12:34:56");
+    assert_eq!(file.metadata().modified.to_string(), "2019-03-14 12:34:56");
+
+    vfat.lock(|vfat| vfat.set_clock(april_clock));
+    file.write_all(b"data").unwrap();
+    assert_eq!(file.metadata().modified.to_string(), "2019-04-01 08:00:10");
+
+    let entry = remount(&disk).open("/stamped").unwrap();
+    let metadata = entry.metadata();
+    assert_eq!(metadata.created.to_string(), "2019-03-14 12:34:56");
+    assert_eq!(metadata.modified.to_string(), "2019-04-01 08:00:10");
+    assert_eq!(metadata.accessed.to_string(), "2019-04-01 00:00:00");
+    assert!(metadata.attributes.archive());
+}
+
+#[test]
+fn check_read_only_file() {
+    let mut image = Image::new(64, 1);
+    image.add_entry(2, "LOCKED", 0x01, 0, 0);
+    let (vfat, _) = mount(image);
+
+    let mut file = vfat.open("/LOCKED").unwrap().into_file().unwrap();
+    assert_eq!(file.write(b"x").unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+    assert_eq!(file.set_len(0).unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+}
+
+#[test]
+fn check_volume_full() {
+    let (vfat, disk) = mount(Image::new(16, 1));
+    let free = free_clusters(&vfat) as usize;
+    let mut file = vfat.create_file("/fill").unwrap();
+
+    let err = file.write_all(&pattern((free + 1) * 512)).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::Other);
+    assert_eq!(file.size(), free as u64 * 512);
+    assert_eq!(free_clusters(&vfat), 0);
+    assert_eq!(vfat.create_dir("/dir").unwrap_err().kind(), io::ErrorKind::Other);
+    assert_clean(&disk.contents());
+
+    vfat.remove("/fill").unwrap();
+    assert_eq!(free_clusters(&vfat) as usize, free);
+    assert_clean(&disk.contents());
+}
+
+#[test]
+fn check_failed_write_frees_clusters() {
+    // Partial sectors are read before being written, so a write to an
+    // unreadable cluster fails after the cluster was allocated.
+    for &size in [0, 512].iter() {
+        let mut image = Image::new(64, 1);
+        let chain = image.add_file(2, "FILE", &pattern(size));
+        let first_unused = chain.last().map_or(3, |&cluster| cluster + 1);
+        let bad = (image.cluster_offset(first_unused) / SECTOR_SIZE) as u64;
+        let disk = SharedDisk(Arc::new(Mutex::new(image.finish())));
+        let vfat = VFat::<StdVFatHandle>::from(BadSectors { disk: disk.clone(), bad }).unwrap();
+        let free = free_clusters(&vfat);
+
+        let mut file = vfat.open("/FILE").unwrap().into_file().unwrap();
+        file.seek(SeekFrom::End(0)).unwrap();
+        assert_eq!(file.write(b"data").unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(file.size(), size as u64);
+        assert_eq!(free_clusters(&vfat), free);
+        assert_eq!(read_file(&vfat, "/FILE"), pattern(size));
+        assert_clean(&disk.contents());
+    }
+}
+
+/// Runs `operation` on a volume prepared by `setup`, interrupting it after
+/// each possible number of sector writes. Every interrupted volume must be
+/// consistent and pass `check`, except for the problems containing one of
+/// `allowed`: leaked clusters and a stale free count are always allowed.
+fn check_interrupted(
+    setup: impl Fn(&StdVFatHandle),
+    operation: impl Fn(&StdVFatHandle) -> io::Result<()>,
+    allowed: &[&str],
+    check: impl Fn(&StdVFatHandle),
+) {
+    let allowed: Vec<&str> = ["lost", "FSInfo free count", "FAT copy"].iter().chain(allowed).copied().collect();
+    for limit in 0.. {
+        let (vfat, disk) = mount(Image::new(64, 1));
+        setup(&vfat);
+
+        let writes = Arc::new(AtomicUsize::new(0));
+        let device = Interrupted { disk: disk.clone(), limit, writes: writes.clone() };
+        let finished = operation(&VFat::<StdVFatHandle>::from(device).unwrap()).is_ok();
+
+        let problems: Vec<String> = fsck(&disk.contents())
+            .into_iter()
+            .filter(|problem| !allowed.iter().any(|allowed| problem.contains(allowed)))
+            .collect();
+        assert!(problems.is_empty(), "interrupted after {} writes: {:#?}", limit, problems);
+        check(&remount(&disk));
+
+        if finished {
+            assert!(limit > 0, "operation finished without writing");
+            return;
+        }
+    }
+}
+
+/// Returns `true` if the root directory has an entry named `name`.
+fn exists(vfat: &StdVFatHandle, name: &str) -> bool {
+    names(&vfat.root()).iter().any(|entry| entry == name)
+}
+
+#[test]
+fn check_interrupted_remove_file() {
+    check_interrupted(
+        |vfat| vfat.create_file("/FILE").unwrap().write_all(&pattern(1024)).unwrap(),
+        |vfat| vfat.remove("/FILE"),
+        &[],
+        |vfat| assert!(!exists(vfat, "FILE") || read_file(vfat, "/FILE") == pattern(1024)),
+    );
+}
+
+#[test]
+fn check_interrupted_create_dir() {
+    check_interrupted(
+        |_| {},
+        |vfat| vfat.create_dir("/DIR").map(|_| ()),
+        &[],
+        |vfat| {
+            if exists(vfat, "DIR") {
+                let dir = vfat.open("/DIR").unwrap().into_dir().unwrap();
+                assert_eq!(names(&dir), [".", ".."]);
+            }
+        },
+    );
+}
+
+#[test]
+fn check_interrupted_remove_dir() {
+    check_interrupted(
+        |vfat| drop(vfat.create_dir("/DIR").unwrap()),
+        |vfat| vfat.remove("/DIR"),
+        &[],
+        |_| {},
+    );
+}
+
+#[test]
+fn check_interrupted_rename() {
+    // Until the old entry is deleted, both entries share the file's clusters,
+    // which leaves the new one without a chain of its own.
+    check_interrupted(
+        |vfat| vfat.create_file("/FILE").unwrap().write_all(&pattern(1024)).unwrap(),
+        |vfat| vfat.rename("/FILE", "/MOVED"),
+        &["cross-linked", "/MOVED: size 1024 needs 2 clusters, chain has 0"],
+        |vfat| {
+            let name = if exists(vfat, "MOVED") { "/MOVED" } else { "/FILE" };
+            assert_eq!(read_file(vfat, name), pattern(1024));
+        },
+    );
+}
+
+#[test]
+fn check_interrupted_rename_into_new_cluster() {
+    // With the root directory full, the new entry goes in a cluster after
+    // the old entry's, which a sync in sector order would write last.
+    check_interrupted(
+        |vfat| {
+            vfat.create_file("/FILE").unwrap().write_all(&pattern(1024)).unwrap();
+            for i in 0..15 {
+                vfat.create_file(&format!("/F{}", i)).unwrap();
+            }
+        },
+        |vfat| vfat.rename("/FILE", "/MOVED"),
+        &["cross-linked", "/MOVED: size 1024 needs 2 clusters, chain has 0"],
+        |vfat| {
+            let name = if exists(vfat, "MOVED") { "/MOVED" } else { "/FILE" };
+            assert_eq!(read_file(vfat, name), pattern(1024));
+        },
+    );
+}
+
+#[test]
+fn check_interrupted_truncation() {
+    // Until the chain is cut, the clusters past the new size are leaked.
+    check_interrupted(
+        |vfat| vfat.create_file("/FILE").unwrap().write_all(&pattern(1536)).unwrap(),
+        |vfat| vfat.open("/FILE")?.into_file().unwrap().set_len(512),
+        &["/FILE: size 512 needs 1 clusters, chain has 3"],
+        |vfat| {
+            let data = read_file(vfat, "/FILE");
+            assert!(data.len() == 512 || data.len() == 1536);
+            assert_eq!(data, pattern(data.len()));
+        },
+    );
+}
+
+#[test]
+fn check_unknown_fsinfo_count() {
+    let mut data = Image::new(64, 1).finish();
+    let offset = (PARTITION_START + 1) * SECTOR_SIZE + 488;
+    data[offset..offset + 8].copy_from_slice(&[0xFF; 8]);
+    let disk = SharedDisk(Arc::new(Mutex::new(data)));
+    let vfat = remount(&disk);
+
+    assert_eq!(free_clusters(&vfat), 63);
+    vfat.create_file("/file").unwrap().write_all(b"data").unwrap();
+    assert_eq!(free_clusters(&vfat), 62);
+    assert_clean(&disk.contents());
+}
+
+#[test]
+fn check_fragmented_allocation() {
+    let (vfat, disk) = mount(Image::new(64, 1));
+    for i in 0..6 {
+        vfat.create_file(&format!("/F{}", i)).unwrap().write_all(&pattern(512)).unwrap();
+    }
+    vfat.remove("/F1").unwrap();
+    vfat.remove("/F3").unwrap();
+
+    // The allocator continues after the last allocation, then wraps around
+    // into the holes.
+    let contents = pattern(59 * 512);
+    vfat.create_file("/big").unwrap().write_all(&contents).unwrap();
+    assert_eq!(read_file(&vfat, "/big"), contents);
+    assert_eq!(free_clusters(&vfat), 0);
+
+    assert_clean(&disk.contents());
+}
+
+#[test]
+fn check_fsck_detects_problems() {
+    let mut image = Image::new(64, 1);
+    let chain = image.add_file(2, "FILE", &pattern(1024));
+    assert_clean(&image.finish());
+
+    let mut image = Image::new(64, 1);
+    image.add_file(2, "FILE", &pattern(1024));
+    image.alloc(1);
+    assert_eq!(super::fsck::fsck(&image.finish()).len(), 1, "lost cluster");
+
+    let mut image = Image::new(64, 1);
+    image.add_file(2, "FILE", &pattern(1024));
+    image.set_fat(chain[0], chain[1] + 1);
+    image.set_fat(chain[1] + 1, 0x0FFF_FFFF);
+    assert!(!super::fsck::fsck(&image.finish()).is_empty(), "chain/size mismatch");
+
+    let mut data = Image::new(64, 1).finish();
+    data[(PARTITION_START + 1) * SECTOR_SIZE + 488] ^= 1;
+    assert_eq!(super::fsck::fsck(&data).len(), 1, "free count");
+}
+
+/// Runs `fsck.vfat -n` from dosfstools on a volume after a mix of writes.
+/// The request asked for writes to leave a volume that `fsck.vfat` finds
+/// clean; the other tests use `tests/fsck.rs` instead, since dosfstools is
+/// rarely installed. Run it with `cargo test -- --ignored`.
+#[test]
+#[ignore = "needs fsck.vfat from dosfstools"]
+fn check_fsck_vfat_accepts_writes() {
+    // `fsck.vfat` warns about FAT32 volumes with fewer than 65525 clusters.
+    let (vfat, disk) = mount(Image::new(66_000, 1));
+    vfat.create_dir("/some directory").unwrap();
+    vfat.create_file("/some directory/a long file name.txt").unwrap().write_all(&pattern(3000)).unwrap();
+    vfat.create_file("/SHORT.TXT").unwrap().write_all(b"short").unwrap();
+    vfat.create_file("/gone").unwrap().write_all(&pattern(1024)).unwrap();
+    vfat.create_file("/cut").unwrap().set_len(2000).unwrap();
+    vfat.open("/cut").unwrap().into_file().unwrap().set_len(100).unwrap();
+    vfat.root().rename("SHORT.TXT", "renamed.txt").unwrap();
+    vfat.remove("/gone").unwrap();
+    assert_clean(&disk.contents());
+
+    let path = std::env::temp_dir().join(format!("fat32-fsck-{}.img", std::process::id()));
+    std::fs::write(&path, &disk.contents()[PARTITION_START * SECTOR_SIZE..]).unwrap();
+    let output = std::process::Command::new("fsck.vfat").arg("-n").arg(&path).output();
+    std::fs::remove_file(&path).unwrap();
+
+    let output = output.expect("failed to run fsck.vfat");
+    assert!(
+        output.status.success(),
+        "fsck.vfat found problems:\n{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
@@ -0,0 +1,131 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use shim::io;
+
+/// Trait implemented by devices that can be read/written in sector
+/// granularities.
+pub trait BlockDevice: Send {
+    /// Sector size in bytes. Must be a multiple of 512 >= 512. Defaults to 512.
+    fn sector_size(&self) -> u64 {
+        512
+    }
+
+    /// Read sector number `n` into `buf`.
+    ///
+    /// `self.sector_size()` or `buf.len()` bytes, whichever is less, are read
+    /// into `buf`. The number of bytes read is returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if seeking or reading from `self` fails.
+    fn read_sector(&mut self, n: u64, buf: &mut [u8]) -> io::Result<usize>;
+
+    /// Append sector number `n` into `vec`.
+    ///
+    /// `self.sector_size()` bytes are appended to `vec`. The number of bytes
+    /// read is returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if seeking or reading from `self` fails.
+    fn read_all_sector(&mut self, n: u64, vec: &mut Vec<u8>) -> io::Result<usize> {
+        let sector_size = self.sector_size() as usize;
+
+        let start = vec.len();
+        vec.resize(start + sector_size, 0);
+
+        let read = self.read_sector(n, &mut vec[start..])?;
+        vec.truncate(start + read);
+        Ok(read)
+    }
+
+    /// Overwrites sector `n` with the contents of `buf`.
+    ///
+    /// `self.sector_size()` or `buf.len()` bytes, whichever is less, are written
+    /// to the sector. The number of byte written is returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if seeking or writing to `self` fails. Returns an
+    /// error of `UnexpectedEof` if the length of `buf` is less than
+    /// `self.sector_size()`.
+    fn write_sector(&mut self, n: u64, buf: &[u8]) -> io::Result<usize>;
+}
+
+impl<'a, T: BlockDevice> BlockDevice for &'a mut T {
+    fn sector_size(&self) -> u64 {
+        (**self).sector_size()
+    }
+
+    fn read_sector(&mut self, n: u64, buf: &mut [u8]) -> io::Result<usize> {
+        (*self).read_sector(n, buf)
+    }
+
+    fn read_all_sector(&mut self, n: u64, vec: &mut Vec<u8>) -> io::Result<usize> {
+        (*self).read_all_sector(n, vec)
+    }
+
+    fn write_sector(&mut self, n: u64, buf: &[u8]) -> io::Result<usize> {
+        (*self).write_sector(n, buf)
+    }
+}
+
+impl<T: BlockDevice + ?Sized> BlockDevice for Box<T> {
+    fn sector_size(&self) -> u64 {
+        (**self).sector_size()
+    }
+
+    fn read_sector(&mut self, n: u64, buf: &mut [u8]) -> io::Result<usize> {
+        (**self).read_sector(n, buf)
+    }
+
+    fn read_all_sector(&mut self, n: u64, vec: &mut Vec<u8>) -> io::Result<usize> {
+        (**self).read_all_sector(n, vec)
+    }
+
+    fn write_sector(&mut self, n: u64, buf: &[u8]) -> io::Result<usize> {
+        (**self).write_sector(n, buf)
+    }
+}
+
+/// Implements `BlockDevice` with 512-byte sectors for types that implement
+/// `io::Read`, `io::Write` and `io::Seek`, such as in-memory cursors or image
+/// files used as fake disks.
+#[allow(unused_macros)]
+macro impl_for_read_write_seek($(<$($gen:tt),*>)* $T:path) {
+    impl $(<$($gen),*>)* BlockDevice for $T {
+        fn read_sector(&mut self, n: u64, buf: &mut [u8]) -> io::Result<usize> {
+            use shim::io::{Read, Seek, SeekFrom};
+
+            let sector_size = self.sector_size();
+            let to_read = core::cmp::min(sector_size as usize, buf.len());
+            self.seek(SeekFrom::Start(n * sector_size))?;
+            self.read_exact(&mut buf[..to_read])?;
+            Ok(to_read)
+        }
+
+        fn write_sector(&mut self, n: u64, buf: &[u8]) -> io::Result<usize> {
+            use shim::io::{Seek, SeekFrom, Write};
+
+            let sector_size = self.sector_size();
+            if (buf.len() as u64) < sector_size {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "buffer too small"));
+            }
+
+            self.seek(SeekFrom::Start(n * sector_size))?;
+            self.write_all(&buf[..sector_size as usize])?;
+            Ok(sector_size as usize)
+        }
+    }
+}
+
+#[cfg(not(feature = "no_std"))]
+mod std_impls {
+    use super::*;
+
+    impl_for_read_write_seek!(<'a> io::Cursor<&'a mut [u8]>);
+    impl_for_read_write_seek!(io::Cursor<Vec<u8>>);
+    impl_for_read_write_seek!(io::Cursor<Box<[u8]>>);
+    impl_for_read_write_seek!(std::fs::File);
+}
@@ -0,0 +1,3 @@
+mod block_device;
+
+pub use self::block_device::BlockDevice;
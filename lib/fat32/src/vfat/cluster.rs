@@ -0,0 +1,32 @@
+/// A cluster number. Data clusters are numbered from 2.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Cluster(u32);
+
+impl From<u32> for Cluster {
+    fn from(raw_num: u32) -> Cluster {
+        Cluster(raw_num & !(0xF << 28))
+    }
+}
+
+impl Cluster {
+    /// Returns the raw cluster number.
+    pub fn number(&self) -> u32 {
+        self.0
+    }
+
+    /// Returns the index of this cluster in the data region. Cluster 2 is the
+    /// first cluster of the data region.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `self` is not a data cluster.
+    pub fn data_index(&self) -> u64 {
+        assert!(self.is_data(), "cluster {} is not a data cluster", self.0);
+        (self.0 - 2) as u64
+    }
+
+    /// Returns `true` if this cluster number may refer to a data cluster.
+    pub fn is_data(&self) -> bool {
+        self.0 >= 2
+    }
+}
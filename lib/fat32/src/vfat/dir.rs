@@ -0,0 +1,706 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::char::decode_utf16;
+use core::ptr;
+
+use shim::const_assert_size;
+use shim::io;
+
+use crate::vfat::name::{self, CASE_LOWER_BASE, CASE_LOWER_EXT};
+use crate::vfat::{Attributes, Cluster, Date, Entry, File, Metadata, Time, Timestamp, VFat, VFatHandle};
+
+/// A directory of a `VFat` filesystem.
+#[derive(Debug)]
+pub struct Dir<HANDLE: VFatHandle> {
+    pub(crate) vfat: HANDLE,
+    pub(crate) start: Cluster,
+    pub(crate) name: String,
+    pub(crate) metadata: Metadata,
+    /// Where the directory's entry is stored; `None` for the root.
+    pub(crate) location: Option<EntryLocation>,
+}
+
+/// A regular (8.3) directory entry.
+#[repr(C, packed)]
+#[derive(Copy, Clone)]
+pub(crate) struct VFatRegularDirEntry {
+    pub(crate) name: [u8; 8],
+    pub(crate) ext: [u8; 3],
+    pub(crate) attributes: Attributes,
+    pub(crate) case_flags: u8,
+    pub(crate) created_tenths: u8,
+    pub(crate) created_time: Time,
+    pub(crate) created_date: Date,
+    pub(crate) accessed_date: Date,
+    pub(crate) cluster_high: u16,
+    pub(crate) modified_time: Time,
+    pub(crate) modified_date: Date,
+    pub(crate) cluster_low: u16,
+    pub(crate) size: u32,
+}
+
+const_assert_size!(VFatRegularDirEntry, 32);
+
+/// A long file name (LFN) directory entry. Each holds 13 UCS-2 characters of
+/// the name of the regular entry that follows the sequence.
+#[repr(C, packed)]
+#[derive(Copy, Clone)]
+pub(crate) struct VFatLfnDirEntry {
+    pub(crate) sequence: u8,
+    pub(crate) name1: [u8; 10],
+    pub(crate) attributes: Attributes,
+    pub(crate) kind: u8,
+    pub(crate) checksum: u8,
+    pub(crate) name2: [u8; 12],
+    pub(crate) first_cluster: u16,
+    pub(crate) name3: [u8; 4],
+}
+
+const_assert_size!(VFatLfnDirEntry, 32);
+
+/// The fields shared by all kinds of directory entries.
+#[repr(C, packed)]
+#[derive(Copy, Clone)]
+pub(crate) struct VFatUnknownDirEntry {
+    pub(crate) marker: u8,
+    __r0: [u8; 10],
+    pub(crate) attributes: Attributes,
+    __r1: [u8; 20],
+}
+
+const_assert_size!(VFatUnknownDirEntry, 32);
+
+#[derive(Copy, Clone)]
+pub(crate) union VFatDirEntry {
+    pub(crate) unknown: VFatUnknownDirEntry,
+    pub(crate) regular: VFatRegularDirEntry,
+    pub(crate) long_filename: VFatLfnDirEntry,
+}
+
+const_assert_size!(VFatDirEntry, 32);
+
+/// Marks the end of a directory: this and all following entries are unused.
+pub(crate) const END_MARKER: u8 = 0x00;
+
+/// Marks a deleted entry.
+pub(crate) const DELETED_MARKER: u8 = 0xE5;
+
+/// Set in the sequence number of the last (first on disk) LFN entry.
+pub(crate) const LFN_LAST_ENTRY: u8 = 0x40;
+
+/// The maximum number of LFN entries making up a single name.
+pub(crate) const LFN_MAX_ENTRIES: usize = 20;
+
+/// The number of UCS-2 characters held by one LFN entry.
+pub(crate) const LFN_CHARS_PER_ENTRY: usize = 13;
+
+/// The largest number of entries a directory may hold.
+const MAX_DIR_ENTRIES: usize = 65536;
+
+/// Where the slots of a directory entry are stored: the first cluster of
+/// the directory and the byte offsets within it of the entry's first slot
+/// (its first LFN entry, if any) and of its regular entry.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct EntryLocation {
+    pub(crate) dir: Cluster,
+    pub(crate) first: u64,
+    pub(crate) regular: u64,
+}
+
+impl VFatDirEntry {
+    /// Reads the directory entry stored in the first 32 bytes of `bytes`.
+    pub(crate) fn from_bytes(bytes: &[u8]) -> VFatDirEntry {
+        assert!(bytes.len() >= 32, "directory entry too short");
+        unsafe { ptr::read_unaligned(bytes.as_ptr() as *const VFatDirEntry) }
+    }
+
+    /// Returns the first byte of the entry.
+    pub(crate) fn marker(&self) -> u8 {
+        unsafe { self.unknown.marker }
+    }
+
+    /// Returns the attributes of the entry.
+    pub(crate) fn attributes(&self) -> Attributes {
+        unsafe { self.unknown.attributes }
+    }
+}
+
+impl VFatRegularDirEntry {
+    /// Creates an entry with 8.3 name `short` whose creation, access and
+    /// modification times are all `now`.
+    pub(crate) fn new(short: &[u8; 11], case_flags: u8, attributes: u8, cluster: Cluster, now: Timestamp) -> VFatRegularDirEntry {
+        let mut name = [0u8; 8];
+        let mut ext = [0u8; 3];
+        name.copy_from_slice(&short[..8]);
+        ext.copy_from_slice(&short[8..]);
+
+        let mut entry = VFatRegularDirEntry {
+            name,
+            ext,
+            attributes: Attributes::from(attributes),
+            case_flags,
+            created_tenths: 0,
+            created_time: now.time,
+            created_date: now.date,
+            accessed_date: now.date,
+            cluster_high: 0,
+            modified_time: now.time,
+            modified_date: now.date,
+            cluster_low: 0,
+            size: 0,
+        };
+        entry.set_cluster(cluster);
+        entry
+    }
+
+    /// Returns the on-disk representation of the entry.
+    pub(crate) fn to_bytes(self) -> [u8; 32] {
+        unsafe { core::mem::transmute(self) }
+    }
+
+    /// Sets the 8.3 name and case flags of the entry.
+    pub(crate) fn set_short_name(&mut self, short: &[u8; 11], case_flags: u8) {
+        self.name.copy_from_slice(&short[..8]);
+        self.ext.copy_from_slice(&short[8..]);
+        self.case_flags = case_flags;
+    }
+
+    /// Sets the first cluster of the entry's data.
+    pub(crate) fn set_cluster(&mut self, cluster: Cluster) {
+        self.cluster_high = ((cluster.number() >> 16) as u16).to_le();
+        self.cluster_low = (cluster.number() as u16).to_le();
+    }
+
+    /// Records a modification of the entry's data at time `now`.
+    pub(crate) fn touch(&mut self, now: Timestamp) {
+        self.modified_date = now.date;
+        self.modified_time = now.time;
+        self.accessed_date = now.date;
+        self.attributes = Attributes::from(self.attributes.raw() | Attributes::ARCHIVE);
+    }
+
+    /// Returns the 11-byte 8.3 name as stored on disk.
+    pub(crate) fn short_name(&self) -> [u8; 11] {
+        let mut short = [0u8; 11];
+        short[..8].copy_from_slice(&self.name);
+        short[8..].copy_from_slice(&self.ext);
+        short
+    }
+
+    /// Returns the 8.3 name formatted as `NAME.EXT`.
+    fn display_name(&self) -> String {
+        fn trimmed(bytes: &[u8], lower: bool) -> String {
+            let len = bytes.iter().rposition(|&b| b != b' ').map_or(0, |i| i + 1);
+            let mut bytes: Vec<u8> = bytes[..len].into();
+            if lower {
+                bytes.make_ascii_lowercase();
+            }
+            String::from_utf8_lossy(&bytes).into_owned()
+        }
+
+        let mut raw_name = self.name;
+        if raw_name[0] == 0x05 {
+            raw_name[0] = DELETED_MARKER;
+        }
+
+        let mut name = trimmed(&raw_name, self.case_flags & CASE_LOWER_BASE != 0);
+        let ext = trimmed(&self.ext, self.case_flags & CASE_LOWER_EXT != 0);
+        if !ext.is_empty() {
+            name.push('.');
+            name.push_str(&ext);
+        }
+        name
+    }
+
+    /// Returns the first cluster of the entry's data.
+    pub(crate) fn cluster(&self) -> Cluster {
+        let high = u16::from_le(self.cluster_high) as u32;
+        let low = u16::from_le(self.cluster_low) as u32;
+        Cluster::from((high << 16) | low)
+    }
+
+    pub(crate) fn metadata(&self) -> Metadata {
+        Metadata {
+            attributes: self.attributes,
+            created: Timestamp { date: self.created_date, time: self.created_time },
+            accessed: Timestamp { date: self.accessed_date, time: Time::default() },
+            modified: Timestamp { date: self.modified_date, time: self.modified_time },
+        }
+    }
+}
+
+impl VFatLfnDirEntry {
+    /// Returns the 13 UCS-2 characters stored in this entry.
+    pub(crate) fn chars(&self) -> [u16; LFN_CHARS_PER_ENTRY] {
+        let mut chars = [0u16; LFN_CHARS_PER_ENTRY];
+        let bytes = self.name1.iter().chain(self.name2.iter()).chain(self.name3.iter());
+        let bytes: Vec<u8> = bytes.cloned().collect();
+        for (c, pair) in chars.iter_mut().zip(bytes.chunks(2)) {
+            *c = u16::from_le_bytes([pair[0], pair[1]]);
+        }
+        chars
+    }
+}
+
+/// Computes the checksum of an 8.3 name stored in LFN entries.
+pub(crate) fn lfn_checksum(short_name: &[u8; 11]) -> u8 {
+    short_name
+        .iter()
+        .fold(0u8, |sum, &b| sum.rotate_right(1).wrapping_add(b))
+}
+
+/// A long file name being assembled from LFN entries.
+struct LfnBuilder {
+    chars: [u16; LFN_MAX_ENTRIES * LFN_CHARS_PER_ENTRY],
+    checksum: u8,
+    /// Sequence number of the next expected entry; `0` once complete.
+    expected: u8,
+    valid: bool,
+    /// Offset within the directory of the first entry of the sequence.
+    start: u64,
+}
+
+impl LfnBuilder {
+    fn new() -> LfnBuilder {
+        LfnBuilder {
+            chars: [0; LFN_MAX_ENTRIES * LFN_CHARS_PER_ENTRY],
+            checksum: 0,
+            expected: 0,
+            valid: false,
+            start: 0,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.valid = false;
+    }
+
+    /// Adds `entry`, stored at byte `offset` of the directory.
+    fn push(&mut self, entry: &VFatLfnDirEntry, offset: u64) {
+        let seq = entry.sequence & 0x1F;
+        if seq == 0 || seq as usize > LFN_MAX_ENTRIES {
+            return self.reset();
+        }
+
+        if entry.sequence & LFN_LAST_ENTRY != 0 {
+            self.chars.iter_mut().for_each(|c| *c = 0xFFFF);
+            self.checksum = entry.checksum;
+            self.valid = true;
+            self.start = offset;
+        } else if !self.valid || seq != self.expected || entry.checksum != self.checksum {
+            return self.reset();
+        }
+
+        let start = (seq as usize - 1) * LFN_CHARS_PER_ENTRY;
+        self.chars[start..start + LFN_CHARS_PER_ENTRY].copy_from_slice(&entry.chars());
+        self.expected = seq - 1;
+    }
+
+    /// Returns the assembled name and the offset of its first entry if it is
+    /// complete and belongs to the 8.3 name `short_name`.
+    fn take(&mut self, short_name: &[u8; 11]) -> Option<(String, u64)> {
+        let complete = self.valid && self.expected == 0 && self.checksum == lfn_checksum(short_name);
+        self.reset();
+        if !complete {
+            return None;
+        }
+
+        let len = self
+            .chars
+            .iter()
+            .position(|&c| c == 0x0000 || c == 0xFFFF)
+            .unwrap_or(self.chars.len());
+        let name = decode_utf16(self.chars[..len].iter().cloned())
+            .map(|c| c.unwrap_or(core::char::REPLACEMENT_CHARACTER))
+            .collect();
+        Some((name, self.start))
+    }
+}
+
+/// An iterator over the entries of a directory.
+pub struct EntryIter<HANDLE: VFatHandle> {
+    vfat: HANDLE,
+    dir: Cluster,
+    root: Cluster,
+    data: Vec<u8>,
+    offset: usize,
+}
+
+impl<HANDLE: VFatHandle> Iterator for EntryIter<HANDLE> {
+    type Item = Entry<HANDLE>;
+
+    fn next(&mut self) -> Option<Entry<HANDLE>> {
+        let mut lfn = LfnBuilder::new();
+        while self.offset + 32 <= self.data.len() {
+            let entry = VFatDirEntry::from_bytes(&self.data[self.offset..]);
+            let offset = self.offset as u64;
+            self.offset += 32;
+
+            match entry.marker() {
+                END_MARKER => {
+                    self.offset = self.data.len();
+                    return None;
+                }
+                DELETED_MARKER => {
+                    lfn.reset();
+                    continue;
+                }
+                _ => {}
+            }
+
+            let attributes = entry.attributes();
+            if attributes.lfn() {
+                lfn.push(unsafe { &entry.long_filename }, offset);
+                continue;
+            } else if attributes.volume_id() {
+                lfn.reset();
+                continue;
+            }
+
+            let regular = unsafe { entry.regular };
+            let (name, first) = lfn
+                .take(&regular.short_name())
+                .unwrap_or_else(|| (regular.display_name(), offset));
+            let metadata = regular.metadata();
+            let location = EntryLocation { dir: self.dir, first, regular: offset };
+
+            return Some(if attributes.directory() {
+                // `..` entries of first-level directories point to cluster 0.
+                let start = match regular.cluster() {
+                    cluster if cluster.is_data() => cluster,
+                    _ => self.root,
+                };
+                Entry::Dir(Dir::new(self.vfat.clone(), start, name, metadata, Some(location)))
+            } else {
+                let size = u32::from_le(regular.size) as u64;
+                Entry::File(File::new(self.vfat.clone(), regular.cluster(), name, metadata, size, location))
+            });
+        }
+
+        None
+    }
+}
+
+impl<HANDLE: VFatHandle> Dir<HANDLE> {
+    pub(crate) fn new(
+        vfat: HANDLE,
+        start: Cluster,
+        name: String,
+        metadata: Metadata,
+        location: Option<EntryLocation>,
+    ) -> Dir<HANDLE> {
+        Dir { vfat, start, name, metadata, location }
+    }
+
+    /// Returns the name of this directory.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the metadata of this directory.
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// Returns the first cluster of this directory.
+    pub fn first_cluster(&self) -> Cluster {
+        self.start
+    }
+
+    /// Returns an iterator over the entries in this directory, including
+    /// `.` and `..` for all directories but the root.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory's clusters can't be read.
+    pub fn entries(&self) -> io::Result<EntryIter<HANDLE>> {
+        self.vfat.lock(|vfat| self.entries_locked(vfat))
+    }
+
+    /// Like `entries`, for callers already holding the filesystem lock.
+    fn entries_locked(&self, vfat: &mut VFat<HANDLE>) -> io::Result<EntryIter<HANDLE>> {
+        let mut data = Vec::new();
+        vfat.read_chain(self.start, &mut data)?;
+        let root = vfat.root_dir_cluster();
+        Ok(EntryIter { vfat: self.vfat.clone(), dir: self.start, root, data, offset: 0 })
+    }
+
+    /// Finds the entry named `name` in `self` and returns it. Comparison is
+    /// case-insensitive.
+    ///
+    /// # Errors
+    ///
+    /// If no entry with name `name` exists in `self`, an error of `NotFound`
+    /// is returned.
+    pub fn find(&self, name: &str) -> io::Result<Entry<HANDLE>> {
+        self.entries()?
+            .find(|entry| entry.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no such file or directory"))
+    }
+
+    /// Like `find`, for callers already holding the filesystem lock.
+    /// Returns `None` if there is no entry named `name`.
+    fn find_locked(&self, vfat: &mut VFat<HANDLE>, name: &str) -> io::Result<Option<Entry<HANDLE>>> {
+        Ok(self.entries_locked(vfat)?.find(|entry| entry.name().eq_ignore_ascii_case(name)))
+    }
+
+    /// Returns the entry named `name` (which must not be `.` or `..`) for
+    /// removal or renaming.
+    fn find_existing(&self, vfat: &mut VFat<HANDLE>, name: &str) -> io::Result<Entry<HANDLE>> {
+        name::validate(name)?;
+        self.find_locked(vfat, name)?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no such file or directory"))
+    }
+
+    /// Stores a new entry named `name` based on `regular` in this directory
+    /// and returns its location. The 8.3 name of `regular` is replaced with
+    /// one generated from `name` that is unique in the directory.
+    fn insert(
+        &self,
+        vfat: &mut VFat<HANDLE>,
+        name: &str,
+        mut regular: VFatRegularDirEntry,
+    ) -> io::Result<EntryLocation> {
+        let mut data = Vec::new();
+        vfat.read_chain(self.start, &mut data)?;
+
+        // Collect the 8.3 names in use and find the free slots.
+        let mut existing = Vec::new();
+        let mut free_runs = Vec::new();
+        let mut run_start = None;
+        let mut end = data.len();
+        for (i, slot) in data.chunks(32).enumerate() {
+            let entry = VFatDirEntry::from_bytes(slot);
+            match entry.marker() {
+                END_MARKER => {
+                    end = i * 32;
+                    break;
+                }
+                DELETED_MARKER => {
+                    run_start.get_or_insert(i * 32);
+                    continue;
+                }
+                _ => {}
+            }
+
+            if let Some(start) = run_start.take() {
+                free_runs.push((start, i * 32));
+            }
+            if !entry.attributes().lfn() {
+                existing.push(unsafe { entry.regular }.short_name());
+            }
+        }
+        let tail_start = run_start.unwrap_or(end);
+
+        let encoded = name::encode(name, &existing)?;
+        regular.set_short_name(&encoded.short, encoded.case_flags);
+        let mut slots = match encoded.long {
+            Some(ref long) => name::lfn_entries(long, &encoded.short),
+            None => Vec::new(),
+        };
+        slots.push(regular.to_bytes());
+        let needed = slots.len() * 32;
+
+        let start = match free_runs.iter().find(|&&(start, stop)| stop - start >= needed) {
+            Some(&(start, _)) => start,
+            None => {
+                // Use the free slots at the end, growing the directory.
+                if (tail_start + needed) / 32 > MAX_DIR_ENTRIES {
+                    return Err(io::Error::new(io::ErrorKind::Other, "directory full"));
+                }
+
+                let cluster_size = vfat.cluster_size();
+                let mut last = *vfat.chain(self.start)?.last().unwrap();
+                let zeros = vec![0u8; cluster_size];
+                while data.len() < tail_start + needed {
+                    // Clear the cluster, and sync it, before linking it in:
+                    // its old contents would read as entries.
+                    let cluster = vfat.alloc_cluster(None)?;
+                    vfat.write_cluster(cluster, 0, &zeros)?;
+                    vfat.sync()?;
+                    vfat.link_cluster(last, cluster)?;
+                    last = cluster;
+                    data.resize(data.len() + cluster_size, 0);
+                }
+                tail_start
+            }
+        };
+
+        let mut offset = start as u64;
+        for slot in slots.iter() {
+            vfat.write_slot(self.start, offset, slot)?;
+            offset += 32;
+        }
+
+        // Keep slots that followed the end marker from becoming visible.
+        let after = start + needed;
+        if start <= end && after > end && after + 32 <= data.len() && data[after] != END_MARKER {
+            vfat.write_slot(self.start, after as u64, &[0u8; 32])?;
+        }
+
+        Ok(EntryLocation { dir: self.start, first: start as u64, regular: offset - 32 })
+    }
+
+    /// Creates an empty file named `name` in this directory and returns it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidInput` if `name` is not a valid file
+    /// name and of kind `AlreadyExists` if an entry named `name` exists.
+    pub fn create_file(&self, name: &str) -> io::Result<File<HANDLE>> {
+        name::validate(name)?;
+        self.vfat.lock(|vfat| {
+            vfat.synced(|vfat| {
+                if self.find_locked(vfat, name)?.is_some() {
+                    return Err(io::Error::new(io::ErrorKind::AlreadyExists, "file exists"));
+                }
+
+                let now = vfat.now();
+                let regular = VFatRegularDirEntry::new(&[b' '; 11], 0, Attributes::ARCHIVE, Cluster::from(0), now);
+                let location = self.insert(vfat, name, regular)?;
+                let metadata = regular.metadata();
+                Ok(File::new(self.vfat.clone(), Cluster::from(0), name.into(), metadata, 0, location))
+            })
+        })
+    }
+
+    /// Creates an empty directory named `name` in this directory and returns
+    /// it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidInput` if `name` is not a valid file
+    /// name and of kind `AlreadyExists` if an entry named `name` exists.
+    pub fn create_dir(&self, name: &str) -> io::Result<Dir<HANDLE>> {
+        name::validate(name)?;
+        self.vfat.lock(|vfat| {
+            vfat.synced(|vfat| {
+                if self.find_locked(vfat, name)?.is_some() {
+                    return Err(io::Error::new(io::ErrorKind::AlreadyExists, "file exists"));
+                }
+
+                // Set up the new directory's cluster, and sync it, before
+                // linking it in so an interrupted operation at worst leaks the
+                // cluster.
+                let now = vfat.now();
+                let cluster = vfat.alloc_cluster(None)?;
+                let mut contents = vec![0u8; vfat.cluster_size()];
+                let parent = if self.start == vfat.root_dir_cluster() {
+                    Cluster::from(0)
+                } else {
+                    self.start
+                };
+                let dot = VFatRegularDirEntry::new(b".          ", 0, Attributes::DIRECTORY, cluster, now);
+                let dotdot = VFatRegularDirEntry::new(b"..         ", 0, Attributes::DIRECTORY, parent, now);
+                contents[..32].copy_from_slice(&dot.to_bytes());
+                contents[32..64].copy_from_slice(&dotdot.to_bytes());
+
+                let regular = VFatRegularDirEntry::new(&[b' '; 11], 0, Attributes::DIRECTORY, cluster, now);
+                let location = vfat
+                    .write_cluster(cluster, 0, &contents)
+                    .and_then(|_| vfat.sync())
+                    .and_then(|_| self.insert(vfat, name, regular));
+                match location {
+                    Ok(location) => {
+                        let metadata = regular.metadata();
+                        Ok(Dir::new(self.vfat.clone(), cluster, name.into(), metadata, Some(location)))
+                    }
+                    Err(e) => {
+                        vfat.free_chain(cluster)?;
+                        Err(e)
+                    }
+                }
+            })
+        })
+    }
+
+    /// Removes the file named `name` from this directory and frees its
+    /// clusters.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `NotFound` if there is no entry named `name`
+    /// and of kind `InvalidInput` if it is a directory.
+    pub fn remove_file(&self, name: &str) -> io::Result<()> {
+        self.vfat.lock(|vfat| {
+            vfat.synced(|vfat| {
+                let file = match self.find_existing(vfat, name)? {
+                    Entry::File(file) => file,
+                    Entry::Dir(_) => {
+                        return Err(io::Error::new(io::ErrorKind::InvalidInput, "is a directory"));
+                    }
+                };
+
+                // Delete the entry, and sync it, before freeing the clusters so
+                // that an interrupted removal at worst leaks them.
+                vfat.delete_entry(file.location)?;
+                if file.start.is_data() {
+                    vfat.sync()?;
+                    vfat.free_chain(file.start)?;
+                }
+                Ok(())
+            })
+        })
+    }
+
+    /// Removes the empty directory named `name` from this directory and
+    /// frees its clusters.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `NotFound` if there is no entry named `name`,
+    /// of kind `InvalidInput` if it is not a directory and of kind `Other`
+    /// if the directory is not empty.
+    pub fn remove_dir(&self, name: &str) -> io::Result<()> {
+        self.vfat.lock(|vfat| {
+            vfat.synced(|vfat| {
+                let dir = match self.find_existing(vfat, name)? {
+                    Entry::Dir(dir) => dir,
+                    Entry::File(_) => {
+                        return Err(io::Error::new(io::ErrorKind::InvalidInput, "not a directory"));
+                    }
+                };
+
+                if dir.entries_locked(vfat)?.any(|entry| entry.name() != "." && entry.name() != "..") {
+                    return Err(io::Error::new(io::ErrorKind::Other, "directory not empty"));
+                }
+
+                vfat.delete_entry(dir.location.expect("subdirectory without an entry"))?;
+                vfat.sync()?;
+                vfat.free_chain(dir.start)
+            })
+        })
+    }
+
+    /// Renames the entry named `from` in this directory to `to`. Changing
+    /// only the case of a name is allowed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `NotFound` if there is no entry named
+    /// `from`, of kind `InvalidInput` if `to` is not a valid file name and
+    /// of kind `AlreadyExists` if another entry is named `to`.
+    pub fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        name::validate(to)?;
+        self.vfat.lock(|vfat| {
+            vfat.synced(|vfat| {
+                let location = self.find_existing(vfat, from)?.location();
+                let conflict = self.entries_locked(vfat)?.any(|entry| {
+                    entry.name().eq_ignore_ascii_case(to) && entry.location() != location
+                });
+                if conflict {
+                    return Err(io::Error::new(io::ErrorKind::AlreadyExists, "file exists"));
+                }
+                let location = location.expect("subdirectory without an entry");
+
+                // Add the new entry, and sync it, before removing the old one
+                // so that an interrupted rename can't lose the file.
+                let slot = vfat.read_slot(location.dir, location.regular)?;
+                let regular = unsafe { VFatDirEntry::from_bytes(&slot).regular };
+                self.insert(vfat, to, regular)?;
+                vfat.sync()?;
+                vfat.delete_entry(location)
+            })
+        })
+    }
+}
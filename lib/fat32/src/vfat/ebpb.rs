@@ -0,0 +1,163 @@
+use core::fmt;
+use core::mem;
+
+use shim::const_assert_size;
+
+use crate::traits::BlockDevice;
+use crate::vfat::Error;
+
+/// The FAT32 boot sector: the BIOS parameter block (BPB) followed by the
+/// FAT32 extended BIOS parameter block (EBPB).
+#[repr(C, packed)]
+pub struct BiosParameterBlock {
+    jump: [u8; 3],
+    oem_id: [u8; 8],
+    bytes_per_sector: u16,
+    sectors_per_cluster: u8,
+    reserved_sectors: u16,
+    num_fats: u8,
+    max_dir_entries: u16,
+    total_sectors_16: u16,
+    media_descriptor: u8,
+    sectors_per_fat_16: u16,
+    sectors_per_track: u16,
+    num_heads: u16,
+    hidden_sectors: u32,
+    total_sectors_32: u32,
+    sectors_per_fat_32: u32,
+    flags: u16,
+    version: u16,
+    root_cluster: u32,
+    fsinfo_sector: u16,
+    backup_boot_sector: u16,
+    __r0: [u8; 12],
+    drive_number: u8,
+    __r1: u8,
+    signature: u8,
+    volume_id: u32,
+    volume_label: [u8; 11],
+    system_id: [u8; 8],
+    boot_code: [u8; 420],
+    bootable_signature: [u8; 2],
+}
+
+const_assert_size!(BiosParameterBlock, 512);
+
+impl BiosParameterBlock {
+    /// Reads the FAT32 extended BIOS parameter block from sector `sector` of
+    /// device `device`.
+    ///
+    /// # Errors
+    ///
+    /// If the EBPB signature is invalid, returns an error of `BadSignature`.
+    pub fn from<T: BlockDevice>(mut device: T, sector: u64) -> Result<BiosParameterBlock, Error> {
+        let mut buf = [0u8; 512];
+        device.read_sector(sector, &mut buf)?;
+
+        let ebpb: BiosParameterBlock = unsafe { mem::transmute(buf) };
+        if ebpb.bootable_signature != [0x55, 0xAA] {
+            return Err(Error::BadSignature);
+        }
+
+        match ebpb.signature {
+            0x28 | 0x29 => {}
+            _ => return Err(Error::BadSignature),
+        }
+
+        Ok(ebpb)
+    }
+
+    /// Checks that the geometry described by `self` is one this driver can
+    /// use with a device whose sectors are `device_sector_size` bytes long.
+    pub(crate) fn validate(&self, device_sector_size: u64) -> Result<(), Error> {
+        let bytes_per_sector = self.bytes_per_sector() as u64;
+        if bytes_per_sector < 512 || !bytes_per_sector.is_power_of_two() {
+            return Err(Error::Unsupported("invalid bytes per sector"));
+        } else if bytes_per_sector % device_sector_size != 0 {
+            return Err(Error::Unsupported("logical sector smaller than device sector"));
+        } else if self.sectors_per_cluster == 0 || !self.sectors_per_cluster.is_power_of_two() {
+            return Err(Error::Unsupported("invalid sectors per cluster"));
+        } else if self.num_fats == 0 || self.sectors_per_fat() == 0 {
+            return Err(Error::Unsupported("not a FAT32 volume"));
+        } else if self.root_cluster() < 2 {
+            return Err(Error::Unsupported("invalid root cluster"));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the number of bytes in a logical sector.
+    pub fn bytes_per_sector(&self) -> u16 {
+        u16::from_le(self.bytes_per_sector)
+    }
+
+    /// Returns the number of logical sectors in a cluster.
+    pub fn sectors_per_cluster(&self) -> u8 {
+        self.sectors_per_cluster
+    }
+
+    /// Returns the number of logical sectors before the first FAT.
+    pub fn reserved_sectors(&self) -> u16 {
+        u16::from_le(self.reserved_sectors)
+    }
+
+    /// Returns the number of copies of the FAT.
+    pub fn num_fats(&self) -> u8 {
+        self.num_fats
+    }
+
+    /// Returns the total number of logical sectors in the volume.
+    pub fn total_sectors(&self) -> u32 {
+        match u16::from_le(self.total_sectors_16) {
+            0 => u32::from_le(self.total_sectors_32),
+            n => n as u32,
+        }
+    }
+
+    /// Returns the number of logical sectors occupied by one FAT.
+    pub fn sectors_per_fat(&self) -> u32 {
+        u32::from_le(self.sectors_per_fat_32)
+    }
+
+    /// Returns the cluster number of the root directory.
+    pub fn root_cluster(&self) -> u32 {
+        u32::from_le(self.root_cluster)
+    }
+
+    /// Returns the logical sector number of the FSInfo structure.
+    pub fn fsinfo_sector(&self) -> u16 {
+        u16::from_le(self.fsinfo_sector)
+    }
+
+    /// Returns the volume serial number.
+    pub fn volume_id(&self) -> u32 {
+        u32::from_le(self.volume_id)
+    }
+
+    /// Returns the volume label with trailing padding removed.
+    pub fn volume_label(&self) -> &[u8] {
+        let len = self
+            .volume_label
+            .iter()
+            .rposition(|&b| b != b' ')
+            .map_or(0, |i| i + 1);
+        &self.volume_label[..len]
+    }
+}
+
+impl fmt::Debug for BiosParameterBlock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BiosParameterBlock")
+            .field("oem_id", &{ self.oem_id })
+            .field("bytes_per_sector", &self.bytes_per_sector())
+            .field("sectors_per_cluster", &self.sectors_per_cluster())
+            .field("reserved_sectors", &self.reserved_sectors())
+            .field("num_fats", &self.num_fats())
+            .field("total_sectors", &self.total_sectors())
+            .field("sectors_per_fat", &self.sectors_per_fat())
+            .field("root_cluster", &self.root_cluster())
+            .field("fsinfo_sector", &self.fsinfo_sector())
+            .field("volume_id", &self.volume_id())
+            .finish()
+    }
+}
@@ -0,0 +1,81 @@
+use crate::vfat::dir::EntryLocation;
+use crate::vfat::{Dir, File, Metadata, VFatHandle};
+
+/// A directory entry: either a file or a directory.
+#[derive(Debug)]
+pub enum Entry<HANDLE: VFatHandle> {
+    File(File<HANDLE>),
+    Dir(Dir<HANDLE>),
+}
+
+impl<HANDLE: VFatHandle> Entry<HANDLE> {
+    /// The name of the file or directory corresponding to this entry.
+    pub fn name(&self) -> &str {
+        match self {
+            Entry::File(file) => file.name(),
+            Entry::Dir(dir) => dir.name(),
+        }
+    }
+
+    /// The metadata associated with the entry.
+    pub fn metadata(&self) -> &Metadata {
+        match self {
+            Entry::File(file) => file.metadata(),
+            Entry::Dir(dir) => dir.metadata(),
+        }
+    }
+
+    /// If `self` is a file, returns `Some` of a reference to the file.
+    /// Otherwise returns `None`.
+    pub fn as_file(&self) -> Option<&File<HANDLE>> {
+        match self {
+            Entry::File(file) => Some(file),
+            Entry::Dir(_) => None,
+        }
+    }
+
+    /// If `self` is a directory, returns `Some` of a reference to the
+    /// directory. Otherwise returns `None`.
+    pub fn as_dir(&self) -> Option<&Dir<HANDLE>> {
+        match self {
+            Entry::Dir(dir) => Some(dir),
+            Entry::File(_) => None,
+        }
+    }
+
+    /// If `self` is a file, returns `Some` of the file. Otherwise returns
+    /// `None`.
+    pub fn into_file(self) -> Option<File<HANDLE>> {
+        match self {
+            Entry::File(file) => Some(file),
+            Entry::Dir(_) => None,
+        }
+    }
+
+    /// If `self` is a directory, returns `Some` of the directory. Otherwise
+    /// returns `None`.
+    pub fn into_dir(self) -> Option<Dir<HANDLE>> {
+        match self {
+            Entry::Dir(dir) => Some(dir),
+            Entry::File(_) => None,
+        }
+    }
+
+    /// Returns `true` if this entry is a file or `false` otherwise.
+    pub fn is_file(&self) -> bool {
+        self.as_file().is_some()
+    }
+
+    /// Returns `true` if this entry is a directory or `false` otherwise.
+    pub fn is_dir(&self) -> bool {
+        self.as_dir().is_some()
+    }
+
+    /// Returns where the entry is stored, or `None` for the root directory.
+    pub(crate) fn location(&self) -> Option<EntryLocation> {
+        match self {
+            Entry::File(file) => Some(file.location),
+            Entry::Dir(dir) => dir.location,
+        }
+    }
+}
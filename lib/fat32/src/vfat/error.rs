@@ -0,0 +1,29 @@
+use shim::io;
+
+use crate::mbr;
+
+#[derive(Debug)]
+pub enum Error {
+    /// There was an I/O error while reading the filesystem.
+    Io(io::Error),
+    /// The master boot record could not be read.
+    Mbr(mbr::Error),
+    /// The partition table contains no FAT32 partition.
+    NoFat32Partition,
+    /// The boot sector's magic signature was invalid.
+    BadSignature,
+    /// The BIOS parameter block describes a volume this driver can't read.
+    Unsupported(&'static str),
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Error {
+        Error::Io(error)
+    }
+}
+
+impl From<mbr::Error> for Error {
+    fn from(error: mbr::Error) -> Error {
+        Error::Mbr(error)
+    }
+}
@@ -0,0 +1,48 @@
+use core::fmt;
+
+use crate::vfat::Cluster;
+
+/// The status of a cluster as indicated by its FAT entry.
+#[derive(Debug, PartialEq)]
+pub enum Status {
+    /// The FAT entry corresponds to an unused (free) cluster.
+    Free,
+    /// The FAT entry/cluster is reserved.
+    Reserved,
+    /// The FAT entry corresponds to a valid data cluster. The next cluster in
+    /// the chain is `Cluster`.
+    Data(Cluster),
+    /// The FAT entry corresponds to a bad (disk failed) cluster.
+    Bad,
+    /// The FAT entry corresponds to a valid data cluster. The corresponding
+    /// cluster is the last in its chain.
+    Eoc(u32),
+}
+
+/// A raw 32-bit FAT entry. Only the low 28 bits are significant.
+#[repr(C, packed)]
+#[derive(Copy, Clone)]
+pub struct FatEntry(pub u32);
+
+impl FatEntry {
+    /// Returns the `Status` of the FAT entry `self`.
+    pub fn status(&self) -> Status {
+        match self.0 & 0x0FFF_FFFF {
+            0x0000_0000 => Status::Free,
+            0x0000_0001 => Status::Reserved,
+            next @ 0x0000_0002..=0x0FFF_FFEF => Status::Data(Cluster::from(next)),
+            0x0FFF_FFF0..=0x0FFF_FFF6 => Status::Reserved,
+            0x0FFF_FFF7 => Status::Bad,
+            eoc => Status::Eoc(eoc),
+        }
+    }
+}
+
+impl fmt::Debug for FatEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FatEntry")
+            .field("value", &{ self.0 })
+            .field("status", &self.status())
+            .finish()
+    }
+}
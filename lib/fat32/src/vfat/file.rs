@@ -0,0 +1,276 @@
+use alloc::string::String;
+use core::cmp;
+
+use shim::io::{self, SeekFrom};
+
+use crate::vfat::dir::EntryLocation;
+use crate::vfat::{Cluster, Metadata, VFatHandle};
+
+/// A file of a `VFat` filesystem.
+#[derive(Debug)]
+pub struct File<HANDLE: VFatHandle> {
+    pub(crate) vfat: HANDLE,
+    pub(crate) start: Cluster,
+    pub(crate) name: String,
+    pub(crate) metadata: Metadata,
+    pub(crate) size: u64,
+    pub(crate) location: EntryLocation,
+    offset: u64,
+    /// The index within the chain and number of the cluster most recently
+    /// read, so sequential reads don't walk the chain from the start.
+    current: Option<(u64, Cluster)>,
+}
+
+impl<HANDLE: VFatHandle> File<HANDLE> {
+    pub(crate) fn new(
+        vfat: HANDLE,
+        start: Cluster,
+        name: String,
+        metadata: Metadata,
+        size: u64,
+        location: EntryLocation,
+    ) -> File<HANDLE> {
+        File { vfat, start, name, metadata, size, location, offset: 0, current: None }
+    }
+
+    /// Returns the name of this file.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the metadata of this file.
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// Returns the size of the file in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Returns the first cluster of this file, which is not a data cluster
+    /// for empty files.
+    pub fn first_cluster(&self) -> Cluster {
+        self.start
+    }
+
+    /// Returns the cluster holding chain index `index`, walking the chain
+    /// from the cached position when possible. If `allocate` is set, the
+    /// chain is extended as needed.
+    fn cluster_at(&mut self, index: u64, allocate: bool) -> io::Result<Cluster> {
+        if !self.start.is_data() && allocate {
+            self.start = self.vfat.lock(|vfat| vfat.alloc_cluster(None))?;
+        }
+
+        let (mut i, mut cluster) = match self.current {
+            Some((i, cluster)) if i <= index => (i, cluster),
+            _ => (0, self.start),
+        };
+
+        self.vfat.lock(|vfat| -> io::Result<()> {
+            while i < index {
+                cluster = match vfat.next_cluster(cluster)? {
+                    Some(next) => next,
+                    None if allocate => vfat.alloc_cluster(Some(cluster))?,
+                    None => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "cluster chain shorter than file",
+                        ));
+                    }
+                };
+                i += 1;
+                if i > vfat.num_clusters() as u64 {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "cluster chain loops"));
+                }
+            }
+            Ok(())
+        })?;
+
+        self.current = Some((index, cluster));
+        Ok(cluster)
+    }
+
+    /// Writes the file's first cluster, size and modification time to its
+    /// directory entry.
+    fn update_entry(&mut self) -> io::Result<()> {
+        let (start, size) = (self.start, self.size as u32);
+        let location = self.location;
+        self.metadata = self.vfat.lock(|vfat| {
+            let now = vfat.now();
+            vfat.update_entry(location, |entry| {
+                entry.set_cluster(start);
+                entry.size = size.to_le();
+                entry.touch(now);
+            })
+        })?;
+        Ok(())
+    }
+
+    /// Truncates or extends the file to `size` bytes. Clusters no longer
+    /// needed are freed; extended files are filled with zeroes. The position
+    /// in the file is moved to `size` if it was beyond it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `PermissionDenied` if the file is read-only
+    /// and of kind `InvalidInput` if `size` exceeds the 4 GiB limit of FAT32.
+    pub fn set_len(&mut self, size: u64) -> io::Result<()> {
+        if self.metadata.read_only() {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, "file is read-only"));
+        } else if size > u32::max_value() as u64 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "file too large"));
+        }
+
+        if size > self.size {
+            let offset = self.offset;
+            self.offset = self.size;
+            let zeros = [0u8; 512];
+            while self.offset < size {
+                let n = cmp::min(zeros.len() as u64, size - self.offset) as usize;
+                io::Write::write_all(self, &zeros[..n])?;
+            }
+            self.offset = offset;
+            return Ok(());
+        }
+
+        // Shorten the entry, and sync it, before freeing clusters so that an
+        // interrupted truncation at worst leaks them.
+        let cluster_size = self.vfat.lock(|vfat| vfat.cluster_size()) as u64;
+        let keep = (size + cluster_size - 1) / cluster_size;
+        let start = self.start;
+        let last = match keep {
+            0 => None,
+            _ => Some(self.cluster_at(keep - 1, false)?),
+        };
+        if last.is_none() {
+            self.start = Cluster::from(0);
+        }
+        self.size = size;
+        self.offset = cmp::min(self.offset, size);
+        self.current = None;
+        self.update_entry()?;
+        self.vfat.lock(|vfat| vfat.sync())?;
+
+        self.vfat.lock(|vfat| {
+            vfat.synced(|vfat| match last {
+                Some(last) => vfat.truncate_chain(last),
+                None if start.is_data() => vfat.free_chain(start),
+                None => Ok(()),
+            })
+        })
+    }
+}
+
+impl<HANDLE: VFatHandle> io::Read for File<HANDLE> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let cluster_size = self.vfat.lock(|vfat| vfat.cluster_size()) as u64;
+        let mut read = 0;
+
+        while read < buf.len() && self.offset < self.size {
+            let cluster = self.cluster_at(self.offset / cluster_size, false)?;
+            let offset = (self.offset % cluster_size) as usize;
+            let remaining = cmp::min((buf.len() - read) as u64, self.size - self.offset) as usize;
+
+            let n = self.vfat.lock(|vfat| {
+                vfat.read_cluster(cluster, offset, &mut buf[read..read + remaining])
+            })?;
+            if n == 0 {
+                break;
+            }
+
+            read += n;
+            self.offset += n as u64;
+        }
+
+        Ok(read)
+    }
+}
+
+impl<HANDLE: VFatHandle> io::Write for File<HANDLE> {
+    /// Writes `buf` at the current position, allocating clusters as the file
+    /// grows. The directory entry is updated before returning.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.metadata.read_only() {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, "file is read-only"));
+        } else if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let max = u32::max_value() as u64 - self.offset;
+        let buf = &buf[..cmp::min(buf.len() as u64, max) as usize];
+        if buf.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::WriteZero, "file too large"));
+        }
+
+        let cluster_size = self.vfat.lock(|vfat| vfat.cluster_size()) as u64;
+        let mut written = 0;
+        let mut result = Ok(());
+        while written < buf.len() {
+            let n = self.cluster_at(self.offset / cluster_size, true).and_then(|cluster| {
+                let offset = (self.offset % cluster_size) as usize;
+                self.vfat.lock(|vfat| vfat.write_cluster(cluster, offset, &buf[written..]))
+            });
+            match n {
+                Ok(n) => {
+                    written += n;
+                    self.offset += n as u64;
+                }
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+
+        // Record whatever was written, even if the volume filled up. After a
+        // failure, clusters allocated past the end of the data written, like
+        // the first cluster of an empty file, hold nothing and are freed.
+        self.size = cmp::max(self.size, self.offset);
+        if result.is_err() {
+            self.set_len(self.size)?;
+        } else {
+            self.update_entry()?;
+        }
+        self.vfat.lock(|vfat| vfat.sync())?;
+
+        match result {
+            Err(e) if written == 0 => Err(e),
+            _ => Ok(written),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.vfat.lock(|vfat| vfat.sync())
+    }
+}
+
+impl<HANDLE: VFatHandle> io::Seek for File<HANDLE> {
+    /// Seek to offset `pos` in the file.
+    ///
+    /// A seek to the end of the file is allowed. A seek _beyond_ the end of
+    /// the file returns an `InvalidInput` error.
+    ///
+    /// If the seek operation completes successfully, this method returns the
+    /// new position from the start of the stream. That position can be used
+    /// later with SeekFrom::Start.
+    ///
+    /// # Errors
+    ///
+    /// Seeking before the start of a file or beyond the end of the file
+    /// results in an `InvalidInput` error.
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => offset as i128,
+            SeekFrom::End(offset) => self.size as i128 + offset as i128,
+            SeekFrom::Current(offset) => self.offset as i128 + offset as i128,
+        };
+
+        if target < 0 || target > self.size as i128 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "seek out of file bounds"));
+        }
+
+        self.offset = target as u64;
+        Ok(self.offset)
+    }
+}
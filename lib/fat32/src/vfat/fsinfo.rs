@@ -0,0 +1,87 @@
+use core::fmt;
+use core::mem;
+
+use shim::const_assert_size;
+
+use crate::traits::BlockDevice;
+use crate::vfat::Error;
+
+const LEAD_SIGNATURE: u32 = 0x4161_5252;
+const STRUCT_SIGNATURE: u32 = 0x6141_7272;
+const TRAIL_SIGNATURE: u32 = 0xAA55_0000;
+
+/// The value of the FSInfo counters when they are unknown.
+const UNKNOWN: u32 = 0xFFFF_FFFF;
+
+/// The FAT32 FSInfo sector, which caches the number of free clusters and a
+/// hint for where to start looking for one.
+#[repr(C, packed)]
+pub struct FsInfo {
+    lead_signature: u32,
+    __r0: [u8; 480],
+    struct_signature: u32,
+    free_count: u32,
+    next_free: u32,
+    __r1: [u8; 12],
+    trail_signature: u32,
+}
+
+const_assert_size!(FsInfo, 512);
+
+impl FsInfo {
+    /// Reads the FSInfo structure from sector `sector` of device `device`.
+    ///
+    /// # Errors
+    ///
+    /// If any of the three signatures is invalid, returns an error of
+    /// `BadSignature`.
+    pub fn from<T: BlockDevice>(mut device: T, sector: u64) -> Result<FsInfo, Error> {
+        let mut buf = [0u8; 512];
+        device.read_sector(sector, &mut buf)?;
+
+        let fsinfo: FsInfo = unsafe { mem::transmute(buf) };
+        if u32::from_le(fsinfo.lead_signature) != LEAD_SIGNATURE
+            || u32::from_le(fsinfo.struct_signature) != STRUCT_SIGNATURE
+            || u32::from_le(fsinfo.trail_signature) != TRAIL_SIGNATURE
+        {
+            return Err(Error::BadSignature);
+        }
+
+        Ok(fsinfo)
+    }
+
+    /// Returns the last known number of free clusters, if any.
+    pub fn free_count(&self) -> Option<u32> {
+        match u32::from_le(self.free_count) {
+            UNKNOWN => None,
+            n => Some(n),
+        }
+    }
+
+    /// Returns the cluster number from which to start looking for free
+    /// clusters, if any.
+    pub fn next_free(&self) -> Option<u32> {
+        match u32::from_le(self.next_free) {
+            UNKNOWN => None,
+            n => Some(n),
+        }
+    }
+
+    /// Stores the counters in the raw sector `sector`, which must hold an
+    /// FSInfo structure, leaving everything else untouched.
+    pub(crate) fn update(sector: &mut [u8], free_count: Option<u32>, next_free: Option<u32>) {
+        let free_count = free_count.unwrap_or(UNKNOWN);
+        let next_free = next_free.unwrap_or(UNKNOWN);
+        sector[488..492].copy_from_slice(&free_count.to_le_bytes());
+        sector[492..496].copy_from_slice(&next_free.to_le_bytes());
+    }
+}
+
+impl fmt::Debug for FsInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FsInfo")
+            .field("free_count", &self.free_count())
+            .field("next_free", &self.next_free())
+            .finish()
+    }
+}
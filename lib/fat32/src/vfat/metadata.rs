@@ -0,0 +1,236 @@
+use core::fmt;
+
+/// A date as represented in FAT32 on-disk structures.
+#[repr(C, packed)]
+#[derive(Default, Copy, Clone, PartialEq, Eq)]
+pub struct Date(u16);
+
+/// Time as represented in FAT32 on-disk structures.
+#[repr(C, packed)]
+#[derive(Default, Copy, Clone, PartialEq, Eq)]
+pub struct Time(u16);
+
+/// File attributes as represented in FAT32 on-disk structures.
+#[repr(C, packed)]
+#[derive(Default, Copy, Clone, PartialEq, Eq)]
+pub struct Attributes(u8);
+
+impl Attributes {
+    pub const READ_ONLY: u8 = 0x01;
+    pub const HIDDEN: u8 = 0x02;
+    pub const SYSTEM: u8 = 0x04;
+    pub const VOLUME_ID: u8 = 0x08;
+    pub const DIRECTORY: u8 = 0x10;
+    pub const ARCHIVE: u8 = 0x20;
+    /// The attribute combination marking a long file name entry.
+    pub const LFN: u8 = 0x0F;
+
+    /// Returns the raw attribute byte.
+    pub fn raw(&self) -> u8 {
+        self.0
+    }
+
+    pub fn read_only(&self) -> bool {
+        self.0 & Attributes::READ_ONLY != 0
+    }
+
+    pub fn hidden(&self) -> bool {
+        self.0 & Attributes::HIDDEN != 0
+    }
+
+    pub fn system(&self) -> bool {
+        self.0 & Attributes::SYSTEM != 0
+    }
+
+    pub fn volume_id(&self) -> bool {
+        self.0 & Attributes::VOLUME_ID != 0
+    }
+
+    pub fn directory(&self) -> bool {
+        self.0 & Attributes::DIRECTORY != 0
+    }
+
+    pub fn archive(&self) -> bool {
+        self.0 & Attributes::ARCHIVE != 0
+    }
+
+    /// Returns `true` if these are the attributes of a long file name entry.
+    pub fn lfn(&self) -> bool {
+        self.0 & 0x3F == Attributes::LFN
+    }
+}
+
+impl From<u8> for Attributes {
+    fn from(raw: u8) -> Attributes {
+        Attributes(raw)
+    }
+}
+
+impl fmt::Debug for Attributes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Attributes({})", self)
+    }
+}
+
+impl fmt::Display for Attributes {
+    /// Formats the attributes as `drhsa`, with `-` for each unset flag.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let flags = [
+            (self.directory(), 'd'),
+            (self.read_only(), 'r'),
+            (self.hidden(), 'h'),
+            (self.system(), 's'),
+            (self.archive(), 'a'),
+        ];
+
+        for &(set, c) in flags.iter() {
+            fmt::Write::write_char(f, if set { c } else { '-' })?;
+        }
+        Ok(())
+    }
+}
+
+impl Date {
+    /// Creates a date from a calendar year (1980 to 2107), month (1 to 12)
+    /// and day (1 to 31). Years outside the representable range are clamped.
+    pub fn new(year: usize, month: u8, day: u8) -> Date {
+        let year = (year.max(1980).min(2107) - 1980) as u16;
+        Date((year << 9) | ((month as u16 & 0x0F) << 5) | (day as u16 & 0x1F))
+    }
+
+    /// Creates a date from its on-disk representation.
+    pub fn from_raw(raw: u16) -> Date {
+        Date(raw)
+    }
+
+    /// Returns the on-disk representation.
+    pub fn raw(&self) -> u16 {
+        self.0
+    }
+
+    /// The calendar year, e.g. 1996.
+    pub fn year(&self) -> usize {
+        ((self.0 >> 9) & 0x7F) as usize + 1980
+    }
+
+    /// The calendar month, in the range 1 to 12.
+    pub fn month(&self) -> u8 {
+        ((self.0 >> 5) & 0x0F) as u8
+    }
+
+    /// The calendar day, in the range 1 to 31.
+    pub fn day(&self) -> u8 {
+        (self.0 & 0x1F) as u8
+    }
+}
+
+impl Time {
+    /// Creates a time from an hour (0 to 23), minute (0 to 59) and second
+    /// (0 to 59). Odd seconds are rounded down.
+    pub fn new(hour: u8, minute: u8, second: u8) -> Time {
+        Time(((hour as u16 & 0x1F) << 11) | ((minute as u16 & 0x3F) << 5) | ((second as u16 / 2) & 0x1F))
+    }
+
+    /// Creates a time from its on-disk representation.
+    pub fn from_raw(raw: u16) -> Time {
+        Time(raw)
+    }
+
+    /// Returns the on-disk representation.
+    pub fn raw(&self) -> u16 {
+        self.0
+    }
+
+    /// The hour, in the range 0 to 23.
+    pub fn hour(&self) -> u8 {
+        ((self.0 >> 11) & 0x1F) as u8
+    }
+
+    /// The minute, in the range 0 to 59.
+    pub fn minute(&self) -> u8 {
+        ((self.0 >> 5) & 0x3F) as u8
+    }
+
+    /// The second, in the range 0 to 58. FAT stores seconds with a two-second
+    /// granularity.
+    pub fn second(&self) -> u8 {
+        ((self.0 & 0x1F) * 2) as u8
+    }
+}
+
+/// A date and time.
+#[derive(Default, Copy, Clone, PartialEq, Eq)]
+pub struct Timestamp {
+    pub date: Date,
+    pub time: Time,
+}
+
+impl Timestamp {
+    pub fn year(&self) -> usize {
+        self.date.year()
+    }
+
+    pub fn month(&self) -> u8 {
+        self.date.month()
+    }
+
+    pub fn day(&self) -> u8 {
+        self.date.day()
+    }
+
+    pub fn hour(&self) -> u8 {
+        self.time.hour()
+    }
+
+    pub fn minute(&self) -> u8 {
+        self.time.minute()
+    }
+
+    pub fn second(&self) -> u8 {
+        self.time.second()
+    }
+}
+
+impl fmt::Debug for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Timestamp({})", self)
+    }
+}
+
+impl fmt::Display for Timestamp {
+    /// Formats the timestamp as `YYYY-MM-DD hh:mm:ss`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            self.year(),
+            self.month(),
+            self.day(),
+            self.hour(),
+            self.minute(),
+            self.second()
+        )
+    }
+}
+
+/// Metadata for a directory entry.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Metadata {
+    pub attributes: Attributes,
+    pub created: Timestamp,
+    /// FAT only records the date of the last access; the time is always zero.
+    pub accessed: Timestamp,
+    pub modified: Timestamp,
+}
+
+impl Metadata {
+    /// Returns `true` if the entry is marked read-only.
+    pub fn read_only(&self) -> bool {
+        self.attributes.read_only()
+    }
+
+    /// Returns `true` if the entry is hidden.
+    pub fn hidden(&self) -> bool {
+        self.attributes.hidden()
+    }
+}
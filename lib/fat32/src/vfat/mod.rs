@@ -0,0 +1,22 @@
+mod cluster;
+mod dir;
+mod ebpb;
+mod entry;
+mod error;
+mod fat;
+mod file;
+mod fsinfo;
+mod metadata;
+mod name;
+mod vfat;
+
+pub use self::cluster::Cluster;
+pub use self::dir::{Dir, EntryIter};
+pub use self::ebpb::BiosParameterBlock;
+pub use self::entry::Entry;
+pub use self::error::Error;
+pub use self::fat::{FatEntry, Status};
+pub use self::file::File;
+pub use self::fsinfo::FsInfo;
+pub use self::metadata::{Attributes, Date, Metadata, Time, Timestamp};
+pub use self::vfat::{VFat, VFatHandle};
@@ -0,0 +1,174 @@
+use alloc::format;
+use alloc::vec::Vec;
+
+use shim::io;
+
+use crate::vfat::dir::{lfn_checksum, LFN_CHARS_PER_ENTRY, LFN_LAST_ENTRY, LFN_MAX_ENTRIES};
+use crate::vfat::Attributes;
+
+/// `case_flags` bits set by Windows NT for all-lowercase 8.3 names.
+pub(crate) const CASE_LOWER_BASE: u8 = 0x08;
+pub(crate) const CASE_LOWER_EXT: u8 = 0x10;
+
+/// The longest file name, in UTF-16 code units, FAT32 can store.
+const MAX_NAME_LEN: usize = 255;
+
+/// Characters other than letters and digits allowed in 8.3 names.
+const SHORT_NAME_SPECIAL: &[u8] = b"!#$%&'()-@^_`{}~";
+
+/// Characters never allowed in file names.
+const INVALID_CHARS: &[char] = &['"', '*', '/', ':', '<', '>', '?', '\\', '|'];
+
+/// The on-disk form of a name: an 8.3 name, its case flags and, when the
+/// 8.3 name can't represent the name exactly, the UTF-16 long name.
+pub(crate) struct EncodedName {
+    pub(crate) short: [u8; 11],
+    pub(crate) case_flags: u8,
+    pub(crate) long: Option<Vec<u16>>,
+}
+
+/// Returns an error unless `name` can be stored in a directory.
+pub(crate) fn validate(name: &str) -> io::Result<()> {
+    let invalid = |msg| Err(io::Error::new(io::ErrorKind::InvalidInput, msg));
+
+    if name.is_empty() || name == "." || name == ".." {
+        return invalid("invalid file name");
+    } else if name.encode_utf16().count() > MAX_NAME_LEN {
+        return invalid("file name too long");
+    } else if name.chars().any(|c| c < ' ' || INVALID_CHARS.contains(&c)) {
+        return invalid("invalid character in file name");
+    } else if name.ends_with('.') || name.ends_with(' ') {
+        return invalid("file name ends with a dot or space");
+    }
+
+    Ok(())
+}
+
+fn is_short_char(b: u8) -> bool {
+    b.is_ascii_uppercase() || b.is_ascii_digit() || SHORT_NAME_SPECIAL.contains(&b)
+}
+
+/// Returns the case flag for `part` if it can be stored in an 8.3 name
+/// exactly: `Some(0)` if it is uppercase, `Some(flag)` if it is lowercase
+/// and `None` if it has mixed case or characters 8.3 names can't hold.
+fn short_part_case(part: &str, max: usize, flag: u8) -> Option<u8> {
+    if part.len() > max {
+        return None;
+    }
+
+    let upper = part.bytes().all(|b| is_short_char(b.to_ascii_uppercase()));
+    let has_lower = part.bytes().any(|b| b.is_ascii_lowercase());
+    let has_upper = part.bytes().any(|b| b.is_ascii_uppercase());
+    match (upper, has_lower, has_upper) {
+        (true, false, _) => Some(0),
+        (true, true, false) => Some(flag),
+        _ => None,
+    }
+}
+
+/// Returns the 8.3 name and case flags exactly representing `name`, if any.
+fn exact_short_name(name: &str) -> Option<([u8; 11], u8)> {
+    let (base, ext) = match name.find('.') {
+        Some(i) => (&name[..i], &name[i + 1..]),
+        None => (name, ""),
+    };
+    if base.is_empty() || ext.contains('.') || (name.contains('.') && ext.is_empty()) {
+        return None;
+    }
+
+    let base_case = short_part_case(base, 8, CASE_LOWER_BASE)?;
+    let ext_case = short_part_case(ext, 3, CASE_LOWER_EXT)?;
+
+    let mut short = [b' '; 11];
+    short[..base.len()].copy_from_slice(base.to_ascii_uppercase().as_bytes());
+    short[8..8 + ext.len()].copy_from_slice(ext.to_ascii_uppercase().as_bytes());
+    Some((short, base_case | ext_case))
+}
+
+/// Converts `part` to at most `max` characters valid in an 8.3 name.
+fn basis_part(part: &str, max: usize) -> Vec<u8> {
+    part.chars()
+        .filter(|&c| c != ' ' && c != '.')
+        .map(|c| match c {
+            c if c.is_ascii() && is_short_char(c.to_ascii_uppercase() as u8) => c.to_ascii_uppercase() as u8,
+            _ => b'_',
+        })
+        .take(max)
+        .collect()
+}
+
+/// Encodes `name`, which must be valid, for storage in a directory whose
+/// regular entries use the 8.3 names `existing`. Names that aren't exact
+/// 8.3 names get a long name and a unique 8.3 alias such as `LONGNA~1.TXT`.
+///
+/// # Errors
+///
+/// Returns an error of kind `AlreadyExists` if no unique alias is left.
+pub(crate) fn encode(name: &str, existing: &[[u8; 11]]) -> io::Result<EncodedName> {
+    if let Some((short, case_flags)) = exact_short_name(name) {
+        if !existing.contains(&short) {
+            return Ok(EncodedName { short, case_flags, long: None });
+        }
+    }
+
+    let trimmed = name.trim_start_matches('.');
+    let (base, ext) = match trimmed.rfind('.') {
+        Some(i) => (&trimmed[..i], &trimmed[i + 1..]),
+        None => (trimmed, ""),
+    };
+    let mut base = basis_part(base, 8);
+    if base.is_empty() {
+        base.push(b'_');
+    }
+    let ext = basis_part(ext, 3);
+
+    let mut short = [b' '; 11];
+    short[8..8 + ext.len()].copy_from_slice(&ext);
+    for n in 1..1_000_000u32 {
+        let tail = format!("~{}", n);
+        let len = core::cmp::min(base.len(), 8 - tail.len());
+        short[..8].copy_from_slice(b"        ");
+        short[..len].copy_from_slice(&base[..len]);
+        short[len..len + tail.len()].copy_from_slice(tail.as_bytes());
+
+        if !existing.contains(&short) {
+            let long = name.encode_utf16().collect();
+            return Ok(EncodedName { short, case_flags: 0, long: Some(long) });
+        }
+    }
+
+    Err(io::Error::new(io::ErrorKind::AlreadyExists, "no unique short name left"))
+}
+
+/// Returns the raw LFN entries holding `long` for the 8.3 name `short`, in
+/// the order they are stored on disk.
+pub(crate) fn lfn_entries(long: &[u16], short: &[u8; 11]) -> Vec<[u8; 32]> {
+    let count = (long.len() + LFN_CHARS_PER_ENTRY - 1) / LFN_CHARS_PER_ENTRY;
+    assert!(count <= LFN_MAX_ENTRIES, "long file name too long");
+
+    let checksum = lfn_checksum(short);
+    let mut entries = Vec::with_capacity(count);
+    for seq in (1..=count).rev() {
+        let mut chars = [0xFFFFu16; LFN_CHARS_PER_ENTRY];
+        let start = (seq - 1) * LFN_CHARS_PER_ENTRY;
+        for (i, c) in chars.iter_mut().enumerate() {
+            match long.get(start + i) {
+                Some(&unit) => *c = unit,
+                None if start + i == long.len() => *c = 0x0000,
+                None => {}
+            }
+        }
+
+        let mut entry = [0u8; 32];
+        entry[0] = seq as u8 | if seq == count { LFN_LAST_ENTRY } else { 0 };
+        entry[11] = Attributes::LFN;
+        entry[13] = checksum;
+        let offsets = (1..11).step_by(2).chain((14..26).step_by(2)).chain((28..32).step_by(2));
+        for (offset, c) in offsets.zip(chars.iter()) {
+            entry[offset..offset + 2].copy_from_slice(&c.to_le_bytes());
+        }
+        entries.push(entry);
+    }
+
+    entries
+}
@@ -0,0 +1,636 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::fmt::{self, Debug};
+use core::marker::PhantomData;
+
+use shim::io;
+
+use crate::mbr::MasterBootRecord;
+use crate::cache::{CacheStats, CachedDevice};
+use crate::partition::Partition;
+use crate::traits::BlockDevice;
+use crate::vfat::dir::{EntryLocation, VFatDirEntry, VFatRegularDirEntry, DELETED_MARKER};
+use crate::vfat::{
+    BiosParameterBlock, Cluster, Date, Dir, Entry, Error, FatEntry, File, FsInfo, Metadata, Status, Time, Timestamp,
+};
+
+/// A shareable handle to a `VFat` filesystem.
+///
+/// Directories and files keep a handle to the filesystem they belong to. The
+/// handle decides how concurrent accesses are serialized: the kernel wraps
+/// the filesystem in its spin lock, while host tests can use `std` types.
+pub trait VFatHandle: Clone + Debug + Send + Sync {
+    /// Wraps `val` in a new handle.
+    fn new(val: VFat<Self>) -> Self;
+
+    /// Runs `f` with exclusive access to the filesystem.
+    fn lock<R>(&self, f: impl FnOnce(&mut VFat<Self>) -> R) -> R;
+
+    /// Returns the root directory of the filesystem.
+    fn root(&self) -> Dir<Self> {
+        let start = self.lock(|vfat| vfat.root_dir_cluster);
+        Dir::new(self.clone(), start, "/".into(), Metadata::default(), None)
+    }
+
+    /// Opens the entry at `path`, which is interpreted relative to the root
+    /// directory. Components are separated by `/`; `.` and `..` are resolved
+    /// as they are encountered. Names are matched case-insensitively.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `NotFound` if a component doesn't exist or
+    /// if a component other than the last one is not a directory.
+    fn open(&self, path: &str) -> io::Result<Entry<Self>> {
+        let mut dirs: Vec<Dir<Self>> = vec![self.root()];
+        let mut components = path.split('/').filter(|c| !c.is_empty() && *c != ".").peekable();
+
+        while let Some(component) = components.next() {
+            if component == ".." {
+                if dirs.len() > 1 {
+                    dirs.pop();
+                }
+                continue;
+            }
+
+            let entry = dirs.last().unwrap().find(component)?;
+            if components.peek().is_none() {
+                return Ok(entry);
+            }
+
+            match entry {
+                Entry::Dir(dir) => dirs.push(dir),
+                Entry::File(_) => {
+                    return Err(io::Error::new(io::ErrorKind::NotFound, "not a directory"));
+                }
+            }
+        }
+
+        Ok(Entry::Dir(dirs.pop().unwrap()))
+    }
+
+    /// Splits `path` into its parent directory, which is opened, and its
+    /// last component.
+    fn open_parent<'a>(&self, path: &'a str) -> io::Result<(Dir<Self>, &'a str)> {
+        let path = path.trim_end_matches('/');
+        let (parent, name) = match path.rfind('/') {
+            Some(i) => (&path[..i], &path[i + 1..]),
+            None => ("", path),
+        };
+
+        match self.open(parent)? {
+            Entry::Dir(dir) => Ok((dir, name)),
+            Entry::File(_) => Err(io::Error::new(io::ErrorKind::NotFound, "not a directory")),
+        }
+    }
+
+    /// Creates an empty file at `path`. See `Dir::create_file`.
+    fn create_file(&self, path: &str) -> io::Result<File<Self>> {
+        let (dir, name) = self.open_parent(path)?;
+        dir.create_file(name)
+    }
+
+    /// Creates an empty directory at `path`. See `Dir::create_dir`.
+    fn create_dir(&self, path: &str) -> io::Result<Dir<Self>> {
+        let (dir, name) = self.open_parent(path)?;
+        dir.create_dir(name)
+    }
+
+    /// Removes the file or empty directory at `path`. See `Dir::remove_file`
+    /// and `Dir::remove_dir`.
+    fn remove(&self, path: &str) -> io::Result<()> {
+        let (dir, name) = self.open_parent(path)?;
+        match dir.find(name)? {
+            Entry::File(_) => dir.remove_file(name),
+            Entry::Dir(_) => dir.remove_dir(name),
+        }
+    }
+
+    /// Renames the entry at `from` to `to`, which must be in the same
+    /// directory. See `Dir::rename`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidInput` if `from` and `to` are in
+    /// different directories.
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        let (dir, from) = self.open_parent(from)?;
+        let (to_dir, to) = self.open_parent(to)?;
+        if dir.first_cluster() != to_dir.first_cluster() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "cannot rename across directories"));
+        }
+
+        dir.rename(from, to)
+    }
+}
+
+/// A FAT32 filesystem on a partition of a block device.
+pub struct VFat<HANDLE: VFatHandle> {
+    phantom: PhantomData<HANDLE>,
+    device: CachedDevice<Partition<Box<dyn BlockDevice>>>,
+    bytes_per_sector: u16,
+    sectors_per_cluster: u8,
+    sectors_per_fat: u32,
+    fat_start_sector: u64,
+    data_start_sector: u64,
+    num_clusters: u32,
+    num_fats: u8,
+    root_dir_cluster: Cluster,
+    fsinfo_sector: Option<u64>,
+    free_count: Option<u32>,
+    next_free: u32,
+    clock: fn() -> Timestamp,
+}
+
+/// The number of logical sectors kept in the sector cache.
+const CACHE_SECTORS: usize = 64;
+
+/// The FAT entry value written to mark the end of a cluster chain.
+const EOC: u32 = 0x0FFF_FFFF;
+
+/// The timestamp used for new and modified entries when no clock is set.
+fn epoch() -> Timestamp {
+    Timestamp { date: Date::new(1980, 1, 1), time: Time::new(0, 0, 0) }
+}
+
+impl<HANDLE: VFatHandle> VFat<HANDLE> {
+    /// Mounts the first FAT32 partition listed in the MBR of `device`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Mbr` if the MBR can't be read, `NoFat32Partition` if it lists
+    /// no FAT32 partition, and the errors of `from_partition` otherwise.
+    pub fn from<T>(mut device: T) -> Result<HANDLE, Error>
+    where
+        T: BlockDevice + 'static,
+    {
+        let mbr = MasterBootRecord::from(&mut device)?;
+        let entry = *mbr.first_fat32().ok_or(Error::NoFat32Partition)?;
+        VFat::from_partition(device, entry.start_lba(), entry.num_sectors())
+    }
+
+    /// Mounts the FAT32 volume occupying `num_sectors` device sectors of
+    /// `device` starting at sector `start`. Use a `start` of `0` for images
+    /// without a partition table.
+    ///
+    /// # Errors
+    ///
+    /// Returns `BadSignature` if the volume has no valid boot sector and
+    /// `Unsupported` if its geometry is invalid or not FAT32.
+    pub fn from_partition<T>(device: T, start: u64, num_sectors: u64) -> Result<HANDLE, Error>
+    where
+        T: BlockDevice + 'static,
+    {
+        let device: Box<dyn BlockDevice> = Box::new(device);
+        let mut device = Partition::new(device, start, num_sectors);
+        let device_sector_size = device.sector_size();
+
+        let ebpb = BiosParameterBlock::from(&mut device, 0)?;
+        ebpb.validate(device_sector_size)?;
+
+        let fat_start_sector = ebpb.reserved_sectors() as u64;
+        let data_start_sector =
+            fat_start_sector + ebpb.num_fats() as u64 * ebpb.sectors_per_fat() as u64;
+        let data_sectors = (ebpb.total_sectors() as u64).saturating_sub(data_start_sector);
+        let num_clusters = (data_sectors / ebpb.sectors_per_cluster() as u64) as u32;
+
+        let fsinfo_sector = match ebpb.fsinfo_sector() as u64 {
+            0 | 0xFFFF => None,
+            n if n >= fat_start_sector => None,
+            n => Some(n),
+        };
+        let mut device = CachedDevice::new(device, ebpb.bytes_per_sector() as u64, CACHE_SECTORS);
+        let mut free_count = None;
+        let mut next_free = 2;
+        if let Some(sector) = fsinfo_sector {
+            if let Ok(fsinfo) = FsInfo::from(&mut device, sector) {
+                free_count = fsinfo.free_count().filter(|&n| n <= num_clusters);
+                next_free = fsinfo.next_free().filter(|&n| n >= 2 && n < num_clusters + 2).unwrap_or(2);
+            }
+        }
+
+        Ok(HANDLE::new(VFat {
+            phantom: PhantomData,
+            device,
+            bytes_per_sector: ebpb.bytes_per_sector(),
+            sectors_per_cluster: ebpb.sectors_per_cluster(),
+            sectors_per_fat: ebpb.sectors_per_fat(),
+            fat_start_sector,
+            data_start_sector,
+            num_clusters,
+            num_fats: ebpb.num_fats(),
+            root_dir_cluster: Cluster::from(ebpb.root_cluster()),
+            fsinfo_sector,
+            free_count,
+            next_free,
+            clock: epoch,
+        }))
+    }
+
+    /// Returns the size of a cluster in bytes.
+    pub fn cluster_size(&self) -> usize {
+        self.bytes_per_sector as usize * self.sectors_per_cluster as usize
+    }
+
+    /// Returns the number of data clusters in the volume.
+    pub fn num_clusters(&self) -> u32 {
+        self.num_clusters
+    }
+
+    /// Returns the first cluster of the root directory.
+    pub fn root_dir_cluster(&self) -> Cluster {
+        self.root_dir_cluster
+    }
+
+    /// Reads logical sector `n` of the volume into `buf`, which must be at
+    /// least `bytes_per_sector` long.
+    fn read_sector(&mut self, n: u64, buf: &mut [u8]) -> io::Result<usize> {
+        self.device.read_sector(n, buf)
+    }
+
+    /// Returns an error if `cluster` is not a data cluster of this volume.
+    fn check_cluster(&self, cluster: Cluster) -> io::Result<()> {
+        if !cluster.is_data() || cluster.number() >= self.num_clusters + 2 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid cluster number"));
+        }
+
+        Ok(())
+    }
+
+    /// Reads from cluster `cluster` starting at byte `offset` into `buf`.
+    /// Returns the number of bytes read, which is less than `buf.len()` when
+    /// the end of the cluster is reached.
+    pub(crate) fn read_cluster(&mut self, cluster: Cluster, offset: usize, buf: &mut [u8]) -> io::Result<usize> {
+        self.check_cluster(cluster)?;
+
+        let sector_size = self.bytes_per_sector as usize;
+        let first_sector =
+            self.data_start_sector + cluster.data_index() * self.sectors_per_cluster as u64;
+        let len = core::cmp::min(buf.len(), self.cluster_size().saturating_sub(offset));
+
+        let mut sector = vec![0u8; sector_size];
+        let mut read = 0;
+        while read < len {
+            let position = offset + read;
+            self.read_sector(first_sector + (position / sector_size) as u64, &mut sector)?;
+
+            let start = position % sector_size;
+            let n = core::cmp::min(len - read, sector_size - start);
+            buf[read..read + n].copy_from_slice(&sector[start..start + n]);
+            read += n;
+        }
+
+        Ok(len)
+    }
+
+    /// Returns the FAT entry for `cluster`.
+    pub(crate) fn fat_entry(&mut self, cluster: Cluster) -> io::Result<FatEntry> {
+        self.check_cluster(cluster)?;
+
+        let sector_size = self.bytes_per_sector as u64;
+        let offset = cluster.number() as u64 * 4;
+        let mut sector = vec![0u8; sector_size as usize];
+        self.read_sector(self.fat_start_sector + offset / sector_size, &mut sector)?;
+
+        let i = (offset % sector_size) as usize;
+        let raw = [sector[i], sector[i + 1], sector[i + 2], sector[i + 3]];
+        Ok(FatEntry(u32::from_le_bytes(raw)))
+    }
+
+    /// Returns the cluster following `cluster` in its chain, or `None` if
+    /// `cluster` is the last one.
+    pub(crate) fn next_cluster(&mut self, cluster: Cluster) -> io::Result<Option<Cluster>> {
+        match self.fat_entry(cluster)?.status() {
+            Status::Data(next) => {
+                self.check_cluster(next)?;
+                Ok(Some(next))
+            }
+            Status::Eoc(_) => Ok(None),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, "broken cluster chain")),
+        }
+    }
+
+    /// Reads the entire cluster chain starting at `start` and appends it to
+    /// `buf`. Returns the number of bytes read.
+    pub(crate) fn read_chain(&mut self, start: Cluster, buf: &mut Vec<u8>) -> io::Result<usize> {
+        let cluster_size = self.cluster_size();
+        let mut current = Some(start);
+        let mut read = 0;
+
+        while let Some(cluster) = current {
+            if read / cluster_size > self.num_clusters as usize {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "cluster chain loops"));
+            }
+
+            let end = buf.len();
+            buf.resize(end + cluster_size, 0);
+            read += self.read_cluster(cluster, 0, &mut buf[end..])?;
+            current = self.next_cluster(cluster)?;
+        }
+
+        Ok(read)
+    }
+
+    /// Returns the clusters of the chain starting at `start`, in order.
+    pub(crate) fn chain(&mut self, start: Cluster) -> io::Result<Vec<Cluster>> {
+        let mut chain = vec![start];
+        while let Some(next) = self.next_cluster(*chain.last().unwrap())? {
+            if chain.len() > self.num_clusters as usize {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "cluster chain loops"));
+            }
+            chain.push(next);
+        }
+
+        Ok(chain)
+    }
+
+    /// Returns the cluster holding byte `offset` of the chain starting at
+    /// `start` and the offset of that byte within the cluster.
+    fn chain_position(&mut self, start: Cluster, offset: u64) -> io::Result<(Cluster, usize)> {
+        let cluster_size = self.cluster_size() as u64;
+        let mut cluster = start;
+        for _ in 0..offset / cluster_size {
+            cluster = self.next_cluster(cluster)?.ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "directory entry beyond end of chain")
+            })?;
+        }
+
+        Ok((cluster, (offset % cluster_size) as usize))
+    }
+
+    /// Reads the 32-byte directory slot at byte `offset` of the directory
+    /// starting at `dir`.
+    pub(crate) fn read_slot(&mut self, dir: Cluster, offset: u64) -> io::Result<[u8; 32]> {
+        let (cluster, offset) = self.chain_position(dir, offset)?;
+        let mut slot = [0u8; 32];
+        self.read_cluster(cluster, offset, &mut slot)?;
+        Ok(slot)
+    }
+
+    /// Writes the 32-byte directory slot at byte `offset` of the directory
+    /// starting at `dir`.
+    pub(crate) fn write_slot(&mut self, dir: Cluster, offset: u64, slot: &[u8; 32]) -> io::Result<()> {
+        let (cluster, offset) = self.chain_position(dir, offset)?;
+        self.write_cluster(cluster, offset, slot)?;
+        Ok(())
+    }
+
+    /// Applies `f` to the regular directory entry at `location` and writes it
+    /// back. Returns the entry's updated metadata.
+    pub(crate) fn update_entry(
+        &mut self,
+        location: EntryLocation,
+        f: impl FnOnce(&mut VFatRegularDirEntry),
+    ) -> io::Result<Metadata> {
+        let slot = self.read_slot(location.dir, location.regular)?;
+        let mut entry = unsafe { VFatDirEntry::from_bytes(&slot).regular };
+        f(&mut entry);
+        self.write_slot(location.dir, location.regular, &entry.to_bytes())?;
+        Ok(entry.metadata())
+    }
+
+    /// Marks every slot of the entry at `location` as deleted.
+    pub(crate) fn delete_entry(&mut self, location: EntryLocation) -> io::Result<()> {
+        let mut offset = location.first;
+        while offset <= location.regular {
+            let mut slot = self.read_slot(location.dir, offset)?;
+            slot[0] = DELETED_MARKER;
+            self.write_slot(location.dir, offset, &slot)?;
+            offset += 32;
+        }
+
+        Ok(())
+    }
+
+    /// Writes all modified sectors held in the sector cache to the device.
+    pub fn sync(&mut self) -> io::Result<()> {
+        self.device.sync()
+    }
+
+    /// Returns the hit and miss counters of the sector cache.
+    pub fn cache_stats(&self) -> CacheStats {
+        self.device.stats()
+    }
+
+    /// Runs `f` and then syncs the sector cache, so that the changes `f`
+    /// makes reach the device even if it fails part way through.
+    ///
+    /// A sync writes sectors in sector order, not in the order they were
+    /// changed: `f` must call `sync` between steps that have to reach the
+    /// device in order.
+    pub(crate) fn synced<R>(&mut self, f: impl FnOnce(&mut Self) -> io::Result<R>) -> io::Result<R> {
+        let result = f(self);
+        self.sync()?;
+        result
+    }
+
+    /// Sets the function used to timestamp created and modified entries.
+    /// Until one is set, entries are stamped 1980-01-01 00:00:00.
+    pub fn set_clock(&mut self, clock: fn() -> Timestamp) {
+        self.clock = clock;
+    }
+
+    /// Returns the current time according to the clock.
+    pub(crate) fn now(&self) -> Timestamp {
+        (self.clock)()
+    }
+
+    /// Writes `buf`, which must be at least `bytes_per_sector` long, to
+    /// logical sector `n` of the volume.
+    fn write_sector(&mut self, n: u64, buf: &[u8]) -> io::Result<usize> {
+        self.device.write_sector(n, buf)
+    }
+
+    /// Writes `buf` to cluster `cluster` starting at byte `offset`. Returns
+    /// the number of bytes written, which is less than `buf.len()` when the
+    /// end of the cluster is reached.
+    pub(crate) fn write_cluster(&mut self, cluster: Cluster, offset: usize, buf: &[u8]) -> io::Result<usize> {
+        self.check_cluster(cluster)?;
+
+        let sector_size = self.bytes_per_sector as usize;
+        let first_sector =
+            self.data_start_sector + cluster.data_index() * self.sectors_per_cluster as u64;
+        let len = core::cmp::min(buf.len(), self.cluster_size().saturating_sub(offset));
+
+        let mut sector = vec![0u8; sector_size];
+        let mut written = 0;
+        while written < len {
+            let position = offset + written;
+            let n = first_sector + (position / sector_size) as u64;
+            let start = position % sector_size;
+            let count = core::cmp::min(len - written, sector_size - start);
+
+            // Partially overwritten sectors are read back first.
+            if count < sector_size {
+                self.read_sector(n, &mut sector)?;
+            }
+            sector[start..start + count].copy_from_slice(&buf[written..written + count]);
+            self.write_sector(n, &sector)?;
+            written += count;
+        }
+
+        Ok(len)
+    }
+
+    /// Sets the FAT entry for `cluster` to `value` in every copy of the FAT,
+    /// preserving the reserved top four bits.
+    fn set_fat_entry(&mut self, cluster: Cluster, value: u32) -> io::Result<()> {
+        self.check_cluster(cluster)?;
+
+        let sector_size = self.bytes_per_sector as u64;
+        let offset = cluster.number() as u64 * 4;
+        let i = (offset % sector_size) as usize;
+        let mut sector = vec![0u8; sector_size as usize];
+        for fat in 0..self.num_fats as u64 {
+            let n = self.fat_start_sector + fat * self.sectors_per_fat as u64 + offset / sector_size;
+            self.read_sector(n, &mut sector)?;
+
+            let old = u32::from_le_bytes([sector[i], sector[i + 1], sector[i + 2], sector[i + 3]]);
+            let new = (old & 0xF000_0000) | (value & 0x0FFF_FFFF);
+            sector[i..i + 4].copy_from_slice(&new.to_le_bytes());
+            self.write_sector(n, &sector)?;
+        }
+
+        Ok(())
+    }
+
+    /// Calls `f` with the number and raw entry of every data cluster in the
+    /// FAT, starting at cluster `from`, until `f` returns `true`. Returns the
+    /// cluster for which `f` returned `true`, if any.
+    fn scan_fat(&mut self, from: u32, mut f: impl FnMut(u32, u32) -> bool) -> io::Result<Option<u32>> {
+        let sector_size = self.bytes_per_sector as u32;
+        let per_sector = sector_size / 4;
+        let end = self.num_clusters + 2;
+        let mut sector = vec![0u8; sector_size as usize];
+
+        let mut cluster = from;
+        while cluster < end {
+            let n = cluster / per_sector;
+            self.read_sector(self.fat_start_sector + n as u64, &mut sector)?;
+            let last = core::cmp::min(end, (n + 1) * per_sector);
+            for number in cluster..last {
+                let i = ((number % per_sector) * 4) as usize;
+                let raw = u32::from_le_bytes([sector[i], sector[i + 1], sector[i + 2], sector[i + 3]]);
+                if f(number, raw) {
+                    return Ok(Some(number));
+                }
+            }
+            cluster = last;
+        }
+
+        Ok(None)
+    }
+
+    /// Returns the number of free clusters in the volume, counting them if
+    /// the FSInfo sector didn't provide a valid count.
+    pub fn free_clusters(&mut self) -> io::Result<u32> {
+        if let Some(count) = self.free_count {
+            return Ok(count);
+        }
+
+        let mut count = 0;
+        self.scan_fat(2, |_, raw| {
+            if FatEntry(raw).status() == Status::Free {
+                count += 1;
+            }
+            false
+        })?;
+        self.free_count = Some(count);
+        Ok(count)
+    }
+
+    /// Writes the free cluster count and next free hint to the FSInfo
+    /// sector, if the volume has one.
+    fn write_fsinfo(&mut self) -> io::Result<()> {
+        let sector = match self.fsinfo_sector {
+            Some(sector) => sector,
+            None => return Ok(()),
+        };
+
+        let mut buf = vec![0u8; self.bytes_per_sector as usize];
+        self.read_sector(sector, &mut buf)?;
+        FsInfo::update(&mut buf, self.free_count, Some(self.next_free));
+        self.write_sector(sector, &buf)?;
+        Ok(())
+    }
+
+    /// Allocates a free cluster, marks it as the end of its chain and, if
+    /// `prev` is given, links it after `prev`. The contents of the cluster
+    /// are left as they are.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `Other` if the volume is full.
+    pub(crate) fn alloc_cluster(&mut self, prev: Option<Cluster>) -> io::Result<Cluster> {
+        self.free_clusters()?;
+
+        let is_free = |_, raw| FatEntry(raw).status() == Status::Free;
+        let hint = self.next_free;
+        let found = match self.scan_fat(hint, is_free)? {
+            Some(number) => Some(number),
+            None => self.scan_fat(2, is_free)?,
+        };
+        let cluster = match found {
+            Some(number) => Cluster::from(number),
+            None => return Err(io::Error::new(io::ErrorKind::Other, "no free clusters")),
+        };
+
+        self.set_fat_entry(cluster, EOC)?;
+        if let Some(prev) = prev {
+            self.set_fat_entry(prev, cluster.number())?;
+        }
+
+        self.free_count = self.free_count.map(|n| n.saturating_sub(1));
+        self.next_free = match cluster.number() + 1 {
+            n if n < self.num_clusters + 2 => n,
+            _ => 2,
+        };
+        self.write_fsinfo()?;
+        Ok(cluster)
+    }
+
+    /// Makes `next` follow `prev`, the last cluster of a chain.
+    pub(crate) fn link_cluster(&mut self, prev: Cluster, next: Cluster) -> io::Result<()> {
+        self.check_cluster(next)?;
+        self.set_fat_entry(prev, next.number())
+    }
+
+    /// Frees every cluster in the chain starting at `start`.
+    pub(crate) fn free_chain(&mut self, start: Cluster) -> io::Result<()> {
+        let chain = self.chain(start)?;
+        for &cluster in chain.iter() {
+            self.set_fat_entry(cluster, 0)?;
+        }
+
+        self.free_count = self.free_count.map(|n| n + chain.len() as u32);
+        self.write_fsinfo()
+    }
+
+    /// Marks `cluster` as the last cluster of its chain and frees the
+    /// clusters that followed it.
+    pub(crate) fn truncate_chain(&mut self, cluster: Cluster) -> io::Result<()> {
+        let next = self.next_cluster(cluster)?;
+        self.set_fat_entry(cluster, EOC)?;
+        match next {
+            Some(next) => self.free_chain(next),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<HANDLE: VFatHandle> Debug for VFat<HANDLE> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("VFat")
+            .field("bytes_per_sector", &self.bytes_per_sector)
+            .field("sectors_per_cluster", &self.sectors_per_cluster)
+            .field("sectors_per_fat", &self.sectors_per_fat)
+            .field("fat_start_sector", &self.fat_start_sector)
+            .field("data_start_sector", &self.data_start_sector)
+            .field("num_clusters", &self.num_clusters)
+            .field("cache", &self.device.stats())
+            .field("num_fats", &self.num_fats)
+            .field("root_dir_cluster", &self.root_dir_cluster)
+            .field("free_count", &self.free_count)
+            .field("next_free", &self.next_free)
+            .finish()
+    }
+}
@@ -0,0 +1,576 @@
+use core::time::Duration;
+
+use shim::const_assert_size;
+use shim::io;
+
+use volatile::prelude::*;
+use volatile::{Volatile, ReadVolatile, Reserved};
+
+use crate::common::IO_BASE;
+use crate::gpio::{Gpio, GpioController, Function};
+use crate::timer::{current_time, spin_sleep};
+
+#[cfg(test)]
+mod tests;
+
+/// The base address of the `EMMC` (Arasan SDHCI) registers.
+const EMMC_REG_BASE: usize = IO_BASE + 0x300000;
+
+/// The GPIO pins wired to the SD card slot.
+const PINS: [u8; 6] = [48, 49, 50, 51, 52, 53];
+
+/// The name the EMMC driver claims its GPIO pins with.
+const OWNER: &str = "EMMC";
+
+/// The frequency of the clock feeding the EMMC controller.
+const EMMC_BASE_CLOCK_HZ: u32 = 41_666_666;
+
+/// Clock used during card identification. Must be at most 400KHz.
+const IDENT_CLOCK_HZ: u32 = 400_000;
+
+/// Clock used for data transfer in default speed mode.
+const TRANSFER_CLOCK_HZ: u32 = 25_000_000;
+
+/// The size of a block, in bytes.
+pub const BLOCK_SIZE: usize = 512;
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    ARG2: Volatile<u32>,
+    BLKSIZECNT: Volatile<u32>,
+    ARG1: Volatile<u32>,
+    CMDTM: Volatile<u32>,
+    RESP: [ReadVolatile<u32>; 4],
+    DATA: Volatile<u32>,
+    STATUS: ReadVolatile<u32>,
+    CONTROL0: Volatile<u32>,
+    CONTROL1: Volatile<u32>,
+    INTERRUPT: Volatile<u32>,
+    IRPT_MASK: Volatile<u32>,
+    IRPT_EN: Volatile<u32>,
+    CONTROL2: Volatile<u32>,
+    __r0: [Reserved<u32>; 47],
+    SLOTISR_VER: ReadVolatile<u32>,
+}
+
+const_assert_size!(Registers, 0x100);
+
+/// Bit fields of the `STATUS` register.
+#[repr(u32)]
+enum Status {
+    CmdInhibit = 1 << 0,
+    DatInhibit = 1 << 1,
+}
+
+/// Bit fields of the `CONTROL0` register.
+#[repr(u32)]
+enum Control0 {
+    DataWidth4 = 1 << 1,
+}
+
+/// Bit fields of the `CONTROL1` register.
+#[repr(u32)]
+enum Control1 {
+    ClockInternalEnable = 1 << 0,
+    ClockStable = 1 << 1,
+    ClockEnable = 1 << 2,
+    TimeoutMax = 0xE << 16,
+    ResetHost = 1 << 24,
+}
+
+/// Mask of the divider fields in `CONTROL1`.
+const CONTROL1_CLOCK_DIV_MASK: u32 = 0xFFC0;
+
+/// Bit fields of the `INTERRUPT` register.
+#[repr(u32)]
+enum Interrupt {
+    CmdDone = 1 << 0,
+    DataDone = 1 << 1,
+    WriteReady = 1 << 4,
+    ReadReady = 1 << 5,
+    CmdTimeout = 1 << 16,
+    DataTimeout = 1 << 20,
+}
+
+/// Interrupt bits that signal an error condition.
+const INTERRUPT_ERROR_MASK: u32 = 0x017F_8000;
+
+/// Bits of an R1 card status response that indicate an error: bits 31-26
+/// (`OUT_OF_RANGE` to `WP_VIOLATION`), 24-19 (`LOCK_UNLOCK_FAILED` to
+/// `ERROR`), 16 (`CSD_OVERWRITE`) and 3 (`AKE_SEQ_ERROR`). Bit 25,
+/// `CARD_IS_LOCKED`, and the state bits are not errors.
+const R1_ERROR_MASK: u32 = 0xFDF9_0008;
+
+/// `ACMD41` argument: high capacity supported, full voltage window.
+const ACMD41_ARG_HC: u32 = 0x51FF_8000;
+const ACMD41_VOLTAGE: u32 = 0x00FF_8000;
+const ACMD41_CMD_COMPLETE: u32 = 1 << 31;
+const ACMD41_CMD_CCS: u32 = 1 << 30;
+
+/// `CMD8` argument: 2.7-3.6V and the check pattern `0xAA`.
+const CMD8_ARG: u32 = 0x1AA;
+
+/// `SCR` flags.
+const SCR_SD_BUS_WIDTH_4: u32 = 1 << 10;
+const SCR_SUPP_SET_BLKCNT: u32 = 1 << 25;
+
+// Fields of the `CMDTM` register.
+const RESP_NONE: u32 = 0b00 << 16;
+const RESP_136: u32 = 0b01 << 16;
+const RESP_48: u32 = 0b10 << 16;
+const RESP_48_BUSY: u32 = 0b11 << 16;
+const IS_DATA: u32 = 1 << 21;
+const MULTI_BLOCK: u32 = 1 << 5;
+const CARD_TO_HOST: u32 = 1 << 4;
+const BLOCK_COUNT_ENABLE: u32 = 1 << 1;
+
+/// An SD command: the `CMDTM` value and whether it is an application-specific
+/// command that must be preceded by `CMD55`.
+#[derive(Copy, Clone, PartialEq)]
+struct Command {
+    cmdtm: u32,
+    app: bool,
+}
+
+impl Command {
+    const fn new(index: u32, flags: u32) -> Command {
+        Command { cmdtm: (index << 24) | flags, app: false }
+    }
+
+    const fn app(index: u32, flags: u32) -> Command {
+        Command { cmdtm: (index << 24) | flags, app: true }
+    }
+}
+
+const GO_IDLE: Command = Command::new(0, RESP_NONE);
+const ALL_SEND_CID: Command = Command::new(2, RESP_136);
+const SEND_RELATIVE_ADDR: Command = Command::new(3, RESP_48);
+const SELECT_CARD: Command = Command::new(7, RESP_48_BUSY);
+const SEND_IF_COND: Command = Command::new(8, RESP_48);
+const STOP_TRANSMISSION: Command = Command::new(12, RESP_48_BUSY);
+const READ_SINGLE_BLOCK: Command = Command::new(17, RESP_48 | IS_DATA | CARD_TO_HOST);
+const READ_MULTIPLE_BLOCK: Command =
+    Command::new(18, RESP_48 | IS_DATA | CARD_TO_HOST | MULTI_BLOCK | BLOCK_COUNT_ENABLE);
+const SET_BLOCK_COUNT: Command = Command::new(23, RESP_48);
+const WRITE_BLOCK: Command = Command::new(24, RESP_48 | IS_DATA);
+const WRITE_MULTIPLE_BLOCK: Command =
+    Command::new(25, RESP_48 | IS_DATA | MULTI_BLOCK | BLOCK_COUNT_ENABLE);
+const APP_CMD: Command = Command::new(55, RESP_NONE);
+const APP_CMD_RCA: Command = Command::new(55, RESP_48);
+const SET_BUS_WIDTH: Command = Command::app(6, RESP_48);
+const SD_SEND_OP_COND: Command = Command::app(41, RESP_48);
+const SEND_SCR: Command = Command::app(51, RESP_48 | IS_DATA | CARD_TO_HOST);
+
+/// Errors reported by the EMMC driver.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The controller or card did not respond in time.
+    Timeout,
+    /// The controller raised the error interrupts in `.0`.
+    Controller(u32),
+    /// The card reported the error status bits in `.0`.
+    CardStatus(u32),
+    /// The card did not echo the `CMD8` check pattern.
+    InterfaceCondition,
+    /// The card does not support the host's voltage window.
+    UnsupportedVoltage,
+    /// The controller's clock did not stabilize.
+    ClockUnstable,
+    /// The buffer length is zero or not a multiple of `BLOCK_SIZE`.
+    InvalidBuffer,
+    /// Another driver claimed some of the GPIO pins of the SD card.
+    PinsTaken,
+}
+
+impl From<Error> for io::Error {
+    fn from(error: Error) -> io::Error {
+        match error {
+            Error::Timeout => io::Error::new(io::ErrorKind::TimedOut, "EMMC timed out"),
+            Error::Controller(_) => io::Error::new(io::ErrorKind::Other, "EMMC controller error"),
+            Error::CardStatus(_) => io::Error::new(io::ErrorKind::Other, "SD card status error"),
+            Error::InterfaceCondition => {
+                io::Error::new(io::ErrorKind::InvalidData, "SD card failed interface condition check")
+            }
+            Error::UnsupportedVoltage => {
+                io::Error::new(io::ErrorKind::Other, "SD card does not support host voltage")
+            }
+            Error::ClockUnstable => io::Error::new(io::ErrorKind::TimedOut, "EMMC clock is unstable"),
+            Error::InvalidBuffer => {
+                io::Error::new(io::ErrorKind::InvalidInput, "buffer is not a multiple of the block size")
+            }
+            Error::PinsTaken => io::Error::new(io::ErrorKind::Other, "SD card pins claimed by another driver"),
+        }
+    }
+}
+
+/// Returns the card status `status` of an R1 response, or the error bits it
+/// has set.
+fn decode_r1(status: u32) -> Result<u32, Error> {
+    if status & R1_ERROR_MASK != 0 {
+        return Err(Error::CardStatus(status & R1_ERROR_MASK));
+    }
+
+    Ok(status)
+}
+
+/// Returns the relative card address of the R6 response `resp`, shifted into
+/// the top 16 bits as commands expect it, or the error bits of its card status.
+fn decode_r6(resp: u32) -> Result<u32, Error> {
+    // Bits 23, 22, 19 and 12:0 of the card status are packed into the low 16
+    // bits of the response.
+    let status = (resp & 0x1FFF) | ((resp & 0x2000) << 6) | ((resp & 0x4000) << 8)
+        | ((resp & 0x8000) << 8);
+    decode_r1(status)?;
+    Ok(resp & 0xFFFF_0000)
+}
+
+/// Returns the `CONTROL1` divider giving the fastest SD clock not exceeding
+/// `hz` on a controller of version `host_version`. The SD clock is the base
+/// clock divided by twice the divider, or the base clock when it is 0.
+fn clock_divider(hz: u32, host_version: u8) -> u32 {
+    let step = 2 * core::cmp::max(hz, 1) as u64;
+    let target = ((EMMC_BASE_CLOCK_HZ as u64 + step - 1) / step) as u32;
+    if host_version >= 2 {
+        // 10-bit divided clock mode.
+        core::cmp::min(target, 0x3FF)
+    } else {
+        // 8-bit power-of-two divider.
+        core::cmp::min(target.next_power_of_two(), 0x80)
+    }
+}
+
+/// Spins until `f` returns `true` or `timeout` elapses. Returns `true` if `f`
+/// returned `true`.
+fn wait_until<F: FnMut() -> bool>(timeout: Duration, mut f: F) -> bool {
+    let deadline = current_time() + timeout;
+    loop {
+        if f() {
+            return true;
+        }
+        if current_time() > deadline {
+            return false;
+        }
+    }
+}
+
+/// The BCM2837 EMMC controller driving an SD card.
+pub struct Emmc {
+    registers: &'static mut Registers,
+    host_version: u8,
+    rca: u32,
+    cid: [u32; 4],
+    scr: [u32; 2],
+    high_capacity: bool,
+}
+
+impl Emmc {
+    /// Resets the EMMC controller, routes GPIO 48-53 to it (alternative
+    /// function 3) and initializes and selects the inserted SD card.
+    ///
+    /// After initialization, the card runs at 25MHz in 4-bit mode if it
+    /// supports it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the controller does not reset, if no card responds
+    /// or if the card does not support the host's voltage or capacity modes.
+    /// Returns `Error::PinsTaken` if another driver claimed one of the pins.
+    pub fn new() -> Result<Emmc, Error> {
+        GpioController::new().claim(&PINS, OWNER).map_err(|_| Error::PinsTaken)?;
+        for &pin in PINS.iter() {
+            // The pins were claimed above.
+            unsafe { Gpio::new(pin).into_alt(Function::Alt3) };
+        }
+
+        let registers = unsafe { &mut *(EMMC_REG_BASE as *mut Registers) };
+        let host_version = ((registers.SLOTISR_VER.read() >> 16) & 0xFF) as u8;
+        let mut emmc = Emmc {
+            registers,
+            host_version,
+            rca: 0,
+            cid: [0; 4],
+            scr: [0; 2],
+            high_capacity: false,
+        };
+
+        emmc.reset()?;
+        emmc.identify_card()?;
+        Ok(emmc)
+    }
+
+    /// Resets the host controller and sets the identification clock.
+    fn reset(&mut self) -> Result<(), Error> {
+        self.registers.CONTROL0.write(0);
+        self.registers.CONTROL1.or_mask(Control1::ResetHost as u32);
+        let registers = &self.registers;
+        if !wait_until(Duration::from_secs(1), || {
+            !registers.CONTROL1.has_mask(Control1::ResetHost as u32)
+        }) {
+            return Err(Error::Timeout);
+        }
+
+        self.registers
+            .CONTROL1
+            .or_mask(Control1::ClockInternalEnable as u32 | Control1::TimeoutMax as u32);
+        spin_sleep(Duration::from_millis(10));
+
+        self.set_clock(IDENT_CLOCK_HZ)?;
+        self.registers.IRPT_EN.write(0xFFFF_FFFF);
+        self.registers.IRPT_MASK.write(0xFFFF_FFFF);
+        Ok(())
+    }
+
+    /// Runs the SD identification sequence: CMD0, CMD8, ACMD41, CMD2, CMD3,
+    /// and CMD7, then reads the SCR and switches to the transfer clock and
+    /// the widest supported bus.
+    fn identify_card(&mut self) -> Result<(), Error> {
+        self.rca = 0;
+        self.send_command(GO_IDLE, 0)?;
+
+        let resp = self.send_command(SEND_IF_COND, CMD8_ARG)?;
+        if resp[0] & 0xFFF != CMD8_ARG {
+            return Err(Error::InterfaceCondition);
+        }
+
+        let mut ocr = 0;
+        for _ in 0..8 {
+            spin_sleep(Duration::from_millis(100));
+            ocr = self.send_command(SD_SEND_OP_COND, ACMD41_ARG_HC)?[0];
+            if ocr & ACMD41_CMD_COMPLETE != 0 {
+                break;
+            }
+        }
+        if ocr & ACMD41_CMD_COMPLETE == 0 {
+            return Err(Error::Timeout);
+        }
+        if ocr & ACMD41_VOLTAGE == 0 {
+            return Err(Error::UnsupportedVoltage);
+        }
+        self.high_capacity = ocr & ACMD41_CMD_CCS != 0;
+
+        self.cid = self.send_command(ALL_SEND_CID, 0)?;
+
+        self.rca = decode_r6(self.send_command(SEND_RELATIVE_ADDR, 0)?[0])?;
+
+        self.set_clock(TRANSFER_CLOCK_HZ)?;
+        self.check_r1(SELECT_CARD, self.rca)?;
+        self.wait_status(Status::DatInhibit)?;
+
+        self.registers.BLKSIZECNT.write((1 << 16) | 8);
+        self.check_r1(SEND_SCR, 0)?;
+        self.wait_interrupt(Interrupt::ReadReady as u32)?;
+        for word in self.scr.iter_mut() {
+            *word = self.registers.DATA.read();
+        }
+        self.wait_interrupt(Interrupt::DataDone as u32)?;
+
+        if self.scr[0] & SCR_SD_BUS_WIDTH_4 != 0 {
+            self.check_r1(SET_BUS_WIDTH, 2)?;
+            self.registers.CONTROL0.or_mask(Control0::DataWidth4 as u32);
+        }
+
+        Ok(())
+    }
+
+    /// Sets the SD clock to the fastest frequency not exceeding `hz`. Returns
+    /// the resulting frequency.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Timeout` if the command or data lines stay busy and
+    /// `ClockUnstable` if the new clock does not stabilize.
+    pub fn set_clock(&mut self, hz: u32) -> Result<u32, Error> {
+        self.wait_status(Status::CmdInhibit)?;
+        self.wait_status(Status::DatInhibit)?;
+
+        self.registers.CONTROL1.and_mask(!(Control1::ClockEnable as u32));
+        spin_sleep(Duration::from_millis(10));
+
+        let divider = clock_divider(hz, self.host_version);
+        let fields = ((divider & 0xFF) << 8) | ((divider & 0x300) >> 2);
+        let control1 = self.registers.CONTROL1.read() & !CONTROL1_CLOCK_DIV_MASK;
+        self.registers.CONTROL1.write(control1 | fields);
+        spin_sleep(Duration::from_millis(10));
+
+        self.registers.CONTROL1.or_mask(Control1::ClockEnable as u32);
+        let registers = &self.registers;
+        if !wait_until(Duration::from_secs(1), || {
+            registers.CONTROL1.has_mask(Control1::ClockStable as u32)
+        }) {
+            return Err(Error::ClockUnstable);
+        }
+
+        Ok(match divider {
+            0 => EMMC_BASE_CLOCK_HZ,
+            d => EMMC_BASE_CLOCK_HZ / (2 * d),
+        })
+    }
+
+    /// Returns `true` if the card uses block (SDHC/SDXC) rather than byte
+    /// addressing.
+    pub fn is_high_capacity(&self) -> bool {
+        self.high_capacity
+    }
+
+    /// Returns the card's relative address.
+    pub fn rca(&self) -> u16 {
+        (self.rca >> 16) as u16
+    }
+
+    /// Returns the card's identification register, as read by `CMD2`.
+    pub fn cid(&self) -> [u32; 4] {
+        self.cid
+    }
+
+    /// Reads `buf.len() / BLOCK_SIZE` blocks starting at block `lba` into
+    /// `buf`. Returns the number of bytes read.
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidBuffer` if `buf` is empty or not a multiple of
+    /// `BLOCK_SIZE` long. Returns an error if a command fails or times out.
+    pub fn read_blocks(&mut self, lba: u32, buf: &mut [u8]) -> Result<usize, Error> {
+        let count = Emmc::block_count(buf.len())?;
+        self.start_transfer(lba, count, READ_SINGLE_BLOCK, READ_MULTIPLE_BLOCK)?;
+
+        for block in buf.chunks_exact_mut(BLOCK_SIZE) {
+            self.wait_interrupt(Interrupt::ReadReady as u32)?;
+            for word in block.chunks_exact_mut(4) {
+                word.copy_from_slice(&self.registers.DATA.read().to_le_bytes());
+            }
+        }
+
+        self.wait_interrupt(Interrupt::DataDone as u32)?;
+        self.finish_transfer(count)?;
+        Ok(buf.len())
+    }
+
+    /// Writes `buf.len() / BLOCK_SIZE` blocks from `buf` starting at block
+    /// `lba`. Returns the number of bytes written.
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidBuffer` if `buf` is empty or not a multiple of
+    /// `BLOCK_SIZE` long. Returns an error if a command fails or times out.
+    pub fn write_blocks(&mut self, lba: u32, buf: &[u8]) -> Result<usize, Error> {
+        let count = Emmc::block_count(buf.len())?;
+        self.start_transfer(lba, count, WRITE_BLOCK, WRITE_MULTIPLE_BLOCK)?;
+
+        for block in buf.chunks_exact(BLOCK_SIZE) {
+            self.wait_interrupt(Interrupt::WriteReady as u32)?;
+            for word in block.chunks_exact(4) {
+                let value = u32::from_le_bytes([word[0], word[1], word[2], word[3]]);
+                self.registers.DATA.write(value);
+            }
+        }
+
+        self.wait_interrupt(Interrupt::DataDone as u32)?;
+        self.finish_transfer(count)?;
+        Ok(buf.len())
+    }
+
+    /// Returns the number of blocks in a buffer of `len` bytes.
+    fn block_count(len: usize) -> Result<u32, Error> {
+        if len == 0 || len % BLOCK_SIZE != 0 || len / BLOCK_SIZE > 0xFFFF {
+            return Err(Error::InvalidBuffer);
+        }
+
+        Ok((len / BLOCK_SIZE) as u32)
+    }
+
+    /// Issues the command that starts a transfer of `count` blocks at `lba`.
+    fn start_transfer(&mut self, lba: u32, count: u32, single: Command, multi: Command) -> Result<(), Error> {
+        self.wait_status(Status::DatInhibit)?;
+
+        if count > 1 && self.scr[0] & SCR_SUPP_SET_BLKCNT != 0 {
+            self.check_r1(SET_BLOCK_COUNT, count)?;
+        }
+
+        let address = if self.high_capacity { lba } else { lba * BLOCK_SIZE as u32 };
+        self.registers.BLKSIZECNT.write((count << 16) | BLOCK_SIZE as u32);
+        self.check_r1(if count == 1 { single } else { multi }, address)?;
+        Ok(())
+    }
+
+    /// Stops an open-ended multi-block transfer if the card could not be told
+    /// the block count up front.
+    fn finish_transfer(&mut self, count: u32) -> Result<(), Error> {
+        if count > 1 && self.scr[0] & SCR_SUPP_SET_BLKCNT == 0 {
+            self.check_r1(STOP_TRANSMISSION, 0)?;
+        }
+
+        Ok(())
+    }
+
+    /// Sends `command` and checks its R1 response for card errors.
+    fn check_r1(&mut self, command: Command, arg: u32) -> Result<u32, Error> {
+        decode_r1(self.send_command(command, arg)?[0])
+    }
+
+    /// Sends `command` with argument `arg`, preceded by `CMD55` for
+    /// application-specific commands, and returns the response registers.
+    fn send_command(&mut self, command: Command, arg: u32) -> Result<[u32; 4], Error> {
+        if command.app {
+            if self.rca == 0 {
+                self.send_command(APP_CMD, 0)?;
+            } else {
+                self.check_r1(APP_CMD_RCA, self.rca)?;
+            }
+        }
+
+        self.wait_status(Status::CmdInhibit)?;
+
+        let pending = self.registers.INTERRUPT.read();
+        self.registers.INTERRUPT.write(pending);
+        self.registers.ARG1.write(arg);
+        self.registers.CMDTM.write(command.cmdtm);
+        self.wait_interrupt(Interrupt::CmdDone as u32)?;
+
+        Ok([
+            self.registers.RESP[0].read(),
+            self.registers.RESP[1].read(),
+            self.registers.RESP[2].read(),
+            self.registers.RESP[3].read(),
+        ])
+    }
+
+    /// Waits until `status` is clear in the `STATUS` register.
+    fn wait_status(&self, status: Status) -> Result<(), Error> {
+        let mask = status as u32;
+        let registers = &self.registers;
+        if !wait_until(Duration::from_secs(1), || registers.STATUS.read() & mask == 0) {
+            return Err(Error::Timeout);
+        }
+
+        Ok(())
+    }
+
+    /// Waits for any of the interrupts in `mask` or an error interrupt.
+    /// Acknowledges `mask` on success and every pending interrupt on failure.
+    fn wait_interrupt(&mut self, mask: u32) -> Result<(), Error> {
+        let registers = &self.registers;
+        let signalled = wait_until(Duration::from_secs(1), || {
+            registers.INTERRUPT.read() & (mask | INTERRUPT_ERROR_MASK) != 0
+        });
+
+        let pending = self.registers.INTERRUPT.read();
+        let timeout = Interrupt::CmdTimeout as u32 | Interrupt::DataTimeout as u32;
+        if !signalled || pending & timeout != 0 {
+            self.registers.INTERRUPT.write(pending);
+            return Err(Error::Timeout);
+        } else if pending & INTERRUPT_ERROR_MASK != 0 {
+            self.registers.INTERRUPT.write(pending);
+            return Err(Error::Controller(pending & INTERRUPT_ERROR_MASK));
+        }
+
+        self.registers.INTERRUPT.write(mask);
+        Ok(())
+    }
+}
+
+impl Drop for Emmc {
+    fn drop(&mut self) {
+        GpioController::new().release(&PINS, OWNER);
+    }
+}
@@ -0,0 +1,56 @@
+use super::*;
+
+#[test]
+fn encodes_commands() {
+    assert_eq!(GO_IDLE.cmdtm, 0x0000_0000);
+    assert_eq!(SEND_IF_COND.cmdtm, 0x0802_0000);
+    assert_eq!(SELECT_CARD.cmdtm, 0x0703_0000);
+    assert_eq!(ALL_SEND_CID.cmdtm, 0x0201_0000);
+    assert_eq!(READ_SINGLE_BLOCK.cmdtm, 0x1122_0010);
+    assert_eq!(READ_MULTIPLE_BLOCK.cmdtm, 0x1222_0032);
+    assert_eq!(WRITE_BLOCK.cmdtm, 0x1822_0000);
+    assert_eq!(WRITE_MULTIPLE_BLOCK.cmdtm, 0x1922_0022);
+    assert_eq!(SEND_SCR.cmdtm, 0x3322_0010);
+
+    assert!([APP_CMD, SEND_IF_COND, READ_SINGLE_BLOCK].iter().all(|command| !command.app));
+    assert!([SET_BUS_WIDTH, SD_SEND_OP_COND, SEND_SCR].iter().all(|command| command.app));
+}
+
+#[test]
+fn decodes_r1_responses() {
+    // `READY_FOR_DATA` in the `tran` state.
+    assert_eq!(decode_r1(0x0000_0900), Ok(0x0000_0900));
+    // `CARD_IS_LOCKED` and `APP_CMD` are not errors.
+    assert_eq!(decode_r1(0x0200_0020), Ok(0x0200_0020));
+
+    assert_eq!(decode_r1(0x8000_0900), Err(Error::CardStatus(0x8000_0000)));
+    assert_eq!(decode_r1(0x0040_0000), Err(Error::CardStatus(0x0040_0000)));
+    assert_eq!(decode_r1(0x0001_0000), Err(Error::CardStatus(0x0001_0000)));
+    assert_eq!(decode_r1(0x0000_0008), Err(Error::CardStatus(0x0000_0008)));
+    assert_eq!(decode_r1(0xFFFF_FFFF), Err(Error::CardStatus(R1_ERROR_MASK)));
+}
+
+#[test]
+fn decodes_r6_responses() {
+    assert_eq!(decode_r6(0xB368_0500), Ok(0xB368_0000));
+
+    // Bits 15, 14 and 13 hold the status bits 23, 22 and 19.
+    assert_eq!(decode_r6(0xB368_8500), Err(Error::CardStatus(1 << 23)));
+    assert_eq!(decode_r6(0xB368_4500), Err(Error::CardStatus(1 << 22)));
+    assert_eq!(decode_r6(0xB368_2500), Err(Error::CardStatus(1 << 19)));
+    assert_eq!(decode_r6(0xB368_0508), Err(Error::CardStatus(1 << 3)));
+}
+
+#[test]
+fn picks_clock_dividers() {
+    // 41.67MHz / (2 * 53) = 393kHz, the fastest clock at most 400kHz.
+    assert_eq!(clock_divider(IDENT_CLOCK_HZ, 2), 53);
+    assert_eq!(clock_divider(TRANSFER_CLOCK_HZ, 2), 1);
+    assert_eq!(clock_divider(EMMC_BASE_CLOCK_HZ, 2), 1);
+    assert_eq!(clock_divider(1, 2), 0x3FF);
+    assert_eq!(clock_divider(0, 2), 0x3FF);
+
+    // Older controllers only divide by powers of two.
+    assert_eq!(clock_divider(IDENT_CLOCK_HZ, 1), 64);
+    assert_eq!(clock_divider(1, 1), 0x80);
+}
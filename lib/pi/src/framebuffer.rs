@@ -0,0 +1,224 @@
+//! A 32-bit framebuffer allocated by the firmware and shown on the HDMI
+//! output.
+
+use core::ptr;
+
+use shim::io;
+
+use crate::mailbox::{self, tag, Mailbox, Message};
+
+/// The alignment of the framebuffer asked of the firmware.
+const ALIGNMENT: u32 = 4096;
+
+/// Mask converting a VideoCore bus address into an ARM physical address.
+const BUS_ADDRESS_MASK: u32 = 0x3FFF_FFFF;
+
+/// A color, as `0x00RRGGBB`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color(pub u32);
+
+impl Color {
+    pub const BLACK: Color = Color::rgb(0, 0, 0);
+    pub const WHITE: Color = Color::rgb(255, 255, 255);
+
+    /// Returns the color with the red, green and blue components `r`, `g`
+    /// and `b`.
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Color {
+        Color((r as u32) << 16 | (g as u32) << 8 | b as u32)
+    }
+}
+
+/// The order of the color components of a pixel in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelOrder {
+    /// Pixels are `0x00RRGGBB`, like `Color`.
+    Bgr = 0,
+    /// Pixels are `0x00BBGGRR`.
+    Rgb = 1,
+}
+
+/// Errors reported when allocating a framebuffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The mailbox reported an error.
+    Mailbox(mailbox::Error),
+    /// The firmware didn't allocate a 32-bit framebuffer.
+    Unavailable,
+}
+
+impl From<mailbox::Error> for Error {
+    fn from(error: mailbox::Error) -> Error {
+        Error::Mailbox(error)
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(error: Error) -> io::Error {
+        match error {
+            Error::Mailbox(error) => error.into(),
+            Error::Unavailable => io::Error::new(io::ErrorKind::Other, "no framebuffer available"),
+        }
+    }
+}
+
+/// A framebuffer of 32-bit pixels. Drawing outside of it is clipped.
+pub struct Framebuffer {
+    base: *mut u32,
+    width: u32,
+    height: u32,
+    /// The distance between rows, in pixels.
+    stride: u32,
+    order: PixelOrder,
+}
+
+unsafe impl Send for Framebuffer {}
+
+impl Framebuffer {
+    /// Asks the firmware for a `width` by `height` framebuffer of 32-bit
+    /// pixels and returns it.
+    pub fn new(width: u32, height: u32) -> Result<Framebuffer, Error> {
+        let mut message = Message::<36>::new();
+        message.push(tag::SET_PHYSICAL_SIZE, &[width, height], 2)?;
+        message.push(tag::SET_VIRTUAL_SIZE, &[width, height], 2)?;
+        message.push(tag::SET_VIRTUAL_OFFSET, &[0, 0], 2)?;
+        let depth = message.push(tag::SET_DEPTH, &[32], 1)?;
+        let order = message.push(tag::SET_PIXEL_ORDER, &[PixelOrder::Bgr as u32], 1)?;
+        let buffer = message.push(tag::ALLOCATE_BUFFER, &[ALIGNMENT, 0], 2)?;
+        let pitch = message.push(tag::GET_PITCH, &[], 1)?;
+        Mailbox::new().send(&mut message)?;
+
+        let (base, size) = match *message.response(buffer)? {
+            [base, size] => (base & BUS_ADDRESS_MASK, size),
+            _ => return Err(Error::Unavailable),
+        };
+        let pitch = message.response(pitch)?.first().copied().unwrap_or(0);
+        if message.response(depth)? != [32] || base == 0 || pitch < width * 4 || size < pitch * height {
+            return Err(Error::Unavailable);
+        }
+
+        let order = match message.response(order)? {
+            [1] => PixelOrder::Rgb,
+            _ => PixelOrder::Bgr,
+        };
+        Ok(unsafe { Framebuffer::from_raw_parts(base as usize as *mut u32, width, height, pitch / 4, order) })
+    }
+
+    /// Returns a framebuffer over the memory at `base`, whose rows of
+    /// `width` pixels start every `stride` pixels.
+    ///
+    /// # Safety
+    ///
+    /// `base` must point to `height` rows of `stride` pixels that nothing
+    /// else uses for as long as the framebuffer exists.
+    pub unsafe fn from_raw_parts(base: *mut u32, width: u32, height: u32, stride: u32, order: PixelOrder) -> Framebuffer {
+        Framebuffer { base, width, height, stride, order }
+    }
+
+    /// Returns the width in pixels.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Returns the height in pixels.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Converts `color` to a pixel value.
+    fn encode(&self, color: Color) -> u32 {
+        match self.order {
+            PixelOrder::Bgr => color.0,
+            PixelOrder::Rgb => ((color.0 >> 16) & 0xFF) | (color.0 & 0xFF00) | ((color.0 & 0xFF) << 16),
+        }
+    }
+
+    /// Returns a pointer to the pixel at (`x`, `y`), which must be in the
+    /// framebuffer.
+    fn at(&self, x: u32, y: u32) -> *mut u32 {
+        unsafe { self.base.add((y * self.stride + x) as usize) }
+    }
+
+    /// Clips the rectangle at (`x`, `y`) of `width` by `height` pixels to
+    /// the framebuffer, returning its new size.
+    fn clip(&self, x: u32, y: u32, width: u32, height: u32) -> (u32, u32) {
+        let width = core::cmp::min(width, self.width.saturating_sub(x));
+        let height = core::cmp::min(height, self.height.saturating_sub(y));
+        (width, height)
+    }
+
+    /// Sets the pixel at (`x`, `y`) to `color`.
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: Color) {
+        if x < self.width && y < self.height {
+            let pixel = self.encode(color);
+            unsafe { ptr::write_volatile(self.at(x, y), pixel) };
+        }
+    }
+
+    /// Returns the color of the pixel at (`x`, `y`).
+    pub fn pixel(&self, x: u32, y: u32) -> Option<Color> {
+        if x < self.width && y < self.height {
+            // Swapping the components back and forth is the same operation.
+            let pixel = unsafe { ptr::read_volatile(self.at(x, y)) };
+            Some(Color(self.encode(Color(pixel))))
+        } else {
+            None
+        }
+    }
+
+    /// Fills the rectangle at (`x`, `y`) of `width` by `height` pixels with
+    /// `color`.
+    pub fn fill_rect(&mut self, x: u32, y: u32, width: u32, height: u32, color: Color) {
+        let (width, height) = self.clip(x, y, width, height);
+        let pixel = self.encode(color);
+        for row in y..y + height {
+            let start = self.at(x, row);
+            for i in 0..width as usize {
+                unsafe { ptr::write_volatile(start.add(i), pixel) };
+            }
+        }
+    }
+
+    /// Fills the whole framebuffer with `color`.
+    pub fn clear(&mut self, color: Color) {
+        self.fill_rect(0, 0, self.width, self.height, color);
+    }
+
+    /// Draws the `width` by `height` image `pixels`, stored row by row, at
+    /// (`x`, `y`).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pixels` holds fewer than `width * height` colors.
+    pub fn blit(&mut self, x: u32, y: u32, width: u32, height: u32, pixels: &[Color]) {
+        assert!(pixels.len() >= (width * height) as usize, "blit: image is too small");
+
+        let (clipped_width, clipped_height) = self.clip(x, y, width, height);
+        for row in 0..clipped_height {
+            let source = &pixels[(row * width) as usize..][..clipped_width as usize];
+            let start = self.at(x, y + row);
+            for (i, &color) in source.iter().enumerate() {
+                let pixel = self.encode(color);
+                unsafe { ptr::write_volatile(start.add(i), pixel) };
+            }
+        }
+    }
+
+    /// Copies the rectangle at (`x`, `y`) of `width` by `height` pixels to
+    /// (`to_x`, `to_y`). The rectangles may overlap, which makes this
+    /// suitable for scrolling.
+    pub fn copy_rect(&mut self, x: u32, y: u32, width: u32, height: u32, to_x: u32, to_y: u32) {
+        let (width, height) = self.clip(x, y, width, height);
+        let (width, height) = self.clip(to_x, to_y, width, height);
+
+        let copy_row = |row: u32| unsafe {
+            ptr::copy(self.at(x, y + row), self.at(to_x, to_y + row), width as usize);
+        };
+        // Copy the rows in the order that doesn't overwrite rows not copied
+        // yet.
+        if to_y <= y {
+            (0..height).for_each(copy_row);
+        } else {
+            (0..height).rev().for_each(copy_row);
+        }
+    }
+}
@@ -1,11 +1,20 @@
+use core::arch::asm;
+use core::cell::UnsafeCell;
+use core::convert::TryFrom;
 use core::marker::PhantomData;
 
+use shim::io;
+
 use crate::common::{IO_BASE, states};
 use volatile::prelude::*;
 use volatile::{Volatile, WriteVolatile, ReadVolatile, Reserved};
 
+/// The number of GPIO pins.
+pub const PIN_COUNT: u8 = 54;
+
 /// An alternative GPIO function.
 #[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Function {
     Input = 0b000,
     Output = 0b001,
@@ -17,6 +26,83 @@ pub enum Function {
     Alt5 = 0b010
 }
 
+impl Function {
+    /// Returns the function selected by the three `FSEL` bits `bits`.
+    fn from_bits(bits: u32) -> Function {
+        match bits & 0b111 {
+            0b000 => Function::Input,
+            0b001 => Function::Output,
+            0b100 => Function::Alt0,
+            0b101 => Function::Alt1,
+            0b110 => Function::Alt2,
+            0b111 => Function::Alt3,
+            0b011 => Function::Alt4,
+            _ => Function::Alt5,
+        }
+    }
+}
+
+/// The setting of a pin's pull-up/down resistor.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pull {
+    /// No resistor: the pin floats unless something drives it.
+    Off = 0b00,
+    /// Pull the pin down to ground.
+    Down = 0b01,
+    /// Pull the pin up to 3.3V.
+    Up = 0b10
+}
+
+/// An event that a GPIO input can detect. A detected event sets the pin's
+/// bit in the event status register and raises a GPIO interrupt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// A low-to-high transition, sampled with the system clock.
+    RisingEdge,
+    /// A high-to-low transition, sampled with the system clock.
+    FallingEdge,
+    /// A low-to-high transition, not synchronized to the system clock so
+    /// that very short pulses are caught.
+    AsyncRisingEdge,
+    /// A high-to-low transition, not synchronized to the system clock.
+    AsyncFallingEdge,
+    /// A high level. Detected again as soon as the status is cleared while
+    /// the level stays high.
+    High,
+    /// A low level. Detected again as soon as the status is cleared while
+    /// the level stays low.
+    Low
+}
+
+/// Error type for `DynPin` operations that the pin's current function
+/// doesn't allow and for failed pin claims.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The operation needs the pin to have function `expected`, but it has
+    /// function `actual`.
+    WrongFunction { expected: Function, actual: Function },
+    /// The pin `pin` is already claimed by `owner`.
+    Taken { pin: u8, owner: &'static str },
+    /// There is no pin with number `.0`.
+    NoSuchPin(u8),
+}
+
+impl From<Error> for io::Error {
+    fn from(error: Error) -> io::Error {
+        match error {
+            Error::WrongFunction { expected: Function::Output, .. } => {
+                io::Error::new(io::ErrorKind::InvalidInput, "pin is not an output")
+            }
+            Error::WrongFunction { .. } => {
+                io::Error::new(io::ErrorKind::InvalidInput, "pin has the wrong function")
+            }
+            Error::Taken { .. } => io::Error::new(io::ErrorKind::Other, "pin is claimed by another driver"),
+            Error::NoSuchPin(_) => io::Error::new(io::ErrorKind::InvalidInput, "no such pin"),
+        }
+    }
+}
+
 #[repr(C)]
 #[allow(non_snake_case)]
 struct Registers {
@@ -46,6 +132,125 @@ struct Registers {
     PUDCLK: [Volatile<u32>; 2],
 }
 
+/// Returns the index of the register holding `pin` in a pair of registers
+/// with one bit per pin, and the mask of that bit.
+fn bank(pin: u8) -> (usize, u32) {
+    ((pin / 32) as usize, 1 << (pin % 32))
+}
+
+/// Spins for at least `cycles` CPU cycles.
+fn wait_cycles(cycles: usize) {
+    for _ in 0..cycles {
+        unsafe { asm!("nop") }
+    }
+}
+
+impl Registers {
+    /// Returns the function currently selected for `pin`.
+    fn function(&self, pin: u8) -> Function {
+        let offset = (pin % 10) * 3;
+        Function::from_bits(self.FSEL[(pin / 10) as usize].read() >> offset)
+    }
+
+    /// Selects `function` for `pin`.
+    fn set_function(&mut self, pin: u8, function: Function) {
+        let offset = (pin % 10) * 3;
+        let fsel = &mut self.FSEL[(pin / 10) as usize];
+        let value = (fsel.read() & !(0b111 << offset)) | ((function as u32) << offset);
+        fsel.write(value);
+    }
+
+    /// Drives `pin` high if it is an output.
+    fn set(&mut self, pin: u8) {
+        let (index, mask) = bank(pin);
+        self.SET[index].write(mask);
+    }
+
+    /// Drives `pin` low if it is an output.
+    fn clear(&mut self, pin: u8) {
+        let (index, mask) = bank(pin);
+        self.CLR[index].write(mask);
+    }
+
+    /// Returns the level of `pin`, whatever its function.
+    fn level(&self, pin: u8) -> bool {
+        let (index, mask) = bank(pin);
+        self.LEV[index].has_mask(mask)
+    }
+
+    /// Returns the pair of detect enable registers for `event`.
+    fn event_enable(&mut self, event: Event) -> &mut [Volatile<u32>; 2] {
+        match event {
+            Event::RisingEdge => &mut self.REN,
+            Event::FallingEdge => &mut self.FEN,
+            Event::AsyncRisingEdge => &mut self.AREN,
+            Event::AsyncFallingEdge => &mut self.AFEN,
+            Event::High => &mut self.HEN,
+            Event::Low => &mut self.LEN,
+        }
+    }
+
+    /// Enables or disables the detection of `event` on `pin`.
+    fn set_event_enabled(&mut self, pin: u8, event: Event, enabled: bool) {
+        let (index, mask) = bank(pin);
+        let register = &mut self.event_enable(event)[index];
+        if enabled {
+            register.or_mask(mask);
+        } else {
+            register.and_mask(!mask);
+        }
+    }
+
+    /// Sets the pull-up/down resistor of `pin` to `pull` with the sequence
+    /// from the BCM2837 manual: write the setting to `PUD`, wait 150
+    /// cycles, clock it into the pin with `PUDCLK`, wait 150 cycles, then
+    /// remove the setting and the clock.
+    fn set_pull(&mut self, pin: u8, pull: Pull) {
+        let (index, mask) = bank(pin);
+        self.PUD.write(pull as u32);
+        wait_cycles(150);
+        self.PUDCLK[index].write(mask);
+        wait_cycles(150);
+        self.PUD.write(0);
+        self.PUDCLK[index].write(0);
+    }
+}
+
+/// Returns a mask with bit `n` set if pin `n` has detected an event since
+/// its status was last cleared.
+pub fn pending_events() -> u64 {
+    let registers = unsafe { &*(GPIO_BASE as *const Registers) };
+    (registers.EDS[1].read() as u64) << 32 | registers.EDS[0].read() as u64
+}
+
+/// Clears the event status of the pins whose bits are set in `mask`.
+pub fn clear_events(mask: u64) {
+    let registers = unsafe { &mut *(GPIO_BASE as *mut Registers) };
+    registers.EDS[0].write(mask as u32);
+    registers.EDS[1].write((mask >> 32) as u32);
+}
+
+/// Returns a mask with bit `n` set if pin `n` is high, whatever its
+/// function.
+pub fn levels() -> u64 {
+    let registers = unsafe { &*(GPIO_BASE as *const Registers) };
+    (registers.LEV[1].read() as u64) << 32 | registers.LEV[0].read() as u64
+}
+
+/// Returns the function currently selected for `pin`.
+///
+/// # Errors
+///
+/// Returns `Error::NoSuchPin` if `pin` > `53`.
+pub fn function(pin: u8) -> Result<Function, Error> {
+    if pin >= PIN_COUNT {
+        return Err(Error::NoSuchPin(pin));
+    }
+
+    let registers = unsafe { &*(GPIO_BASE as *const Registers) };
+    Ok(registers.function(pin))
+}
+
 /// Possible states for a GPIO pin.
 #[allow(unused_doc_comments)]
 states! {
@@ -59,9 +264,13 @@ states! {
 /// structure starts in the `Uninitialized` state and must be transitions into
 /// one of `Input`, `Output`, or `Alt` via the `into_input`, `into_output`, and
 /// `into_alt` methods before it can be used.
+///
+/// A pin taken from the `GpioController` releases its claim when dropped,
+/// whatever state it was transitioned to.
 pub struct Gpio<State> {
     pin: u8,
     registers: &'static mut Registers,
+    owner: Option<&'static str>,
     _state: PhantomData<State>
 }
 
@@ -73,29 +282,77 @@ impl<T> Gpio<T> {
     /// `Gpio` instance in state `S`. This method should _never_ be exposed to
     /// the public!
     #[inline(always)]
-    fn transition<S>(self) -> Gpio<S> {
+    fn transition<S>(mut self) -> Gpio<S> {
+        // The claim moves to the new pin, so dropping `self` keeps it.
         Gpio {
             pin: self.pin,
-            registers: self.registers,
+            registers: unsafe { &mut *(GPIO_BASE as *mut Registers) },
+            owner: self.owner.take(),
             _state: PhantomData
         }
     }
 }
 
+impl<T> Drop for Gpio<T> {
+    fn drop(&mut self) {
+        if let Some(owner) = self.owner {
+            GpioController::new().release(&[self.pin], owner);
+        }
+    }
+}
+
+impl<T> From<Gpio<T>> for DynPin {
+    fn from(mut gpio: Gpio<T>) -> DynPin {
+        DynPin {
+            pin: gpio.pin,
+            registers: unsafe { &mut *(GPIO_BASE as *mut Registers) },
+            owner: gpio.owner.take()
+        }
+    }
+}
+
+impl TryFrom<DynPin> for Gpio<Input> {
+    type Error = (DynPin, Error);
+
+    /// Converts `pin` back to a typed pin if it is an input. Otherwise, `pin`
+    /// is returned with the error, still holding its claim.
+    fn try_from(pin: DynPin) -> Result<Gpio<Input>, (DynPin, Error)> {
+        pin.into_typed(Function::Input)
+    }
+}
+
+impl TryFrom<DynPin> for Gpio<Output> {
+    type Error = (DynPin, Error);
+
+    /// Converts `pin` back to a typed pin if it is an output. Otherwise,
+    /// `pin` is returned with the error, still holding its claim.
+    fn try_from(pin: DynPin) -> Result<Gpio<Output>, (DynPin, Error)> {
+        pin.into_typed(Function::Output)
+    }
+}
+
 impl Gpio<Uninitialized> {
-    /// Returns a new `GPIO` structure for pin number `pin`.
+    /// Returns a new `GPIO` structure for pin number `pin`. Use
+    /// `GpioController::take` to claim the pin at the same time.
+    ///
+    /// # Safety
+    ///
+    /// The caller must have claimed `pin` in the `GpioController`, or know
+    /// that no other code uses it, since the pin is reconfigured without
+    /// checking claims.
     ///
     /// # Panics
     ///
     /// Panics if `pin` > `53`.
-    pub fn new(pin: u8) -> Gpio<Uninitialized> {
+    pub unsafe fn new(pin: u8) -> Gpio<Uninitialized> {
         if pin > 53 {
             panic!("Gpio::new(): pin {} exceeds maximum of 53", pin);
         }
 
         Gpio {
-            registers: unsafe { &mut *(GPIO_BASE as *mut Registers) },
+            registers: &mut *(GPIO_BASE as *mut Registers),
             pin: pin,
+            owner: None,
             _state: PhantomData
         }
     }
@@ -103,27 +360,8 @@ impl Gpio<Uninitialized> {
     /// Enables the alternative function `function` for `self`. Consumes self
     /// and returns a `Gpio` structure in the `Alt` state.
     pub fn into_alt(self, function: Function) -> Gpio<Alt> {
-        let gpio = Gpio { 
-            pin: self.pin,
-            registers: self.registers,
-            _state: PhantomData {} as PhantomData<Alt>
-        };
-
-        let fsel_reg_index = self.pin / 10;
-        let fsel_reg_bits_offset = self.pin % 10;
-        let bits = match function {
-            Function::Alt0 => 0b100,
-            Function::Alt1 => 0b101,
-            Function::Alt2 => 0b110,
-            Function::Alt3 => 0b111,
-            Function::Alt4 => 0b011,
-            Function::Alt5 => 0b010,
-            Function::Input => 0b000,
-            Function::Output => 0b001,
-        };
-        let value = bits << fsel_reg_bits_offset;
-        gpio.registers.FSEL[fsel_reg_index as usize].write(value);
-
+        let gpio: Gpio<Alt> = self.transition();
+        gpio.registers.set_function(gpio.pin, function);
         gpio
     }
 
@@ -138,23 +376,26 @@ impl Gpio<Uninitialized> {
     pub fn into_input(self) -> Gpio<Input> {
         self.into_alt(Function::Input).transition()
     }
+
+    /// Sets this pin to be an _input_ pin with its pull-up/down resistor set
+    /// to `pull`. The resistor is set before the pin becomes an input, so it
+    /// never floats. Consumes self and returns a `Gpio` structure in the
+    /// `Input` state.
+    pub fn into_pulled_input(self, pull: Pull) -> Gpio<Input> {
+        self.registers.set_pull(self.pin, pull);
+        self.into_input()
+    }
 }
 
 impl Gpio<Output> {
     /// Sets (turns on) the pin.
     pub fn set(&mut self) {
-        // let reg_index = if self.pin < 32 { 0 } else { 1 };
-        let reg_index = self.pin & 0b100000;
-        let reg_value = 0x1 << (self.pin - 1);
-        self.registers.SET[reg_index as usize].write(reg_value);
+        self.registers.set(self.pin);
     }
 
     /// Clears (turns off) the pin.
     pub fn clear(&mut self) {
-        // let reg_index = if self.pin < 32 { 0 } else { 1 };
-        let reg_index = self.pin & 0b100000;
-        let reg_value = 0x1 << (self.pin - 1);
-        self.registers.CLR[reg_index as usize].write(reg_value);
+        self.registers.clear(self.pin);
     }
 }
 
@@ -162,9 +403,456 @@ impl Gpio<Input> {
     /// Reads the pin's value. Returns `true` if the level is high and `false`
     /// if the level is low.
     pub fn level(&mut self) -> bool {
-        // let reg_index = if self.pin < 32 { 0 } else { 1 };
-        let reg_index = self.pin & 0b100000;
-        let reg_mask = 0x1 << (self.pin | 0b11111);
-        self.registers.LEV[reg_index as usize].read() & reg_mask == 1
+        self.registers.level(self.pin)
+    }
+
+    /// Enables the detection of `event`. Several events can be enabled at
+    /// once; any of them sets the pin's event status.
+    pub fn enable_event(&mut self, event: Event) {
+        self.registers.set_event_enabled(self.pin, event, true);
+    }
+
+    /// Disables the detection of `event`. An event already detected stays
+    /// pending until it is cleared.
+    pub fn disable_event(&mut self, event: Event) {
+        self.registers.set_event_enabled(self.pin, event, false);
+    }
+
+    /// Returns `true` if an enabled event has been detected since the status
+    /// was last cleared.
+    pub fn has_event(&self) -> bool {
+        let (index, mask) = bank(self.pin);
+        self.registers.EDS[index].has_mask(mask)
+    }
+
+    /// Clears the pin's event status.
+    pub fn clear_event(&mut self) {
+        let (index, mask) = bank(self.pin);
+        self.registers.EDS[index].write(mask);
+    }
+
+    /// Sets the pin's pull-up/down resistor to `pull`. The setting is kept
+    /// while the board is powered, even if the pin changes function.
+    pub fn set_pull(&mut self, pull: Pull) {
+        self.registers.set_pull(self.pin, pull);
+    }
+
+    /// Sets the pin's pull-up/down resistor to `pull` and returns the pin,
+    /// for use right after `into_input`:
+    ///
+    /// ```rust,no_run
+    /// # use pi::gpio::{GpioController, Pull};
+    /// let mut button = GpioController::new().take(5, "button")?.into_input().with_pull(Pull::Up);
+    /// let pressed = !button.level();
+    /// # Ok::<(), pi::gpio::Error>(())
+    /// ```
+    pub fn with_pull(mut self, pull: Pull) -> Gpio<Input> {
+        self.set_pull(pull);
+        self
+    }
+}
+
+/// A mask with a bit set for every GPIO pin.
+pub const ALL_PINS: u64 = (1 << PIN_COUNT) - 1;
+
+/// A set of GPIO pins driven together, for parallel buses and other uses
+/// where pins must change at the same time.
+///
+/// Pins are given as masks with bit `n` set for pin `n`. Each operation
+/// touches every register at most once: levels change with one write to
+/// each `SET` and `CLR` register, so pins in the same register switch at
+/// the same instant. Bits outside the bank's own pins are ignored. A bank
+/// taken from the `GpioController` releases its claims when dropped.
+pub struct GpioBank {
+    pins: u64,
+    registers: &'static mut Registers,
+    owner: Option<&'static str>
+}
+
+impl GpioBank {
+    /// Returns a bank of the pins in `pins`. Bits for pins above 53 are
+    /// ignored. Use `GpioController::take_bank` to claim the pins at the
+    /// same time.
+    ///
+    /// # Safety
+    ///
+    /// The caller must have claimed the pins in the `GpioController`, or
+    /// know that no other code uses them, since they are reconfigured
+    /// without checking claims.
+    pub unsafe fn new(pins: u64) -> GpioBank {
+        GpioBank {
+            pins: pins & ALL_PINS,
+            registers: &mut *(GPIO_BASE as *mut Registers),
+            owner: None
+        }
+    }
+
+    /// Returns the mask of the pins in this bank.
+    pub fn pins(&self) -> u64 {
+        self.pins
+    }
+
+    /// Writes the low and high halves of `mask`, restricted to this bank, to
+    /// the registers `pair`, skipping halves without pins.
+    fn write_pair(pins: u64, pair: &mut [WriteVolatile<u32>; 2], mask: u64) {
+        let mask = mask & pins;
+        if mask as u32 != 0 {
+            pair[0].write(mask as u32);
+        }
+        if (mask >> 32) as u32 != 0 {
+            pair[1].write((mask >> 32) as u32);
+        }
+    }
+
+    /// Drives the pins in `mask` high. Only output pins change.
+    pub fn set(&mut self, mask: u64) {
+        GpioBank::write_pair(self.pins, &mut self.registers.SET, mask);
+    }
+
+    /// Drives the pins in `mask` low. Only output pins change.
+    pub fn clear(&mut self, mask: u64) {
+        GpioBank::write_pair(self.pins, &mut self.registers.CLR, mask);
+    }
+
+    /// Drives each pin in `mask` to its level in `levels`: high if its bit
+    /// is set and low otherwise. Pins going high change before pins going
+    /// low.
+    pub fn write(&mut self, mask: u64, levels: u64) {
+        self.set(mask & levels);
+        self.clear(mask & !levels);
+    }
+
+    /// Returns the levels of the bank's pins, with bits set for pins that
+    /// are high. Bits for other pins are zero.
+    pub fn levels(&self) -> u64 {
+        let levels = (self.registers.LEV[1].read() as u64) << 32 | self.registers.LEV[0].read() as u64;
+        levels & self.pins
+    }
+
+    /// Selects `function` for the pins in `mask`, with one read-modify-write
+    /// of each `FSEL` register holding some of them.
+    pub fn set_function(&mut self, mask: u64, function: Function) {
+        let mask = mask & self.pins;
+        for (index, fsel) in self.registers.FSEL.iter_mut().enumerate() {
+            let (mut clear, mut bits) = (0u32, 0u32);
+            for slot in 0..10 {
+                let pin = index * 10 + slot;
+                if pin < PIN_COUNT as usize && mask & (1 << pin) != 0 {
+                    clear |= 0b111 << (slot * 3);
+                    bits |= (function as u32) << (slot * 3);
+                }
+            }
+
+            if clear != 0 {
+                fsel.write((fsel.read() & !clear) | bits);
+            }
+        }
+    }
+}
+
+/// A GPIO pin whose function is chosen and checked at runtime.
+///
+/// Unlike `Gpio<State>`, a `DynPin` can change its function any number of
+/// times, for code that only learns what a pin is used for while running,
+/// like the kernel shell. Operations that only make sense for some
+/// functions return an `Error` when the pin has another one. A pin taken
+/// from the `GpioController` releases its claim when dropped.
+pub struct DynPin {
+    pin: u8,
+    registers: &'static mut Registers,
+    owner: Option<&'static str>
+}
+
+impl DynPin {
+    /// Returns a `DynPin` for pin number `pin`. The pin's function is left
+    /// unchanged. Use `GpioController::take_dyn` to claim the pin at the
+    /// same time.
+    ///
+    /// # Safety
+    ///
+    /// The caller must have claimed `pin` in the `GpioController`, or know
+    /// that no other code uses it, since the pin is reconfigured without
+    /// checking claims.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pin` > `53`.
+    pub unsafe fn new(pin: u8) -> DynPin {
+        if pin >= PIN_COUNT {
+            panic!("DynPin::new(): pin {} exceeds maximum of 53", pin);
+        }
+
+        DynPin {
+            registers: &mut *(GPIO_BASE as *mut Registers),
+            pin,
+            owner: None
+        }
+    }
+
+    /// Returns the number of this pin.
+    pub fn pin(&self) -> u8 {
+        self.pin
+    }
+
+    /// Returns the function currently selected for this pin.
+    pub fn function(&self) -> Function {
+        self.registers.function(self.pin)
+    }
+
+    /// Selects `function` for this pin.
+    pub fn set_function(&mut self, function: Function) {
+        self.registers.set_function(self.pin, function);
+    }
+
+    /// Returns `Ok` if this pin has the function `expected`.
+    fn check_function(&self, expected: Function) -> Result<(), Error> {
+        match self.function() {
+            actual if actual == expected => Ok(()),
+            actual => Err(Error::WrongFunction { expected, actual }),
+        }
+    }
+
+    /// Converts `self` to a typed pin if it has the function `expected`, or
+    /// returns it unchanged with the error otherwise.
+    fn into_typed<S>(mut self, expected: Function) -> Result<Gpio<S>, (DynPin, Error)> {
+        if let Err(e) = self.check_function(expected) {
+            return Err((self, e));
+        }
+
+        Ok(Gpio {
+            pin: self.pin,
+            registers: unsafe { &mut *(GPIO_BASE as *mut Registers) },
+            owner: self.owner.take(),
+            _state: PhantomData
+        })
+    }
+
+    /// Sets (turns on) the pin.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::WrongFunction` if the pin is not an output.
+    pub fn set(&mut self) -> Result<(), Error> {
+        self.check_function(Function::Output)?;
+        self.registers.set(self.pin);
+        Ok(())
+    }
+
+    /// Clears (turns off) the pin.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::WrongFunction` if the pin is not an output.
+    pub fn clear(&mut self) -> Result<(), Error> {
+        self.check_function(Function::Output)?;
+        self.registers.clear(self.pin);
+        Ok(())
+    }
+
+    /// Inverts the level of the pin.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::WrongFunction` if the pin is not an output.
+    pub fn toggle(&mut self) -> Result<(), Error> {
+        if self.level() {
+            self.clear()
+        } else {
+            self.set()
+        }
+    }
+
+    /// Reads the pin's level, whatever its function. Returns `true` if the
+    /// level is high and `false` if the level is low.
+    pub fn level(&self) -> bool {
+        self.registers.level(self.pin)
+    }
+
+    /// Sets the pin's pull-up/down resistor to `pull`.
+    pub fn set_pull(&mut self, pull: Pull) {
+        self.registers.set_pull(self.pin, pull);
+    }
+
+    /// Enables the detection of `event`, whatever the pin's function.
+    pub fn enable_event(&mut self, event: Event) {
+        self.registers.set_event_enabled(self.pin, event, true);
+    }
+
+    /// Disables the detection of `event`.
+    pub fn disable_event(&mut self, event: Event) {
+        self.registers.set_event_enabled(self.pin, event, false);
+    }
+}
+
+impl Drop for GpioBank {
+    fn drop(&mut self) {
+        if let Some(owner) = self.owner {
+            let (numbers, count) = pin_numbers(self.pins);
+            GpioController::new().release(&numbers[..count], owner);
+        }
+    }
+}
+
+impl Drop for DynPin {
+    fn drop(&mut self) {
+        if let Some(owner) = self.owner {
+            GpioController::new().release(&[self.pin], owner);
+        }
+    }
+}
+
+/// Returns the numbers of the pins in `pins`, in ascending order, and how
+/// many there are. Bits for pins above 53 are ignored.
+fn pin_numbers(pins: u64) -> ([u8; PIN_COUNT as usize], usize) {
+    let mut numbers = [0u8; PIN_COUNT as usize];
+    let mut count = 0;
+    for pin in (0..PIN_COUNT).filter(|&pin| pins & (1 << pin) != 0) {
+        numbers[count] = pin;
+        count += 1;
+    }
+    (numbers, count)
+}
+
+/// The owners of the claimed pins, indexed by pin number.
+struct Claims(UnsafeCell<[Option<&'static str>; PIN_COUNT as usize]>);
+
+// The claims are only accessed by `with_owners`, on one core, with IRQs
+// masked, so accesses never overlap.
+unsafe impl Sync for Claims {}
+
+static CLAIMS: Claims = Claims(UnsafeCell::new([None; PIN_COUNT as usize]));
+
+/// Runs `f` with IRQs masked on the current core, then restores the mask it
+/// had before.
+fn without_irqs<T>(f: impl FnOnce() -> T) -> T {
+    #[cfg(target_arch = "aarch64")]
+    let daif: u64 = unsafe {
+        let daif;
+        asm!("mrs {}, DAIF", "msr DAIFSet, #0b0010", out(reg) daif);
+        daif
+    };
+
+    let result = f();
+
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        asm!("msr DAIF, {}", in(reg) daif);
+    }
+    result
+}
+
+/// Runs `f` on the owners of the pins, indexed by pin number. IRQs are
+/// masked meanwhile, so an interrupt handler claiming pins never sees a
+/// claim half done. `f` must not call `with_owners` again.
+fn with_owners<T>(f: impl FnOnce(&mut [Option<&'static str>; PIN_COUNT as usize]) -> T) -> T {
+    without_irqs(|| f(unsafe { &mut *CLAIMS.0.get() }))
+}
+
+/// Hands out GPIO pins, each to at most one owner at a time.
+///
+/// All controllers share one record of which pins are claimed, so every
+/// driver can create its own. A driver claims the pins it uses before
+/// configuring them, and a claim that conflicts with another owner's is
+/// reported instead of silently reconfiguring that owner's pins. Pins taken
+/// from a controller release their claims when dropped. Pins can only be
+/// created without a claim with the unsafe `Gpio::new`, `GpioBank::new` and
+/// `DynPin::new`.
+pub struct GpioController(());
+
+impl GpioController {
+    /// Returns a handle to the record of claimed pins.
+    pub const fn new() -> GpioController {
+        GpioController(())
+    }
+
+    /// Returns the owner of `pin`, or `None` if it isn't claimed.
+    pub fn owner(&self, pin: u8) -> Option<&'static str> {
+        with_owners(|owners| owners.get(pin as usize).copied().flatten())
+    }
+
+    /// Claims all of `pins` for `owner`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::NoSuchPin` or `Error::Taken` for the first pin that
+    /// doesn't exist or is already claimed, even by `owner`. In that case, no
+    /// pin is claimed.
+    pub fn claim(&self, pins: &[u8], owner: &'static str) -> Result<(), Error> {
+        with_owners(|owners| {
+            for &pin in pins {
+                if pin >= PIN_COUNT {
+                    return Err(Error::NoSuchPin(pin));
+                } else if let Some(owner) = owners[pin as usize] {
+                    return Err(Error::Taken { pin, owner });
+                }
+            }
+
+            for &pin in pins {
+                owners[pin as usize] = Some(owner);
+            }
+            Ok(())
+        })
+    }
+
+    /// Releases the pins in `pins` claimed by `owner`. Pins claimed by other
+    /// owners are left claimed.
+    pub fn release(&self, pins: &[u8], owner: &'static str) {
+        with_owners(|owners| {
+            for &pin in pins {
+                if let Some(claim) = owners.get_mut(pin as usize) {
+                    if *claim == Some(owner) {
+                        *claim = None;
+                    }
+                }
+            }
+        })
+    }
+
+    /// Claims `pin` for `owner` and returns it in the `Uninitialized` state.
+    /// The claim is released when the pin, in whatever state or as a
+    /// `DynPin`, is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::NoSuchPin` or `Error::Taken` if the pin doesn't exist
+    /// or is already claimed.
+    pub fn take(&self, pin: u8, owner: &'static str) -> Result<Gpio<Uninitialized>, Error> {
+        self.claim(&[pin], owner)?;
+        let mut gpio = unsafe { Gpio::new(pin) };
+        gpio.owner = Some(owner);
+        Ok(gpio)
+    }
+
+    /// Claims the pins in `pins` for `owner` and returns them as a bank. The
+    /// claims are released when the bank is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::NoSuchPin` or `Error::Taken` for the first pin that
+    /// doesn't exist or is already claimed. In that case, no pin is claimed.
+    pub fn take_bank(&self, pins: u64, owner: &'static str) -> Result<GpioBank, Error> {
+        if let Some(pin) = (0..64).find(|&pin| pins & (1 << pin) != 0 && pin >= PIN_COUNT) {
+            return Err(Error::NoSuchPin(pin));
+        }
+
+        let (numbers, count) = pin_numbers(pins);
+        self.claim(&numbers[..count], owner)?;
+        let mut bank = unsafe { GpioBank::new(pins) };
+        bank.owner = Some(owner);
+        Ok(bank)
+    }
+
+    /// Claims `pin` for `owner` and returns it as a `DynPin`, with its
+    /// function unchanged. The claim is released when the pin is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::NoSuchPin` or `Error::Taken` if the pin doesn't exist
+    /// or is already claimed.
+    pub fn take_dyn(&self, pin: u8, owner: &'static str) -> Result<DynPin, Error> {
+        self.claim(&[pin], owner)?;
+        let mut pin = unsafe { DynPin::new(pin) };
+        pin.owner = Some(owner);
+        Ok(pin)
     }
 }
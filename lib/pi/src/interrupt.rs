@@ -0,0 +1,97 @@
+use crate::common::IO_BASE;
+
+use volatile::prelude::*;
+use volatile::{Volatile, ReadVolatile};
+
+/// The base address of the interrupt controller registers.
+const INT_BASE: usize = IO_BASE + 0xB000 + 0x200;
+
+/// An interrupt source of the BCM2837 interrupt controller. The value of
+/// each variant is its IRQ number.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Interrupt {
+    Timer1 = 1,
+    Timer3 = 3,
+    Usb = 9,
+    Aux = 29,
+    Gpio0 = 49,
+    Gpio1 = 50,
+    Gpio2 = 51,
+    Gpio3 = 52,
+    Uart = 57,
+}
+
+impl Interrupt {
+    /// The number of interrupt sources.
+    pub const MAX: usize = 9;
+
+    /// Returns an iterator over all interrupt sources.
+    pub fn iter() -> impl Iterator<Item = Interrupt> {
+        INTERRUPTS.iter().copied()
+    }
+
+    /// Returns the register index and bit mask of this source in a pair of
+    /// `IRQ_PENDING`, `ENABLE_IRQ` or `DISABLE_IRQ` registers.
+    fn bank(self) -> (usize, u32) {
+        let irq = self as usize;
+        (irq / 32, 1 << (irq % 32))
+    }
+}
+
+/// Every interrupt source, in the order of `Interrupt::iter()`.
+static INTERRUPTS: [Interrupt; Interrupt::MAX] = [
+    Interrupt::Timer1,
+    Interrupt::Timer3,
+    Interrupt::Usb,
+    Interrupt::Aux,
+    Interrupt::Gpio0,
+    Interrupt::Gpio1,
+    Interrupt::Gpio2,
+    Interrupt::Gpio3,
+    Interrupt::Uart,
+];
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    IRQ_BASIC_PENDING: ReadVolatile<u32>,
+    IRQ_PENDING: [ReadVolatile<u32>; 2],
+    FIQ_CONTROL: Volatile<u32>,
+    ENABLE_IRQ: [Volatile<u32>; 2],
+    ENABLE_BASIC_IRQ: Volatile<u32>,
+    DISABLE_IRQ: [Volatile<u32>; 2],
+    DISABLE_BASIC_IRQ: Volatile<u32>,
+}
+
+/// The Raspberry Pi's interrupt controller, which routes peripheral
+/// interrupts to the ARM core as IRQs.
+pub struct Controller {
+    registers: &'static mut Registers
+}
+
+impl Controller {
+    /// Returns a new handle to the interrupt controller.
+    pub fn new() -> Controller {
+        Controller {
+            registers: unsafe { &mut *(INT_BASE as *mut Registers) },
+        }
+    }
+
+    /// Enables the interrupt `int`.
+    pub fn enable(&mut self, int: Interrupt) {
+        let (index, mask) = int.bank();
+        self.registers.ENABLE_IRQ[index].write(mask);
+    }
+
+    /// Disables the interrupt `int`.
+    pub fn disable(&mut self, int: Interrupt) {
+        let (index, mask) = int.bank();
+        self.registers.DISABLE_IRQ[index].write(mask);
+    }
+
+    /// Returns `true` if `int` is pending.
+    pub fn is_pending(&self, int: Interrupt) -> bool {
+        let (index, mask) = int.bank();
+        self.registers.IRQ_PENDING[index].has_mask(mask)
+    }
+}
@@ -6,6 +6,13 @@
 #![no_std]
 
 pub mod common;
+pub mod emmc;
+pub mod framebuffer;
 pub mod gpio;
+pub mod interrupt;
+pub mod mailbox;
+pub mod pl011;
+pub mod pm;
+pub mod rng;
 pub mod timer;
 pub mod uart;
@@ -0,0 +1,428 @@
+//! The VideoCore mailbox and its property interface, through which the ARM
+//! cores query and configure the firmware.
+//!
+//! A property message is a 16-byte aligned buffer holding a sequence of
+//! tags. Each tag carries a request and room for its response, which the
+//! firmware writes in place. `Mailbox` has a method for each supported tag;
+//! `Message` builds messages with several tags, which some requests (such
+//! as allocating a framebuffer) need to be made together.
+//!
+//! ```rust,no_run
+//! use pi::mailbox::{Clock, Mailbox};
+//!
+//! let mut mailbox = Mailbox::new();
+//! let revision = mailbox.board_revision();
+//! let arm_hz = mailbox.clock_rate(Clock::Arm);
+//! ```
+
+use core::sync::atomic::{compiler_fence, Ordering};
+
+use shim::io;
+use shim::const_assert_size;
+
+use volatile::prelude::*;
+use volatile::{ReadVolatile, Reserved, Volatile};
+
+use crate::common::IO_BASE;
+
+#[cfg(test)]
+mod tests;
+
+/// The base address of the mailbox registers.
+const MAILBOX_BASE: usize = IO_BASE + 0xB880;
+
+/// Bit of a status register set while the mailbox is full.
+const STATUS_FULL: u32 = 1 << 31;
+
+/// Bit of a status register set while the mailbox is empty.
+const STATUS_EMPTY: u32 = 1 << 30;
+
+/// The code of a property message sent as a request.
+const REQUEST: u32 = 0;
+
+/// The code of a property message the firmware processed.
+const RESPONSE_SUCCESS: u32 = 0x8000_0000;
+
+/// Bit of a tag's code set once the firmware wrote its response. The other
+/// bits hold the length of the response in bytes.
+const TAG_RESPONSE: u32 = 1 << 31;
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    /// Mailbox 0, from the VideoCore to the ARM.
+    READ: ReadVolatile<u32>,
+    __r0: [Reserved<u32>; 3],
+    PEEK: ReadVolatile<u32>,
+    SENDER: ReadVolatile<u32>,
+    STATUS: ReadVolatile<u32>,
+    CONFIG: Volatile<u32>,
+    /// Mailbox 1, from the ARM to the VideoCore.
+    WRITE: Volatile<u32>,
+    __r1: [Reserved<u32>; 5],
+    WRITE_STATUS: ReadVolatile<u32>,
+}
+
+const_assert_size!(Registers, 0x3C);
+
+/// A mailbox channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    Power = 0,
+    Framebuffer = 1,
+    VirtualUart = 2,
+    Vchiq = 3,
+    Leds = 4,
+    Buttons = 5,
+    TouchScreen = 6,
+    /// Property tags, from the ARM to the VideoCore.
+    Property = 8,
+}
+
+/// Errors reported by the mailbox.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The tags don't fit in the message.
+    MessageFull,
+    /// The firmware couldn't parse the message.
+    Failed,
+    /// The firmware didn't answer the tag `.0`, which it doesn't support.
+    Unsupported(u32),
+    /// The firmware's response to the tag `.0` is longer than the room
+    /// reserved for it.
+    Truncated(u32),
+    /// The device or clock asked for doesn't exist.
+    NoSuchDevice,
+}
+
+impl From<Error> for io::Error {
+    fn from(error: Error) -> io::Error {
+        match error {
+            Error::MessageFull => io::Error::new(io::ErrorKind::InvalidInput, "mailbox message is full"),
+            Error::Failed => io::Error::new(io::ErrorKind::Other, "firmware rejected the mailbox message"),
+            Error::Unsupported(_) => io::Error::new(io::ErrorKind::Other, "firmware doesn't support the property"),
+            Error::Truncated(_) => io::Error::new(io::ErrorKind::Other, "firmware response is too long"),
+            Error::NoSuchDevice => io::Error::new(io::ErrorKind::NotFound, "no such device"),
+        }
+    }
+}
+
+/// A property tag identifier.
+pub mod tag {
+    pub const FIRMWARE_REVISION: u32 = 0x0000_0001;
+    pub const BOARD_MODEL: u32 = 0x0001_0001;
+    pub const BOARD_REVISION: u32 = 0x0001_0002;
+    pub const MAC_ADDRESS: u32 = 0x0001_0003;
+    pub const BOARD_SERIAL: u32 = 0x0001_0004;
+    pub const ARM_MEMORY: u32 = 0x0001_0005;
+    pub const VC_MEMORY: u32 = 0x0001_0006;
+    pub const GET_POWER_STATE: u32 = 0x0002_0001;
+    pub const SET_POWER_STATE: u32 = 0x0002_8001;
+    pub const GET_CLOCK_RATE: u32 = 0x0003_0002;
+    pub const GET_MAX_CLOCK_RATE: u32 = 0x0003_0004;
+    pub const GET_TEMPERATURE: u32 = 0x0003_0006;
+    pub const GET_MIN_CLOCK_RATE: u32 = 0x0003_0007;
+    pub const GET_MAX_TEMPERATURE: u32 = 0x0003_000A;
+    pub const SET_CLOCK_RATE: u32 = 0x0003_8002;
+    pub const ALLOCATE_BUFFER: u32 = 0x0004_0001;
+    pub const GET_PITCH: u32 = 0x0004_0008;
+    pub const SET_PHYSICAL_SIZE: u32 = 0x0004_8003;
+    pub const SET_VIRTUAL_SIZE: u32 = 0x0004_8004;
+    pub const SET_DEPTH: u32 = 0x0004_8005;
+    pub const SET_PIXEL_ORDER: u32 = 0x0004_8006;
+    pub const SET_VIRTUAL_OFFSET: u32 = 0x0004_8009;
+}
+
+/// A device whose power the firmware controls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Device {
+    SdCard = 0,
+    Uart0 = 1,
+    Uart1 = 2,
+    UsbHcd = 3,
+    I2c0 = 4,
+    I2c1 = 5,
+    I2c2 = 6,
+    Spi = 7,
+    Ccp2tx = 8,
+}
+
+impl Device {
+    /// Returns an iterator over all devices.
+    pub fn iter() -> impl Iterator<Item = Device> {
+        DEVICES.iter().copied()
+    }
+}
+
+/// Every device, in the order of `Device::iter()`.
+static DEVICES: [Device; 9] = [
+    Device::SdCard,
+    Device::Uart0,
+    Device::Uart1,
+    Device::UsbHcd,
+    Device::I2c0,
+    Device::I2c1,
+    Device::I2c2,
+    Device::Spi,
+    Device::Ccp2tx,
+];
+
+/// A clock generated by the firmware.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Clock {
+    Emmc = 1,
+    Uart = 2,
+    Arm = 3,
+    Core = 4,
+    V3d = 5,
+    H264 = 6,
+    Isp = 7,
+    Sdram = 8,
+    Pixel = 9,
+    Pwm = 10,
+}
+
+/// A range of physical memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryRange {
+    pub base: u32,
+    pub size: u32,
+}
+
+/// The position of a tag in a `Message`, to read its response with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TagIndex(usize);
+
+/// A property message of up to `N` words, including its 2-word header and
+/// the end tag.
+#[repr(C, align(16))]
+pub struct Message<const N: usize> {
+    words: [u32; N],
+    len: usize,
+}
+
+impl<const N: usize> Message<N> {
+    /// Returns a message without tags.
+    pub const fn new() -> Message<N> {
+        Message { words: [0; N], len: 2 }
+    }
+
+    /// Appends the tag `tag` with the request `values`, reserving
+    /// `response_len` words for its response. Returns the index to read the
+    /// response at after sending the message.
+    pub fn push(&mut self, tag: u32, values: &[u32], response_len: usize) -> Result<TagIndex, Error> {
+        let len = core::cmp::max(values.len(), response_len);
+        // The tag's header and values, and the end tag after them.
+        if self.len + 3 + len + 1 > N {
+            return Err(Error::MessageFull);
+        }
+
+        let start = self.len;
+        self.words[start] = tag;
+        self.words[start + 1] = (len * 4) as u32;
+        self.words[start + 2] = REQUEST;
+        self.words[start + 3..start + 3 + values.len()].copy_from_slice(values);
+        for word in &mut self.words[start + 3 + values.len()..start + 3 + len] {
+            *word = 0;
+        }
+        self.len += 3 + len;
+        Ok(TagIndex(start))
+    }
+
+    /// Returns the response to the tag at `index`.
+    pub fn response(&self, index: TagIndex) -> Result<&[u32], Error> {
+        let tag = self.words[index.0];
+        let capacity = self.words[index.0 + 1] as usize;
+        let code = self.words[index.0 + 2];
+        if code & TAG_RESPONSE == 0 {
+            return Err(Error::Unsupported(tag));
+        }
+
+        let len = (code & !TAG_RESPONSE) as usize;
+        if len > capacity {
+            return Err(Error::Truncated(tag));
+        }
+
+        let values = index.0 + 3;
+        // Responses are padded to whole words.
+        Ok(&self.words[values..values + ((len + 3) >> 2)])
+    }
+
+    /// Terminates the message, filling in its header. Returns the bus
+    /// address of the message.
+    fn finish(&mut self) -> u32 {
+        let len = self.len;
+        self.words[len] = 0;
+        self.words[0] = ((len + 1) * 4) as u32;
+        self.words[1] = REQUEST;
+        self.words.as_ptr() as usize as u32
+    }
+}
+
+/// The mailbox to the VideoCore firmware.
+pub struct Mailbox {
+    registers: &'static mut Registers,
+}
+
+impl Mailbox {
+    /// Returns a new handle to the mailbox.
+    pub fn new() -> Mailbox {
+        Mailbox {
+            registers: unsafe { &mut *(MAILBOX_BASE as *mut Registers) },
+        }
+    }
+
+    /// Sends `data`, whose low 4 bits must be clear, on `channel` and waits
+    /// for the reply on the same channel, discarding replies on other
+    /// channels. Returns the reply without its channel.
+    pub fn call(&mut self, channel: Channel, data: u32) -> u32 {
+        let channel = channel as u32;
+        while self.registers.WRITE_STATUS.has_mask(STATUS_FULL) {}
+        self.registers.WRITE.write((data & !0xF) | channel);
+
+        loop {
+            while self.registers.STATUS.has_mask(STATUS_EMPTY) {}
+            let reply = self.registers.READ.read();
+            if reply & 0xF == channel {
+                return reply & !0xF;
+            }
+        }
+    }
+
+    /// Sends `message` on the property channel and waits for the firmware
+    /// to write its responses. Tags it didn't answer are reported when
+    /// reading their response.
+    pub fn send<const N: usize>(&mut self, message: &mut Message<N>) -> Result<(), Error> {
+        let address = message.finish();
+
+        // The firmware reads and writes the message behind the compiler's
+        // back.
+        compiler_fence(Ordering::SeqCst);
+        self.call(Channel::Property, address);
+        compiler_fence(Ordering::SeqCst);
+
+        match message.words[1] {
+            RESPONSE_SUCCESS => Ok(()),
+            _ => Err(Error::Failed),
+        }
+    }
+
+    /// Sends the single tag `tag` with the request `values` and returns the
+    /// first `R` words of its response.
+    fn property<const R: usize>(&mut self, tag: u32, values: &[u32]) -> Result<[u32; R], Error> {
+        let mut message = Message::<16>::new();
+        let index = message.push(tag, values, R)?;
+        self.send(&mut message)?;
+
+        let response = message.response(index)?;
+        if response.len() < R {
+            return Err(Error::Truncated(tag));
+        }
+        let mut words = [0; R];
+        words.copy_from_slice(&response[..R]);
+        Ok(words)
+    }
+
+    /// Returns the firmware's revision.
+    pub fn firmware_revision(&mut self) -> Result<u32, Error> {
+        self.property::<1>(tag::FIRMWARE_REVISION, &[]).map(|[revision]| revision)
+    }
+
+    /// Returns the board model.
+    pub fn board_model(&mut self) -> Result<u32, Error> {
+        self.property::<1>(tag::BOARD_MODEL, &[]).map(|[model]| model)
+    }
+
+    /// Returns the board revision code, which identifies the model, memory
+    /// size and manufacturer.
+    pub fn board_revision(&mut self) -> Result<u32, Error> {
+        self.property::<1>(tag::BOARD_REVISION, &[]).map(|[revision]| revision)
+    }
+
+    /// Returns the MAC address of the board's Ethernet interface.
+    pub fn mac_address(&mut self) -> Result<[u8; 6], Error> {
+        let [low, high] = self.property::<2>(tag::MAC_ADDRESS, &[])?;
+        let (low, high) = (low.to_le_bytes(), high.to_le_bytes());
+        Ok([low[0], low[1], low[2], low[3], high[0], high[1]])
+    }
+
+    /// Returns the board's serial number.
+    pub fn serial_number(&mut self) -> Result<u64, Error> {
+        let [low, high] = self.property::<2>(tag::BOARD_SERIAL, &[])?;
+        Ok(((high as u64) << 32) | low as u64)
+    }
+
+    /// Returns the memory given to the ARM cores.
+    pub fn arm_memory(&mut self) -> Result<MemoryRange, Error> {
+        let [base, size] = self.property::<2>(tag::ARM_MEMORY, &[])?;
+        Ok(MemoryRange { base, size })
+    }
+
+    /// Returns the memory given to the VideoCore.
+    pub fn vc_memory(&mut self) -> Result<MemoryRange, Error> {
+        let [base, size] = self.property::<2>(tag::VC_MEMORY, &[])?;
+        Ok(MemoryRange { base, size })
+    }
+
+    /// Returns `true` if `device` is powered on.
+    pub fn power_state(&mut self, device: Device) -> Result<bool, Error> {
+        let [_, state] = self.property::<2>(tag::GET_POWER_STATE, &[device as u32])?;
+        power_state(state)
+    }
+
+    /// Powers `device` on or off, waiting for it to become stable. Returns
+    /// the new state, which is `on` unless the firmware refused.
+    pub fn set_power_state(&mut self, device: Device, on: bool) -> Result<bool, Error> {
+        // Bit 0: on, bit 1: wait for the device to become stable.
+        let request = on as u32 | (1 << 1);
+        let [_, state] = self.property::<2>(tag::SET_POWER_STATE, &[device as u32, request])?;
+        power_state(state)
+    }
+
+    /// Returns the rate of `clock` in Hz, or 0 if it is disabled.
+    pub fn clock_rate(&mut self, clock: Clock) -> Result<u32, Error> {
+        self.property::<2>(tag::GET_CLOCK_RATE, &[clock as u32]).map(|[_, hz]| hz)
+    }
+
+    /// Returns the highest rate `clock` can be set to, in Hz.
+    pub fn max_clock_rate(&mut self, clock: Clock) -> Result<u32, Error> {
+        self.property::<2>(tag::GET_MAX_CLOCK_RATE, &[clock as u32]).map(|[_, hz]| hz)
+    }
+
+    /// Returns the lowest rate `clock` can be set to, in Hz.
+    pub fn min_clock_rate(&mut self, clock: Clock) -> Result<u32, Error> {
+        self.property::<2>(tag::GET_MIN_CLOCK_RATE, &[clock as u32]).map(|[_, hz]| hz)
+    }
+
+    /// Sets the rate of `clock` to `hz`, which the firmware clamps to the
+    /// supported range. Returns the new rate.
+    pub fn set_clock_rate(&mut self, clock: Clock, hz: u32) -> Result<u32, Error> {
+        // The last word asks the firmware to leave the turbo settings alone.
+        match self.property::<2>(tag::SET_CLOCK_RATE, &[clock as u32, hz, 1])? {
+            // The firmware answers with clock 0 if it doesn't have the clock.
+            [0, _] => Err(Error::NoSuchDevice),
+            [_, hz] => Ok(hz),
+        }
+    }
+
+    /// Returns the SoC's temperature in thousandths of a degree Celsius.
+    pub fn temperature(&mut self) -> Result<u32, Error> {
+        self.property::<2>(tag::GET_TEMPERATURE, &[0]).map(|[_, temperature]| temperature)
+    }
+
+    /// Returns the temperature, in thousandths of a degree Celsius, above
+    /// which the firmware throttles the clocks.
+    pub fn max_temperature(&mut self) -> Result<u32, Error> {
+        self.property::<2>(tag::GET_MAX_TEMPERATURE, &[0]).map(|[_, temperature]| temperature)
+    }
+}
+
+/// Decodes the state of a power state response.
+fn power_state(state: u32) -> Result<bool, Error> {
+    // Bit 0: on, bit 1: the device doesn't exist.
+    if state & (1 << 1) != 0 {
+        Err(Error::NoSuchDevice)
+    } else {
+        Ok(state & 1 != 0)
+    }
+}
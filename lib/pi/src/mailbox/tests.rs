@@ -0,0 +1,88 @@
+use super::*;
+
+/// Answers the tag at `index` like the firmware: writes `response` over its
+/// values and sets its code to `len` bytes with the response bit.
+fn answer<const N: usize>(message: &mut Message<N>, index: TagIndex, response: &[u32], len: u32) {
+    let values = index.0 + 3;
+    message.words[values..values + response.len()].copy_from_slice(response);
+    message.words[index.0 + 2] = TAG_RESPONSE | len;
+    message.words[1] = RESPONSE_SUCCESS;
+}
+
+#[test]
+fn lays_out_words() {
+    let mut message = Message::<16>::new();
+    let revision = message.push(tag::BOARD_REVISION, &[], 1).unwrap();
+    let clock = message.push(tag::SET_CLOCK_RATE, &[3, 600_000_000, 0], 2).unwrap();
+    assert_eq!((revision, clock), (TagIndex(2), TagIndex(6)));
+
+    let address = message.finish();
+    assert_eq!(address & 0xF, 0);
+    assert_eq!(
+        message.words[..13],
+        [
+            13 * 4, REQUEST,
+            tag::BOARD_REVISION, 4, REQUEST, 0,
+            tag::SET_CLOCK_RATE, 12, REQUEST, 3, 600_000_000, 0,
+            0,
+        ]
+    );
+}
+
+#[test]
+fn clears_reused_response_room() {
+    let mut message = Message::<16>::new();
+    message.words = [0xFFFF_FFFF; 16];
+    let index = message.push(tag::GET_CLOCK_RATE, &[3], 2).unwrap();
+    assert_eq!(message.words[index.0 + 3..index.0 + 5], [3, 0]);
+}
+
+#[test]
+fn reports_full_messages() {
+    // The header, one tag with one value, and the end tag.
+    let mut message = Message::<7>::new();
+    assert_eq!(message.push(tag::BOARD_MODEL, &[], 2), Err(Error::MessageFull));
+    assert!(message.push(tag::BOARD_MODEL, &[], 1).is_ok());
+    assert_eq!(message.push(tag::BOARD_MODEL, &[], 0), Err(Error::MessageFull));
+    assert_eq!(message.finish() & 0xF, 0);
+    assert_eq!(message.words[0], 7 * 4);
+}
+
+#[test]
+fn decodes_responses() {
+    let mut message = Message::<16>::new();
+    let index = message.push(tag::ARM_MEMORY, &[], 2).unwrap();
+    answer(&mut message, index, &[0, 0x3B40_0000], 8);
+    assert_eq!(message.response(index), Ok(&[0, 0x3B40_0000][..]));
+}
+
+#[test]
+fn reports_unanswered_tags() {
+    let mut message = Message::<16>::new();
+    let answered = message.push(tag::BOARD_MODEL, &[], 1).unwrap();
+    let unanswered = message.push(tag::GET_TEMPERATURE, &[0], 2).unwrap();
+    answer(&mut message, answered, &[0], 4);
+    assert_eq!(message.response(unanswered), Err(Error::Unsupported(tag::GET_TEMPERATURE)));
+}
+
+#[test]
+fn reports_truncated_responses() {
+    let mut message = Message::<16>::new();
+    let index = message.push(tag::BOARD_SERIAL, &[], 1).unwrap();
+    // The firmware sets the full length of a response that didn't fit.
+    answer(&mut message, index, &[0x1234_5678], 8);
+    assert_eq!(message.response(index), Err(Error::Truncated(tag::BOARD_SERIAL)));
+}
+
+#[test]
+fn pads_partial_word_responses() {
+    let mut message = Message::<16>::new();
+    let index = message.push(tag::MAC_ADDRESS, &[], 2).unwrap();
+    answer(&mut message, index, &[0x1234_27B8, 0x0000_BC9A], 6);
+    assert_eq!(message.response(index), Ok(&[0x1234_27B8, 0x0000_BC9A][..]));
+
+    let mut message = Message::<16>::new();
+    let index = message.push(tag::BOARD_MODEL, &[], 2).unwrap();
+    answer(&mut message, index, &[0], 0);
+    assert_eq!(message.response(index), Ok(&[][..]));
+}
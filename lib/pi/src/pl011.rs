@@ -0,0 +1,381 @@
+use core::fmt;
+use core::time::Duration;
+
+use shim::io;
+use shim::const_assert_size;
+
+use volatile::prelude::*;
+use volatile::{Volatile, ReadVolatile, Reserved};
+
+use crate::common::IO_BASE;
+use crate::gpio::{self, Function, Gpio, GpioController};
+use crate::timer::Timer;
+
+#[cfg(test)]
+mod tests;
+
+/// The base address of the UART0 (PL011) registers.
+const UART0_REG_BASE: usize = IO_BASE + 0x201000;
+
+/// The frequency of the UART reference clock set by the firmware
+/// (`init_uart_clock` in `config.txt`).
+pub const UART_CLOCK_HZ: u32 = 48_000_000;
+
+/// The GPIO pins of UART0's transmit and receive lines, in alternative
+/// function 0.
+const DATA_PINS: [u8; 2] = [14, 15];
+
+/// The GPIO pins of UART0's CTS and RTS lines, in alternative function 3.
+const FLOW_CONTROL_PINS: [u8; 2] = [16, 17];
+
+/// The name UART0 claims its GPIO pins with.
+const OWNER: &str = "PL011 UART";
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    DR: Volatile<u32>,
+    RSRECR: Volatile<u32>,
+    __r0: [Reserved<u32>; 4],
+    FR: ReadVolatile<u32>,
+    __r1: Reserved<u32>,
+    ILPR: Volatile<u32>,
+    IBRD: Volatile<u32>,
+    FBRD: Volatile<u32>,
+    LCRH: Volatile<u32>,
+    CR: Volatile<u32>,
+    IFLS: Volatile<u32>,
+    IMSC: Volatile<u32>,
+    RIS: ReadVolatile<u32>,
+    MIS: ReadVolatile<u32>,
+    ICR: Volatile<u32>,
+    DMACR: Volatile<u32>,
+}
+
+const_assert_size!(Registers, 0x4C);
+
+/// Bit fields of the `FR` (flag) register.
+#[repr(u32)]
+enum Flag {
+    Busy = 1 << 3,
+    RxFifoEmpty = 1 << 4,
+    TxFifoFull = 1 << 5,
+}
+
+/// Bit fields of the `LCRH` (line control) register.
+#[repr(u32)]
+enum LineControl {
+    ParityEnable = 1 << 1,
+    EvenParity = 1 << 2,
+    TwoStopBits = 1 << 3,
+    FifoEnable = 1 << 4,
+}
+
+/// Bit fields of the `CR` (control) register.
+#[repr(u32)]
+enum Control {
+    Enable = 1 << 0,
+    TxEnable = 1 << 8,
+    RxEnable = 1 << 9,
+    RtsEnable = 1 << 14,
+    CtsEnable = 1 << 15,
+}
+
+/// The number of data bits in a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataBits {
+    Five = 0b00,
+    Six = 0b01,
+    Seven = 0b10,
+    Eight = 0b11,
+}
+
+/// The parity bit of a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parity {
+    None,
+    Even,
+    Odd,
+}
+
+/// The number of stop bits of a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopBits {
+    One,
+    Two,
+}
+
+/// The line settings of a `Pl011`. The default is 115200 baud, 8 data bits,
+/// no parity and one stop bit (8N1) with the FIFOs enabled and no flow
+/// control.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    pub baud_rate: u32,
+    pub data_bits: DataBits,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+    /// Enables the 16-byte transmit and receive FIFOs. Without them, the
+    /// UART holds a single byte in each direction.
+    pub fifo: bool,
+    /// Enables RTS/CTS hardware flow control on GPIO 16 and 17.
+    pub flow_control: bool,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            baud_rate: 115200,
+            data_bits: DataBits::Eight,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+            fifo: true,
+            flow_control: false,
+        }
+    }
+}
+
+/// Errors reported by `Pl011::new`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The baud rate can't be derived from the UART clock.
+    InvalidBaudRate,
+    /// A GPIO pin of UART0 is claimed by another driver.
+    Gpio(gpio::Error),
+}
+
+impl From<gpio::Error> for Error {
+    fn from(error: gpio::Error) -> Error {
+        Error::Gpio(error)
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(error: Error) -> io::Error {
+        match error {
+            Error::InvalidBaudRate => io::Error::new(io::ErrorKind::InvalidInput, "unsupported baud rate"),
+            Error::Gpio(error) => error.into(),
+        }
+    }
+}
+
+/// Returns the integer and fractional baud rate divisors for `baud_rate`
+/// from a `clock_hz` reference clock, or `None` if they are out of range.
+///
+/// The divisor is `clock_hz / (16 * baud_rate)` in fixed point with six
+/// fractional bits, rounded to the nearest value. The PL011 accepts integer
+/// divisors from 1 to `0xFFFF`, the latter only with a zero fraction.
+fn divisors(clock_hz: u32, baud_rate: u32) -> Option<(u32, u32)> {
+    if baud_rate == 0 {
+        return None;
+    }
+
+    let divisor = (4 * clock_hz as u64 + baud_rate as u64 / 2) / baud_rate as u64;
+    let (integer, fraction) = ((divisor >> 6) as u32, (divisor & 0x3F) as u32);
+    match (integer, fraction) {
+        (1..=0xFFFE, _) | (0xFFFF, 0) => Some((integer, fraction)),
+        _ => None,
+    }
+}
+
+/// The Raspberry Pi's UART0, an ARM PL011.
+pub struct Pl011 {
+    registers: &'static mut Registers,
+    timeout: Option<Duration>,
+    flow_control: bool,
+}
+
+impl Pl011 {
+    /// Initializes UART0 with the settings in `config`: claims and routes
+    /// GPIO 14 and 15 (and 16 and 17 with flow control) to it, programs the
+    /// baud rate divisors and the frame format, and enables the transmitter
+    /// and receiver. Interrupts are left masked.
+    ///
+    /// By default, reads will never time out. To set a read timeout, use
+    /// `set_read_timeout()`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidBaudRate` if the baud rate can't be derived
+    /// from the UART clock and `Error::Gpio` if another driver claimed one
+    /// of the pins. In both cases, the UART and the pins are left untouched.
+    pub fn new(config: Config) -> Result<Pl011, Error> {
+        let (integer, fraction) = divisors(UART_CLOCK_HZ, config.baud_rate).ok_or(Error::InvalidBaudRate)?;
+
+        let controller = GpioController::new();
+        controller.claim(&DATA_PINS, OWNER)?;
+        if config.flow_control {
+            if let Err(e) = controller.claim(&FLOW_CONTROL_PINS, OWNER) {
+                controller.release(&DATA_PINS, OWNER);
+                return Err(e.into());
+            }
+        }
+
+        let registers = unsafe { &mut *(UART0_REG_BASE as *mut Registers) };
+
+        // Disable the UART and let it finish the current frame, then flush
+        // the transmit FIFO by disabling it.
+        registers.CR.write(0);
+        while registers.FR.has_mask(Flag::Busy as u32) {}
+        registers.LCRH.write(0);
+
+        // The pins were claimed above.
+        for &pin in DATA_PINS.iter() {
+            unsafe { Gpio::new(pin).into_alt(Function::Alt0) };
+        }
+        if config.flow_control {
+            for &pin in FLOW_CONTROL_PINS.iter() {
+                unsafe { Gpio::new(pin).into_alt(Function::Alt3) };
+            }
+        }
+
+        registers.IMSC.write(0);
+        registers.ICR.write(0x7FF);
+        registers.IBRD.write(integer);
+        registers.FBRD.write(fraction);
+
+        let mut line_control = (config.data_bits as u32) << 5;
+        line_control |= match config.parity {
+            Parity::None => 0,
+            Parity::Even => LineControl::ParityEnable as u32 | LineControl::EvenParity as u32,
+            Parity::Odd => LineControl::ParityEnable as u32,
+        };
+        if config.stop_bits == StopBits::Two {
+            line_control |= LineControl::TwoStopBits as u32;
+        }
+        if config.fifo {
+            line_control |= LineControl::FifoEnable as u32;
+        }
+        registers.LCRH.write(line_control);
+
+        let mut control = Control::Enable as u32 | Control::TxEnable as u32 | Control::RxEnable as u32;
+        if config.flow_control {
+            control |= Control::RtsEnable as u32 | Control::CtsEnable as u32;
+        }
+        registers.CR.write(control);
+
+        Ok(Pl011 {
+            registers,
+            timeout: None,
+            flow_control: config.flow_control,
+        })
+    }
+
+    /// Set the read timeout to `t` duration.
+    pub fn set_read_timeout(&mut self, t: Duration) {
+        self.timeout = Some(t);
+    }
+
+    /// Write the byte `byte`. This method blocks until there is space available
+    /// in the output FIFO.
+    pub fn write_byte(&mut self, byte: u8) {
+        while self.registers.FR.has_mask(Flag::TxFifoFull as u32) {}
+        self.registers.DR.write(byte as u32);
+    }
+
+    /// Returns `true` if there is at least one byte ready to be read. If this
+    /// method returns `true`, a subsequent call to `read_byte` is guaranteed to
+    /// return immediately. This method does not block.
+    pub fn has_byte(&self) -> bool {
+        !self.registers.FR.has_mask(Flag::RxFifoEmpty as u32)
+    }
+
+    /// Blocks until there is a byte ready to read. If a read timeout is set,
+    /// this method blocks for at most that amount of time. Otherwise, this
+    /// method blocks indefinitely until there is a byte to read.
+    ///
+    /// Returns `Ok(())` if a byte is ready to read. Returns `Err(())` if the
+    /// timeout expired while waiting for a byte to be ready. If this method
+    /// returns `Ok(())`, a subsequent call to `read_byte` is guaranteed to
+    /// return immediately.
+    pub fn wait_for_byte(&self) -> Result<(), ()> {
+        let timeout = match self.timeout {
+            Some(timeout) => timeout,
+            None => {
+                while !self.has_byte() {}
+                return Ok(());
+            }
+        };
+
+        let timer = Timer::new();
+        let deadline = timer.read() + timeout;
+        while !self.has_byte() {
+            if timer.read() > deadline {
+                return Err(());
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a byte. Blocks indefinitely until a byte is ready to be read.
+    /// Framing, parity and overrun errors of the byte are ignored.
+    pub fn read_byte(&mut self) -> u8 {
+        while !self.has_byte() {}
+        self.registers.DR.read() as u8
+    }
+
+    /// Blocks until every byte written has been sent.
+    fn wait_until_sent(&self) {
+        while self.registers.FR.has_mask(Flag::Busy as u32) {}
+    }
+}
+
+impl Drop for Pl011 {
+    fn drop(&mut self) {
+        self.wait_until_sent();
+        self.registers.CR.write(0);
+
+        let controller = GpioController::new();
+        controller.release(&DATA_PINS, OWNER);
+        if self.flow_control {
+            controller.release(&FLOW_CONTROL_PINS, OWNER);
+        }
+    }
+}
+
+impl fmt::Write for Pl011 {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for &byte in s.as_bytes() {
+            if byte == b'\n' {
+                self.write_byte(b'\r');
+            }
+            self.write_byte(byte);
+        }
+        Ok(())
+    }
+}
+
+impl io::Read for Pl011 {
+    /// Waits at most the read timeout for the first byte, then reads the
+    /// bytes already received without waiting for more. Returns an error of
+    /// kind `TimedOut` if no byte arrives in time.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        } else if self.wait_for_byte().is_err() {
+            return Err(io::Error::from(io::ErrorKind::TimedOut));
+        }
+
+        let mut read = 0;
+        while read < buf.len() && self.has_byte() {
+            buf[read] = self.read_byte();
+            read += 1;
+        }
+        Ok(read)
+    }
+}
+
+impl io::Write for Pl011 {
+    /// Writes all of `buf`, blocking while the transmit FIFO is full.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for &byte in buf {
+            self.write_byte(byte);
+        }
+        Ok(buf.len())
+    }
+
+    /// Blocks until every byte written has been sent.
+    fn flush(&mut self) -> io::Result<()> {
+        self.wait_until_sent();
+        Ok(())
+    }
+}
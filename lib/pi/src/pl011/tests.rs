@@ -0,0 +1,37 @@
+use super::*;
+
+#[test]
+fn computes_divisors() {
+    // 48MHz / (16 * 115200) = 26.042, and 0.042 * 64 = 2.67.
+    assert_eq!(divisors(UART_CLOCK_HZ, 115_200), Some((26, 3)));
+    assert_eq!(divisors(UART_CLOCK_HZ, 9600), Some((312, 32)));
+    assert_eq!(divisors(UART_CLOCK_HZ, 3_000_000), Some((1, 0)));
+}
+
+#[test]
+fn rounds_to_the_nearest_divisor() {
+    // 100 / 48 = 2.0833 is 133.33 / 64, rounded down.
+    assert_eq!(divisors(100, 3), Some((2, 5)));
+    // 100 / 96 = 1.0417 is 66.67 / 64, rounded up.
+    assert_eq!(divisors(100, 6), Some((1, 3)));
+    // 63.5 / 64 rounds up to an integer divisor of 1.
+    assert_eq!(divisors(127, 8), Some((1, 0)));
+}
+
+#[test]
+fn rejects_a_zero_baud_rate() {
+    assert_eq!(divisors(UART_CLOCK_HZ, 0), None);
+}
+
+#[test]
+fn rejects_out_of_range_divisors() {
+    // Below 1.
+    assert_eq!(divisors(UART_CLOCK_HZ, 4_000_000), None);
+
+    // `0xFFFF` is only allowed with a zero fraction.
+    assert_eq!(divisors(0xF_FFF0, 1), Some((0xFFFF, 0)));
+    assert_eq!(divisors(0xF_FFF1, 1), None);
+    assert_eq!(divisors(0xF_FFEF, 1), Some((0xFFFE, 0x3C)));
+    assert_eq!(divisors(0x10_0000, 1), None);
+    assert_eq!(divisors(UART_CLOCK_HZ, 45), None);
+}
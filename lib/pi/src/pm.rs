@@ -0,0 +1,151 @@
+//! The power management block's watchdog, which resets the board when it
+//! expires.
+//!
+//! Resetting the board is done by starting the watchdog with a tiny timeout.
+//! The firmware then boots the partition stored in the `RSTS` register: 0,
+//! the default, boots the boot partition again, which is how the serial
+//! bootloader is restarted, and 63 halts instead of booting.
+
+use core::time::Duration;
+
+use shim::io;
+use volatile::prelude::*;
+use volatile::{Reserved, Volatile};
+
+use crate::common::IO_BASE;
+use crate::mailbox::{Device, Mailbox};
+
+/// The base address of the power management registers.
+const PM_REG_BASE: usize = IO_BASE + 0x100000;
+
+/// Must be in the top byte of every write to a PM register, or the write is
+/// ignored.
+const PASSWORD: u32 = 0x5A00_0000;
+
+/// Mask clearing the watchdog configuration bits of `RSTC`.
+const RSTC_WRCFG_CLR: u32 = 0xFFFF_FFCF;
+/// Watchdog configuration resetting the whole board when it expires.
+const RSTC_WRCFG_FULL_RESET: u32 = 0x0000_0020;
+/// Value of `RSTC` stopping the watchdog.
+const RSTC_RESET: u32 = 0x0000_0102;
+
+/// Mask clearing the partition bits of `RSTS`: bit `n` of the partition is
+/// stored in bit `2n`.
+const RSTS_PARTITION_CLR: u32 = 0xFFFF_FAAA;
+
+/// The bits of `WDOG` holding the number of ticks before the watchdog
+/// expires.
+const WDOG_TIME_SET: u32 = 0x000F_FFFF;
+
+/// The watchdog counts down in ticks of 1/65536th of a second.
+const TICKS_PER_SECOND: u64 = 1 << 16;
+
+/// The watchdog timeout used to reset the board right away.
+const RESET_TICKS: u32 = 10;
+
+/// The longest watchdog timeout, just below the limit of `WDOG`.
+pub const MAX_WATCHDOG_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// The partition to boot after a reset which makes the firmware halt.
+pub const HALT_PARTITION: u8 = 63;
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    __r0: [Reserved<u32>; 7],
+    RSTC: Volatile<u32>,
+    RSTS: Volatile<u32>,
+    WDOG: Volatile<u32>,
+}
+
+/// Errors reported by the watchdog.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The timeout is zero or longer than `MAX_WATCHDOG_TIMEOUT`.
+    InvalidTimeout,
+}
+
+impl From<Error> for io::Error {
+    fn from(error: Error) -> io::Error {
+        match error {
+            Error::InvalidTimeout => io::Error::new(io::ErrorKind::InvalidInput, "invalid watchdog timeout"),
+        }
+    }
+}
+
+/// The power management block.
+pub struct Pm {
+    registers: &'static mut Registers,
+}
+
+impl Pm {
+    /// Returns a new handle to the power management block.
+    pub fn new() -> Pm {
+        Pm { registers: unsafe { &mut *(PM_REG_BASE as *mut Registers) } }
+    }
+
+    /// Starts the watchdog, or restarts it if it is running, so that it
+    /// resets the board after `timeout` unless it is fed again.
+    pub fn start_watchdog(&mut self, timeout: Duration) -> Result<(), Error> {
+        if timeout == Duration::from_secs(0) || timeout > MAX_WATCHDOG_TIMEOUT {
+            return Err(Error::InvalidTimeout);
+        }
+
+        let ticks = timeout.as_micros() as u64 * TICKS_PER_SECOND / 1_000_000;
+        self.start(core::cmp::max(ticks, 1) as u32);
+        Ok(())
+    }
+
+    fn start(&mut self, ticks: u32) {
+        let rstc = self.registers.RSTC.read() & RSTC_WRCFG_CLR;
+        self.registers.WDOG.write(PASSWORD | (ticks & WDOG_TIME_SET));
+        self.registers.RSTC.write(PASSWORD | rstc | RSTC_WRCFG_FULL_RESET);
+    }
+
+    /// Stops the watchdog.
+    pub fn stop_watchdog(&mut self) {
+        self.registers.RSTC.write(PASSWORD | RSTC_RESET);
+    }
+
+    /// Returns `true` if the watchdog is running.
+    pub fn watchdog_running(&self) -> bool {
+        self.registers.RSTC.has_mask(RSTC_WRCFG_FULL_RESET)
+    }
+
+    /// Returns the time left before the watchdog expires.
+    pub fn watchdog_remaining(&self) -> Duration {
+        let ticks = (self.registers.WDOG.read() & WDOG_TIME_SET) as u64;
+        Duration::from_micros(ticks * 1_000_000 / TICKS_PER_SECOND)
+    }
+
+    /// Resets the board, after which the firmware boots `partition`. Only
+    /// the low 6 bits of `partition` are used.
+    pub fn reset(&mut self, partition: u8) -> ! {
+        let mut rsts = self.registers.RSTS.read() & RSTS_PARTITION_CLR;
+        for bit in 0..6 {
+            rsts |= ((partition as u32 >> bit) & 1) << (2 * bit);
+        }
+        self.registers.RSTS.write(PASSWORD | rsts);
+
+        self.start(RESET_TICKS);
+        loop {
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Resets the board, booting the default partition again.
+    pub fn reboot(&mut self) -> ! {
+        self.reset(0)
+    }
+
+    /// Powers off every device the firmware controls and resets the board
+    /// into `HALT_PARTITION`, stopping until it is power-cycled.
+    pub fn halt(&mut self) -> ! {
+        let mut mailbox = Mailbox::new();
+        for device in Device::iter() {
+            // Devices that don't exist on this board can't be powered off.
+            let _ = mailbox.set_power_state(device, false);
+        }
+        self.reset(HALT_PARTITION)
+    }
+}
@@ -0,0 +1,68 @@
+use volatile::prelude::*;
+use volatile::{ReadVolatile, Reserved, Volatile};
+
+use crate::common::IO_BASE;
+
+/// The base address of the hardware random number generator registers.
+const RNG_REG_BASE: usize = IO_BASE + 0x104000;
+
+/// Bit of `CTRL` enabling the generator.
+const CTRL_ENABLE: u32 = 1;
+
+/// Bit of `INT_MASK` masking the generator's interrupt.
+const INT_OFF: u32 = 1;
+
+/// The number of initial numbers the generator discards after being enabled,
+/// written to `STATUS`. They have less entropy while the generator warms up.
+const WARMUP_COUNT: u32 = 0x40000;
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    CTRL: Volatile<u32>,
+    /// Bits 31:24 hold the number of words in the FIFO.
+    STATUS: Volatile<u32>,
+    DATA: ReadVolatile<u32>,
+    __r0: Reserved<u32>,
+    INT_MASK: Volatile<u32>,
+}
+
+/// The BCM2837 hardware random number generator.
+pub struct Rng {
+    registers: &'static mut Registers,
+}
+
+impl Rng {
+    /// Returns a handle to the generator, enabling it if needed. The first
+    /// read after enabling it waits for the generator to warm up.
+    pub fn new() -> Rng {
+        let registers = unsafe { &mut *(RNG_REG_BASE as *mut Registers) };
+        if !registers.CTRL.has_mask(CTRL_ENABLE) {
+            registers.INT_MASK.or_mask(INT_OFF);
+            registers.STATUS.write(WARMUP_COUNT);
+            registers.CTRL.write(CTRL_ENABLE);
+        }
+
+        Rng { registers }
+    }
+
+    /// Returns the number of random words ready to be read without
+    /// waiting.
+    pub fn available(&self) -> u32 {
+        self.registers.STATUS.read() >> 24
+    }
+
+    /// Returns 32 random bits, waiting for the generator to produce them.
+    pub fn next_u32(&mut self) -> u32 {
+        while self.available() == 0 {}
+        self.registers.DATA.read()
+    }
+
+    /// Fills `buf` with random bytes.
+    pub fn fill_bytes(&mut self, buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(4) {
+            let bytes = self.next_u32().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+}
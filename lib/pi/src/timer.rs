@@ -37,6 +37,20 @@ impl Timer {
         let count = chi << 32 | clo;
         Duration::from_micros(count)
     }
+
+    /// Sets up a match in timer 1 to occur `t` duration from now, clearing
+    /// any earlier match. The `Timer1` interrupt is pending until the match
+    /// is cleared.
+    pub fn tick_in(&mut self, t: Duration) {
+        let target = self.registers.CLO.read().wrapping_add(t.as_micros() as u32);
+        self.registers.COMPARE[1].write(target);
+        self.clear_tick();
+    }
+
+    /// Clears a match in timer 1.
+    pub fn clear_tick(&mut self) {
+        self.registers.CS.write(1 << 1);
+    }
 }
 
 /// Returns current time.
@@ -44,6 +58,12 @@ pub fn current_time() -> Duration {
     Timer::new().read()
 }
 
+/// Sets up a match in timer 1 to occur `t` duration from now. See
+/// `Timer::tick_in`.
+pub fn tick_in(t: Duration) {
+    Timer::new().tick_in(t);
+}
+
 /// Spins until `t` duration have passed.
 pub fn spin_sleep(t: Duration) {
     let timer = Timer::new();
@@ -2,14 +2,13 @@ use core::fmt;
 use core::time::Duration;
 
 use shim::io;
-use shim::const_assert_size;
 
 use volatile::prelude::*;
-use volatile::{Volatile, ReadVolatile, Reserved};
+use volatile::{Volatile, ReadVolatile};
 
 use crate::timer::Timer;
 use crate::common::IO_BASE;
-use crate::gpio::{Gpio, Function};
+use crate::gpio::{self, Gpio, GpioController, Function};
 
 /// The base address for the `MU` registers.
 const MU_REG_BASE: usize = IO_BASE + 0x215040;
@@ -17,13 +16,31 @@ const MU_REG_BASE: usize = IO_BASE + 0x215040;
 /// The `AUXENB` register from page 9 of the BCM2837 documentation.
 const AUX_ENABLES: *mut Volatile<u8> = (IO_BASE + 0x215004) as *mut Volatile<u8>;
 
+/// The GPIO pins used by the mini UART: TXD1 and RXD1.
+const PINS: [u8; 2] = [14, 15];
+
+/// The name the mini UART claims its GPIO pins with.
+const OWNER: &str = "mini UART";
+
 /// Enum representing bit fields of the `AUX_MU_LSR_REG` register.
 #[repr(u8)]
 enum LsrStatus {
     DataReady = 1,
+    RxOverrun = 1 << 1,
     TxAvailable = 1 << 5,
+    TxIdle = 1 << 6,
 }
 
+/// Enum representing bit fields of the `AUX_MU_IER_REG` register.
+#[repr(u8)]
+enum IerStatus {
+    RxInterrupt = 1,
+    TxInterrupt = 1 << 1,
+}
+
+/// The bit of `AUX_MU_IIR_REG` that is clear while an interrupt is pending.
+const IIR_NO_INTERRUPT: u32 = 1;
+
 #[repr(C)]
 #[allow(non_snake_case)]
 struct Registers {
@@ -45,6 +62,7 @@ struct Registers {
 pub struct MiniUart {
     registers: &'static mut Registers,
     timeout: Option<Duration>,
+    claimed: bool,
 }
 
 impl MiniUart {
@@ -55,7 +73,39 @@ impl MiniUart {
     ///
     /// By default, reads will never time out. To set a read timeout, use
     /// `set_read_timeout()`.
+    ///
+    /// The pins are claimed in the `GpioController` like `try_new()` does
+    /// when they are free. Unlike `try_new()`, this never fails, so that
+    /// panic handlers and bootloaders always get a UART: pins claimed by
+    /// another driver are reconfigured anyway, without a claim.
     pub fn new() -> MiniUart {
+        MiniUart::try_new().unwrap_or_else(|_| MiniUart::init(false))
+    }
+
+    /// Initializes the mini UART like `new()` after claiming GPIO pins 14
+    /// and 15 in the `GpioController`. The pins are released when the
+    /// `MiniUart` is dropped. If another `MiniUart` already holds the pins,
+    /// they are shared instead, and stay claimed until that one is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns `gpio::Error::Taken` if either pin is claimed by another
+    /// driver, without touching the mini UART or the pins.
+    pub fn try_new() -> Result<MiniUart, gpio::Error> {
+        let controller = GpioController::new();
+        let claimed = match controller.claim(&PINS, OWNER) {
+            Ok(()) => true,
+            Err(_) if PINS.iter().all(|&pin| controller.owner(pin) == Some(OWNER)) => false,
+            Err(e) => return Err(e),
+        };
+
+        Ok(MiniUart::init(claimed))
+    }
+
+    /// Initializes the mini UART and its pins, as described in `new()`.
+    /// `claimed` is `true` if this `MiniUart` claimed the pins and must
+    /// release them.
+    fn init(claimed: bool) -> MiniUart {
         let registers = unsafe {
             // Enable the mini UART as an auxiliary device.
             (*AUX_ENABLES).or_mask(1);
@@ -67,17 +117,21 @@ impl MiniUart {
         registers.AUX_MU_BAUD_REG.write(270);
         // set data length to 8 bits
         registers.AUX_MU_LCR_REG.write(0x01);
-        // set GPIO pins 14 and 15 to alternative function 5 (TXD1/RDXD1)
-        Gpio::new(14).into_alt(Function::Alt5);
-        Gpio::new(15).into_alt(Function::Alt5);
+        // set GPIO pins 14 and 15 to alternative function 5 (TXD1/RDXD1);
+        // `new()` and `try_new()` checked the claims
+        unsafe {
+            Gpio::new(14).into_alt(Function::Alt5);
+            Gpio::new(15).into_alt(Function::Alt5);
+        }
         // enable the UART transmitter and receiver
         registers.AUX_MU_CNTL_REG.write(0x11);
 
         // AUX_MU_LCR_REG DLAB (bit 7) set 0
         
-        MiniUart { 
-            registers: registers,
-            timeout: None
+        MiniUart {
+            registers,
+            timeout: None,
+            claimed
         }
     }
 
@@ -101,7 +155,7 @@ impl MiniUart {
     /// method returns `true`, a subsequent call to `read_byte` is guaranteed to
     /// return immediately. This method does not block.
     pub fn has_byte(&self) -> bool {
-        self.registers.AUX_MU_STAT_REG.has_mask(LsrStatus::DataReady as u32)
+        self.registers.AUX_MU_LSR_REG.has_mask(LsrStatus::DataReady as u32)
     }
 
     /// Blocks until there is a byte ready to read. If a read timeout is set,
@@ -113,23 +167,22 @@ impl MiniUart {
     /// returns `Ok(())`, a subsequent call to `read_byte` is guaranteed to
     /// return immediately.
     pub fn wait_for_byte(&self) -> Result<(), ()> {
-        if self.timeout.is_none() {
-            loop {
-                if self.has_byte() {
-                    return Ok(());
-                }
-            }
-        } else {
-            let timer = Timer::new();
-            let current_time = timer.read();
-            let target_time = current_time
-                .checked_add(self.timeout.unwrap())
-                .expect("Duration addition failed");
-            while timer.read() <= target_time && self.has_byte() {
+        let timeout = match self.timeout {
+            Some(timeout) => timeout,
+            None => {
+                while !self.has_byte() {}
                 return Ok(());
             }
-            Err(())
+        };
+
+        let timer = Timer::new();
+        let deadline = timer.read() + timeout;
+        while !self.has_byte() {
+            if timer.read() > deadline {
+                return Err(());
+            }
         }
+        Ok(())
     }
 
     /// Reads a byte. Blocks indefinitely until a byte is ready to be read.
@@ -140,6 +193,96 @@ impl MiniUart {
             }
         }
     }
+
+    /// Returns a handle to the mini UART's interrupts and data registers for
+    /// an interrupt handler. Interrupts are disabled until enabled through a
+    /// handle.
+    ///
+    /// While interrupts are enabled, the handler should be the only code
+    /// reading and writing bytes: the polled methods of `MiniUart` race with
+    /// it for the FIFOs.
+    pub fn irq_handle(&self) -> IrqHandle {
+        IrqHandle {
+            registers: unsafe { &mut *(MU_REG_BASE as *mut Registers) },
+            overrun: false,
+        }
+    }
+}
+
+/// Access to the mini UART from its interrupt handler, returned by
+/// `MiniUart::irq_handle()`. Unlike the methods of `MiniUart`, none of these
+/// block.
+pub struct IrqHandle {
+    registers: &'static mut Registers,
+    overrun: bool,
+}
+
+impl IrqHandle {
+    /// Enables or disables the interrupt raised while the receive FIFO holds
+    /// at least one byte.
+    pub fn set_rx_interrupt(&mut self, enabled: bool) {
+        self.set_interrupt(IerStatus::RxInterrupt, enabled);
+    }
+
+    /// Enables or disables the interrupt raised while the transmit FIFO can
+    /// accept at least one byte.
+    pub fn set_tx_interrupt(&mut self, enabled: bool) {
+        self.set_interrupt(IerStatus::TxInterrupt, enabled);
+    }
+
+    fn set_interrupt(&mut self, interrupt: IerStatus, enabled: bool) {
+        if enabled {
+            self.registers.AUX_MU_IER_REG.or_mask(interrupt as u32);
+        } else {
+            self.registers.AUX_MU_IER_REG.and_mask(!(interrupt as u32));
+        }
+    }
+
+    /// Returns `true` if the mini UART is raising an interrupt. The AUX
+    /// interrupt is shared with the SPI controllers.
+    pub fn is_pending(&self) -> bool {
+        !self.registers.AUX_MU_IIR_REG.has_mask(IIR_NO_INTERRUPT)
+    }
+
+    /// Reads a byte from the receive FIFO, or returns `None` if it's empty.
+    pub fn try_read_byte(&mut self) -> Option<u8> {
+        // Reading the line status clears the overrun bit, so latch it.
+        let status = self.registers.AUX_MU_LSR_REG.read();
+        self.overrun |= status & LsrStatus::RxOverrun as u32 != 0;
+        if status & LsrStatus::DataReady as u32 != 0 {
+            Some(self.registers.AUX_MU_IO_REG.read() as u8)
+        } else {
+            None
+        }
+    }
+
+    /// Writes `byte` to the transmit FIFO. Returns `false`, without writing
+    /// it, if the FIFO is full.
+    pub fn try_write_byte(&mut self, byte: u8) -> bool {
+        if self.registers.AUX_MU_LSR_REG.has_mask(LsrStatus::TxAvailable as u32) {
+            self.registers.AUX_MU_IO_REG.write(byte as u32);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns `true` if the receive FIFO overflowed, losing bytes, since the
+    /// last call. Only overruns seen through this handle are reported.
+    pub fn take_overrun(&mut self) -> bool {
+        let status = self.registers.AUX_MU_LSR_REG.read();
+        let overrun = self.overrun || status & LsrStatus::RxOverrun as u32 != 0;
+        self.overrun = false;
+        overrun
+    }
+}
+
+impl Drop for MiniUart {
+    fn drop(&mut self) {
+        if self.claimed {
+            GpioController::new().release(&PINS, OWNER);
+        }
+    }
 }
 
 // FIXME: Implement `fmt::Write` for `MiniUart`. A b'\r' byte should be written
@@ -158,7 +301,7 @@ impl fmt::Write for MiniUart {
 
 mod uart_io {
     use super::io;
-    use super::MiniUart;
+    use super::{LsrStatus, MiniUart};
     use shim::io::ErrorKind;
     use volatile::prelude::*;
 
@@ -174,16 +317,18 @@ mod uart_io {
 
     impl io::Read for MiniUart {
         fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-            if buf.len() == 0 {
+            if buf.is_empty() {
                 return io::Result::Ok(0);
             } else if self.wait_for_byte().is_err() {
                 return io::Result::Err(io::Error::from(ErrorKind::TimedOut));
-            } else {
-                for i in 0..buf.len() {
-                    buf[i] = self.read_byte();
-                }
-                return io::Result::Ok(buf.len());
             }
+
+            let mut read = 0;
+            while read < buf.len() && self.has_byte() {
+                buf[read] = self.read_byte();
+                read += 1;
+            }
+            io::Result::Ok(read)
         }
     }
 
@@ -192,10 +337,12 @@ mod uart_io {
             for byte in buf {
                 self.write_byte(*byte);
             }
-            return io::Result::Ok(buf.len());
+            io::Result::Ok(buf.len())
         }
 
+        /// Blocks until every byte written has been sent.
         fn flush(&mut self) -> io::Result<()> {
+            while !self.registers.AUX_MU_LSR_REG.has_mask(LsrStatus::TxIdle as u32) {}
             io::Result::Ok(())
         }
     }